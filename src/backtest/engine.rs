@@ -0,0 +1,497 @@
+//! 回测引擎：按录制时间回放订单簿，驱动与主程序相同的检测（ArbitrageDetector → 执行价差）
+//! 与开仓检查链（monitor::EntryChecks：价格阈值/临近结束 → 风险敞口 → 持仓平衡 → 风控熔断 → 每日交易次数 → 交易间隔 → 套利闸门），
+//! 下单经 TradingExecutor 发往 SimulatedExchange，成交由模拟交易所的队列位置模型给出。
+//!
+//! 时钟完全由录制的接收时间推进：闸门冷却、交易间隔、交易次数周期、临近结束检查与挂单过期都使用模拟时间；
+//! 订单在决策后 latency_ms 才到达交易所，期间的订单簿更新先生效。
+//! 每个窗口使用独立的模拟账户、持仓与敞口（窗口结束时撤销挂单、merge 双边持仓，剩余单边持仓按买一价估值计入盈亏）；
+//! 风控熔断与每日交易次数按 UTC 交易日跨窗口累计，不读写主程序的状态文件。
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use polymarket_client_sdk::types::{B256, Decimal, U256};
+use rust_decimal_macros::dec;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use super::report::{BacktestReport, BacktestStats, WindowReport};
+use crate::config::Config as BotConfig;
+use crate::monitor::{
+    ArbitrageDetector, ArbitrageOpportunity, EntryChecks, InFlightGuard, OpportunityGate, PreTradeCheck,
+    SellArbitrageOpportunity,
+};
+use crate::recorder::{self, RecordLine, RecordedBook, RecordedMarket};
+use crate::risk::{PositionBalancer, RiskGuard, RiskManager, TradeBudget};
+use crate::trading::exchange::{BookLevel, BookSnapshot};
+use crate::trading::{Exchange, FeeSchedule, SimulatedExchange, TradingExecutor};
+
+/// 回测参数（策略参数取自 Config）
+#[derive(Debug, Clone)]
+pub struct BacktestOptions {
+    pub latency_ms: i64,      // 决策到订单到达交易所的延迟
+    pub initial_usdc: Decimal, // 每个窗口模拟账户的初始 USDC
+}
+
+/// 一个窗口的录制数据
+#[derive(Debug, Clone)]
+pub struct WindowData {
+    pub window_ts: i64,
+    pub markets: Vec<RecordedMarket>,
+    pub books: Vec<RecordedBook>, // 按接收时间升序
+}
+
+/// 读取录制目录中 [from, to] 范围内的窗口（时间戳为窗口开始的 Unix 秒）
+pub fn load_windows(dir: &Path, from: Option<i64>, to: Option<i64>) -> Result<Vec<WindowData>> {
+    let mut windows = Vec::new();
+    for (window_ts, path) in recorder::list_window_files(dir)? {
+        if from.is_some_and(|f| window_ts < f) || to.is_some_and(|t| window_ts > t) {
+            continue;
+        }
+        let mut markets: Vec<RecordedMarket> = Vec::new();
+        let mut books = Vec::new();
+        for line in recorder::read_window_file(&path)? {
+            match line {
+                // 同一窗口重启后会重复写入元数据，按 market_id 去重
+                RecordLine::Market { market, .. } => {
+                    if !markets.iter().any(|m| m.market_id == market.market_id) {
+                        markets.push(market);
+                    }
+                }
+                RecordLine::Book(book) => books.push(book),
+            }
+        }
+        books.sort_by_key(|b| b.recv_ts_ms);
+        debug!(window_ts, markets = markets.len(), books = books.len(), "载入录制窗口");
+        windows.push(WindowData { window_ts, markets, books });
+    }
+    Ok(windows)
+}
+
+/// 跨窗口共享的风控状态：熔断与每日交易次数（与主程序一样按交易日累计）
+pub struct BacktestRisk {
+    risk_guard: Arc<RiskGuard>,
+    trade_budget: TradeBudget,
+    day: Option<chrono::NaiveDate>,
+    day_pnl: Decimal, // 当前 UTC 交易日已完成窗口的盈亏合计
+}
+
+impl BacktestRisk {
+    pub fn new(config: &BotConfig) -> Self {
+        Self {
+            risk_guard: Arc::new(RiskGuard::in_memory(config)),
+            trade_budget: TradeBudget::in_memory(config.max_trades_per_day, config.trade_budget_reset_hour_utc),
+            day: None,
+            day_pnl: dec!(0),
+        }
+    }
+
+    /// 窗口开始：按当日已实现盈亏检查亏损与回撤（跨日时清零）
+    fn on_window_start(&mut self, now: DateTime<Utc>) {
+        let today = now.date_naive();
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.day_pnl = dec!(0);
+        }
+        self.risk_guard.check_pnl(now, self.day_pnl);
+        if let Some(reason) = self.risk_guard.take_new_trip() {
+            info!(reason = %reason, "回测：风控熔断，停止开新仓");
+        }
+    }
+
+    fn on_window_end(&mut self, pnl: Decimal) {
+        self.day_pnl += pnl;
+    }
+}
+
+/// 依次回放所有窗口
+pub async fn run_backtest(config: &BotConfig, windows: &[WindowData], options: &BacktestOptions) -> BacktestReport {
+    let mut report = BacktestReport::default();
+    let mut risk = BacktestRisk::new(config);
+    for window in windows {
+        report.windows.push(run_window(config, window, options, &mut risk).await);
+    }
+    report
+}
+
+enum PendingKind {
+    Buy(ArbitrageOpportunity),
+    Sell(SellArbitrageOpportunity),
+}
+
+/// 已通过检查、等待到达交易所的套利对；执行完成前持有闸门的执行中标记
+struct PendingOrder {
+    exec_ts: i64,
+    kind: PendingKind,
+    yes_dir: &'static str,
+    no_dir: &'static str,
+    _in_flight: InFlightGuard,
+}
+
+/// 已下单的套利对，窗口结束时按成交记录统计两腿实际成交（挂单可能在之后才成交）
+struct SubmittedPair {
+    market_id: B256,
+    yes_order_id: String,
+    no_order_id: String,
+    requested: Decimal,
+}
+
+/// 与上一拍比较得到涨跌方向（↑涨 ↓跌 −平），用于滑点分配
+fn direction(current: Decimal, previous: Option<Decimal>) -> &'static str {
+    match previous {
+        Some(prev) if current > prev => "↑",
+        Some(prev) if current < prev => "↓",
+        Some(_) => "−",
+        None => "",
+    }
+}
+
+fn snapshot(book: &RecordedBook) -> BookSnapshot {
+    let level = |&(price, size): &(Decimal, Decimal)| BookLevel { price, size };
+    BookSnapshot {
+        bids: book.bids.iter().map(level).collect(),
+        asks: book.asks.iter().map(level).collect(),
+    }
+}
+
+struct WindowRun<'a> {
+    config: &'a BotConfig,
+    latency_ms: i64,
+    sim: Arc<SimulatedExchange>,
+    executor: TradingExecutor,
+    risk_manager: RiskManager,
+    balancer: PositionBalancer,
+    detector: ArbitrageDetector,
+    gate: Arc<OpportunityGate>,
+    pre_trade_check: PreTradeCheck,
+    risk: &'a BacktestRisk,
+    markets: HashMap<B256, &'a RecordedMarket>,
+    stats: HashMap<B256, BacktestStats>,
+    submitted: Vec<SubmittedPair>,
+    last_trade: Option<Instant>,
+    base: Instant,
+    first_ts: i64,
+}
+
+impl<'a> WindowRun<'a> {
+    /// 录制时间（毫秒）映射为闸门使用的 Instant
+    fn instant(&self, ts_ms: i64) -> Instant {
+        self.base + Duration::from_millis((ts_ms - self.first_ts).max(0) as u64)
+    }
+
+    fn entry_checks(&self) -> EntryChecks<'_> {
+        EntryChecks {
+            pre_trade_check: &self.pre_trade_check,
+            risk_manager: &self.risk_manager,
+            balancer: &self.balancer,
+            risk_guard: &self.risk.risk_guard,
+            trade_budget: &self.risk.trade_budget,
+            gate: &self.gate,
+        }
+    }
+
+    fn check_buy(&mut self, opp: ArbitrageOpportunity, ts_ms: i64, dirs: (&'static str, &'static str)) -> Option<PendingOrder> {
+        let end_date = self.markets.get(&opp.market_id).map(|m| m.end_date);
+        let now_dt = DateTime::from_timestamp_millis(ts_ms).unwrap_or_else(Utc::now);
+        let mut last_trade = self.last_trade;
+        let result = self
+            .entry_checks()
+            .admit_buy(&opp, end_date, now_dt, self.instant(ts_ms), &mut last_trade);
+        self.last_trade = last_trade;
+        let guard = match result {
+            Ok(g) => g,
+            Err(reason) => {
+                debug!("{}，跳过套利执行", reason);
+                return None;
+            }
+        };
+        Some(PendingOrder {
+            exec_ts: ts_ms + self.latency_ms,
+            kind: PendingKind::Buy(opp),
+            yes_dir: dirs.0,
+            no_dir: dirs.1,
+            _in_flight: guard,
+        })
+    }
+
+    fn check_sell(&mut self, opp: SellArbitrageOpportunity, ts_ms: i64, dirs: (&'static str, &'static str)) -> Option<PendingOrder> {
+        let end_date = self.markets.get(&opp.market_id).map(|m| m.end_date);
+        let now_dt = DateTime::from_timestamp_millis(ts_ms).unwrap_or_else(Utc::now);
+        let mut last_trade = self.last_trade;
+        let result = self
+            .entry_checks()
+            .admit_sell(&opp, end_date, now_dt, self.instant(ts_ms), &mut last_trade);
+        self.last_trade = last_trade;
+        let guard = match result {
+            Ok(g) => g,
+            Err(reason) => {
+                debug!("{}，跳过拆分卖出", reason);
+                return None;
+            }
+        };
+        Some(PendingOrder {
+            exec_ts: ts_ms + self.latency_ms,
+            kind: PendingKind::Sell(opp),
+            yes_dir: dirs.0,
+            no_dir: dirs.1,
+            _in_flight: guard,
+        })
+    }
+
+    /// 订单到达交易所：与主程序的执行任务相同，成交后登记持仓
+    async fn execute(&mut self, pending: PendingOrder) {
+        let (market_id, submitted, requested) = match &pending.kind {
+            PendingKind::Buy(opp) => {
+                let result = self
+                    .executor
+                    .execute_arbitrage_pair(opp, pending.yes_dir, pending.no_dir)
+                    .await;
+                let submitted = result.map(|r| {
+                    let pair = SubmittedPair {
+                        market_id: opp.market_id,
+                        yes_order_id: r.yes_order_id.clone(),
+                        no_order_id: r.no_order_id.clone(),
+                        requested: r.yes_size + r.no_size,
+                    };
+                    self.risk_manager.register_order_pair(
+                        r,
+                        opp.market_id,
+                        opp.yes_token_id,
+                        opp.no_token_id,
+                    );
+                    pair
+                });
+                (opp.market_id, submitted, opp.yes_size.min(opp.no_size) * dec!(2))
+            }
+            PendingKind::Sell(opp) => {
+                let result = self
+                    .executor
+                    .execute_split_and_sell(opp, pending.yes_dir, pending.no_dir)
+                    .await;
+                let submitted = match result {
+                    Ok(r) => {
+                        let pair = SubmittedPair {
+                            market_id: opp.market_id,
+                            yes_order_id: r.yes_order_id.clone(),
+                            no_order_id: r.no_order_id.clone(),
+                            requested: r.yes_size + r.no_size,
+                        };
+                        self.risk_manager.register_sell_pair(r, opp.market_id, opp.yes_token_id, opp.no_token_id);
+                        Ok(pair)
+                    }
+                    Err(e) => {
+                        // 未发生 split，撤回执行前计入的敞口
+                        let pt = self.risk_manager.position_tracker();
+                        pt.revert_exposure_cost(opp.yes_token_id, dec!(0.5), opp.size);
+                        pt.revert_exposure_cost(opp.no_token_id, dec!(0.5), opp.size);
+                        Err(e)
+                    }
+                };
+                (opp.market_id, submitted, opp.size * dec!(2))
+            }
+        };
+
+        let stats = self.stats.entry(market_id).or_default();
+        stats.pairs += 1;
+        match submitted {
+            Ok(pair) => self.submitted.push(pair),
+            Err(e) => {
+                debug!(error = %e, "回测：套利对下单失败");
+                stats.unfilled += 1;
+                stats.requested += requested;
+            }
+        }
+    }
+
+    /// 窗口结束：撤销挂单，按成交记录统计各套利对，merge 双边持仓并计算各市场盈亏
+    async fn finish(mut self, window_ts: i64) -> WindowReport {
+        let exchange: Arc<dyn Exchange> = self.sim.clone();
+        if let Err(e) = exchange.cancel_all_orders().await {
+            warn!(error = %e, "回测：撤销挂单失败");
+        }
+
+        let mut filled_by_order: HashMap<String, Decimal> = HashMap::new();
+        for trade in exchange.trades().await.unwrap_or_default() {
+            *filled_by_order.entry(trade.order_id).or_insert(dec!(0)) += trade.size;
+        }
+        for pair in &self.submitted {
+            let yes = filled_by_order.get(&pair.yes_order_id).copied().unwrap_or(dec!(0));
+            let no = filled_by_order.get(&pair.no_order_id).copied().unwrap_or(dec!(0));
+            let stats = self.stats.entry(pair.market_id).or_default();
+            stats.requested += pair.requested;
+            stats.filled += yes + no;
+            match (yes > dec!(0), no > dec!(0)) {
+                (true, true) => stats.both_filled += 1,
+                (false, false) => stats.unfilled += 1,
+                _ => stats.one_sided += 1,
+            }
+        }
+
+        let mergeable = self.sim.mergeable();
+        for market_id in mergeable.keys() {
+            match exchange.merge_max(*market_id).await {
+                Ok(outcome) => self.stats.entry(*market_id).or_default().merged += outcome.amount(*market_id).unwrap_or_default(),
+                Err(e) => warn!(market_id = %market_id, error = %e, "回测：merge 失败"),
+            }
+        }
+
+        let balances: HashMap<U256, Decimal> = self.sim.token_balances().into_iter().collect();
+        let mut markets: Vec<(String, BacktestStats)> = Vec::new();
+        for (market_id, market) in &self.markets {
+            let mut stats = self.stats.remove(market_id).unwrap_or_default();
+            stats.unhedged = [market.yes_token_id, market.no_token_id]
+                .iter()
+                .map(|t| balances.get(t).copied().unwrap_or(dec!(0)))
+                .sum();
+            stats.pnl = self.sim.market_pnl(*market_id);
+            markets.push((market.crypto_symbol.clone(), stats));
+        }
+        markets.sort_by(|a, b| a.0.cmp(&b.0));
+
+        WindowReport { window_ts, markets }
+    }
+}
+
+/// 回放单个窗口
+pub async fn run_window(
+    config: &BotConfig,
+    window: &WindowData,
+    options: &BacktestOptions,
+    risk: &mut BacktestRisk,
+) -> WindowReport {
+    if let Some(start) = window.books.first().and_then(|b| DateTime::from_timestamp_millis(b.recv_ts_ms)) {
+        risk.on_window_start(start);
+    }
+
+    // 录制数据不含市场手续费元数据，使用配置中的手续费
+    let fees = FeeSchedule::from_config(config);
+    let sim = Arc::new(SimulatedExchange::new(options.initial_usdc, fees.clone()));
+    for m in &window.markets {
+        sim.register_market(m.market_id, m.yes_token_id, m.no_token_id);
+    }
+    let exchange: Arc<dyn Exchange> = sim.clone();
+    let risk_manager = RiskManager::new(exchange.clone(), config, fees.clone()).with_risk_guard(risk.risk_guard.clone());
+    for m in &window.markets {
+        risk_manager
+            .position_tracker()
+            .register_market(m.market_id, &m.crypto_symbol, m.yes_token_id, m.no_token_id);
+    }
+    let balancer = PositionBalancer::new(exchange.clone(), risk_manager.position_tracker(), config);
+
+    let mut run = WindowRun {
+        config,
+        latency_ms: options.latency_ms,
+        sim: sim.clone(),
+        executor: TradingExecutor::new(
+            exchange,
+            config.max_order_size_usdc,
+            config.slippage,
+            config.gtd_expiration_secs,
+            config.arbitrage_order_type.clone(),
+            fees,
+        ),
+        risk_manager,
+        balancer,
+        detector: ArbitrageDetector::new(config),
+        gate: Arc::new(OpportunityGate::new(config)),
+        pre_trade_check: PreTradeCheck::new(config),
+        risk,
+        markets: window.markets.iter().map(|m| (m.market_id, m)).collect(),
+        stats: HashMap::new(),
+        submitted: Vec::new(),
+        last_trade: None,
+        base: Instant::now(),
+        first_ts: window.books.first().map(|b| b.recv_ts_ms).unwrap_or(0),
+    };
+
+    let spread = Decimal::try_from(config.arbitrage_execution_spread).unwrap_or(dec!(0.01));
+    let execution_threshold = dec!(1.0) - spread;
+    let sell_threshold = dec!(1.0) + spread;
+
+    // token -> market_id；各 token 的最新订单簿；各市场上一拍卖一价
+    let token_market: HashMap<U256, B256> = window
+        .markets
+        .iter()
+        .flat_map(|m| [(m.yes_token_id, m.market_id), (m.no_token_id, m.market_id)])
+        .collect();
+    let mut latest: HashMap<U256, &RecordedBook> = HashMap::new();
+    let mut last_prices: HashMap<B256, (Decimal, Decimal)> = HashMap::new();
+    let mut pending: VecDeque<PendingOrder> = VecDeque::new();
+
+    for book in &window.books {
+        // 到达时间不晚于本条更新的订单先执行，看到的是延迟期间已生效的订单簿
+        while pending.front().is_some_and(|p| p.exec_ts <= book.recv_ts_ms) {
+            let order = pending.pop_front().unwrap();
+            run.execute(order).await;
+        }
+
+        if let Some(now) = DateTime::from_timestamp_millis(book.recv_ts_ms) {
+            run.sim.set_time(now);
+        }
+        run.sim.set_book(book.asset_id, snapshot(book));
+        latest.insert(book.asset_id, book);
+
+        let Some(&market_id) = token_market.get(&book.asset_id) else { continue };
+        let Some(market) = run.markets.get(&market_id).copied() else { continue };
+        let (Some(yes_book), Some(no_book)) = (latest.get(&market.yes_token_id).copied(), latest.get(&market.no_token_id).copied()) else {
+            continue;
+        };
+
+        let yes_best_ask = yes_book.asks.last().map(|l| l.0);
+        let no_best_ask = no_book.asks.last().map(|l| l.0);
+        let dirs = match (yes_best_ask, no_best_ask) {
+            (Some(yp), Some(np)) => {
+                let prev = last_prices.insert(market_id, (yp, np));
+                (direction(yp, prev.map(|p| p.0)), direction(np, prev.map(|p| p.1)))
+            }
+            _ => ("", ""),
+        };
+
+        // 买方向：YES+NO 卖一价之和 <= 1 - 执行价差
+        if let (Some(yp), Some(np)) = (yes_best_ask, no_best_ask) {
+            if yp + np <= execution_threshold {
+                if let Some(opp) = run.detector.check_arbitrage(yes_book, no_book, &market_id) {
+                    run.stats.entry(market_id).or_default().opportunities += 1;
+                    match run.check_buy(opp, book.recv_ts_ms, dirs) {
+                        Some(order) => pending.push_back(order),
+                        None => run.stats.entry(market_id).or_default().skipped += 1,
+                    }
+                }
+            }
+        }
+
+        // 卖方向：YES+NO 买一价之和 >= 1 + 执行价差
+        if run.config.enable_sell_arbitrage {
+            let yes_best_bid = yes_book.bids.last().map(|l| l.0);
+            let no_best_bid = no_book.bids.last().map(|l| l.0);
+            if let (Some(yb), Some(nb)) = (yes_best_bid, no_best_bid) {
+                if yb + nb >= sell_threshold {
+                    if let Some(opp) = run.detector.check_sell_arbitrage(yes_book, no_book, &market_id) {
+                        run.stats.entry(market_id).or_default().opportunities += 1;
+                        match run.check_sell(opp, book.recv_ts_ms, dirs) {
+                            Some(order) => pending.push_back(order),
+                            None => run.stats.entry(market_id).or_default().skipped += 1,
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // 录制结束时仍在途的订单按最后的订单簿执行
+    while let Some(order) = pending.pop_front() {
+        run.execute(order).await;
+    }
+
+    let report = run.finish(window.window_ts).await;
+    risk.on_window_end(report.total().pnl);
+    info!(
+        window_ts = window.window_ts,
+        pnl = %report.total().pnl,
+        pairs = report.total().pairs,
+        "回测窗口完成"
+    );
+    report
+}
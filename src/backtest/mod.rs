@@ -0,0 +1,7 @@
+pub mod engine;
+pub mod report;
+pub mod sweep;
+
+pub use engine::*;
+pub use report::*;
+pub use sweep::*;
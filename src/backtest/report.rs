@@ -0,0 +1,160 @@
+//! 回测统计与报告：按窗口、按币种汇总成交、成交率、单边成交、merge 量与净盈亏。
+
+use polymarket_client_sdk::types::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// 一个市场（或其汇总）的回测统计
+#[derive(Debug, Clone, Default)]
+pub struct BacktestStats {
+    pub opportunities: u64, // 检测到的套利机会（含被拦截的）
+    pub skipped: u64,       // 被价格阈值、临近结束、敞口、持仓平衡、交易间隔或闸门拦截
+    pub pairs: u64,         // 实际下单的套利对
+    pub both_filled: u64,   // 两腿都有成交
+    pub one_sided: u64,     // 只有一腿成交
+    pub unfilled: u64,      // 两腿都未成交（含下单失败）
+    pub requested: Decimal, // 两腿请求份额合计
+    pub filled: Decimal,    // 两腿成交份额合计
+    pub merged: Decimal,    // 窗口结束时 merge 的份额
+    pub unhedged: Decimal,  // merge 后剩余的单边份额
+    pub pnl: Decimal,       // 净盈亏（USD，剩余持仓按买一价估值）
+}
+
+impl BacktestStats {
+    pub fn absorb(&mut self, other: &BacktestStats) {
+        self.opportunities += other.opportunities;
+        self.skipped += other.skipped;
+        self.pairs += other.pairs;
+        self.both_filled += other.both_filled;
+        self.one_sided += other.one_sided;
+        self.unfilled += other.unfilled;
+        self.requested += other.requested;
+        self.filled += other.filled;
+        self.merged += other.merged;
+        self.unhedged += other.unhedged;
+        self.pnl += other.pnl;
+    }
+
+    /// 成交率 = 成交份额 / 请求份额
+    pub fn fill_rate(&self) -> Decimal {
+        if self.requested.is_zero() {
+            dec!(0)
+        } else {
+            self.filled / self.requested
+        }
+    }
+
+    /// 单边成交率 = 单边成交的套利对 / 下单的套利对
+    pub fn one_sided_rate(&self) -> Decimal {
+        if self.pairs == 0 {
+            dec!(0)
+        } else {
+            Decimal::from(self.one_sided) / Decimal::from(self.pairs)
+        }
+    }
+}
+
+/// 一个窗口的回测结果：按市场统计
+#[derive(Debug, Clone)]
+pub struct WindowReport {
+    pub window_ts: i64,
+    pub markets: Vec<(String, BacktestStats)>, // (币种, 统计)
+}
+
+impl WindowReport {
+    pub fn total(&self) -> BacktestStats {
+        let mut total = BacktestStats::default();
+        for (_, stats) in &self.markets {
+            total.absorb(stats);
+        }
+        total
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BacktestReport {
+    pub windows: Vec<WindowReport>,
+}
+
+impl BacktestReport {
+    pub fn total(&self) -> BacktestStats {
+        let mut total = BacktestStats::default();
+        for window in &self.windows {
+            total.absorb(&window.total());
+        }
+        total
+    }
+
+    /// 按币种汇总（币种按字母序）
+    pub fn by_symbol(&self) -> BTreeMap<String, BacktestStats> {
+        let mut by_symbol: BTreeMap<String, BacktestStats> = BTreeMap::new();
+        for window in &self.windows {
+            for (symbol, stats) in &window.markets {
+                by_symbol.entry(symbol.clone()).or_default().absorb(stats);
+            }
+        }
+        by_symbol
+    }
+
+    /// 按窗口累计盈亏的最大回撤（USD，非负）
+    pub fn max_drawdown(&self) -> Decimal {
+        let mut equity = dec!(0);
+        let mut peak = dec!(0);
+        let mut max_dd = dec!(0);
+        for window in &self.windows {
+            equity += window.total().pnl;
+            peak = peak.max(equity);
+            max_dd = max_dd.max(peak - equity);
+        }
+        max_dd
+    }
+}
+
+const HEADER: &str = "机会  拦截  下单  双边  单边  未成交  成交率   merge      单边剩余   净盈亏(USD)";
+
+fn write_row(f: &mut fmt::Formatter<'_>, label: &str, s: &BacktestStats) -> fmt::Result {
+    writeln!(
+        f,
+        "{:<12} {:>4} {:>5} {:>5} {:>5} {:>5} {:>7} {:>6.1}% {:>10.2} {:>10.2} {:>+12.4}",
+        label,
+        s.opportunities,
+        s.skipped,
+        s.pairs,
+        s.both_filled,
+        s.one_sided,
+        s.unfilled,
+        s.fill_rate() * dec!(100),
+        s.merged,
+        s.unhedged,
+        s.pnl
+    )
+}
+
+impl fmt::Display for BacktestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "===== 按窗口 =====")?;
+        writeln!(f, "{:<12} {}", "窗口", HEADER)?;
+        for window in &self.windows {
+            write_row(f, &window.window_ts.to_string(), &window.total())?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "===== 按币种 =====")?;
+        writeln!(f, "{:<12} {}", "币种", HEADER)?;
+        for (symbol, stats) in self.by_symbol() {
+            write_row(f, &symbol, &stats)?;
+        }
+
+        let total = self.total();
+        writeln!(f)?;
+        write_row(f, "合计", &total)?;
+        write!(
+            f,
+            "窗口数:{} | 单边成交率:{:.1}% | 最大回撤:{:.4} USD",
+            self.windows.len(),
+            total.one_sided_rate() * dec!(100),
+            self.max_drawdown()
+        )
+    }
+}
@@ -0,0 +1,214 @@
+//! 参数扫描：对执行价差、滑点、单笔上限、YES/NO 最低价格、临近结束停止时间做网格组合，
+//! 每组参数在同一批录制数据上完整回测（多核并行），按盈亏、最大回撤、单边成交率排序并输出 CSV。
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use polymarket_client_sdk::types::Decimal;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use super::engine::{run_backtest, BacktestOptions, WindowData};
+use crate::config::Config as BotConfig;
+
+/// 一组待扫描的参数（对应 .env 中的同名配置）
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepParams {
+    pub arbitrage_execution_spread: f64,
+    pub slippage: [f64; 2],
+    pub max_order_size_usdc: f64,
+    pub min_yes_price_threshold: f64,
+    pub min_no_price_threshold: f64,
+    pub stop_arbitrage_before_end_minutes: u64,
+}
+
+impl SweepParams {
+    /// 在基础配置上覆盖本组参数
+    pub fn apply(&self, base: &BotConfig) -> BotConfig {
+        let mut config = base.clone();
+        config.arbitrage_execution_spread = self.arbitrage_execution_spread;
+        config.slippage = self.slippage;
+        config.max_order_size_usdc = self.max_order_size_usdc;
+        config.min_yes_price_threshold = self.min_yes_price_threshold;
+        config.min_no_price_threshold = self.min_no_price_threshold;
+        config.stop_arbitrage_before_end_minutes = self.stop_arbitrage_before_end_minutes;
+        config
+    }
+}
+
+/// 各参数的取值列表，grid() 生成笛卡尔积
+#[derive(Debug, Clone)]
+pub struct SweepGrid {
+    pub spreads: Vec<f64>,
+    pub slippages: Vec<[f64; 2]>,
+    pub max_order_sizes: Vec<f64>,
+    pub min_yes_prices: Vec<f64>,
+    pub min_no_prices: Vec<f64>,
+    pub stop_minutes: Vec<u64>,
+}
+
+impl SweepGrid {
+    /// 未指定的参数使用基础配置中的值
+    pub fn from_config(config: &BotConfig) -> Self {
+        Self {
+            spreads: vec![config.arbitrage_execution_spread],
+            slippages: vec![config.slippage],
+            max_order_sizes: vec![config.max_order_size_usdc],
+            min_yes_prices: vec![config.min_yes_price_threshold],
+            min_no_prices: vec![config.min_no_price_threshold],
+            stop_minutes: vec![config.stop_arbitrage_before_end_minutes],
+        }
+    }
+
+    pub fn combinations(&self) -> Vec<SweepParams> {
+        let mut out = Vec::new();
+        for &spread in &self.spreads {
+            for &slippage in &self.slippages {
+                for &max_order_size in &self.max_order_sizes {
+                    for &min_yes in &self.min_yes_prices {
+                        for &min_no in &self.min_no_prices {
+                            for &stop in &self.stop_minutes {
+                                out.push(SweepParams {
+                                    arbitrage_execution_spread: spread,
+                                    slippage,
+                                    max_order_size_usdc: max_order_size,
+                                    min_yes_price_threshold: min_yes,
+                                    min_no_price_threshold: min_no,
+                                    stop_arbitrage_before_end_minutes: stop,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// 一组参数的回测结果
+#[derive(Debug, Clone)]
+pub struct SweepResult {
+    pub params: SweepParams,
+    pub pnl: Decimal,
+    pub max_drawdown: Decimal,
+    pub one_sided_rate: Decimal,
+    pub fill_rate: Decimal,
+    pub pairs: u64,
+    pub one_sided: u64,
+    pub merged: Decimal,
+}
+
+/// 排序依据：主键之外依次用其余两项打破平局
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankBy {
+    Pnl,       // 盈亏从高到低
+    Drawdown,  // 最大回撤从低到高
+    OneSided,  // 单边成交率从低到高
+}
+
+impl std::str::FromStr for RankBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "pnl" => Ok(RankBy::Pnl),
+            "drawdown" => Ok(RankBy::Drawdown),
+            "one_sided" | "one-sided" => Ok(RankBy::OneSided),
+            other => anyhow::bail!("未知排序依据: {}（可选 pnl / drawdown / one_sided）", other),
+        }
+    }
+}
+
+pub fn rank(results: &mut [SweepResult], by: RankBy) {
+    let pnl = |a: &SweepResult, b: &SweepResult| b.pnl.cmp(&a.pnl);
+    let drawdown = |a: &SweepResult, b: &SweepResult| a.max_drawdown.cmp(&b.max_drawdown);
+    let one_sided = |a: &SweepResult, b: &SweepResult| a.one_sided_rate.cmp(&b.one_sided_rate);
+    results.sort_by(|a, b| -> Ordering {
+        match by {
+            RankBy::Pnl => pnl(a, b).then(drawdown(a, b)).then(one_sided(a, b)),
+            RankBy::Drawdown => drawdown(a, b).then(pnl(a, b)).then(one_sided(a, b)),
+            RankBy::OneSided => one_sided(a, b).then(pnl(a, b)).then(drawdown(a, b)),
+        }
+    });
+}
+
+/// 并行回测所有参数组合；parallelism 为同时运行的回测数（通常取 CPU 核数）
+pub async fn run_sweep(
+    base: &BotConfig,
+    windows: Arc<Vec<WindowData>>,
+    options: &BacktestOptions,
+    combinations: Vec<SweepParams>,
+    parallelism: usize,
+) -> Vec<SweepResult> {
+    let total = combinations.len();
+    let tasks = combinations.into_iter().map(|params| {
+        let config = params.apply(base);
+        let windows = windows.clone();
+        let options = options.clone();
+        tokio::spawn(async move {
+            let report = run_backtest(&config, &windows, &options).await;
+            let stats = report.total();
+            SweepResult {
+                params,
+                pnl: stats.pnl,
+                max_drawdown: report.max_drawdown(),
+                one_sided_rate: stats.one_sided_rate(),
+                fill_rate: stats.fill_rate(),
+                pairs: stats.pairs,
+                one_sided: stats.one_sided,
+                merged: stats.merged,
+            }
+        })
+    });
+
+    let mut results = Vec::with_capacity(total);
+    let mut stream = futures::stream::iter(tasks).buffer_unordered(parallelism.max(1));
+    while let Some(joined) = stream.next().await {
+        match joined {
+            Ok(result) => {
+                results.push(result);
+                info!("参数扫描进度 {}/{}", results.len(), total);
+            }
+            Err(e) => warn!(error = %e, "参数扫描：回测任务异常退出"),
+        }
+    }
+    results
+}
+
+/// 按当前顺序写出 CSV（第一列为名次）
+pub fn write_csv(path: &Path, results: &[SweepResult]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("创建 CSV 失败: {}", path.display()))?;
+    let mut w = BufWriter::new(file);
+    writeln!(
+        w,
+        "rank,arbitrage_execution_spread,slippage_first,slippage_second,max_order_size_usdc,min_yes_price_threshold,min_no_price_threshold,stop_arbitrage_before_end_minutes,pnl,max_drawdown,one_sided_rate,fill_rate,pairs,one_sided,merged"
+    )?;
+    for (i, r) in results.iter().enumerate() {
+        let p = &r.params;
+        writeln!(
+            w,
+            "{},{},{},{},{},{},{},{},{:.4},{:.4},{:.4},{:.4},{},{},{:.2}",
+            i + 1,
+            p.arbitrage_execution_spread,
+            p.slippage[0],
+            p.slippage[1],
+            p.max_order_size_usdc,
+            p.min_yes_price_threshold,
+            p.min_no_price_threshold,
+            p.stop_arbitrage_before_end_minutes,
+            r.pnl,
+            r.max_drawdown,
+            r.one_sided_rate,
+            r.fill_rate,
+            r.pairs,
+            r.one_sided,
+            r.merged
+        )?;
+    }
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! 回测工具：回放 RECORD_BOOKS_DIR 录制的订单簿，按 .env 中的策略参数模拟下单，输出按窗口、按币种的报告。
+//!
+//! 用法示例：
+//!   cargo run --release --bin backtest -- --dir data/books
+//!   cargo run --release --bin backtest -- --dir data/books --from 1770972300 --to 1770975900 --latency-ms 150
+
+use anyhow::{Context, Result};
+use poly_5min_bot::backtest::{load_windows, run_backtest, BacktestOptions};
+use poly_5min_bot::config::Config;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::env;
+use std::path::PathBuf;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // 回测时执行器的逐笔日志过多，默认只输出 warn 及以上
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")))
+        .init();
+
+    let config = Config::from_env_offline()?;
+
+    let args: Vec<String> = env::args().collect();
+    let mut dir: Option<PathBuf> = config.record_books_dir.as_ref().map(PathBuf::from);
+    let mut from: Option<i64> = None;
+    let mut to: Option<i64> = None;
+    let mut latency_ms: i64 = 100;
+    let mut initial_usdc = Decimal::try_from(config.dry_run_initial_usdc).unwrap_or(dec!(1000.0));
+
+    let mut i = 1;
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match args[i].as_str() {
+            "--dir" => dir = Some(value.context("--dir 需要参数")?.into()),
+            "--from" => from = Some(value.context("--from 需要参数")?.parse().context("--from 必须为 Unix 秒")?),
+            "--to" => to = Some(value.context("--to 需要参数")?.parse().context("--to 必须为 Unix 秒")?),
+            "--latency-ms" => {
+                latency_ms = value
+                    .context("--latency-ms 需要参数")?
+                    .parse()
+                    .context("--latency-ms 必须为整数")?
+            }
+            "--initial-usdc" => {
+                initial_usdc = value
+                    .context("--initial-usdc 需要参数")?
+                    .parse()
+                    .context("--initial-usdc 必须为数字")?
+            }
+            _ => {
+                eprintln!("用法: backtest [--dir DIR] [--from TS] [--to TS] [--latency-ms N] [--initial-usdc N]");
+                eprintln!("  --dir DIR          录制目录，默认 RECORD_BOOKS_DIR");
+                eprintln!("  --from / --to TS   只回放窗口时间戳（Unix 秒）在范围内的文件");
+                eprintln!("  --latency-ms N     下单到达交易所的延迟，默认 100");
+                eprintln!("  --initial-usdc N   每个窗口模拟账户初始 USDC，默认 DRY_RUN_INITIAL_USDC");
+                std::process::exit(1);
+            }
+        }
+        i += 2;
+    }
+
+    let dir = dir.context("请通过 --dir 或 RECORD_BOOKS_DIR 指定录制目录")?;
+    let windows = load_windows(&dir, from, to)?;
+    if windows.is_empty() {
+        anyhow::bail!("录制目录 {} 中没有符合条件的窗口文件", dir.display());
+    }
+    eprintln!(
+        "回放 {} 个窗口 | 执行价差:{} | 单笔上限:{} USDC | 订单类型:{} | 延迟:{}ms",
+        windows.len(),
+        config.arbitrage_execution_spread,
+        config.max_order_size_usdc,
+        config.arbitrage_order_type,
+        latency_ms
+    );
+
+    let options = BacktestOptions { latency_ms, initial_usdc };
+    let report = run_backtest(&config, &windows, &options).await;
+    println!("{}", report);
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! 参数扫描工具：在录制的订单簿上对多组策略参数并行回测，按盈亏 / 回撤 / 单边成交率排序并写出 CSV。
+//!
+//! 取值写法：逗号分隔的列表（0.01,0.02）或 起:止:步长（0.005:0.03:0.005）；滑点每组为 "first,second"，多组用 ; 分隔。
+//! 未指定的参数沿用 .env 中的配置。
+//!
+//! 用法示例：
+//!   cargo run --release --bin sweep -- --dir data/books --spread 0.005:0.03:0.005 --max-order-size 5,10,20
+//!   cargo run --release --bin sweep -- --dir data/books --slippage "0,0.01;0.01,0.02" --stop-minutes 0,1,2 --rank drawdown --out sweep.csv
+
+use anyhow::{Context, Result};
+use poly_5min_bot::backtest::{load_windows, rank, run_sweep, write_csv, BacktestOptions, RankBy, SweepGrid};
+use poly_5min_bot::config::{parse_slippage, Config};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+
+/// 解析取值：列表 "a,b,c" 或范围 "start:end:step"（含端点）
+fn parse_values(s: &str) -> Result<Vec<f64>> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() == 3 {
+        let start: f64 = parts[0].trim().parse().context("范围起点必须为数字")?;
+        let end: f64 = parts[1].trim().parse().context("范围终点必须为数字")?;
+        let step: f64 = parts[2].trim().parse().context("范围步长必须为数字")?;
+        if step <= 0.0 || end < start {
+            anyhow::bail!("范围无效: {}（需要 起点 <= 终点 且 步长 > 0）", s);
+        }
+        // 按步数生成并四舍五入，避免浮点累加误差
+        let steps = ((end - start) / step + 1e-9).floor() as usize;
+        return Ok((0..=steps)
+            .map(|i| ((start + i as f64 * step) * 1e6).round() / 1e6)
+            .collect());
+    }
+    s.split(',')
+        .map(|x| x.trim().parse::<f64>().with_context(|| format!("无效数值: {}", x)))
+        .collect()
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn,poly_5min_bot::backtest::sweep=info")))
+        .init();
+
+    let config = Config::from_env_offline()?;
+    let mut grid = SweepGrid::from_config(&config);
+
+    let args: Vec<String> = env::args().collect();
+    let mut dir: Option<PathBuf> = config.record_books_dir.as_ref().map(PathBuf::from);
+    let mut from: Option<i64> = None;
+    let mut to: Option<i64> = None;
+    let mut latency_ms: i64 = 100;
+    let mut initial_usdc = Decimal::try_from(config.dry_run_initial_usdc).unwrap_or(dec!(1000.0));
+    let mut rank_by = RankBy::Pnl;
+    let mut out = PathBuf::from("sweep_results.csv");
+    let mut parallelism = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = args.get(i + 1).with_context(|| format!("{} 需要参数", flag));
+        match flag {
+            "--dir" => dir = Some(value?.into()),
+            "--from" => from = Some(value?.parse().context("--from 必须为 Unix 秒")?),
+            "--to" => to = Some(value?.parse().context("--to 必须为 Unix 秒")?),
+            "--latency-ms" => latency_ms = value?.parse().context("--latency-ms 必须为整数")?,
+            "--initial-usdc" => initial_usdc = value?.parse().context("--initial-usdc 必须为数字")?,
+            "--spread" => grid.spreads = parse_values(value?)?,
+            "--slippage" => grid.slippages = value?.split(';').map(parse_slippage).collect(),
+            "--max-order-size" => grid.max_order_sizes = parse_values(value?)?,
+            "--min-yes-price" => grid.min_yes_prices = parse_values(value?)?,
+            "--min-no-price" => grid.min_no_prices = parse_values(value?)?,
+            "--stop-minutes" => {
+                grid.stop_minutes = parse_values(value?)?.into_iter().map(|v| v.max(0.0) as u64).collect()
+            }
+            "--rank" => rank_by = value?.parse()?,
+            "--out" => out = value?.into(),
+            "--jobs" => parallelism = value?.parse().context("--jobs 必须为正整数")?,
+            _ => {
+                eprintln!("用法: sweep [--dir DIR] [--from TS] [--to TS] [--latency-ms N] [--initial-usdc N]");
+                eprintln!("             [--spread V] [--slippage \"a,b;c,d\"] [--max-order-size V]");
+                eprintln!("             [--min-yes-price V] [--min-no-price V] [--stop-minutes V]");
+                eprintln!("             [--rank pnl|drawdown|one_sided] [--out FILE] [--jobs N]");
+                eprintln!("  V 为逗号分隔列表（0.01,0.02）或 起:止:步长（0.005:0.03:0.005），未指定的参数沿用 .env");
+                std::process::exit(1);
+            }
+        }
+        i += 2;
+    }
+
+    let dir = dir.context("请通过 --dir 或 RECORD_BOOKS_DIR 指定录制目录")?;
+    let windows = load_windows(&dir, from, to)?;
+    if windows.is_empty() {
+        anyhow::bail!("录制目录 {} 中没有符合条件的窗口文件", dir.display());
+    }
+
+    let combinations = grid.combinations();
+    eprintln!(
+        "参数组合 {} 组 × {} 个窗口 | 并行 {} | 延迟:{}ms",
+        combinations.len(),
+        windows.len(),
+        parallelism,
+        latency_ms
+    );
+
+    let options = BacktestOptions { latency_ms, initial_usdc };
+    let mut results = run_sweep(&config, Arc::new(windows), &options, combinations, parallelism).await;
+    rank(&mut results, rank_by);
+    write_csv(&out, &results)?;
+
+    println!("{:<4} {:>7} {:>13} {:>8} {:>7} {:>7} {:>5} {:>12} {:>10} {:>8}", "名次", "价差", "滑点", "单笔上限", "YES下限", "NO下限", "停止", "净盈亏", "最大回撤", "单边率");
+    for (i, r) in results.iter().take(10).enumerate() {
+        let p = &r.params;
+        println!(
+            "{:<4} {:>7} {:>13} {:>8} {:>7} {:>7} {:>5} {:>+12.4} {:>10.4} {:>7.1}%",
+            i + 1,
+            p.arbitrage_execution_spread,
+            format!("{},{}", p.slippage[0], p.slippage[1]),
+            p.max_order_size_usdc,
+            p.min_yes_price_threshold,
+            p.min_no_price_threshold,
+            p.stop_arbitrage_before_end_minutes,
+            r.pnl,
+            r.max_drawdown,
+            r.one_sided_rate * dec!(100)
+        );
+    }
+    eprintln!("已写入: {}", out.display());
+    Ok(())
+}
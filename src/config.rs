@@ -1,6 +1,7 @@
 use anyhow::Result;
 use polymarket_client_sdk::clob::types::OrderType;
 use polymarket_client_sdk::types::Address;
+use std::collections::HashMap;
 use std::env;
 
 /* ============================================================
@@ -49,7 +50,7 @@ fn parse_arbitrage_order_type(s: &str) -> OrderType {
     }
 }
 
-fn parse_slippage(s: &str) -> [f64; 2] {
+pub fn parse_slippage(s: &str) -> [f64; 2] {
     let parts: Vec<f64> = s
         .split(',')
         .map(|x| x.trim().parse().unwrap_or(0.0))
@@ -62,6 +63,18 @@ fn parse_slippage(s: &str) -> [f64; 2] {
     }
 }
 
+/// 按币种的上限，格式 `btc:300,eth:200`；无法解析的项忽略
+pub fn parse_symbol_limits(s: &str) -> HashMap<String, f64> {
+    s.split(',')
+        .filter_map(|item| {
+            let (symbol, limit) = item.split_once(':')?;
+            let limit = limit.trim().parse::<f64>().ok()?;
+            Some((symbol.trim().to_lowercase(), limit))
+        })
+        .filter(|(symbol, _)| !symbol.is_empty())
+        .collect()
+}
+
 /* ============================================================
    Config struct
    ============================================================ */
@@ -72,17 +85,32 @@ pub struct Config {
     pub proxy_address: Option<Address>,
 
     pub min_profit_threshold: f64,
+    pub opportunity_cooldown_secs: u64,
     pub max_order_size_usdc: f64,
     pub crypto_symbols: Vec<String>,
     pub market_refresh_advance_secs: u64,
 
     pub risk_max_exposure_usdc: f64,
     pub risk_imbalance_threshold: f64,
+    pub risk_max_exposure_per_symbol_usdc: f64,   // 单个币种的敞口上限，0 表示不限制
+    pub risk_max_exposure_by_symbol: HashMap<String, f64>, // 按币种覆盖上一项
+    pub risk_max_exposure_per_market_usdc: f64,   // 单个市场（condition_id）的敞口上限，0 表示不限制
+    pub risk_max_unhedged_usdc: f64,              // 未对冲单腿持仓的上限，0 表示不限制
+    pub risk_max_daily_loss_usdc: f64,        // 当日亏损上限（已实现+未实现），0 表示不限制
+    pub risk_max_drawdown_usdc: f64,          // 自当日盈亏峰值的最大回撤，0 表示不限制
+    pub risk_max_consecutive_one_sided: u32,  // 连续单边成交的订单对上限，0 表示不限制
 
     pub hedge_take_profit_pct: f64,
     pub hedge_stop_loss_pct: f64,
+    pub recovery_policy: String,       // 单边 / 不平衡成交的恢复策略：none | chase | monitor | sell
+    pub recovery_chase_tolerance: f64, // chase：已成交价 + 补单价 + 每对手续费损耗 <= 1 + 容差
+    pub recovery_chase_cutoff_secs: u64, // chase：窗口结束前多少秒停止补单并卖出已成交一腿
+
+    pub taker_fee_rate: f64,
+    pub taker_fee_exponent: f64,
 
     pub arbitrage_execution_spread: f64,
+    pub enable_sell_arbitrage: bool,
     pub slippage: [f64; 2],
 
     pub gtd_expiration_secs: u64,
@@ -90,6 +118,7 @@ pub struct Config {
     pub stop_arbitrage_before_end_minutes: u64,
 
     pub merge_interval_minutes: u64,
+    pub redeem_interval_minutes: u64, // 定时赎回已结算市场持仓的间隔，0 表示不启用
 
     pub min_yes_price_threshold: f64,
     pub min_no_price_threshold: f64,
@@ -108,8 +137,20 @@ pub struct Config {
     pub scalp_take_profit_pct: f64,
     pub scalp_stop_loss_pct: f64,
     pub scalp_max_hold_seconds: u64,
+    pub scalp_signal_pct: f64, // YES 中间价相对上一拍变动达到该百分比时入场
+
+    pub max_trades_per_day: u32, // 0 表示不限制
+    pub trade_budget_reset_hour_utc: u32,
+
+    // ===== dry run =====
+    pub dry_run: bool,
+    pub dry_run_initial_usdc: f64,
+
+    // ===== 订单簿录制 =====
+    pub record_books_dir: Option<String>, // 为空则不录制
 
-    pub max_trades_per_day: u32,
+    // ===== 交易台账 =====
+    pub ledger_db_path: Option<String>, // SQLite 台账文件，设为空则不记录
 }
 
 /* ============================================================
@@ -120,17 +161,30 @@ impl Config {
     pub fn from_env() -> Result<Self> {
         dotenvy::dotenv().ok();
 
+        let private_key = env::var("POLYMARKET_PRIVATE_KEY")
+            .expect("POLYMARKET_PRIVATE_KEY must be set");
+        Self::load(private_key)
+    }
+
+    /// 不需要私钥的离线配置（回测、参数扫描），私钥留空
+    pub fn from_env_offline() -> Result<Self> {
+        dotenvy::dotenv().ok();
+
+        Self::load(env::var("POLYMARKET_PRIVATE_KEY").unwrap_or_default())
+    }
+
+    fn load(private_key: String) -> Result<Self> {
         let proxy_address: Option<Address> = env::var("POLYMARKET_PROXY_ADDRESS")
             .ok()
             .and_then(|v| v.parse().ok());
 
         Ok(Self {
-            private_key: env::var("POLYMARKET_PRIVATE_KEY")
-                .expect("POLYMARKET_PRIVATE_KEY must be set"),
+            private_key,
 
             proxy_address,
 
             min_profit_threshold: env_f64("MIN_PROFIT_THRESHOLD", 0.001),
+            opportunity_cooldown_secs: env_u64("OPPORTUNITY_COOLDOWN_SECS", 10),
             max_order_size_usdc: env_f64("MAX_ORDER_SIZE_USDC", 100.0),
 
             crypto_symbols: env::var("CRYPTO_SYMBOLS")
@@ -143,11 +197,33 @@ impl Config {
 
             risk_max_exposure_usdc: env_f64("RISK_MAX_EXPOSURE_USDC", 1000.0),
             risk_imbalance_threshold: env_f64("RISK_IMBALANCE_THRESHOLD", 0.1),
+            risk_max_exposure_per_symbol_usdc: env_f64("RISK_MAX_EXPOSURE_PER_SYMBOL_USDC", 0.0),
+            risk_max_exposure_by_symbol: parse_symbol_limits(
+                &env::var("RISK_MAX_EXPOSURE_BY_SYMBOL").unwrap_or_default(),
+            ),
+            risk_max_exposure_per_market_usdc: env_f64("RISK_MAX_EXPOSURE_PER_MARKET_USDC", 0.0),
+            risk_max_unhedged_usdc: env_f64("RISK_MAX_UNHEDGED_USDC", 0.0),
+            risk_max_daily_loss_usdc: env_f64("RISK_MAX_DAILY_LOSS_USDC", 0.0),
+            risk_max_drawdown_usdc: env_f64("RISK_MAX_DRAWDOWN_USDC", 0.0),
+            risk_max_consecutive_one_sided: env_u32("RISK_MAX_CONSECUTIVE_ONE_SIDED", 0),
 
             hedge_take_profit_pct: env_f64("HEDGE_TAKE_PROFIT_PCT", 0.05),
             hedge_stop_loss_pct: env_f64("HEDGE_STOP_LOSS_PCT", 0.05),
+            recovery_policy: env::var("RECOVERY_POLICY")
+                .unwrap_or_else(|_| "none".to_string())
+                .trim()
+                .to_lowercase(),
+            recovery_chase_tolerance: env_f64("RECOVERY_CHASE_TOLERANCE", 0.0),
+            recovery_chase_cutoff_secs: env::var("RECOVERY_CHASE_CUTOFF_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(60),
+
+            taker_fee_rate: env_f64("TAKER_FEE_RATE", 0.25),
+            taker_fee_exponent: env_f64("TAKER_FEE_EXPONENT", 2.0),
 
             arbitrage_execution_spread: env_f64("ARBITRAGE_EXECUTION_SPREAD", 0.01),
+            enable_sell_arbitrage: env_bool("ENABLE_SELL_ARBITRAGE", false),
 
             slippage: parse_slippage(
                 &env::var("SLIPPAGE").unwrap_or_else(|_| "0,0.01".to_string()),
@@ -165,6 +241,7 @@ impl Config {
             ),
 
             merge_interval_minutes: env_u64("MERGE_INTERVAL_MINUTES", 0),
+            redeem_interval_minutes: env_u64("REDEEM_INTERVAL_MINUTES", 0),
 
             min_yes_price_threshold: env_f64("MIN_YES_PRICE_THRESHOLD", 0.0),
             min_no_price_threshold: env_f64("MIN_NO_PRICE_THRESHOLD", 0.0),
@@ -195,8 +272,29 @@ impl Config {
             scalp_take_profit_pct: env_f64("SCALP_TAKE_PROFIT_PCT", 1.0),
             scalp_stop_loss_pct: env_f64("SCALP_STOP_LOSS_PCT", 0.5),
             scalp_max_hold_seconds: env_u64("SCALP_MAX_HOLD_SECONDS", 90),
+            scalp_signal_pct: env_f64("SCALP_SIGNAL_PCT", 2.0),
 
             max_trades_per_day: env_u32("MAX_TRADES_PER_DAY", 5),
+            trade_budget_reset_hour_utc: env_u32("TRADE_BUDGET_RESET_HOUR_UTC", 0).min(23),
+
+            // ===== dry run =====
+            dry_run: env_bool("DRY_RUN", false),
+            dry_run_initial_usdc: env_f64("DRY_RUN_INITIAL_USDC", 1000.0),
+
+            // ===== 订单簿录制 =====
+            record_books_dir: env::var("RECORD_BOOKS_DIR")
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()),
+
+            // ===== 交易台账 =====
+            ledger_db_path: Some(
+                env::var("LEDGER_DB_PATH")
+                    .unwrap_or_else(|_| "trade_ledger.db".to_string())
+                    .trim()
+                    .to_string(),
+            )
+            .filter(|v| !v.is_empty()),
         })
     }
 }
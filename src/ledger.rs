@@ -0,0 +1,531 @@
+//! 交易台账：把下单、成交、撤单、split、merge、收尾卖出与 redeem 逐条写入 SQLite（默认 `trade_ledger.db`），
+//! 每行带 pair_id、market_id、slug、币种与窗口时间戳，供对账与事后复盘直接查询，不必再翻 LOG_FILE。
+//!
+//! 写库在独立的阻塞线程中进行；调用方只做一次无界通道发送，不阻塞下单与订单簿处理。
+//! 未开启（LEDGER_DB_PATH 为空）时不写库；挂接了盈亏引擎时，每条记录同时同步计入盈亏。
+//! 启动时把当日（UTC）已写入的记录重放进盈亏引擎（replay_into），重启后当日盈亏与风控熔断的亏损检查从库中接续。
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use polymarket_client_sdk::clob::types::Side;
+use rust_decimal_macros::dec;
+use polymarket_client_sdk::types::{B256, Decimal, U256};
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::market::MarketInfo;
+use crate::risk::PnlEngine;
+use crate::trading::exchange::{OrderAck, OrderRequest};
+use crate::trading::FeeModel;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS ledger (
+    id        INTEGER PRIMARY KEY AUTOINCREMENT,
+    ts_ms     INTEGER NOT NULL,
+    kind      TEXT    NOT NULL,
+    pair_id   TEXT,
+    market_id TEXT,
+    slug      TEXT,
+    symbol    TEXT,
+    window_ts INTEGER,
+    order_id  TEXT,
+    token_id  TEXT,
+    side      TEXT,
+    price     TEXT,
+    size      TEXT,
+    amount    TEXT,
+    fee       TEXT,
+    tx_hash   TEXT,
+    note      TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_ledger_pair   ON ledger(pair_id);
+CREATE INDEX IF NOT EXISTS idx_ledger_market ON ledger(market_id);
+CREATE INDEX IF NOT EXISTS idx_ledger_window ON ledger(window_ts);
+";
+
+/// 台账记录类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerKind {
+    OrderSubmitted, // 订单已提交（含失败回执）
+    Fill,           // 成交（size 为本次新增成交份额）
+    Cancel,         // 未成交部分撤销或过期（size 为撤销份额）
+    Split,          // USDC 拆分为 YES+NO
+    Merge,          // YES+NO 合并回 USDC
+    WindDownSell,   // 收尾卖出单腿持仓
+    Redeem,         // 市场结算后赎回
+}
+
+impl LedgerKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LedgerKind::OrderSubmitted => "order_submitted",
+            LedgerKind::Fill => "fill",
+            LedgerKind::Cancel => "cancel",
+            LedgerKind::Split => "split",
+            LedgerKind::Merge => "merge",
+            LedgerKind::WindDownSell => "wind_down_sell",
+            LedgerKind::Redeem => "redeem",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [
+            LedgerKind::OrderSubmitted,
+            LedgerKind::Fill,
+            LedgerKind::Cancel,
+            LedgerKind::Split,
+            LedgerKind::Merge,
+            LedgerKind::WindDownSell,
+            LedgerKind::Redeem,
+        ]
+        .into_iter()
+        .find(|k| k.as_str() == s)
+    }
+}
+
+/// 一条台账记录；market_id 未给出时按 token_id 反查所属市场
+#[derive(Debug, Clone)]
+pub struct LedgerEntry {
+    pub kind: LedgerKind,
+    pub pair_id: Option<String>,
+    pub market_id: Option<B256>,
+    pub order_id: Option<String>,
+    pub token_id: Option<U256>,
+    pub side: Option<Side>,
+    pub price: Option<Decimal>,
+    pub size: Option<Decimal>,
+    pub amount: Option<Decimal>, // USDC 金额：买入为支付、卖出为收到、split/merge/redeem 为对应 USDC
+    pub fee: Option<Decimal>,    // 手续费（USD），买入时以份额扣除、卖出时从所得 USDC 扣除
+    pub tx_hash: Option<String>,
+    pub note: Option<String>,
+}
+
+impl LedgerEntry {
+    pub fn new(kind: LedgerKind) -> Self {
+        Self {
+            kind,
+            pair_id: None,
+            market_id: None,
+            order_id: None,
+            token_id: None,
+            side: None,
+            price: None,
+            size: None,
+            amount: None,
+            fee: None,
+            tx_hash: None,
+            note: None,
+        }
+    }
+
+    pub fn with_pair(mut self, pair_id: impl Into<String>) -> Self {
+        self.pair_id = Some(pair_id.into());
+        self
+    }
+
+    pub fn with_market(mut self, market_id: B256) -> Self {
+        self.market_id = Some(market_id);
+        self
+    }
+
+    /// 空订单号（下单失败时回执可能为空）不记录
+    pub fn with_order(mut self, order_id: impl Into<String>) -> Self {
+        self.order_id = Some(order_id.into()).filter(|id| !id.is_empty());
+        self
+    }
+
+    pub fn with_token(mut self, token_id: U256) -> Self {
+        self.token_id = Some(token_id);
+        self
+    }
+
+    pub fn with_trade(mut self, side: Side, price: Decimal, size: Decimal) -> Self {
+        self.side = Some(side);
+        self.price = Some(price);
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_size(mut self, size: Decimal) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_amount(mut self, amount: Decimal) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    pub fn with_fee(mut self, fee: Decimal) -> Self {
+        self.fee = Some(fee);
+        self
+    }
+
+    pub fn with_tx(mut self, tx_hash: impl Into<String>) -> Self {
+        self.tx_hash = Some(tx_hash.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+}
+
+/// 记录所属市场（由 start_window 登记）
+#[derive(Debug, Clone)]
+pub struct LedgerMarket {
+    pub market_id: B256,
+    pub slug: String,
+    pub symbol: String,
+    pub window_ts: i64,
+    pub yes_token_id: U256,
+    pub no_token_id: U256,
+}
+
+/// 已见过的市场与 token 索引；跨窗口保留，上一窗口的 merge / 收尾记录仍能关联到市场
+#[derive(Default)]
+struct MarketIndex {
+    markets: HashMap<B256, LedgerMarket>,
+    tokens: HashMap<U256, B256>,
+}
+
+struct LedgerRow {
+    ts: DateTime<Utc>,
+    entry: LedgerEntry,
+    market_id: Option<B256>,
+    market: Option<LedgerMarket>,
+}
+
+/// 台账句柄：可 clone，所有 clone 共用同一个后台写线程
+#[derive(Clone)]
+pub struct TradeLedger {
+    tx: Option<mpsc::UnboundedSender<LedgerRow>>,
+    index: Arc<RwLock<MarketIndex>>,
+    pnl: Option<Arc<PnlEngine>>,
+}
+
+impl TradeLedger {
+    /// 不记录的空实现（未配置台账或回测时使用）
+    pub fn disabled() -> Self {
+        Self {
+            tx: None,
+            index: Arc::new(RwLock::new(MarketIndex::default())),
+            pnl: None,
+        }
+    }
+
+    /// 打开（不存在则创建）台账数据库并启动后台写线程
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let conn = open_db(&path)?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        info!(path = %path.display(), "📒 交易台账已开启");
+        tokio::task::spawn_blocking(move || run_writer(conn, rx));
+        Ok(Self {
+            tx: Some(tx),
+            index: Arc::new(RwLock::new(MarketIndex::default())),
+            pnl: None,
+        })
+    }
+
+    /// 挂接盈亏引擎：成交、merge、收尾卖出与 redeem 记录同时计入盈亏（不依赖是否写库）
+    pub fn with_pnl(mut self, pnl: Arc<PnlEngine>) -> Self {
+        self.pnl = Some(pnl);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    /// 进入新窗口：登记该窗口的市场，之后的记录据此补全 slug、币种与窗口时间戳
+    pub fn start_window(&self, window_ts: i64, markets: &[MarketInfo]) {
+        let mut index = self.index.write().unwrap();
+        for m in markets {
+            index.markets.insert(
+                m.market_id,
+                LedgerMarket {
+                    market_id: m.market_id,
+                    slug: m.slug.clone(),
+                    symbol: m.crypto_symbol.clone(),
+                    window_ts,
+                    yes_token_id: m.yes_token_id,
+                    no_token_id: m.no_token_id,
+                },
+            );
+            index.tokens.insert(m.yes_token_id, m.market_id);
+            index.tokens.insert(m.no_token_id, m.market_id);
+        }
+    }
+
+    /// 记录一条台账（非阻塞）
+    pub fn record(&self, entry: LedgerEntry) {
+        if self.tx.is_none() && self.pnl.is_none() {
+            return;
+        }
+        let (market_id, market) = {
+            let index = self.index.read().unwrap();
+            let market_id = entry
+                .market_id
+                .or_else(|| entry.token_id.and_then(|t| index.tokens.get(&t).copied()));
+            let market = market_id.and_then(|m| index.markets.get(&m).cloned());
+            (market_id, market)
+        };
+        let ts = Utc::now();
+        if let Some(pnl) = &self.pnl {
+            pnl.on_entry(&entry, market.as_ref(), ts);
+        }
+        let Some(tx) = &self.tx else { return };
+        let row = LedgerRow {
+            ts,
+            entry,
+            market_id,
+            market,
+        };
+        if tx.send(row).is_err() {
+            warn!("交易台账写线程已退出，记录丢弃");
+        }
+    }
+
+    /// 记录一笔下单回执（kind 为下单或收尾卖出）；有即时成交时另记一条成交，
+    /// 成交均价按回执金额计算，即时成交按吃单计手续费
+    pub fn record_order(
+        &self,
+        kind: LedgerKind,
+        pair_id: Option<&str>,
+        market_id: Option<B256>,
+        request: &OrderRequest,
+        ack: &OrderAck,
+        fee_model: &FeeModel,
+    ) {
+        let base = |kind| {
+            let mut entry = LedgerEntry::new(kind).with_order(ack.order_id.clone()).with_token(request.token_id);
+            if let Some(pair_id) = pair_id {
+                entry = entry.with_pair(pair_id);
+            }
+            if let Some(market_id) = market_id {
+                entry = entry.with_market(market_id);
+            }
+            entry
+        };
+        let mut submitted = base(kind)
+            .with_trade(request.side, request.price, request.size)
+            .with_note(request.order_type.to_string());
+        if !ack.success {
+            submitted = submitted.with_note(format!(
+                "{} 失败: {}",
+                request.order_type,
+                ack.error_msg.as_deref().unwrap_or("未知错误")
+            ));
+        }
+        self.record(submitted);
+
+        // 买单：taking 为份额、making 为 USDC；卖单相反
+        let (shares, usdc) = match request.side {
+            Side::Sell => (ack.making_amount, ack.taking_amount),
+            _ => (ack.taking_amount, ack.making_amount),
+        };
+        if shares > dec!(0) {
+            let price = usdc / shares;
+            let fee = match request.side {
+                Side::Sell => fee_model.sell_fee_usd(price, shares),
+                _ => fee_model.fee_shares(price, shares) * price,
+            };
+            self.record(
+                base(LedgerKind::Fill)
+                    .with_trade(request.side, price, shares)
+                    .with_amount(usdc)
+                    .with_fee(fee),
+            );
+        }
+    }
+}
+
+/// 把台账中 since 之后写入的记录按写入顺序重放进盈亏引擎，返回重放条数；台账文件不存在时返回 0。
+/// 库中不保存市场的 YES/NO token，按该市场出现过的 token 还原（merge / redeem 对两腿对称处理，顺序不影响）
+pub fn replay_into(path: &Path, since: DateTime<Utc>, pnl: &PnlEngine) -> Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let conn = open_db(path)?;
+    let mut stmt = conn.prepare(
+        "SELECT ts_ms, kind, pair_id, market_id, slug, symbol, window_ts, order_id, token_id, side, price, size, amount, fee, tx_hash, note
+         FROM ledger WHERE ts_ms >= ?1 ORDER BY id",
+    )?;
+    let mut tokens_stmt =
+        conn.prepare("SELECT DISTINCT token_id FROM ledger WHERE market_id = ?1 AND token_id IS NOT NULL")?;
+    let mut markets: HashMap<B256, LedgerMarket> = HashMap::new();
+    let mut rows = stmt.query(params![since.timestamp_millis()])?;
+    let mut replayed = 0;
+    while let Some(row) = rows.next()? {
+        let Some(kind) = LedgerKind::parse(&row.get::<_, String>(1)?) else { continue };
+        let decimal = |i: usize| -> rusqlite::Result<Option<Decimal>> {
+            Ok(row.get::<_, Option<String>>(i)?.and_then(|s| s.parse().ok()))
+        };
+        let ts = DateTime::from_timestamp_millis(row.get(0)?).unwrap_or(since);
+        let market_id: Option<B256> = row.get::<_, Option<String>>(3)?.and_then(|s| s.parse().ok());
+        let entry = LedgerEntry {
+            kind,
+            pair_id: row.get(2)?,
+            market_id,
+            order_id: row.get(7)?,
+            token_id: row.get::<_, Option<String>>(8)?.and_then(|s| s.parse().ok()),
+            side: match row.get::<_, Option<String>>(9)?.as_deref() {
+                Some("BUY") => Some(Side::Buy),
+                Some("SELL") => Some(Side::Sell),
+                _ => None,
+            },
+            price: decimal(10)?,
+            size: decimal(11)?,
+            amount: decimal(12)?,
+            fee: decimal(13)?,
+            tx_hash: row.get(14)?,
+            note: row.get(15)?,
+        };
+        let market = match market_id {
+            Some(market_id) if markets.contains_key(&market_id) => markets.get(&market_id).cloned(),
+            Some(market_id) => {
+                let slug: Option<String> = row.get(4)?;
+                let symbol: Option<String> = row.get(5)?;
+                let window_ts: Option<i64> = row.get(6)?;
+                let mut tokens = tokens_stmt
+                    .query_map(params![format!("{:#x}", market_id)], |r| r.get::<_, String>(0))?
+                    .filter_map(|t| t.ok()?.parse::<U256>().ok());
+                let market = match (slug, symbol, window_ts, tokens.next(), tokens.next()) {
+                    (Some(slug), Some(symbol), Some(window_ts), Some(yes), Some(no)) => Some(LedgerMarket {
+                        market_id,
+                        slug,
+                        symbol,
+                        window_ts,
+                        yes_token_id: yes,
+                        no_token_id: no,
+                    }),
+                    _ => None,
+                };
+                if let Some(market) = &market {
+                    markets.insert(market_id, market.clone());
+                }
+                market
+            }
+            None => None,
+        };
+        pnl.on_entry(&entry, market.as_ref(), ts);
+        replayed += 1;
+    }
+    Ok(replayed)
+}
+
+fn open_db(path: &Path) -> Result<Connection> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("创建台账目录失败: {}", dir.display()))?;
+    }
+    let conn = Connection::open(path).with_context(|| format!("打开交易台账失败: {}", path.display()))?;
+    // WAL：运行中也可以用 sqlite3 等工具只读查询
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.execute_batch(SCHEMA).context("初始化交易台账表结构失败")?;
+    // 早期版本的台账没有 fee 列
+    let has_fee: bool = conn
+        .prepare("SELECT 1 FROM pragma_table_info('ledger') WHERE name = 'fee'")?
+        .exists([])?;
+    if !has_fee {
+        conn.execute("ALTER TABLE ledger ADD COLUMN fee TEXT", [])
+            .context("交易台账增加 fee 列失败")?;
+    }
+    Ok(conn)
+}
+
+fn insert_row(conn: &Connection, row: &LedgerRow) -> rusqlite::Result<usize> {
+    let e = &row.entry;
+    conn.execute(
+        "INSERT INTO ledger (ts_ms, kind, pair_id, market_id, slug, symbol, window_ts, order_id, token_id, side, price, size, amount, fee, tx_hash, note)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        params![
+            row.ts.timestamp_millis(),
+            e.kind.as_str(),
+            e.pair_id,
+            row.market_id.map(|m| format!("{:#x}", m)),
+            row.market.as_ref().map(|m| m.slug.as_str()),
+            row.market.as_ref().map(|m| m.symbol.as_str()),
+            row.market.as_ref().map(|m| m.window_ts),
+            e.order_id,
+            e.token_id.map(|t| t.to_string()),
+            e.side.map(|s| s.to_string()),
+            e.price.map(|d| d.to_string()),
+            e.size.map(|d| d.to_string()),
+            e.amount.map(|d| d.to_string()),
+            e.fee.map(|d| d.to_string()),
+            e.tx_hash,
+            e.note,
+        ],
+    )
+}
+
+fn run_writer(conn: Connection, mut rx: mpsc::UnboundedReceiver<LedgerRow>) {
+    while let Some(row) = rx.blocking_recv() {
+        if let Err(e) = insert_row(&conn, &row) {
+            warn!(kind = row.entry.kind.as_str(), error = %e, "写入交易台账失败");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(ts: DateTime<Utc>, entry: LedgerEntry, market: &LedgerMarket) -> LedgerRow {
+        LedgerRow {
+            ts,
+            market_id: Some(market.market_id),
+            market: Some(market.clone()),
+            entry,
+        }
+    }
+
+    #[test]
+    fn replay_restores_day_pnl_from_database() {
+        let path = std::env::temp_dir().join(format!("poly_ledger_replay_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let market = LedgerMarket {
+            market_id: B256::repeat_byte(0x11),
+            slug: "btc-updown-5m-1770972300".to_string(),
+            symbol: "btc".to_string(),
+            window_ts: 1770972300,
+            yes_token_id: U256::from(1),
+            no_token_id: U256::from(2),
+        };
+        let day_start = Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let now = Utc::now();
+        {
+            let conn = open_db(&path).unwrap();
+            let buy = |token: u64, price: Decimal| {
+                LedgerEntry::new(LedgerKind::Fill)
+                    .with_token(U256::from(token))
+                    .with_trade(Side::Buy, price, dec!(10))
+                    .with_amount(price * dec!(10))
+            };
+            // 前一天的成交不重放
+            insert_row(&conn, &row(day_start - chrono::Duration::hours(1), buy(1, dec!(0.9)), &market)).unwrap();
+            insert_row(&conn, &row(now, buy(1, dec!(0.45)), &market)).unwrap();
+            insert_row(&conn, &row(now, buy(2, dec!(0.5)), &market)).unwrap();
+            let merge = LedgerEntry::new(LedgerKind::Merge).with_market(market.market_id).with_size(dec!(10));
+            // merge 记录不带 token：按该市场出现过的 token 还原两腿
+            insert_row(
+                &conn,
+                &LedgerRow { ts: now, market_id: Some(market.market_id), market: None, entry: merge },
+            )
+            .unwrap();
+        }
+
+        let pnl = PnlEngine::new();
+        assert_eq!(replay_into(&path, day_start, &pnl).unwrap(), 3);
+        assert_eq!(pnl.day_summary(now.date_naive(), |_| None).realized, dec!(0.5));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("db-wal"));
+        let _ = std::fs::remove_file(path.with_extension("db-shm"));
+    }
+}
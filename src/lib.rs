@@ -1,5 +1,15 @@
 //! poly_15min_bot 库：供主程序和 binaries 复用的模块。
 
+pub mod backtest;
+pub mod config;
+pub mod ledger;
+pub mod market;
 pub mod merge;
+pub mod monitor;
 pub mod positions;
-pub mod trial;
\ No newline at end of file
+pub mod recorder;
+pub mod redeem;
+pub mod risk;
+pub mod rpc_pool;
+pub mod trading;
+pub mod trial;
@@ -7,16 +7,16 @@ fn install_rustls_provider() {
         .expect("failed to install rustls ring provider");
 }
 
-mod config;
-mod market;
-mod monitor;
-mod risk;
-mod trading;
 mod utils;
 mod scalp;
 
-use poly_5min_bot::merge;
+use poly_5min_bot::{config, market, monitor, risk, trading};
 use poly_5min_bot::positions::{get_positions, Position};
+use poly_5min_bot::ledger::{LedgerEntry, LedgerKind, TradeLedger};
+use poly_5min_bot::recorder::{BookRecorder, RecordedMarket};
+use poly_5min_bot::merge::{MergeError, MergeOutcome};
+use poly_5min_bot::redeem::RedeemError;
+use poly_5min_bot::rpc_pool::RpcPool;
 
 use anyhow::Result;
 use dashmap::DashMap;
@@ -29,14 +29,14 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
-use polymarket_client_sdk::types::{Address, B256, U256};
+use polymarket_client_sdk::types::{B256, U256};
 
 use crate::config::Config;
 use crate::market::{MarketDiscoverer, MarketInfo, MarketScheduler};
-use crate::monitor::{ArbitrageDetector, OrderBookMonitor};
-use crate::risk::positions::PositionTracker;
-use crate::risk::{HedgeMonitor, PositionBalancer, RiskManager};
-use crate::trading::TradingExecutor;
+use crate::monitor::{ArbitrageDetector, EntryChecks, OpportunityGate, OrderBookMonitor, PreTradeCheck};
+use crate::risk::recovery::RecoveryAction;
+use crate::risk::{HedgeMonitor, LegChaser, PnlEngine, PositionBalancer, RiskGuard, RiskManager, TradeBudget};
+use crate::trading::{ClobExchange, Exchange, FeeSchedule, SimulatedExchange, TradingExecutor};
 use crate::scalp::ScalpState;
 
 /// 从持仓中筛出 **YES 和 NO 都持仓** 的 condition_id，仅这些市场才能 merge；单边持仓直接跳过。
@@ -94,21 +94,153 @@ fn merge_info_with_both_sides(positions: &[Position]) -> HashMap<B256, (U256, U2
         .collect()
 }
 
-/// 定时 Merge 任务：每 interval_minutes 分钟拉取**持仓**，仅对 YES+NO 双边都持仓的市场 **串行**执行 merge_max，
-/// 单边持仓跳过；每笔之间间隔、对 RPC 限速做一次重试。Merge 成功后扣减 position_tracker 的持仓与敞口。
-/// 首次执行前短暂延迟，避免与订单簿监听的启动抢占同一 runtime，导致阻塞 stream。
+/// 已结算可赎回的持仓：condition_id -> [(token_id, 份额, 结算价)]，结算价取 Data API 的 cur_price（胜方 1、负方 0）
+fn redeemable_by_condition(positions: &[Position]) -> HashMap<B256, Vec<(U256, Decimal, Decimal)>> {
+    let mut by_condition: HashMap<B256, Vec<(U256, Decimal, Decimal)>> = HashMap::new();
+    for p in positions.iter().filter(|p| p.redeemable && p.size > dec!(0)) {
+        by_condition
+            .entry(p.condition_id)
+            .or_default()
+            .push((p.asset, p.size, p.cur_price));
+    }
+    by_condition
+}
+
+/// 双边持仓的 merge 信息：实盘从 Data API 拉取持仓，模拟盘取模拟交易所的虚拟余额
+async fn fetch_merge_info(
+    simulator: Option<&SimulatedExchange>,
+) -> Result<(Vec<B256>, HashMap<B256, (U256, U256, Decimal)>)> {
+    if let Some(sim) = simulator {
+        let merge_info = sim.mergeable();
+        return Ok((merge_info.keys().copied().collect(), merge_info));
+    }
+    let positions = get_positions().await?;
+    Ok((
+        condition_ids_with_both_sides(&positions),
+        merge_info_with_both_sides(&positions),
+    ))
+}
+
+/// 当前持仓 (token_id, 份额)：实盘来自 Data API，模拟盘来自虚拟余额
+async fn fetch_holdings(simulator: Option<&SimulatedExchange>) -> Result<Vec<(U256, Decimal)>> {
+    if let Some(sim) = simulator {
+        return Ok(sim.token_balances());
+    }
+    Ok(get_positions()
+        .await?
+        .into_iter()
+        .map(|p| (p.asset, p.size))
+        .collect())
+}
+
+/// Merge 交易确认上链后，对本笔合并的每个市场记台账、扣减持仓与风险敞口（先扣敞口再扣持仓，保证 update_exposure_cost
+/// 读到的是合并前持仓），并结清该市场的订单对。扣减数量取链上实际合并份额，merge_info 只用于查 YES/NO token。
+fn settle_merged(
+    outcome: &MergeOutcome,
+    merge_info: &HashMap<B256, (U256, U256, Decimal)>,
+    risk_manager: &RiskManager,
+    ledger: &TradeLedger,
+    note: &str,
+) {
+    let position_tracker = risk_manager.position_tracker();
+    for &(condition_id, merge_amt) in &outcome.merged {
+        ledger.record(
+            LedgerEntry::new(LedgerKind::Merge)
+                .with_market(condition_id)
+                .with_tx(&outcome.tx_hash)
+                .with_size(merge_amt)
+                .with_amount(merge_amt)
+                .with_note(note),
+        );
+        if let Some((yes_token, no_token, _)) = merge_info.get(&condition_id) {
+            position_tracker.update_exposure_cost(*yes_token, dec!(0), -merge_amt);
+            position_tracker.update_exposure_cost(*no_token, dec!(0), -merge_amt);
+            position_tracker.update_position(*yes_token, -merge_amt);
+            position_tracker.update_position(*no_token, -merge_amt);
+            info!("💰 Merge 已扣减敞口 | condition_id={:#x} | 数量:{}", condition_id, merge_amt);
+        }
+        risk_manager.on_merged(condition_id);
+    }
+}
+
+/// 执行恢复动作所需的句柄（成交跟踪任务、套利任务与主循环共用）
+#[derive(Clone)]
+struct RecoveryContext {
+    hedge_monitor: Arc<HedgeMonitor>,
+    leg_chaser: Arc<LegChaser>,
+}
+
+/// 处理恢复动作（由 RECOVERY_POLICY 选择的恢复策略给出）；下单在独立任务中执行，不阻塞调用方
+fn dispatch_recovery_action(action: RecoveryAction, ctx: &RecoveryContext) {
+    match action {
+        RecoveryAction::None => {
+            // 正常情况，无需处理
+        }
+        RecoveryAction::MonitorForExit { .. } => {
+            if let Err(e) = ctx.hedge_monitor.add_position(&action) {
+                error!(error = %e, "添加对冲监测失败");
+            }
+        }
+        RecoveryAction::SellExcess { token_id, amount, pair_id } => {
+            ctx.leg_chaser.sell_leg(token_id, amount, pair_id);
+        }
+        RecoveryAction::ChaseMissingLeg { .. } => {
+            // 由主循环按订单簿驱动补单，截止后回退为卖出已成交一腿
+            ctx.leg_chaser.start(&action);
+        }
+        RecoveryAction::ManualIntervention { reason } => {
+            warn!("需要手动干预: {}", reason);
+        }
+    }
+}
+
+/// 成交跟踪任务：订阅用户频道（模拟盘为模拟交易所的成交推送），GTC/GTD 挂单后续成交时更新订单对与持仓，
+/// 订单对状态变化时重新执行恢复策略。流结束或出错后等待片刻重新订阅。
+async fn run_fill_tracker(exchange: Arc<dyn Exchange>, risk_manager: Arc<RiskManager>, recovery: RecoveryContext) {
+    const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+    loop {
+        let mut fills = match exchange.subscribe_fills(Vec::new()).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!(error = %e, "订阅成交推送失败，稍后重试");
+                sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        info!("已订阅用户频道成交推送");
+
+        while let Some(item) = fills.next().await {
+            match item {
+                Ok(event) => match risk_manager.on_fill(&event).await {
+                    Ok(Some(action)) => dispatch_recovery_action(action, &recovery),
+                    Ok(None) => {}
+                    Err(e) => error!("风险处理失败: {}", e),
+                },
+                Err(e) => {
+                    warn!(error = %e, "成交推送错误，重新订阅");
+                    break;
+                }
+            }
+        }
+
+        warn!("成交推送流结束，{} 秒后重新订阅", RECONNECT_DELAY.as_secs());
+        sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// 定时 Merge 任务：每 interval_minutes 分钟拉取**持仓**，将 YES+NO 双边都持仓的市场放进**一笔**批量 merge 交易
+/// （Safe MultiSend / Relayer 多调用），单边持仓跳过；RPC 限速与节点故障由节点池切换处理。Merge 成功后扣减 position_tracker 的持仓与敞口，
+/// 并结清相应市场的订单对。首次执行前短暂延迟，避免与订单簿监听的启动抢占同一 runtime，导致阻塞 stream。
 async fn run_merge_task(
     interval_minutes: u64,
-    proxy: Address,
-    private_key: String,
-    position_tracker: Arc<PositionTracker>,
+    exchange: Arc<dyn Exchange>,
+    simulator: Option<Arc<SimulatedExchange>>,
+    risk_manager: Arc<RiskManager>,
+    ledger: TradeLedger,
     wind_down_in_progress: Arc<AtomicBool>,
 ) {
     let interval = Duration::from_secs(interval_minutes * 60);
-    /// 每笔 merge 之间间隔，降低 RPC  bursts
-    const DELAY_BETWEEN_MERGES: Duration = Duration::from_secs(30);
-    /// 遇限速时等待后重试的时长（略大于 "retry in 10s"）
-    const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(12);
     /// 首次执行前延迟，让主循环先完成订单簿订阅并进入 select!，避免 merge 阻塞 stream
     const INITIAL_DELAY: Duration = Duration::from_secs(10);
 
@@ -121,11 +253,8 @@ async fn run_merge_task(
             sleep(interval).await;
             continue;
         }
-        let (condition_ids, merge_info) = match get_positions().await {
-            Ok(positions) => (
-                condition_ids_with_both_sides(&positions),
-                merge_info_with_both_sides(&positions),
-            ),
+        let (condition_ids, merge_info) = match fetch_merge_info(simulator.as_deref()).await {
+            Ok(info) => info,
             Err(e) => {
                 warn!(error = %e, "❌ 获取持仓失败，跳过本轮回 merge");
                 sleep(interval).await;
@@ -135,53 +264,99 @@ async fn run_merge_task(
 
         if condition_ids.is_empty() {
             debug!("🔄 本轮回 merge: 无满足 YES+NO 双边持仓的市场");
+            sleep(interval).await;
+            continue;
+        }
+        info!(
+            count = condition_ids.len(),
+            "🔄 本轮回 merge: 共 {} 个市场满足 YES+NO 双边持仓，合并为一笔交易",
+            condition_ids.len()
+        );
+
+        match exchange.merge_max_batch(&condition_ids).await {
+            Ok(outcome) => {
+                info!("✅ Merge 已确认 | {} 个市场", outcome.merged.len());
+                info!("  📝 tx={}", outcome.tx_hash);
+                settle_merged(&outcome, &merge_info, &risk_manager, &ledger, "定时 merge");
+            }
+            Err(e) => match e.downcast_ref::<MergeError>() {
+                Some(MergeError::NothingToMerge) => debug!("⏭️ 跳过 merge: 无可用份额"),
+                Some(postponed @ MergeError::Postponed { .. }) => info!("⏸️ {}，下一轮再 merge", postponed),
+                None => warn!(error = %e, "❌ Merge 失败"),
+            },
+        }
+
+        sleep(interval).await;
+    }
+}
+
+/// 定时 Redeem 任务：每 interval_minutes 分钟拉取持仓，对已结算（redeemable）的市场**串行**执行 redeem，
+/// 按结算价为每腿记一条 redeem 台账，成功后扣减 position_tracker 的持仓与敞口。仅实盘启用（模拟盘不跟踪结算）。
+/// 收尾进行中时跳过本轮，避免与收尾 merge 争用 Safe nonce。
+async fn run_redeem_task(
+    interval_minutes: u64,
+    exchange: Arc<dyn Exchange>,
+    risk_manager: Arc<RiskManager>,
+    ledger: TradeLedger,
+    wind_down_in_progress: Arc<AtomicBool>,
+) {
+    let position_tracker = risk_manager.position_tracker();
+    let interval = Duration::from_secs(interval_minutes * 60);
+    /// 每笔 redeem 之间间隔，降低 RPC bursts
+    const DELAY_BETWEEN_REDEEMS: Duration = Duration::from_secs(30);
+    /// 首次执行前延迟，让主循环先完成订单簿订阅
+    const INITIAL_DELAY: Duration = Duration::from_secs(20);
+
+    sleep(INITIAL_DELAY).await;
+
+    loop {
+        if wind_down_in_progress.load(Ordering::Relaxed) {
+            info!("收尾进行中，本轮 redeem 跳过");
+            sleep(interval).await;
+            continue;
+        }
+        let redeemable = match get_positions().await {
+            Ok(positions) => redeemable_by_condition(&positions),
+            Err(e) => {
+                warn!(error = %e, "❌ 获取持仓失败，跳过本轮 redeem");
+                sleep(interval).await;
+                continue;
+            }
+        };
+
+        if redeemable.is_empty() {
+            debug!("💵 本轮 redeem: 无已结算的持仓");
         } else {
-            info!(
-                count = condition_ids.len(),
-                "🔄 本轮回 merge: 共 {} 个市场满足 YES+NO 双边持仓",
-                condition_ids.len()
-            );
+            info!("💵 本轮 redeem: 共 {} 个已结算市场", redeemable.len());
         }
 
-        for (i, &condition_id) in condition_ids.iter().enumerate() {
-            // 第 2 个及以后的市场：先等 30 秒再 merge，避免与上一笔链上处理重叠
+        for (i, (condition_id, legs)) in redeemable.iter().enumerate() {
             if i > 0 {
-                info!("本轮回 merge: 等待 30 秒后合并下一市场 (第 {}/{} 个)", i + 1, condition_ids.len());
-                sleep(DELAY_BETWEEN_MERGES).await;
-            }
-            let mut result = merge::merge_max(condition_id, proxy, &private_key, None).await;
-            if result.is_err() {
-                let msg = result.as_ref().unwrap_err().to_string();
-                if msg.contains("rate limit") || msg.contains("retry in") {
-                    warn!(condition_id = %condition_id, "⏳ RPC 限速，等待 {}s 后重试一次", RATE_LIMIT_BACKOFF.as_secs());
-                    sleep(RATE_LIMIT_BACKOFF).await;
-                    result = merge::merge_max(condition_id, proxy, &private_key, None).await;
-                }
+                sleep(DELAY_BETWEEN_REDEEMS).await;
             }
-            match result {
+            match exchange.redeem(*condition_id).await {
                 Ok(tx) => {
-                    info!("✅ Merge 完成 | condition_id={:#x}", condition_id);
+                    let payout: Decimal = legs.iter().map(|(_, size, price)| size * price).sum();
+                    info!("✅ Redeem 完成 | condition_id={:#x} | 收回:{:.4} USDC", condition_id, payout);
                     info!("  📝 tx={}", tx);
-                    // Merge 成功：扣减持仓与风险敞口（先扣敞口再扣持仓，保证 update_exposure_cost 读到的是合并前持仓）
-                    if let Some((yes_token, no_token, merge_amt)) = merge_info.get(&condition_id) {
-                        position_tracker.update_exposure_cost(*yes_token, dec!(0), -*merge_amt);
-                        position_tracker.update_exposure_cost(*no_token, dec!(0), -*merge_amt);
-                        position_tracker.update_position(*yes_token, -*merge_amt);
-                        position_tracker.update_position(*no_token, -*merge_amt);
-                        info!(
-                            "💰 Merge 已扣减敞口 | condition_id={:#x} | 数量:{}",
-                            condition_id, merge_amt
+                    for (token_id, size, price) in legs {
+                        ledger.record(
+                            LedgerEntry::new(LedgerKind::Redeem)
+                                .with_market(*condition_id)
+                                .with_token(*token_id)
+                                .with_size(*size)
+                                .with_amount(size * price)
+                                .with_tx(tx.clone())
+                                .with_note("定时 redeem"),
                         );
                     }
+                    // 市场已结算并赎回，注销后不再计入持仓与各项敞口
+                    position_tracker.unregister_market(*condition_id);
                 }
-                Err(e) => {
-                    let msg = e.to_string();
-                    if msg.contains("无可用份额") {
-                        debug!(condition_id = %condition_id, "⏭️ 跳过 merge: 无可用份额");
-                    } else {
-                        warn!(condition_id = %condition_id, error = %e, "❌ Merge 失败");
-                    }
-                }
+                Err(e) => match e.downcast_ref::<RedeemError>() {
+                    Some(reason) => debug!(condition_id = %condition_id, reason = %reason, "⏭️ 跳过 redeem"),
+                    None => warn!(condition_id = %condition_id, error = %e, "❌ Redeem 失败"),
+                },
             }
             tokio::task::yield_now().await;
         }
@@ -204,18 +379,21 @@ async fn main() -> Result<()> {
     let config = Config::from_env()?;
     tracing::info!("配置加载完成");
 
+    // 各市场手续费：发现市场时按元数据登记，配置中的参数为默认值
+    let fees = FeeSchedule::from_config(&config);
+
     // 初始化组件（暂时不使用，主循环已禁用）
-    let _discoverer = MarketDiscoverer::new(config.crypto_symbols.clone());
+    let _discoverer = MarketDiscoverer::new(config.crypto_symbols.clone(), fees.default_model());
     let _scheduler = MarketScheduler::new(_discoverer, config.market_refresh_advance_secs);
-    let _detector = ArbitrageDetector::new(config.min_profit_threshold);
-    
-    // ---------- SCALP STATE ----------
-let mut scalp_state = Some(ScalpState::new());
+    let _detector = ArbitrageDetector::new(&config).with_fees(fees.clone());
+    // 套利闸门：利润阈值 + 同市场执行中去重 + 同价位冷却
+    let opportunity_gate = Arc::new(OpportunityGate::new(&config));
+    // 下单前检查：YES/NO 价格阈值、临近结束停止套利
+    let pre_trade_check = PreTradeCheck::new(&config);
     
     // 验证私钥格式
     info!("正在验证私钥格式...");
     use alloy::signers::local::LocalSigner;
-    use polymarket_client_sdk::POLYGON;
     use std::str::FromStr;
     
     let _signer_test = LocalSigner::from_str(&config.private_key)
@@ -223,82 +401,195 @@ let mut scalp_state = Some(ScalpState::new());
     info!("私钥格式验证通过");
 
     // 初始化交易执行器（需要认证）
-    info!("正在初始化交易执行器（需要API认证）...");
+    info!("正在初始化交易所客户端（需要API认证）...");
     if let Some(ref proxy) = config.proxy_address {
         info!(proxy_address = %proxy, "使用Proxy签名类型（Email/Magic或Browser Wallet）");
     } else {
         info!("使用EOA签名类型（直接交易）");
     }
     info!("注意：如果看到'Could not create api key'警告，这是正常的。SDK会先尝试创建新API key，失败后会自动使用派生方式，认证仍然会成功。");
-    let executor = match TradingExecutor::new(
-        config.private_key.clone(),
-        config.max_order_size_usdc,
-        config.proxy_address,
-        config.slippage,
-        config.gtd_expiration_secs,
-        config.arbitrage_order_type.clone(),
-    ).await {
-        Ok(exec) => {
-            info!("交易执行器认证成功（可能使用了派生API key）");
-            Arc::new(exec)
-        }
-        Err(e) => {
-            error!(error = %e, "交易执行器认证失败！无法继续运行。");
-            error!("请检查：");
-            error!("  1. POLYMARKET_PRIVATE_KEY 环境变量是否正确设置");
-            error!("  2. 私钥格式是否正确（应该是64字符的十六进制字符串，不带0x前缀）");
-            error!("  3. 网络连接是否正常");
-            error!("  4. Polymarket API服务是否可用");
-            return Err(anyhow::anyhow!("认证失败，程序退出: {}", e));
-        }
+    // 模拟盘（DRY_RUN）：使用真实订单簿流，但下单、卖出与 merge 全部路由到内存模拟交易所
+    let simulator: Option<Arc<SimulatedExchange>> = if config.dry_run {
+        let initial_usdc = Decimal::try_from(config.dry_run_initial_usdc).unwrap_or(dec!(1000.0));
+        warn!("🧪 DRY_RUN 模拟盘模式 | 初始虚拟 USDC:{} | 不会提交任何真实订单", initial_usdc);
+        Some(Arc::new(SimulatedExchange::new(initial_usdc, fees.clone())))
+    } else {
+        None
     };
 
-    // 创建CLOB客户端用于风险管理（需要认证）
-    info!("正在初始化风险管理客户端（需要API认证）...");
-    use alloy::signers::Signer;
-    use polymarket_client_sdk::clob::{Client, Config as ClobConfig};
-    use polymarket_client_sdk::clob::types::SignatureType;
-
-    let signer_for_risk = LocalSigner::from_str(&config.private_key)?
-        .with_chain_id(Some(POLYGON));
-    let clob_config = ClobConfig::builder().use_server_time(true).build();
-    let mut auth_builder_risk = Client::new("https://clob.polymarket.com", clob_config)?
-        .authentication_builder(&signer_for_risk);
-    
-    // 如果提供了proxy_address，设置funder和signature_type
-    if let Some(funder) = config.proxy_address {
-        auth_builder_risk = auth_builder_risk
-            .funder(funder)
-            .signature_type(SignatureType::Proxy);
+    // 交易所：模拟盘直接使用模拟交易所；实盘认证一次，signer 只创建一次，由执行器与风控组件共享
+    let exchange: Arc<dyn Exchange> = match simulator.clone() {
+        Some(sim) => sim,
+        None => match ClobExchange::connect(&config.private_key, config.proxy_address).await {
+            Ok(exchange) => {
+                info!("交易所客户端认证成功（可能使用了派生API key）");
+                Arc::new(exchange)
+            }
+            Err(e) => {
+                error!(error = %e, "交易所客户端认证失败！无法继续运行。");
+                error!("请检查：");
+                error!("  1. POLYMARKET_PRIVATE_KEY 环境变量是否正确设置");
+                error!("  2. 私钥格式是否正确（应该是64字符的十六进制字符串，不带0x前缀）");
+                error!("  3. 网络连接是否正常");
+                error!("  4. Polymarket API服务是否可用");
+                return Err(anyhow::anyhow!("认证失败，程序退出: {}", e));
+            }
+        },
+    };
+
+    // 订单簿录制（RECORD_BOOKS_DIR 非空时开启），后台线程写盘，不阻塞主循环
+    let recorder = match config.record_books_dir.as_deref() {
+        Some(dir) => match BookRecorder::spawn(dir) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                warn!(error = %e, "订单簿录制启动失败，本次运行不录制");
+                None
+            }
+        },
+        None => None,
+    };
+
+    // 交易台账（LEDGER_DB_PATH，默认 trade_ledger.db），后台线程写库；打开失败时本次运行不记录
+    // 盈亏引擎挂在台账上，台账每条成交 / merge / redeem 记录同步计入盈亏（不写库时同样生效）
+    let pnl = Arc::new(PnlEngine::new());
+    let ledger = match config.ledger_db_path.as_deref() {
+        Some(path) => TradeLedger::open(path).unwrap_or_else(|e| {
+            warn!(error = %e, "交易台账打开失败，本次运行不记录");
+            TradeLedger::disabled()
+        }),
+        None => TradeLedger::disabled(),
     }
-    
-    let clob_client = match auth_builder_risk.authenticate().await {
-        Ok(client) => {
-            info!("风险管理客户端认证成功（可能使用了派生API key）");
-            client
+    .with_pnl(pnl.clone());
+
+    // 重放当日（UTC）已写入台账的记录：重启后当日盈亏与风控熔断的亏损、回撤检查从库中接续
+    if let Some(path) = config.ledger_db_path.as_deref() {
+        let day_start = chrono::Utc::now()
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .map(|t| t.and_utc())
+            .unwrap_or_else(chrono::Utc::now);
+        match poly_5min_bot::ledger::replay_into(std::path::Path::new(path), day_start, &pnl) {
+            Ok(0) => {}
+            Ok(n) => info!(
+                "📒 已重放当日台账 {} 条记录 | 当日已实现盈亏:{:+.4} USD",
+                n,
+                pnl.day_summary(day_start.date_naive(), |_| None).realized
+            ),
+            Err(e) => warn!(error = %e, "重放当日台账失败，当日盈亏从 0 开始计"),
         }
-        Err(e) => {
-            error!(error = %e, "风险管理客户端认证失败！无法继续运行。");
-            error!("请检查：");
-            error!("  1. POLYMARKET_PRIVATE_KEY 环境变量是否正确设置");
-            error!("  2. 私钥格式是否正确");
-            error!("  3. 网络连接是否正常");
-            error!("  4. Polymarket API服务是否可用");
-            return Err(anyhow::anyhow!("认证失败，程序退出: {}", e));
+    }
+
+    // 重启前的持仓没有本次运行的成交记录，按 Data API 持仓均价补齐成本，避免 merge / 卖出时按 0 成本计盈亏
+    if simulator.is_none() {
+        match get_positions().await {
+            Ok(positions) => {
+                let seeded = pnl.seed_positions(&positions);
+                if seeded > 0 {
+                    info!("📒 已按持仓均价补齐 {} 个重启前持仓的成本", seeded);
+                }
+            }
+            Err(e) => warn!(error = %e, "获取持仓失败，重启前持仓的成本无法补齐，merge / 卖出时按 0 成本计"),
         }
+    }
+
+    let executor = Arc::new(
+        TradingExecutor::new(
+            exchange.clone(),
+            config.max_order_size_usdc,
+            config.slippage,
+            config.gtd_expiration_secs,
+            config.arbitrage_order_type.clone(),
+            fees.clone(),
+        )
+        .with_ledger(ledger.clone()),
+    );
+
+    // 全局风控熔断：当日亏损、回撤或连续单边成交超限后停止开新仓并收尾，直到下一个 UTC 交易日或手动解除
+    let risk_guard = Arc::new(RiskGuard::load(RiskGuard::default_path(), &config));
+    info!(
+        "风控熔断 | 当日亏损上限:{} USD | 回撤上限:{} USD | 连续单边上限:{} （0 表示不限制）",
+        config.risk_max_daily_loss_usdc, config.risk_max_drawdown_usdc, config.risk_max_consecutive_one_sided
+    );
+
+    // 订单对的单边/双边结果在生命周期到达可判定状态时计入风控熔断（含挂单后续成交、撤单与过期）
+    let _risk_manager = Arc::new(
+        RiskManager::new(exchange.clone(), &config, fees.clone())
+            .with_ledger(ledger.clone())
+            .with_risk_guard(risk_guard.clone()),
+    );
+
+    // 每日交易次数预算：套利与剥头皮共用，状态文件位于可执行文件同目录，重启后继续累计
+    let trade_budget = Arc::new(TradeBudget::load(
+        TradeBudget::default_path(),
+        config.max_trades_per_day,
+        config.trade_budget_reset_hour_utc,
+    ));
+    {
+        let (used, max) = trade_budget.usage(chrono::Utc::now());
+        if max > 0 {
+            info!(
+                "每日交易次数预算 | 已用 {}/{} | 每天 {} 点（UTC）重置",
+                used, max, config.trade_budget_reset_hour_utc
+            );
+        } else {
+            info!("每日交易次数不限制（MAX_TRADES_PER_DAY=0）");
+        }
+    }
+
+    info!(
+        "敞口上限 | 总计:{} USD | 每币种:{} USD {:?} | 每市场:{} USD | 未对冲:{} USD （0 表示不限制）",
+        config.risk_max_exposure_usdc,
+        config.risk_max_exposure_per_symbol_usdc,
+        config.risk_max_exposure_by_symbol,
+        config.risk_max_exposure_per_market_usdc,
+        config.risk_max_unhedged_usdc
+    );
+
+    // 剥头皮策略（ENABLE_SCALPING），与套利共用执行器、交易次数预算与风控熔断
+    let mut scalp_state = if config.enable_scalping {
+        info!(
+            "剥头皮已启用 | 单笔:{} USDC | 信号:{}% | 止盈:{}% | 止损:{}% | 最长持有:{}秒",
+            config.scalp_order_size_usdc,
+            config.scalp_signal_pct,
+            config.scalp_take_profit_pct,
+            config.scalp_stop_loss_pct,
+            config.scalp_max_hold_seconds
+        );
+        Some(
+            ScalpState::new(
+                &config,
+                executor.clone(),
+                trade_budget.clone(),
+                risk_guard.clone(),
+                _risk_manager.position_tracker(),
+            )
+            .with_fees(fees.clone()),
+        )
+    } else {
+        None
     };
     
-    let _risk_manager = Arc::new(RiskManager::new(clob_client.clone(), &config));
-    
     // 创建对冲监测器（传入PositionTracker的Arc引用以更新风险敞口）
-    // 对冲策略已暂时关闭，但保留hedge_monitor变量以备将来使用
+    // 恢复策略为 monitor 时由订单簿更新驱动止盈止损卖出
     let position_tracker = _risk_manager.position_tracker();
-    let _hedge_monitor = HedgeMonitor::new(
-        clob_client.clone(),
-        config.private_key.clone(),
-        config.proxy_address.clone(),
-        position_tracker,
+    let hedge_monitor = Arc::new(
+        HedgeMonitor::new(
+            exchange.clone(),
+            position_tracker.clone(),
+            fees.clone(),
+        )
+        .with_ledger(ledger.clone()),
     );
+    let leg_chaser = Arc::new(LegChaser::new(
+        executor.clone(),
+        _risk_manager.clone(),
+        fees.clone(),
+        Decimal::try_from(config.wind_down_sell_price).unwrap_or(dec!(0.01)),
+    ));
+    let recovery = RecoveryContext {
+        hedge_monitor: hedge_monitor.clone(),
+        leg_chaser: leg_chaser.clone(),
+    };
 
     // 验证认证是否真的成功 - 尝试一个简单的API调用
     info!("正在验证认证状态（通过API调用测试）...");
@@ -320,25 +611,23 @@ let mut scalp_state = Some(ScalpState::new());
 
     info!("✅ 所有组件初始化完成，认证验证通过");
 
-    // RPC 健康检查组件（端点探测、熔断、指标）
-    // let rpc_cfg = tracing_check::CheckConfig::builder()
-    //     .timeout(Duration::from_secs(5))
-    //     .build();
-    // let _tracing_checker = tracing_check::RpcChecker::new(rpc_cfg);
-    // let _rpc_circuit = tracing_check::CircuitBreaker::new();
-    // let _rpc_metrics = tracing_check::Metrics::new();
-    // let _ = _tracing_checker.validate_endpoint("https://clob.polymarket.com");
-    // let _ = _tracing_checker.validate_endpoint("https://gamma-api.polymarket.com");
+    // Polygon RPC 节点池（split / merge / redeem 的链上调用）：后台健康探测，熔断节点恢复后自动放行
+    if !config.dry_run {
+        let rpc_pool = RpcPool::global();
+        info!(endpoints = ?rpc_pool.urls(), "🔗 Polygon RPC 节点池");
+        rpc_pool.spawn_health_checks();
+    }
 
     // 创建仓位平衡器
     let position_balancer = Arc::new(PositionBalancer::new(
-        clob_client.clone(),
+        exchange.clone(),
         _risk_manager.position_tracker(),
         &config,
     ));
 
     // 定时持仓同步任务：每N秒从API获取最新持仓，覆盖本地缓存
-    let position_sync_interval = config.position_sync_interval_secs;
+    // 模拟盘的持仓只存在于模拟交易所，不能用真实账户持仓覆盖
+    let position_sync_interval = if config.dry_run { 0 } else { config.position_sync_interval_secs };
     if position_sync_interval > 0 {
         let position_tracker_sync = _risk_manager.position_tracker();
         tokio::spawn(async move {
@@ -360,19 +649,24 @@ let mut scalp_state = Some(ScalpState::new());
             "已启动定时持仓同步任务，每 {} 秒从API获取最新持仓覆盖本地缓存",
             position_sync_interval
         );
+    } else if config.dry_run {
+        info!("模拟盘：持仓由模拟交易所记账，跳过 Data API 持仓同步");
     } else {
         warn!("POSITION_SYNC_INTERVAL_SECS=0，持仓同步已禁用");
     }
 
     // 定时仓位平衡任务：每N秒检查持仓和挂单，取消多余挂单
     // 注意：由于需要市场映射，平衡任务将在主循环中调用
-    let balance_interval = config.position_balance_interval_secs;
+    // 仓位平衡依赖 Data API 真实持仓，模拟盘下关闭
+    let balance_interval = if config.dry_run { 0 } else { config.position_balance_interval_secs };
     if balance_interval > 0 {
         info!(
             interval_secs = balance_interval,
             "仓位平衡任务将在主循环中每 {} 秒执行一次",
             balance_interval
         );
+    } else if config.dry_run {
+        info!("模拟盘：定时仓位平衡已关闭");
     } else {
         info!("定时仓位平衡未启用（POSITION_BALANCE_INTERVAL_SECS=0）");
     }
@@ -381,18 +675,30 @@ let mut scalp_state = Some(ScalpState::new());
     let wind_down_in_progress = Arc::new(AtomicBool::new(false));
 
     // 两次套利交易之间的最小间隔
-    const MIN_TRADE_INTERVAL: Duration = Duration::from_secs(3);
     let last_trade_time: Arc<tokio::sync::Mutex<Option<Instant>>> = Arc::new(tokio::sync::Mutex::new(None));
 
+    // 开仓检查链（与回测共用）
+    let entry_checks = EntryChecks {
+        pre_trade_check: &pre_trade_check,
+        risk_manager: &_risk_manager,
+        balancer: &position_balancer,
+        risk_guard: &risk_guard,
+        trade_budget: &trade_budget,
+        gate: &opportunity_gate,
+    };
+
     // 定时 Merge：每 N 分钟根据持仓执行 merge，仅对 YES+NO 双边都持仓的市场
     let merge_interval = config.merge_interval_minutes;
     if merge_interval > 0 {
-        if let Some(proxy) = config.proxy_address {
-            let private_key = config.private_key.clone();
-            let position_tracker = _risk_manager.position_tracker().clone();
+        // 模拟盘的 merge 在模拟交易所内记账，不需要代理钱包
+        if config.proxy_address.is_some() || config.dry_run {
+            let exchange = exchange.clone();
+            let simulator = simulator.clone();
+            let risk_manager = _risk_manager.clone();
+            let ledger = ledger.clone();
             let wind_down_flag = wind_down_in_progress.clone();
             tokio::spawn(async move {
-                run_merge_task(merge_interval, proxy, private_key, position_tracker, wind_down_flag).await;
+                run_merge_task(merge_interval, exchange, simulator, risk_manager, ledger, wind_down_flag).await;
             });
             info!(
                 interval_minutes = merge_interval,
@@ -406,6 +712,39 @@ let mut scalp_state = Some(ScalpState::new());
         info!("定时 Merge 未启用（MERGE_INTERVAL_MINUTES=0），如需启用请在 .env 中设置 MERGE_INTERVAL_MINUTES 为正数，例如 5 或 15");
     }
 
+    // 定时 Redeem：每 N 分钟赎回已结算市场的剩余持仓（实盘）
+    let redeem_interval = config.redeem_interval_minutes;
+    if redeem_interval > 0 {
+        if config.dry_run {
+            info!("模拟盘不跟踪市场结算，定时 Redeem 不启用");
+        } else if config.proxy_address.is_some() {
+            let exchange = exchange.clone();
+            let risk_manager = _risk_manager.clone();
+            let ledger = ledger.clone();
+            let wind_down_flag = wind_down_in_progress.clone();
+            tokio::spawn(async move {
+                run_redeem_task(redeem_interval, exchange, risk_manager, ledger, wind_down_flag).await;
+            });
+            info!(
+                interval_minutes = redeem_interval,
+                "已启动定时 Redeem 任务，每 {} 分钟赎回已结算市场的持仓",
+                redeem_interval
+            );
+        } else {
+            warn!("REDEEM_INTERVAL_MINUTES={} 但未设置 POLYMARKET_PROXY_ADDRESS，定时 Redeem 已禁用", redeem_interval);
+        }
+    }
+
+    // 成交跟踪：挂单在下单回执之后的成交通过用户频道推送更新订单对
+    {
+        let exchange = exchange.clone();
+        let risk_manager = _risk_manager.clone();
+        let recovery = recovery.clone();
+        tokio::spawn(async move {
+            run_fill_tracker(exchange, risk_manager, recovery).await;
+        });
+    }
+
     // 主循环已启用，开始监控和交易
     #[allow(unreachable_code)]
     loop {
@@ -424,18 +763,19 @@ let mut scalp_state = Some(ScalpState::new());
             continue;
         }
 
-        // RPC 端点健康检查（主循环前批量探测）
-        // let _ep_validator = tracing_check::EndpointValidator::new();
-        // let _ = _ep_validator.normalize("https://clob.polymarket.com");
-        // let _status = _tracing_checker.perform_health_check_sync("https://clob.polymarket.com");
-        // let _ = _rpc_circuit.status_allows_request(_status);
-        // if !_rpc_circuit.is_open() {
-        //     _rpc_circuit.record_success();
-        //     _rpc_metrics.record_check(true);
-        // }
-
-        // 新一轮开始：重置风险敞口，使本轮从 0 敞口重新累计
-        _risk_manager.position_tracker().reset_exposure();
+        // 新一轮开始：按实际持仓与挂单重建风险敞口（上一轮未 merge、单边或未结算的持仓继续占用额度）；
+        // 模拟盘没有 Data API 持仓，沿用本地累计的敞口
+        if simulator.is_none() {
+            match _risk_manager.rebuild_exposure().await {
+                Ok(exposure) => info!("🔄 风险敞口已按持仓与挂单重建 | 当前敞口:{:.2} USD", exposure),
+                Err(e) => warn!(error = %e, "重建风险敞口失败，沿用当前敞口"),
+            }
+        } else {
+            info!(
+                "🔄 模拟盘沿用本地风险敞口 | 当前敞口:{:.2} USD",
+                _risk_manager.position_tracker().calculate_exposure()
+            );
+        }
 
         // 初始化订单簿监控器
         let mut monitor = OrderBookMonitor::new();
@@ -463,7 +803,7 @@ let mut scalp_state = Some(ScalpState::new());
         use crate::market::discoverer::FIVE_MIN_SECS;
         let current_window_timestamp = MarketDiscoverer::calculate_current_window_timestamp(Utc::now());
         let window_end = chrono::DateTime::from_timestamp(current_window_timestamp + FIVE_MIN_SECS, 0)
-            .unwrap_or_else(|| Utc::now());
+            .unwrap_or_else(Utc::now);
         let mut wind_down_done = false;
 
         // 创建市场ID到市场信息的映射
@@ -471,13 +811,43 @@ let mut scalp_state = Some(ScalpState::new());
             .map(|m| (m.market_id, m))
             .collect();
 
+        ledger.start_window(current_window_timestamp, &markets);
+        for m in &markets {
+            fees.register_market(m.yes_token_id, m.no_token_id, m.fee_model);
+            _risk_manager
+                .position_tracker()
+                .register_market(m.market_id, &m.crypto_symbol, m.yes_token_id, m.no_token_id);
+        }
+
+        if let Some(recorder) = &recorder {
+            let recorded = markets
+                .iter()
+                .map(|m| RecordedMarket {
+                    market_id: m.market_id,
+                    slug: m.slug.clone(),
+                    yes_token_id: m.yes_token_id,
+                    no_token_id: m.no_token_id,
+                    title: m.title.clone(),
+                    end_date: m.end_date,
+                    crypto_symbol: m.crypto_symbol.clone(),
+                })
+                .collect();
+            recorder.start_window(current_window_timestamp, recorded);
+        }
+
+        // 模拟盘需要知道 condition_id 与 YES/NO token 的对应关系才能模拟 split/merge
+        if let Some(sim) = &simulator {
+            for m in &markets {
+                sim.register_market(m.market_id, m.yes_token_id, m.no_token_id);
+            }
+        }
+
         // 创建市场映射（condition_id -> (yes_token_id, no_token_id)）用于仓位平衡
         let market_token_map: HashMap<B256, (U256, U256)> = markets.iter()
             .map(|m| (m.market_id, (m.yes_token_id, m.no_token_id)))
             .collect();
 
         // 创建定时仓位平衡定时器
-        let balance_interval = config.position_balance_interval_secs;
         let mut balance_timer = if balance_interval > 0 {
             let mut timer = tokio::time::interval(Duration::from_secs(balance_interval));
             timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
@@ -490,71 +860,89 @@ let mut scalp_state = Some(ScalpState::new());
         // 按市场记录上一拍卖一价，用于计算涨跌方向（仅一次 HashMap 读写，不影响监控性能）
         let last_prices: DashMap<B256, (Decimal, Decimal)> = DashMap::new();
 
+        // 风控熔断按当日盈亏检查的节流（订单簿更新频繁时 select! 的 1 秒定时分支可能长时间不触发）
+        let mut last_guard_check = Instant::now();
+        let chase_cutoff = window_end - chrono::Duration::seconds(config.recovery_chase_cutoff_secs as i64);
+
         // 监控订单簿更新
         loop {
+            // 风控熔断：每秒按买一价估值检查当日盈亏；新触发时重新执行本窗口收尾（撤单、merge、卖出单腿）
+            if last_guard_check.elapsed() >= Duration::from_secs(1) {
+                last_guard_check = Instant::now();
+                let now = Utc::now();
+                let best_bid = |token_id: U256| monitor.get_book(token_id).and_then(|b| b.bids.last().map(|l| l.price));
+                risk_guard.check_pnl(now, pnl.day_summary(now.date_naive(), best_bid).total());
+                if let Some(reason) = risk_guard.take_new_trip() {
+                    warn!("🚨 风控熔断已触发（{}），撤销挂单并执行收尾", reason);
+                    if !wind_down_in_progress.load(Ordering::Relaxed) {
+                        wind_down_done = false;
+                    }
+                }
+            }
+
+            // 单边成交补单：缺失一腿卖一价进入上限内时补买，截止后回退为卖出已成交一腿
+            leg_chaser.on_tick(&monitor, Utc::now(), chase_cutoff);
+
             // 收尾检查：距窗口结束 <= N 分钟时执行一次收尾（不跳出，继续监控直到窗口结束由下方「新窗口检测」自然切换）
-            // 使用秒级精度，5分钟窗口下 num_minutes() 截断可能导致漏检
-            if config.wind_down_before_window_end_minutes > 0 && !wind_down_done {
+            // 使用秒级精度，5分钟窗口下 num_minutes() 截断可能导致漏检；风控熔断期间每个窗口开始即收尾
+            if !wind_down_done {
                 let now = Utc::now();
                 let seconds_until_end = (window_end - now).num_seconds();
                 let threshold_seconds = config.wind_down_before_window_end_minutes as i64 * 60;
-                if seconds_until_end <= threshold_seconds {
-                    info!("🛑 触发收尾 | 距窗口结束 {} 秒", seconds_until_end);
+                let by_time = config.wind_down_before_window_end_minutes > 0 && seconds_until_end <= threshold_seconds;
+                if by_time || risk_guard.is_tripped() {
+                    if by_time {
+                        info!("🛑 触发收尾 | 距窗口结束 {} 秒", seconds_until_end);
+                    } else {
+                        info!("🛑 触发收尾 | 风控熔断中");
+                    }
                     wind_down_done = true;
                     wind_down_in_progress.store(true, Ordering::Relaxed);
+                    // 收尾会撤单并卖出单腿，放弃进行中的补单以免重复卖出
+                    leg_chaser.abandon_all("收尾");
+                    // 收尾开始后剥头皮只离场、不再入场
+                    if let Some(scalp_state) = scalp_state.as_mut() {
+                        scalp_state.stop_entries();
+                    }
 
-                    // 收尾在独立任务中执行，不阻塞订单簿；各市场 merge 之间间隔 30 秒
+                    // 收尾在独立任务中执行，不阻塞订单簿；双边持仓合并为一笔批量 merge 交易
                     let executor_wd = executor.clone();
                     let config_wd = config.clone();
                     let risk_manager_wd = _risk_manager.clone();
                     let wind_down_flag = wind_down_in_progress.clone();
+                    let exchange_wd = exchange.clone();
+                    let simulator_wd = simulator.clone();
+                    let ledger_wd = ledger.clone();
                     tokio::spawn(async move {
-                        const MERGE_INTERVAL: Duration = Duration::from_secs(30);
+                        const MERGE_SETTLE_DELAY: Duration = Duration::from_secs(30);
 
                         // 1. 取消所有挂单
                         if let Err(e) = executor_wd.cancel_all_orders().await {
                             warn!(error = %e, "收尾：取消所有挂单失败，继续执行 Merge 与卖出");
                         } else {
                             info!("✅ 收尾：已取消所有挂单");
+                            risk_manager_wd.cancel_open_pairs("收尾撤单");
                         }
 
                         // 取消后等 10 秒再 Merge，避免取消前刚成交的订单尚未上链更新持仓
                         const DELAY_AFTER_CANCEL: Duration = Duration::from_secs(10);
                         sleep(DELAY_AFTER_CANCEL).await;
 
-                        // 2. Merge 双边持仓（每完成一个市场后等 30 秒再合并下一个）并更新敞口
-                        let position_tracker = risk_manager_wd.position_tracker();
+                        // 2. 双边持仓合并为一笔批量 merge 交易并更新敞口
                         let mut did_any_merge = false;
-                        if let Some(proxy) = config_wd.proxy_address {
-                            match get_positions().await {
-                                Ok(positions) => {
-                                    let condition_ids = condition_ids_with_both_sides(&positions);
-                                    let merge_info = merge_info_with_both_sides(&positions);
-                                    let n = condition_ids.len();
-                                    for (i, condition_id) in condition_ids.iter().enumerate() {
-                                        match merge::merge_max(*condition_id, proxy, &config_wd.private_key, None).await {
-                                            Ok(tx) => {
-                                                did_any_merge = true;
-                                                info!("✅ 收尾：Merge 完成 | condition_id={:#x} | tx={}", condition_id, tx);
-                                                if let Some((yes_token, no_token, merge_amt)) = merge_info.get(condition_id) {
-                                                    position_tracker.update_exposure_cost(*yes_token, dec!(0), -*merge_amt);
-                                                    position_tracker.update_exposure_cost(*no_token, dec!(0), -*merge_amt);
-                                                    position_tracker.update_position(*yes_token, -*merge_amt);
-                                                    position_tracker.update_position(*no_token, -*merge_amt);
-                                                    info!("💰 收尾：Merge 已扣减敞口 | condition_id={:#x} | 数量:{}", condition_id, merge_amt);
-                                                }
-                                            }
-                                            Err(e) => {
-                                                warn!(condition_id = %condition_id, error = %e, "收尾：Merge 失败");
-                                            }
-                                        }
-                                        // 每完成一个市场的 merge 后等 30 秒再处理下一个，给链上时间
-                                        if i + 1 < n {
-                                            info!("收尾：等待 30 秒后合并下一市场");
-                                            sleep(MERGE_INTERVAL).await;
-                                        }
-                                    }
+                        if config_wd.proxy_address.is_some() || simulator_wd.is_some() {
+                            match fetch_merge_info(simulator_wd.as_deref()).await {
+                                Ok((condition_ids, _)) if condition_ids.is_empty() => {
+                                    debug!("收尾：无 YES+NO 双边持仓，跳过 Merge");
                                 }
+                                Ok((condition_ids, merge_info)) => match exchange_wd.merge_max_batch(&condition_ids).await {
+                                    Ok(outcome) => {
+                                        did_any_merge = true;
+                                        info!("✅ 收尾：Merge 已确认 | {} 个市场 | tx={}", outcome.merged.len(), outcome.tx_hash);
+                                        settle_merged(&outcome, &merge_info, &risk_manager_wd, &ledger_wd, "收尾 merge");
+                                    }
+                                    Err(e) => warn!(error = %e, "收尾：Merge 失败"),
+                                },
                                 Err(e) => { warn!(error = %e, "收尾：获取持仓失败，跳过 Merge"); }
                             }
                         } else {
@@ -562,30 +950,35 @@ let mut scalp_state = Some(ScalpState::new());
                         }
 
                         // 若有执行过 Merge，等半分钟再卖出单腿，给链上处理时间；无 Merge 则不等
-                        if did_any_merge {
-                            sleep(MERGE_INTERVAL).await;
+                        if did_any_merge && simulator_wd.is_none() {
+                            sleep(MERGE_SETTLE_DELAY).await;
                         }
 
                         // 3. 市价卖出剩余单腿持仓
                         let wind_down_sell_price = Decimal::try_from(config_wd.wind_down_sell_price).unwrap_or(dec!(0.01));
-                        match get_positions().await {
-                            Ok(positions) => {
-                                for pos in positions.iter().filter(|p| p.size > dec!(0)) {
-                                    let size_floor = (pos.size * dec!(100)).floor() / dec!(100);
+                        match fetch_holdings(simulator_wd.as_deref()).await {
+                            Ok(holdings) => {
+                                for (token_id, size) in holdings.into_iter().filter(|(_, size)| *size > dec!(0)) {
+                                    let size_floor = (size * dec!(100)).floor() / dec!(100);
                                     if size_floor < dec!(0.01) {
-                                        debug!(token_id = %pos.asset, size = %pos.size, "收尾：持仓过小，跳过卖出");
+                                        debug!(token_id = %token_id, size = %size, "收尾：持仓过小，跳过卖出");
                                         continue;
                                     }
-                                    if let Err(e) = executor_wd.sell_at_price(pos.asset, wind_down_sell_price, size_floor).await {
-                                        warn!(token_id = %pos.asset, size = %pos.size, error = %e, "收尾：卖出单腿失败");
-                                    } else {
-                                        info!("✅ 收尾：已下卖单 | token_id={:#x} | 数量:{} | 价格:{:.4}", pos.asset, size_floor, wind_down_sell_price);
+                                    match executor_wd.sell_at_price(token_id, wind_down_sell_price, size_floor).await {
+                                        Ok(_) => {
+                                            info!("✅ 收尾：已下卖单 | token_id={:#x} | 数量:{} | 价格:{:.4}", token_id, size_floor, wind_down_sell_price);
+                                        }
+                                        Err(e) => warn!(token_id = %token_id, size = %size, error = %e, "收尾：卖出单腿失败"),
                                     }
                                 }
                             }
                             Err(e) => { warn!(error = %e, "收尾：获取持仓失败，跳过卖出"); }
                         }
 
+                        if let Some(sim) = &simulator_wd {
+                            info!("📒 模拟盘收尾 | {}", sim.report());
+                        }
+
                         info!("🛑 收尾完成，继续监控至窗口结束");
                         wind_down_flag.store(false, Ordering::Relaxed);
                     });
@@ -597,33 +990,32 @@ let mut scalp_state = Some(ScalpState::new());
                 book_result = stream.next() => {
                     match book_result {
                         Some(Ok(book)) => {
-                            // 然后处理订单簿更新（book会被move）
-                            if let Some(pair) = monitor.handle_book_update(book) 
-                            { if let Some(pair) = monitor.handle_book_update(book) {
-
-    // ---------- SCALPING ----------
-    if let Some(ref mut scalp_state) = scalp_state {
-        scalp_state.on_orderbook(
-            pair.market_id,
-            &pair.yes_book,
-            &pair.no_book,
-        );
-    }
+                            if let Some(recorder) = &recorder {
+                                recorder.record(&book);
+                            }
 
-    // existing arbitrage / logging code continues here
-}
+                            // 模拟盘：先用最新订单簿撮合挂单，再进入套利检测
+                            if let Some(sim) = &simulator {
+                                sim.apply_book_update(&book);
+                            }
+
+                            // 对冲监测：单腿持仓达到止盈 / 止损时卖出（卖单在独立任务中提交）
+                            if let Err(e) = hedge_monitor.check_and_execute(&book).await {
+                                warn!(error = %e, "对冲监测失败");
+                            }
+
+                            // 然后处理订单簿更新（book会被move）
+                            if let Some(pair) = monitor.handle_book_update(book) {
+                                // 剥头皮：检查离场与入场信号，下单在独立任务中执行，不阻塞
+                                if let Some(scalp_state) = scalp_state.as_mut() {
+                                    scalp_state.on_orderbook(
+                                        pair.market_id,
+                                        &pair.yes_book,
+                                        &pair.no_book,
+                                        market_map.get(&pair.market_id).map(|m| m.end_date),
+                                    );
+                                }
 
-                                
-                                
-// ---- SCALP HOOK (NON-BLOCKING) ----
-if let Some(ref mut scalp_state) = scalp_state {
-    scalp_state.on_orderbook(
-        pair.market_id,
-        &pair.yes_book,
-        &pair.no_book,
-    );
-}
-                                
                                 // 注意：asks 最后一个为卖一价
                                 let yes_best_ask = pair.yes_book.asks.last().map(|a| (a.price, a.size));
                                 let no_best_ask = pair.no_book.asks.last().map(|a| (a.price, a.size));
@@ -715,170 +1107,73 @@ if let Some(ref mut scalp_state) = scalp_state {
                                             &pair.no_book,
                                             &pair.market_id,
                                         ) {
-                                            // 检查 YES 价格是否达到阈值
-                                            if config.min_yes_price_threshold > 0.0 {
-                                                use rust_decimal::Decimal;
-                                                let min_yes_price_decimal = Decimal::try_from(config.min_yes_price_threshold)
-                                                    .unwrap_or(dec!(0.0));
-                                                if opp.yes_ask_price < min_yes_price_decimal {
-                                                    debug!(
-                                                        "⏸️ YES价格未达到阈值，跳过套利执行 | 市场:{} | YES价格:{:.4} | 阈值:{:.4}",
-                                                        market_display,
-                                                        opp.yes_ask_price,
-                                                        config.min_yes_price_threshold
-                                                    );
-                                                    continue; // 跳过这个套利机会
-                                                }
-                                            }
-                                            
-                                            // 检查 NO 价格是否达到阈值
-                                            if config.min_no_price_threshold > 0.0 {
-                                                use rust_decimal::Decimal;
-                                                let min_no_price_decimal = Decimal::try_from(config.min_no_price_threshold)
-                                                    .unwrap_or(dec!(0.0));
-                                                if opp.no_ask_price < min_no_price_decimal {
-                                                    debug!(
-                                                        "⏸️ NO价格未达到阈值，跳过套利执行 | 市场:{} | NO价格:{:.4} | 阈值:{:.4}",
-                                                        market_display,
-                                                        opp.no_ask_price,
-                                                        config.min_no_price_threshold
-                                                    );
-                                                    continue; // 跳过这个套利机会
-                                                }
-                                            }
-                                            
-                                            // 检查是否接近市场结束时间（如果配置了停止时间）
-                                            // 使用秒级精度，5分钟市场下 num_minutes() 截断可能导致漏检
-                                            if config.stop_arbitrage_before_end_minutes > 0 {
-                                                if let Some(market_info) = market_map.get(&pair.market_id) {
-                                                    use chrono::Utc;
-                                                    let now = Utc::now();
-                                                    let time_until_end = market_info.end_date.signed_duration_since(now);
-                                                    let seconds_until_end = time_until_end.num_seconds();
-                                                    let threshold_seconds = config.stop_arbitrage_before_end_minutes as i64 * 60;
-                                                    
-                                                    if seconds_until_end <= threshold_seconds {
-                                                        debug!(
-                                                            "⏰ 接近市场结束时间，跳过套利执行 | 市场:{} | 距离结束:{}秒 | 停止阈值:{}分钟",
-                                                            market_display,
-                                                            seconds_until_end,
-                                                            config.stop_arbitrage_before_end_minutes
-                                                        );
+                                            // 份额已由 detector 按深度计算并受最大订单大小约束，成本按各腿 VWAP 计
+                                            let order_size = opp.yes_size.min(opp.no_size);
+                                            let total_cost = (opp.yes_vwap + opp.no_vwap) * order_size;
+                                            let current_exposure = _risk_manager.position_tracker().calculate_exposure();
+
+                                            // 开仓检查链：价格阈值与临近结束 → 风险敞口 → 持仓平衡 → 熔断与交易次数 → 交易间隔 → 套利闸门
+                                            let in_flight_guard = {
+                                                let mut last_trade = last_trade_time.lock().await;
+                                                match entry_checks.admit_buy(
+                                                    &opp,
+                                                    market_map.get(&pair.market_id).map(|m| m.end_date),
+                                                    Utc::now(),
+                                                    Instant::now(),
+                                                    &mut last_trade,
+                                                ) {
+                                                    Ok(g) => g,
+                                                    Err(reason) if reason.is_risk_limit() => {
+                                                        warn!("⚠️ {}，拒绝执行套利交易 | 市场:{}", reason, market_display);
                                                         continue; // 跳过这个套利机会
                                                     }
-                                                }
-                                            }
-                                            
-                                            // 计算订单成本（USD）
-                                            // 使用套利机会中的实际可用数量，但不超过配置的最大订单大小
-                                            use rust_decimal::Decimal;
-                                            let max_order_size = Decimal::try_from(config.max_order_size_usdc).unwrap_or(dec!(100.0));
-                                            let order_size = opp.yes_size.min(opp.no_size).min(max_order_size);
-                                            let yes_cost = opp.yes_ask_price * order_size;
-                                            let no_cost = opp.no_ask_price * order_size;
-                                            let total_cost = yes_cost + no_cost;
-                                            
-                                            // 检查风险敞口限制
-                                            let position_tracker = _risk_manager.position_tracker();
-                                            let current_exposure = position_tracker.calculate_exposure();
-                                            
-                                            if position_tracker.would_exceed_limit(yes_cost, no_cost) {
-                                                warn!(
-                                                    "⚠️ 风险敞口超限，拒绝执行套利交易 | 市场:{} | 当前敞口:{:.2} USD | 订单成本:{:.2} USD | 限制:{:.2} USD",
-                                                    market_display,
-                                                    current_exposure,
-                                                    total_cost,
-                                                    position_tracker.max_exposure()
-                                                );
-                                                continue; // 跳过这个套利机会
-                                            }
-                                            
-                                            // 检查持仓平衡（使用本地缓存，零延迟）
-                                            if position_balancer.should_skip_arbitrage(opp.yes_token_id, opp.no_token_id) {
-                                                warn!(
-                                                    "⚠️ 持仓已严重不平衡，跳过套利执行 | 市场:{}",
-                                                    market_display
-                                                );
-                                                continue; // 跳过这个套利机会
-                                            }
-                                            
-                                            // 检查交易间隔：两次交易间隔不少于 3 秒
-                                            {
-                                                let mut guard = last_trade_time.lock().await;
-                                                let now = Instant::now();
-                                                if let Some(last) = *guard {
-                                                    if now.saturating_duration_since(last) < MIN_TRADE_INTERVAL {
-                                                        let elapsed = now.saturating_duration_since(last).as_secs_f32();
-                                                        debug!(
-                                                            "⏱️ 交易间隔不足 3 秒，跳过 | 市场:{} | 距上次:{}秒",
-                                                            market_display,
-                                                            elapsed
-                                                        );
-                                                        continue; // 跳过此套利机会
+                                                    Err(reason) => {
+                                                        debug!("{}，跳过套利执行 | 市场:{}", reason, market_display);
+                                                        continue; // 跳过这个套利机会
                                                     }
                                                 }
-                                                *guard = Some(now);
-                                            }
+                                            };
 
                                             info!(
-                                                "⚡ 执行套利交易 | 市场:{} | 利润:{:.2}% | 下单数量:{}份 | 订单成本:{:.2} USD | 当前敞口:{:.2} USD",
+                                                "⚡ 执行套利交易 | 市场:{} | 利润:{:.2}% ({:.4} USD) | 下单数量:{}份 | 订单成本:{:.2} USD | 当前敞口:{:.2} USD",
                                                 market_display,
                                                 opp.profit_percentage,
+                                                opp.expected_profit,
                                                 order_size,
                                                 total_cost,
                                                 current_exposure
                                             );
-                                            // 简化敞口：只要执行套利就增加敞口，不管是否成交
-                                            let _pt = _risk_manager.position_tracker();
-                                            _pt.update_exposure_cost(opp.yes_token_id, opp.yes_ask_price, order_size);
-                                            _pt.update_exposure_cost(opp.no_token_id, opp.no_ask_price, order_size);
-                                            
+
                                             // 套利执行：只要总价 <= 阈值即执行，不因涨跌组合跳过；涨跌仅用于滑点分配（仅下降=second，上涨与持平=first）
                                             // 克隆需要的变量到独立任务中（涨跌方向用于按方向分配滑点）
                                             let executor_clone = executor.clone();
                                             let risk_manager_clone = _risk_manager.clone();
+                                            let recovery_clone = recovery.clone();
                                             let opp_clone = opp.clone();
                                             let yes_dir_s = yes_dir.to_string();
                                             let no_dir_s = no_dir.to_string();
                                             
                                             // 使用 tokio::spawn 异步执行套利交易，不阻塞订单簿更新处理
                                             tokio::spawn(async move {
+                                                // 持有执行中标记直到订单对注册完成；之后仍在挂单的腿由闸门按 RiskManager 的订单对判定
+                                                let _in_flight = in_flight_guard;
                                                 // 执行套利交易（滑点：仅下降=second，上涨与持平=first）
                                                 match executor_clone.execute_arbitrage_pair(&opp_clone, &yes_dir_s, &no_dir_s).await {
                                                     Ok(result) => {
                                                         // 先保存 pair_id，因为 result 会被移动
                                                         let pair_id = result.pair_id.clone();
                                                         
-                                                        // 注册到风险管理器（传入价格信息以计算风险敞口）
+                                                        // 注册到风险管理器
                                                         risk_manager_clone.register_order_pair(
                                                             result,
                                                             opp_clone.market_id,
                                                             opp_clone.yes_token_id,
                                                             opp_clone.no_token_id,
-                                                            opp_clone.yes_ask_price,
-                                                            opp_clone.no_ask_price,
                                                         );
 
                                                         // 处理风险恢复
-                                                        // 对冲策略已暂时关闭，买进单边不做任何处理
                                                         match risk_manager_clone.handle_order_pair(&pair_id).await {
-                                                            Ok(action) => {
-                                                                // 对冲策略已关闭，不再处理MonitorForExit和SellExcess
-                                                                match action {
-                                                                    crate::risk::recovery::RecoveryAction::None => {
-                                                                        // 正常情况，无需处理
-                                                                    }
-                                                                    crate::risk::recovery::RecoveryAction::MonitorForExit { .. } => {
-                                                                        info!("单边成交，但对冲策略已关闭，不做处理");
-                                                                    }
-                                                                    crate::risk::recovery::RecoveryAction::SellExcess { .. } => {
-                                                                        info!("部分成交不平衡，但对冲策略已关闭，不做处理");
-                                                                    }
-                                                                    crate::risk::recovery::RecoveryAction::ManualIntervention { reason } => {
-                                                                        warn!("需要手动干预: {}", reason);
-                                                                    }
-                                                                }
-                                                            }
+                                                            Ok(action) => dispatch_recovery_action(action, &recovery_clone),
                                                             Err(e) => {
                                                                 error!("风险处理失败: {}", e);
                                                             }
@@ -900,6 +1195,84 @@ if let Some(ref mut scalp_state) = scalp_state {
                                         }
                                     }
                                 }
+
+                                // 卖方向套利（split-and-sell）：YES+NO 买一价之和 >= 1 + 套利执行价差 时，
+                                // 先用 USDC split 出等量 YES+NO，再按深度卖出两腿；split 需要代理钱包
+                                let yes_best_bid = pair.yes_book.bids.last().map(|b| b.price);
+                                let no_best_bid = pair.no_book.bids.last().map(|b| b.price);
+                                let total_bid_price = yes_best_bid.and_then(|p| no_best_bid.map(|np| p + np));
+                                let sell_threshold = dec!(1.0) + Decimal::try_from(config.arbitrage_execution_spread)
+                                    .unwrap_or(dec!(0.01));
+                                if config.enable_sell_arbitrage && (config.proxy_address.is_some() || config.dry_run) {
+                                    if let Some(total_bid) = total_bid_price {
+                                        if total_bid >= sell_threshold {
+                                            if let Some(opp) = _detector.check_sell_arbitrage(
+                                                &pair.yes_book,
+                                                &pair.no_book,
+                                                &pair.market_id,
+                                            ) {
+                                                // 开仓检查链：临近结束 → 风险敞口 → 熔断与交易次数 → 交易间隔 → 套利闸门
+                                                let current_exposure = _risk_manager.position_tracker().calculate_exposure();
+                                                let in_flight_guard = {
+                                                    let mut last_trade = last_trade_time.lock().await;
+                                                    match entry_checks.admit_sell(
+                                                        &opp,
+                                                        market_map.get(&pair.market_id).map(|m| m.end_date),
+                                                        Utc::now(),
+                                                        Instant::now(),
+                                                        &mut last_trade,
+                                                    ) {
+                                                        Ok(g) => g,
+                                                        Err(reason) if reason.is_risk_limit() => {
+                                                            warn!("⚠️ {}，拒绝执行拆分卖出 | 市场:{}", reason, market_display);
+                                                            continue;
+                                                        }
+                                                        Err(reason) => {
+                                                            debug!("{}，跳过拆分卖出 | 市场:{}", reason, market_display);
+                                                            continue;
+                                                        }
+                                                    }
+                                                };
+
+                                                info!(
+                                                    "⚡ 执行拆分卖出 | 市场:{} | 买一价和:{:.4} | 利润:{:.2}% ({:.4} USD) | 拆分:{}份 | 当前敞口:{:.2} USD",
+                                                    market_display,
+                                                    total_bid,
+                                                    opp.profit_percentage,
+                                                    opp.expected_profit,
+                                                    opp.size,
+                                                    current_exposure
+                                                );
+
+                                                let executor_clone = executor.clone();
+                                                let risk_manager_clone = _risk_manager.clone();
+                                                let yes_dir_s = yes_dir.to_string();
+                                                let no_dir_s = no_dir.to_string();
+                                                tokio::spawn(async move {
+                                                    let _in_flight = in_flight_guard;
+                                                    match executor_clone.execute_split_and_sell(&opp, &yes_dir_s, &no_dir_s).await {
+                                                        Ok(result) => {
+                                                            // 登记 split 得到的持仓与两腿卖单，挂单后续成交由用户频道扣减持仓与敞口
+                                                            risk_manager_clone.register_sell_pair(
+                                                                result,
+                                                                opp.market_id,
+                                                                opp.yes_token_id,
+                                                                opp.no_token_id,
+                                                            );
+                                                        }
+                                                        Err(e) => {
+                                                            // 未发生 split，撤回执行前计入的敞口
+                                                            let pt = risk_manager_clone.position_tracker();
+                                                            pt.revert_exposure_cost(opp.yes_token_id, dec!(0.5), opp.size);
+                                                            pt.revert_exposure_cost(opp.no_token_id, dec!(0.5), opp.size);
+                                                            error!("执行拆分卖出失败: {}", e);
+                                                        }
+                                                    }
+                                                });
+                                            }
+                                        }
+                                    }
+                                }
                             }
                         }
                         Some(Err(e)) => {
@@ -931,6 +1304,13 @@ if let Some(ref mut scalp_state) = scalp_state {
                 // 定期检查：1) 是否进入新的5分钟窗口 2) 收尾触发（5分钟窗口需更频繁检查）
                 _ = sleep(Duration::from_secs(1)) => {
                     let now = Utc::now();
+                    // GTD 挂单到期的订单对标记为过期；仍有成交的重新执行恢复策略
+                    for pair_id in _risk_manager.expire_pairs(now) {
+                        match _risk_manager.handle_order_pair(&pair_id).await {
+                            Ok(action) => dispatch_recovery_action(action, &recovery),
+                            Err(e) => error!("风险处理失败: {}", e),
+                        }
+                    }
                     let new_window_timestamp = MarketDiscoverer::calculate_current_window_timestamp(now);
 
                     // 如果当前窗口时间戳与记录的不同，说明已经进入新窗口
@@ -942,6 +1322,18 @@ if let Some(ref mut scalp_state) = scalp_state {
                         );
                         // 先drop stream以释放对monitor的借用，然后清理旧的订阅
                         drop(stream);
+                        // 清理订单簿前按买一价估值，输出本窗口与当日盈亏
+                        let best_bid = |token_id: U256| monitor.get_book(token_id).and_then(|b| b.bids.last().map(|l| l.price));
+                        info!(
+                            "📊 窗口盈亏 | 窗口:{} | {}",
+                            current_window_timestamp,
+                            pnl.window_summary(current_window_timestamp, best_bid)
+                        );
+                        let today = now.date_naive();
+                        for (symbol, summary) in pnl.day_by_symbol(today, best_bid) {
+                            info!("📊 当日盈亏 | {} | {} | {}", today, symbol.to_uppercase(), summary);
+                        }
+                        info!("📊 当日盈亏合计 | {} | {}", today, pnl.day_summary(today, best_bid));
                         monitor.clear();
                         break;
                     }
@@ -950,6 +1342,31 @@ if let Some(ref mut scalp_state) = scalp_state {
         }
 
         // monitor 会在循环结束时自动 drop，无需手动清理
+        if let Some(scalp_state) = scalp_state.as_mut() {
+            scalp_state.end_window();
+        }
+        // 本窗口市场已结束，剩余订单对结清；风控视图只保留仍未结清的订单对
+        let window_market_ids: Vec<B256> = markets.iter().map(|m| m.market_id).collect();
+        _risk_manager.close_market_pairs(&window_market_ids, "窗口结束");
+        // 已结束的市场不再下单，注销其手续费登记
+        for m in &markets {
+            fees.unregister_market(m.yes_token_id, m.no_token_id);
+        }
+        hedge_monitor.clear();
+        leg_chaser.abandon_all("窗口结束");
+        let open_pairs = _risk_manager.open_pairs();
+        if !open_pairs.is_empty() {
+            warn!(count = open_pairs.len(), "窗口结束后仍有未结清的订单对");
+        }
+        if let Some(sim) = &simulator {
+            info!("📒 模拟盘 | {}", sim.report());
+        }
+        if let Some(recorder) = &recorder {
+            let dropped = recorder.dropped();
+            if dropped > 0 {
+                warn!(dropped, "订单簿录制累计丢弃记录");
+            }
+        }
         info!("当前窗口监控结束，刷新市场进入下一轮");
     }
 }
@@ -2,7 +2,9 @@ use anyhow::Result;
 use chrono::{DateTime, Utc};
 use polymarket_client_sdk::gamma::{Client, types::request::MarketsRequest};
 use polymarket_client_sdk::types::{B256, U256};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+
+use crate::trading::FeeModel;
 
 /// 5分钟窗口的秒数（供 main 等模块计算 window_end 使用）
 pub const FIVE_MIN_SECS: i64 = 300;
@@ -16,18 +18,21 @@ pub struct MarketInfo {
     pub title: String,
     pub end_date: DateTime<Utc>,
     pub crypto_symbol: String,
+    pub fee_model: FeeModel, // 按市场元数据确定的 taker 手续费
 }
 
 pub struct MarketDiscoverer {
     gamma_client: Client,
     crypto_symbols: Vec<String>,
+    fee_fallback: FeeModel, // 元数据未给出手续费信息时使用
 }
 
 impl MarketDiscoverer {
-    pub fn new(crypto_symbols: Vec<String>) -> Self {
+    pub fn new(crypto_symbols: Vec<String>, fee_fallback: FeeModel) -> Self {
         Self {
             gamma_client: Client::default(),
             crypto_symbols,
+            fee_fallback,
         }
     }
 
@@ -126,6 +131,16 @@ impl MarketDiscoverer {
         // 获取endDate
         let end_date = market.end_date?;
 
+        // 手续费：按市场元数据确定，缺失时使用配置
+        let fee_model = FeeModel::from_market(market.fees_enabled, market.taker_base_fee, self.fee_fallback);
+        debug!(
+            slug = %slug,
+            fees_enabled = ?market.fees_enabled,
+            taker_base_fee = ?market.taker_base_fee,
+            fee_model = ?fee_model,
+            "市场手续费"
+        );
+
         Some(MarketInfo {
             market_id,
             slug: slug.clone(),
@@ -134,6 +149,7 @@ impl MarketDiscoverer {
             title: market.question.unwrap_or_default(),
             end_date,
             crypto_symbol,
+            fee_model,
         })
     }
 }
@@ -1,7 +1,13 @@
-//! CTF Merge 模块：将等量 YES/NO 代币合并回 USDC。
+//! CTF Merge 模块：将等量 YES/NO 代币合并回 USDC，或将 USDC 拆分（split）为等量 YES/NO。
 //!
 //! 支持 **Gnosis Safe**（execTransaction）与 **Magic/Email EIP-1167**（Polymarket Relayer）。
-//! 合并数量自动取 `min(YES余额, NO余额)`，无需传入。
+//! 合并数量自动取 `min(YES余额, NO余额)`，无需传入；拆分数量由调用方指定。
+//! [`merge_max_batch`] 将多个市场的 merge 放进一笔交易：Safe 通过 MultiSend（delegatecall），Relayer 通过多条 `ProxyCallTuple`。
+//! Safe 路径发送前先 eth_call + estimateGas 预检，EIP-1559 费用可设上限，gas 价格过高时推迟（[`MergeError::Postponed`]），
+//! 交易在本地签名、广播前即记下哈希（广播超时后重试得到 already known / nonce too low 时按该哈希等待 receipt），
+//! receipt 超时则同 nonce 加价重发，费用到达上限后不再加价。
+//! 链上读取与发送都经 [`RpcPool`] 节点池，节点故障或限速时自动切换。
+//! Relayer 路径提交后轮询交易状态直到上链或失败；merge 只在确认上链后返回 [`MergeOutcome`]。
 //!
 //! ## 调用示例
 //!
@@ -9,7 +15,7 @@
 //! use alloy::primitives::B256;
 //! use polymarket_client_sdk::types::Address;
 //!
-//! let tx = poly_15min_bot::merge::merge_max(
+//! let outcome = poly_15min_bot::merge::merge_max(
 //!     condition_id,
 //!     proxy,
 //!     &private_key,
@@ -19,9 +25,11 @@
 
 use std::env;
 
+use alloy::network::eip2718::Encodable2718 as _;
+use alloy::network::{Ethereum, EthereumWallet, Network, TransactionBuilder as _};
 use alloy::primitives::{keccak256, Address, B256, Bytes, U256};
 use alloy::providers::{Provider, ProviderBuilder};
-use alloy::signers::local::LocalSigner;
+use alloy::signers::local::{LocalSigner, PrivateKeySigner};
 use alloy::signers::Signer as _;
 use alloy::sol_types::SolCall;
 use anyhow::Result;
@@ -29,8 +37,11 @@ use polymarket_client_sdk::ctf::types::{CollectionIdRequest, MergePositionsReque
 use polymarket_client_sdk::ctf::Client;
 use polymarket_client_sdk::types::address;
 use polymarket_client_sdk::{contract_config, POLYGON};
+use rust_decimal::Decimal;
 use std::str::FromStr as _;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+
+use crate::rpc_pool::{self, RpcPool};
 
 use alloy::sol;
 sol! {
@@ -40,6 +51,13 @@ sol! {
     }
 
     #[sol(rpc)]
+    interface IERC20Balance {
+        function balanceOf(address account) external view returns (uint256);
+    }
+
+    // Safe 方法签名参数较多，生成的绑定函数同样如此
+    #[sol(rpc)]
+    #[allow(clippy::too_many_arguments)]
     interface IGnosisSafe {
         function nonce() external view returns (uint256);
         function encodeTransactionData(
@@ -77,14 +95,15 @@ sol! {
         bytes data;
     }
     function proxy(ProxyCallTuple[] calls) external payable returns (bytes[] returnValues);
+    function multiSend(bytes transactions) external payable;
 }
 
-const RPC_URL_DEFAULT: &str = "https://polygon-bor-rpc.publicnode.com";
 const RELAYER_URL_DEFAULT: &str = "https://relayer-v2.polymarket.com";
-const USDC_POLYGON: Address = address!("0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174");
+pub(crate) const USDC_POLYGON: Address = address!("0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174");
 
 const RELAYER_GET_RELAY_PAYLOAD: &str = "/relay-payload";
 const RELAYER_SUBMIT: &str = "/submit";
+const RELAYER_TRANSACTION: &str = "/transaction";
 
 const PROXY_FACTORY: Address = address!("0xaB45c5A4B0c941a2F231C04C3f49182e1A254052");
 const RELAY_HUB: Address = address!("0xD216153c06E857cD7f72665E0aF1d7D82172F494");
@@ -92,7 +111,9 @@ const PROXY_INIT_CODE_HASH: [u8; 32] = [
     0xd2, 0x1d, 0xf8, 0xdc, 0x65, 0x88, 0x0a, 0x86, 0x06, 0xf0, 0x9f, 0xe0, 0xce, 0x3d, 0xf9, 0xb8,
     0x86, 0x92, 0x87, 0xab, 0x0b, 0x05, 0x8b, 0xe0, 0x5a, 0xa9, 0xe8, 0xaf, 0x63, 0x30, 0xa0, 0x0b,
 ];
-const PROXY_DEFAULT_GAS: u64 = 160_000;
+const PROXY_DEFAULT_GAS: u64 = 160_000; // 每条调用
+/// Gnosis Safe MultiSendCallOnly v1.3.0（Polygon）：批量调用时 Safe 以 delegatecall 执行，子调用只允许 call
+const MULTISEND_CALL_ONLY: Address = address!("0x40A2aCCbd92BCA938b02010E17A5b8929b49130D");
 
 /// 将 0x 开头的长 hex 缩短为 `0x` + 前 8 位 + `..` + 后 6 位，便于日志。
 pub fn short_hex(s: &str) -> String {
@@ -110,22 +131,53 @@ use hmac::{Hmac, Mac};
 use sha2::Sha256;
 type HmacSha256 = Hmac<Sha256>;
 
-fn encode_merge_calldata(req: &MergePositionsRequest) -> Vec<u8> {
-    let sel = &keccak256(b"mergePositions(address,bytes32,bytes32,uint256[],uint256)")[..4];
+/// mergePositions / splitPositions 参数布局相同：(collateral, parentCollectionId, conditionId, partition[], amount)
+fn encode_positions_calldata(
+    signature: &[u8],
+    collateral: Address,
+    parent_collection_id: B256,
+    condition_id: B256,
+    partition: &[U256],
+    amount: U256,
+) -> Vec<u8> {
+    let sel = &keccak256(signature)[..4];
     let mut out = Vec::from(sel);
     out.extend_from_slice(&[0u8; 12]);
-    out.extend_from_slice(req.collateral_token.as_slice());
-    out.extend_from_slice(req.parent_collection_id.as_slice());
-    out.extend_from_slice(req.condition_id.as_slice());
+    out.extend_from_slice(collateral.as_slice());
+    out.extend_from_slice(parent_collection_id.as_slice());
+    out.extend_from_slice(condition_id.as_slice());
     out.extend_from_slice(&U256::from(160u64).to_be_bytes::<32>());
-    out.extend_from_slice(&req.amount.to_be_bytes::<32>());
-    out.extend_from_slice(&U256::from(req.partition.len()).to_be_bytes::<32>());
-    for p in &req.partition {
+    out.extend_from_slice(&amount.to_be_bytes::<32>());
+    out.extend_from_slice(&U256::from(partition.len()).to_be_bytes::<32>());
+    for p in partition {
         out.extend_from_slice(&p.to_be_bytes::<32>());
     }
     out
 }
 
+fn encode_merge_calldata(req: &MergePositionsRequest) -> Vec<u8> {
+    encode_positions_calldata(
+        b"mergePositions(address,bytes32,bytes32,uint256[],uint256)",
+        req.collateral_token,
+        req.parent_collection_id,
+        req.condition_id,
+        &req.partition,
+        req.amount,
+    )
+}
+
+/// 二元市场 split：partition = [1, 2]（YES, NO）
+fn encode_split_calldata(condition_id: B256, amount: U256) -> Vec<u8> {
+    encode_positions_calldata(
+        b"splitPositions(address,bytes32,bytes32,uint256[],uint256)",
+        USDC_POLYGON,
+        B256::ZERO,
+        condition_id,
+        &[U256::from(1), U256::from(2)],
+        amount,
+    )
+}
+
 fn derive_proxy_wallet(eoa: Address, proxy_factory: Address) -> Address {
     let salt = keccak256(eoa.as_slice());
     let mut buf = [0u8; 1 + 20 + 32 + 32];
@@ -183,40 +235,68 @@ async fn get_relay_payload(client: &reqwest::Client, base: &str, eoa: Address) -
     Ok((relay, nonce.to_string()))
 }
 
-fn encode_proxy_call(ctf: Address, data: &[u8]) -> Vec<u8> {
-    let t = ProxyCallTuple {
-        typeCode: 1u8,
-        to: ctf,
-        value: U256::ZERO,
-        data: Bytes::from(data.to_vec()),
-    };
-    proxyCall { calls: vec![t] }.abi_encode().to_vec()
+fn encode_proxy_calls(ctf: Address, calls: &[Vec<u8>]) -> Vec<u8> {
+    let calls = calls
+        .iter()
+        .map(|data| ProxyCallTuple {
+            typeCode: 1u8,
+            to: ctf,
+            value: U256::ZERO,
+            data: Bytes::from(data.clone()),
+        })
+        .collect();
+    proxyCall { calls }.abi_encode().to_vec()
 }
 
-fn create_struct_hash(
+/// MultiSend 打包格式：每笔为 operation(1) + to(20) + value(32) + dataLength(32) + data，首尾相接
+fn encode_multisend(to: Address, calls: &[Vec<u8>]) -> Vec<u8> {
+    let mut packed = Vec::new();
+    for data in calls {
+        packed.push(0u8); // call
+        packed.extend_from_slice(to.as_slice());
+        packed.extend_from_slice(&U256::ZERO.to_be_bytes::<32>());
+        packed.extend_from_slice(&U256::from(data.len()).to_be_bytes::<32>());
+        packed.extend_from_slice(data);
+    }
+    multiSendCall { transactions: Bytes::from(packed) }.abi_encode()
+}
+
+/// Relayer PROXY 交易中参与签名的字段
+struct RelayTx<'a> {
     from: Address,
     to: Address,
-    data: &[u8],
+    data: &'a [u8],
     tx_fee: u64,
     gas_price: u64,
     gas_limit: u64,
-    nonce: &str,
+    nonce: &'a str,
     relay_hub: Address,
     relay: Address,
-) -> B256 {
-    let mut buf = Vec::new();
-    buf.extend_from_slice(b"rlx:");
-    buf.extend_from_slice(from.as_slice());
-    buf.extend_from_slice(to.as_slice());
-    buf.extend_from_slice(data);
-    buf.extend_from_slice(&U256::from(tx_fee).to_be_bytes::<32>());
-    buf.extend_from_slice(&U256::from(gas_price).to_be_bytes::<32>());
-    buf.extend_from_slice(&U256::from(gas_limit).to_be_bytes::<32>());
-    let n: u64 = nonce.parse().unwrap_or(0);
-    buf.extend_from_slice(&U256::from(n).to_be_bytes::<32>());
-    buf.extend_from_slice(relay_hub.as_slice());
-    buf.extend_from_slice(relay.as_slice());
-    keccak256(buf)
+}
+
+impl RelayTx<'_> {
+    fn struct_hash(&self) -> B256 {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(b"rlx:");
+        buf.extend_from_slice(self.from.as_slice());
+        buf.extend_from_slice(self.to.as_slice());
+        buf.extend_from_slice(self.data);
+        buf.extend_from_slice(&U256::from(self.tx_fee).to_be_bytes::<32>());
+        buf.extend_from_slice(&U256::from(self.gas_price).to_be_bytes::<32>());
+        buf.extend_from_slice(&U256::from(self.gas_limit).to_be_bytes::<32>());
+        let n: u64 = self.nonce.parse().unwrap_or(0);
+        buf.extend_from_slice(&U256::from(n).to_be_bytes::<32>());
+        buf.extend_from_slice(self.relay_hub.as_slice());
+        buf.extend_from_slice(self.relay.as_slice());
+        keccak256(buf)
+    }
+}
+
+/// Builder API 凭证（`POLY_BUILDER_API_KEY` / `POLY_BUILDER_SECRET` / `POLY_BUILDER_PASSPHRASE`）
+struct BuilderCredentials<'a> {
+    key: &'a str,
+    secret: &'a str,
+    passphrase: &'a str,
 }
 
 fn eip191_hash(struct_hash: B256) -> B256 {
@@ -225,32 +305,43 @@ fn eip191_hash(struct_hash: B256) -> B256 {
     keccak256(msg)
 }
 
-async fn relayer_execute_merge(
-    merge_calldata: &[u8],
+async fn relayer_execute(
+    calls: &[Vec<u8>],
+    metadata: &str,
     ctf_address: Address,
     proxy_wallet: Address,
     signer: &impl alloy::signers::Signer,
-    builder_key: &str,
-    builder_secret: &str,
-    builder_passphrase: &str,
+    builder: &BuilderCredentials<'_>,
     relayer_url: &str,
-) -> Result<String> {
+) -> Result<RelayerSubmission> {
     let client = reqwest::Client::new();
     let eoa = signer.address();
     let base = relayer_url.trim_end_matches('/');
 
     let (relay, nonce) = get_relay_payload(&client, base, eoa).await?;
-    let proxy_data = encode_proxy_call(ctf_address, merge_calldata);
-    let gas_limit: u64 = env::var("MERGE_PROXY_GAS_LIMIT")
+    let proxy_data = encode_proxy_calls(ctf_address, calls);
+    let gas_per_call: u64 = env::var("MERGE_PROXY_GAS_LIMIT")
         .ok()
         .and_then(|s| s.trim().parse().ok())
         .unwrap_or(PROXY_DEFAULT_GAS);
+    let gas_limit = gas_per_call * calls.len() as u64;
 
     if env::var("MERGE_PROXY_TO").map(|s| s.trim().eq_ignore_ascii_case("PROXY_WALLET")).unwrap_or(false) {
         info!("ℹ️ MERGE_PROXY_TO=PROXY_WALLET 已忽略，使用 to=PROXY_FACTORY");
     }
     let to = PROXY_FACTORY;
-    let struct_hash = create_struct_hash(eoa, to, &proxy_data, 0, 0, gas_limit, &nonce, RELAY_HUB, relay);
+    let struct_hash = RelayTx {
+        from: eoa,
+        to,
+        data: &proxy_data,
+        tx_fee: 0,
+        gas_price: 0,
+        gas_limit,
+        nonce: &nonce,
+        relay_hub: RELAY_HUB,
+        relay,
+    }
+    .struct_hash();
     let to_sign = eip191_hash(struct_hash);
     let sig = signer.sign_hash(&to_sign).await.map_err(|e| anyhow::anyhow!("EOA 签名失败: {}", e))?;
     let mut sig_bytes = sig.as_bytes().to_vec();
@@ -275,7 +366,7 @@ async fn relayer_execute_merge(
         "signature": signature_hex,
         "signatureParams": signature_params,
         "type": "PROXY",
-        "metadata": "Merge positions"
+        "metadata": metadata
     });
     let body_str = serde_json::to_string(&body)?;
 
@@ -283,7 +374,8 @@ async fn relayer_execute_merge(
     let method = "POST";
     let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_millis() as u64;
     // 支持标准 Base64 (+/) 与 Base64URL (-_) 两种格式
-    let secret_b64 = builder_secret
+    let secret_b64 = builder
+        .secret
         .trim()
         .replace('-', "+")
         .replace('_', "/");
@@ -296,9 +388,9 @@ async fn relayer_execute_merge(
     let resp = client
         .post(&url)
         .header("Content-Type", "application/json")
-        .header("POLY_BUILDER_API_KEY", builder_key)
+        .header("POLY_BUILDER_API_KEY", builder.key)
         .header("POLY_BUILDER_TIMESTAMP", timestamp.to_string())
-        .header("POLY_BUILDER_PASSPHRASE", builder_passphrase)
+        .header("POLY_BUILDER_PASSPHRASE", builder.passphrase)
         .header("POLY_BUILDER_SIGNATURE", sig_hmac)
         .body(body_str)
         .send()
@@ -309,12 +401,201 @@ async fn relayer_execute_merge(
         anyhow::bail!("Relayer 请求失败 status={} body={}", status, text);
     }
     let json: serde_json::Value = serde_json::from_str(&text)?;
-    let hash = json
-        .get("transactionHash")
-        .or_else(|| json.get("transaction_hash"))
+    let submission = RelayerSubmission {
+        id: json_str(&json, &["transactionID", "transactionId", "id"]),
+        tx_hash: json_str(&json, &["transactionHash", "transaction_hash"]),
+    };
+    if submission.id.is_none() && submission.tx_hash.is_none() {
+        anyhow::bail!("Relayer 响应缺少 transactionID 与 transactionHash: {}", text);
+    }
+    Ok(submission)
+}
+
+/// Relayer `/submit` 返回的交易标识：`id` 用于查询状态，`tx_hash` 可能要等交易广播后才有
+#[derive(Debug)]
+struct RelayerSubmission {
+    id: Option<String>,
+    tx_hash: Option<String>,
+}
+
+fn json_str(json: &serde_json::Value, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|k| json.get(*k))
         .and_then(|v| v.as_str())
-        .map(String::from);
-    Ok(hash.unwrap_or_else(|| text))
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+}
+
+/// 查询 Relayer 交易状态，返回 (state, transactionHash)
+async fn relayer_transaction_state(client: &reqwest::Client, base: &str, id: &str) -> Result<(String, Option<String>)> {
+    let url = format!("{}{}", base.trim_end_matches('/'), RELAYER_TRANSACTION);
+    let resp = client.get(&url).query(&[("id", id)]).send().await?;
+    let status = resp.status();
+    let text = resp.text().await?;
+    if !status.is_success() {
+        anyhow::bail!("GET /transaction 失败 status={} body={}", status, text);
+    }
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    // 返回数组（按 id 查询时只有一条）或单个对象
+    let tx = json.as_array().and_then(|a| a.first()).unwrap_or(&json);
+    let state = json_str(tx, &["state"]).ok_or_else(|| anyhow::anyhow!("Relayer 交易状态缺少 state: {}", text))?;
+    Ok((state, json_str(tx, &["transactionHash", "transaction_hash"])))
+}
+
+/// 轮询 Relayer 交易直到上链（STATE_MINED / STATE_CONFIRMED）或失败（STATE_FAILED / STATE_INVALID），
+/// 上链后再用 receipt 确认执行成功；超时未确认返回错误。返回交易哈希。
+async fn relayer_wait_confirmed(
+    pool: &RpcPool,
+    base: &str,
+    submission: RelayerSubmission,
+    timeout: std::time::Duration,
+    metadata: &str,
+) -> Result<B256> {
+    const RELAYER_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+    /// 上链后等待 receipt 的最短时间（节点可能稍晚于 Relayer 看到区块）
+    const RECEIPT_GRACE: std::time::Duration = std::time::Duration::from_secs(30);
+
+    let client = reqwest::Client::new();
+    let start = std::time::Instant::now();
+    let mut tx_hash = submission.tx_hash;
+    if let Some(id) = submission.id.as_deref() {
+        loop {
+            match relayer_transaction_state(&client, base, id).await {
+                Ok((state, hash)) => {
+                    tx_hash = hash.or(tx_hash);
+                    match state.as_str() {
+                        "STATE_MINED" | "STATE_CONFIRMED" => break,
+                        "STATE_FAILED" | "STATE_INVALID" => {
+                            anyhow::bail!("{} Relayer 交易失败 state={} id={} tx={:?}", metadata, state, id, tx_hash)
+                        }
+                        _ => debug!(id, state = %state, "Relayer 交易未上链，继续等待"),
+                    }
+                }
+                Err(e) => warn!(id, error = %e, "查询 Relayer 交易状态失败，继续等待"),
+            }
+            if start.elapsed() >= timeout {
+                anyhow::bail!("{} Relayer 交易 {} 秒内未确认 id={} tx={:?}", metadata, timeout.as_secs(), id, tx_hash);
+            }
+            tokio::time::sleep(RELAYER_POLL_INTERVAL).await;
+        }
+    }
+
+    let tx_hash: B256 = tx_hash
+        .ok_or_else(|| anyhow::anyhow!("{} Relayer 未返回交易哈希，无法确认 id={:?}", metadata, submission.id))?
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Relayer 交易哈希解析失败: {}", e))?;
+    let remaining = timeout.saturating_sub(start.elapsed()).max(RECEIPT_GRACE);
+    match wait_for_receipt(pool, &[tx_hash], remaining).await {
+        Some((_, receipt)) if receipt.status() => Ok(tx_hash),
+        Some(_) => anyhow::bail!("{} 交易已上链但执行失败 tx: {:#x}", metadata, tx_hash),
+        None => anyhow::bail!("{} Relayer 交易 {} 秒内未查到 receipt tx: {:#x}", metadata, remaining.as_secs(), tx_hash),
+    }
+}
+
+/// 给 RPC / 合约调用错误加上说明；保留原始错误以便节点池判断是否切换节点
+fn rpc_err<E: std::error::Error + Send + Sync + 'static>(what: &str, e: E) -> anyhow::Error {
+    let msg = format!("{}: {}", what, e);
+    anyhow::Error::new(e).context(msg)
+}
+
+fn ctf_address() -> Result<Address> {
+    Ok(contract_config(POLYGON, false)
+        .ok_or_else(|| anyhow::anyhow!("不支持的 chain_id: {}", POLYGON))?
+        .conditional_tokens)
+}
+
+/// 二元市场 YES（indexSet=1）、NO（indexSet=2）的 ERC1155 position id
+pub(crate) async fn binary_position_ids(pool: &RpcPool, condition_id: B256) -> Result<[U256; 2]> {
+    pool.with_failover("getPositionId", move |url| async move {
+        let provider = ProviderBuilder::new().connect(&url).await?;
+        let client = Client::new(provider, POLYGON)?;
+        let mut ids = [U256::ZERO; 2];
+        for (id, index_set) in ids.iter_mut().zip([1u64, 2]) {
+            let req_col = CollectionIdRequest::builder().parent_collection_id(B256::ZERO).condition_id(condition_id).index_set(U256::from(index_set)).build();
+            let col = client.collection_id(&req_col).await?;
+            let req_pos = PositionIdRequest::builder().collateral_token(USDC_POLYGON).collection_id(col.collection_id).build();
+            *id = client.position_id(&req_pos).await?.position_id;
+        }
+        Ok(ids)
+    })
+    .await
+}
+
+/// `proxy` 在 CTF 上某个 position id 的余额
+pub(crate) async fn ctf_balance(pool: &RpcPool, proxy: Address, position_id: U256) -> Result<U256> {
+    let ctf = ctf_address()?;
+    pool.with_failover("balanceOf", move |url| async move {
+        let provider = ProviderBuilder::new().connect(&url).await?;
+        IERC1155Balance::new(ctf, provider)
+            .balanceOf(proxy, position_id)
+            .call()
+            .await
+            .map_err(|e| rpc_err("读取 CTF 余额失败", e))
+    })
+    .await
+}
+
+/// proxy 无合约代码或为 EIP-1167 最小代理（Magic/Email）时走 Relayer，否则视为 Gnosis Safe
+async fn proxy_uses_relayer(pool: &RpcPool, proxy: Address) -> Result<bool> {
+    let code = pool
+        .with_failover("getCode", move |url| async move {
+            let provider = ProviderBuilder::new().connect(&url).await?;
+            provider.get_code_at(proxy).await.map_err(|e| rpc_err("读取 proxy 代码失败", e))
+        })
+        .await?;
+    Ok(code.len() < 150)
+}
+
+/// 已确认上链的 merge 结果：Safe 路径 receipt 成功，或 Relayer 交易上链且 receipt 成功。
+/// 提交失败、执行失败或超时未确认都以错误返回，调用方只在拿到本结果后扣减持仓与敞口。
+#[derive(Debug, Clone)]
+pub struct MergeOutcome {
+    /// 交易哈希（十六进制字符串）
+    pub tx_hash: String,
+    /// 本笔交易合并的 condition_id 及合并份额
+    pub merged: Vec<(B256, Decimal)>,
+}
+
+impl MergeOutcome {
+    /// 某市场本笔合并的份额
+    pub fn amount(&self, condition_id: B256) -> Option<Decimal> {
+        self.merged.iter().find(|(c, _)| *c == condition_id).map(|(_, a)| *a)
+    }
+}
+
+/// merge 可识别的失败原因，包在 `anyhow::Error` 中返回，调用方用 `downcast_ref::<MergeError>()` 区分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeError {
+    /// 没有 YES+NO 双边余额可合并（批量 merge 时为所有市场都没有）
+    NothingToMerge,
+    /// 网络 gas 价格高于 `MERGE_GAS_PRICE_CEILING_GWEI`，交易未发送，留待下一轮（wei）
+    Postponed { gas_price: u128, ceiling: u128 },
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::NothingToMerge => write!(f, "无可用份额可 merge：没有 YES+NO 双边余额"),
+            MergeError::Postponed { gas_price, ceiling } => {
+                write!(f, "Gas 价格过高（{} gwei > 上限 {} gwei），推迟交易", gwei(*gas_price), gwei(*ceiling))
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// 链上份额（6 位小数）换算为 Decimal
+fn shares(raw: U256) -> Decimal {
+    Decimal::from_i128_with_scale(i128::try_from(raw).unwrap_or(i128::MAX), 6)
+}
+
+/// `proxy` 上 `condition_id` 的 (YES余额, NO余额)，可合并数量为两者较小值
+async fn mergeable_amount(pool: &RpcPool, proxy: Address, condition_id: B256) -> Result<(U256, U256)> {
+    let [pos_yes, pos_no] = binary_position_ids(pool, condition_id).await?;
+    let b_yes = ctf_balance(pool, proxy, pos_yes).await?;
+    let b_no = ctf_balance(pool, proxy, pos_no).await?;
+    Ok((b_yes, b_no))
 }
 
 /// 对指定 `condition_id` 在 `proxy` 上合并最大可用 YES+NO 为 USDC。
@@ -324,53 +605,215 @@ async fn relayer_execute_merge(
 /// - `condition_id`: 市场的 condition ID（32 字节十六进制）
 /// - `proxy`: Proxy 地址（Gnosis Safe 或 EIP-1167）
 /// - `private_key`: EOA 私钥
-/// - `rpc_url`: Polygon RPC，`None` 时用 `https://polygon-rpc.com`
+/// - `rpc_url`: Polygon RPC，`None` 时使用 [`RpcPool::global`] 节点池（自动切换节点）
 ///
 /// Magic/Email 路径会从环境变量读取：`POLY_BUILDER_API_KEY`、`POLY_BUILDER_SECRET`、`POLY_BUILDER_PASSPHRASE`、`RELAYER_URL`（可选）。
 ///
-/// 返回交易哈希（十六进制字符串）。
+/// 交易确认上链后返回 [`MergeOutcome`]。
 pub async fn merge_max(
     condition_id: B256,
     proxy: Address,
     private_key: &str,
     rpc_url: Option<&str>,
-) -> Result<String> {
-    let rpc = rpc_url.unwrap_or(RPC_URL_DEFAULT);
-    let chain = POLYGON;
-    let signer = LocalSigner::from_str(private_key)?.with_chain_id(Some(chain));
-    let wallet = signer.address();
-
-    let provider = ProviderBuilder::new().wallet(signer.clone()).connect(rpc).await?;
-    let client = Client::new(provider.clone(), chain)?;
-    let config = contract_config(chain, false).ok_or_else(|| anyhow::anyhow!("不支持的 chain_id: {}", chain))?;
-    let prov_read = ProviderBuilder::new().connect(rpc).await?;
-    let erc1155 = IERC1155Balance::new(config.conditional_tokens, prov_read);
-    let ctf = config.conditional_tokens;
-
-    let req_col_yes = CollectionIdRequest::builder().parent_collection_id(B256::ZERO).condition_id(condition_id).index_set(U256::from(1)).build();
-    let req_col_no = CollectionIdRequest::builder().parent_collection_id(B256::ZERO).condition_id(condition_id).index_set(U256::from(2)).build();
-    let col_yes = client.collection_id(&req_col_yes).await?;
-    let col_no = client.collection_id(&req_col_no).await?;
-
-    let req_pos_yes = PositionIdRequest::builder().collateral_token(USDC_POLYGON).collection_id(col_yes.collection_id).build();
-    let req_pos_no = PositionIdRequest::builder().collateral_token(USDC_POLYGON).collection_id(col_no.collection_id).build();
-    let pos_yes = client.position_id(&req_pos_yes).await?;
-    let pos_no = client.position_id(&req_pos_no).await?;
-
-    let b_yes: U256 = erc1155.balanceOf(proxy, pos_yes.position_id).call().await.unwrap_or(U256::ZERO);
-    let b_no: U256 = erc1155.balanceOf(proxy, pos_no.position_id).call().await.unwrap_or(U256::ZERO);
+) -> Result<MergeOutcome> {
+    let pool = RpcPool::resolve(rpc_url);
+    let signer = LocalSigner::from_str(private_key)?.with_chain_id(Some(POLYGON));
+    let ctf = ctf_address()?;
 
+    let (b_yes, b_no) = mergeable_amount(&pool, proxy, condition_id).await?;
     let merge_amount = b_yes.min(b_no);
     if merge_amount == U256::ZERO {
-        anyhow::bail!("无可用份额可 merge：YES={} NO={}，至少一方为 0。", b_yes, b_no);
+        info!("⏭️ 跳过 condition_id={:#x}：YES={} NO={}，无可用份额", condition_id, b_yes, b_no);
+        return Err(MergeError::NothingToMerge.into());
     }
     info!("🔄 合并数量: {} ({} USDC)", merge_amount, merge_amount / U256::from(1_000_000));
 
     let merge_req = MergePositionsRequest::for_binary_market(USDC_POLYGON, condition_id, merge_amount);
     let merge_calldata = encode_merge_calldata(&merge_req);
-    let code = provider.get_code_at(proxy).await.unwrap_or_default();
+    let tx_hash = execute_ctf_call(&pool, &signer, proxy, ctf, merge_calldata, "Merge positions").await?;
+    Ok(MergeOutcome { tx_hash, merged: vec![(condition_id, shares(merge_amount))] })
+}
+
+/// 在一笔交易中合并多个 `condition_ids` 的最大可用 YES+NO：Safe 走 MultiSend，Relayer 走多条 `ProxyCallTuple`。
+///
+/// 无可合并份额或读取余额失败的市场跳过（失败的市场留待下一轮），不影响其余市场；
+/// 没有任何市场纳入交易时：有读取失败则返回最后一个读取错误，否则返回 [`MergeError::NothingToMerge`]。
+/// 各市场合并数量同 [`merge_max`]。
+///
+/// 交易确认上链后返回 [`MergeOutcome`]，其中只包含实际纳入本笔交易的市场。
+pub async fn merge_max_batch(
+    condition_ids: &[B256],
+    proxy: Address,
+    private_key: &str,
+    rpc_url: Option<&str>,
+) -> Result<MergeOutcome> {
+    let pool = RpcPool::resolve(rpc_url);
+    let signer = LocalSigner::from_str(private_key)?.with_chain_id(Some(POLYGON));
+    let ctf = ctf_address()?;
+
+    let mut merged = Vec::with_capacity(condition_ids.len());
+    let mut calls = Vec::with_capacity(condition_ids.len());
+    let mut read_error = None;
+    for &condition_id in condition_ids {
+        let (b_yes, b_no) = match mergeable_amount(&pool, proxy, condition_id).await {
+            Ok(balances) => balances,
+            Err(e) => {
+                warn!(condition_id = %condition_id, error = %e, "读取可合并余额失败，本轮跳过该市场");
+                read_error = Some(e);
+                continue;
+            }
+        };
+        let merge_amount = b_yes.min(b_no);
+        if merge_amount == U256::ZERO {
+            info!("⏭️ 跳过 condition_id={:#x}：YES={} NO={}，无可用份额", condition_id, b_yes, b_no);
+            continue;
+        }
+        info!("🔄 合并数量: {} ({} USDC) | condition_id={:#x}", merge_amount, merge_amount / U256::from(1_000_000), condition_id);
+        let merge_req = MergePositionsRequest::for_binary_market(USDC_POLYGON, condition_id, merge_amount);
+        calls.push(encode_merge_calldata(&merge_req));
+        merged.push((condition_id, shares(merge_amount)));
+    }
+    if calls.is_empty() {
+        return Err(read_error.unwrap_or_else(|| MergeError::NothingToMerge.into()));
+    }
+
+    let tx_hash = execute_ctf_calls(&pool, &signer, proxy, ctf, calls, "Merge positions (batch)").await?;
+    Ok(MergeOutcome { tx_hash, merged })
+}
+
+/// 在 `proxy` 上将 `amount`（USDC 最小单位，6 位小数）拆分为等量 YES+NO。
+///
+/// 与 [`merge_max`] 走相同的 Safe（execTransaction）/ Relayer 路径，交易确认上链后返回。
+///
+/// 返回交易哈希（十六进制字符串）。
+pub async fn split(
+    condition_id: B256,
+    proxy: Address,
+    private_key: &str,
+    rpc_url: Option<&str>,
+    amount: U256,
+) -> Result<String> {
+    if amount == U256::ZERO {
+        anyhow::bail!("split 数量为 0");
+    }
+    let pool = RpcPool::resolve(rpc_url);
+    let signer = LocalSigner::from_str(private_key)?.with_chain_id(Some(POLYGON));
+    let ctf = ctf_address()?;
+
+    let usdc_balance: U256 = pool
+        .with_failover("balanceOf", move |url| async move {
+            let provider = ProviderBuilder::new().connect(&url).await?;
+            IERC20Balance::new(USDC_POLYGON, provider)
+                .balanceOf(proxy)
+                .call()
+                .await
+                .map_err(|e| rpc_err("读取 USDC 余额失败", e))
+        })
+        .await?;
+    if usdc_balance < amount {
+        anyhow::bail!("USDC 余额不足以 split：需要 {} 可用 {}", amount, usdc_balance);
+    }
+
+    info!("🔀 拆分数量: {} ({} USDC)", amount, amount / U256::from(1_000_000));
+    let split_calldata = encode_split_calldata(condition_id, amount);
+    let tx = execute_ctf_call(&pool, &signer, proxy, ctf, split_calldata, "Split position").await?;
+    info!("✅ Split 成功 tx: {}", tx);
+    Ok(tx)
+}
+
+const WEI_PER_GWEI: u128 = 1_000_000_000;
+
+fn gwei(wei: u128) -> String {
+    format!("{:.2}", wei as f64 / WEI_PER_GWEI as f64)
+}
+
+/// Safe 路径的 Gas 控制，从环境变量读取（gwei，0 或未设置表示不限制）：
+/// `MERGE_MAX_FEE_GWEI`（maxFeePerGas 上限）、`MERGE_PRIORITY_FEE_GWEI`（maxPriorityFeePerGas 上限）、
+/// `MERGE_GAS_PRICE_CEILING_GWEI`（网络 gas 价格高于此值时推迟交易）、
+/// `MERGE_RECEIPT_TIMEOUT_SECS`（等待 receipt 的超时，默认 90；Relayer 路径也用它作为确认超时）、`MERGE_MAX_SPEEDUPS`（超时后加价重发次数，默认 2）。
+struct GasPolicy {
+    max_fee_cap: Option<u128>,
+    priority_fee_cap: Option<u128>,
+    gas_price_ceiling: Option<u128>,
+    receipt_timeout: std::time::Duration,
+    max_speedups: u32,
+}
 
-    if code.len() < 150 {
+impl GasPolicy {
+    fn from_env() -> Self {
+        let gwei_env = |key: &str| {
+            env::var(key)
+                .ok()
+                .and_then(|s| s.trim().parse::<f64>().ok())
+                .filter(|v| *v > 0.0)
+                .map(|v| (v * WEI_PER_GWEI as f64) as u128)
+        };
+        Self {
+            max_fee_cap: gwei_env("MERGE_MAX_FEE_GWEI"),
+            priority_fee_cap: gwei_env("MERGE_PRIORITY_FEE_GWEI"),
+            gas_price_ceiling: gwei_env("MERGE_GAS_PRICE_CEILING_GWEI"),
+            receipt_timeout: std::time::Duration::from_secs(
+                env::var("MERGE_RECEIPT_TIMEOUT_SECS").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(90),
+            ),
+            max_speedups: env::var("MERGE_MAX_SPEEDUPS").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(2),
+        }
+    }
+
+    /// 按上限截断 (maxFee, priorityFee)，并保证 priorityFee <= maxFee
+    fn cap(&self, max_fee: u128, priority_fee: u128) -> (u128, u128) {
+        let max_fee = self.max_fee_cap.map_or(max_fee, |c| max_fee.min(c));
+        let priority_fee = self.priority_fee_cap.map_or(priority_fee, |c| priority_fee.min(c));
+        (max_fee, priority_fee.min(max_fee))
+    }
+
+    /// 加价重发的下一档费用：两项各加 25% 后按上限截断；
+    /// maxFee 已到上限、截断后不再上涨时返回 None（同 nonce 替换交易必须提高费用，否则节点拒绝）
+    fn bump(&self, max_fee: u128, priority_fee: u128) -> Option<(u128, u128)> {
+        let (next_max_fee, next_priority_fee) = self.cap(max_fee + max_fee / 4, priority_fee + priority_fee / 4);
+        (next_max_fee > max_fee).then_some((next_max_fee, next_priority_fee))
+    }
+}
+
+/// Safe 交易发送前的准备结果（只读调用得到）
+struct SafePrepared {
+    signature: Vec<u8>,
+    gas_estimate: u64,
+    gas_price: u128,
+    max_fee: u128,
+    priority_fee: u128,
+    nonce: u64, // EOA 交易 nonce
+    chain_id: u64,
+}
+
+/// 通过 proxy 调用 CTF 合约：EIP-1167（Magic/Email）走 Relayer 并轮询交易状态，Gnosis Safe 走 execTransaction 并等待 receipt。
+pub(crate) async fn execute_ctf_call(
+    pool: &RpcPool,
+    signer: &PrivateKeySigner,
+    proxy: Address,
+    ctf: Address,
+    calldata: Vec<u8>,
+    metadata: &str,
+) -> Result<String> {
+    execute_ctf_calls(pool, signer, proxy, ctf, vec![calldata], metadata).await
+}
+
+/// 在一笔交易中通过 proxy 依次执行多条 CTF 调用：Relayer 为多条 `ProxyCallTuple`；
+/// Safe 只有一条时直接 call CTF，多条时 delegatecall MultiSendCallOnly。任一条失败整笔回滚。
+/// 链上读取、发送与 receipt 查询都经节点池，节点失败或限速时自动切换。
+async fn execute_ctf_calls(
+    pool: &RpcPool,
+    signer: &PrivateKeySigner,
+    proxy: Address,
+    ctf: Address,
+    calls: Vec<Vec<u8>>,
+    metadata: &str,
+) -> Result<String> {
+    if calls.is_empty() {
+        anyhow::bail!("{}：没有可执行的调用", metadata);
+    }
+    let wallet = signer.address();
+
+    if proxy_uses_relayer(pool, proxy).await? {
         let derived = derive_proxy_wallet(wallet, PROXY_FACTORY);
         let try_anyway = env::var("MERGE_TRY_ANYWAY").map(|s| s.trim() == "1" || s.trim().eq_ignore_ascii_case("true")).unwrap_or(false);
         if derived != proxy {
@@ -388,10 +831,13 @@ pub async fn merge_max(
         let builder_passphrase = env::var("POLY_BUILDER_PASSPHRASE").ok();
         let relayer_url = env::var("RELAYER_URL").unwrap_or_else(|_| RELAYER_URL_DEFAULT.to_string());
         match (builder_key.as_deref(), builder_secret.as_deref(), builder_passphrase.as_deref()) {
-            (Some(k), Some(s), Some(p)) => {
-                let out = relayer_execute_merge(&merge_calldata, ctf, proxy, &signer, k, s, p, &relayer_url).await?;
-                info!("✅ Relayer 已提交 tx: {}", out);
-                return Ok(out);
+            (Some(key), Some(secret), Some(passphrase)) => {
+                let submission = relayer_execute(&calls, metadata, ctf, proxy, signer, &BuilderCredentials { key, secret, passphrase }, &relayer_url).await?;
+                info!("📨 Relayer 已提交 {} | id={:?} tx={:?}，等待上链确认", metadata, submission.id, submission.tx_hash);
+                let timeout = GasPolicy::from_env().receipt_timeout;
+                let tx_hash = relayer_wait_confirmed(pool, &relayer_url, submission, timeout, metadata).await?;
+                info!("✅ {} 成功（Relayer）tx: {:#x}", metadata, tx_hash);
+                return Ok(format!("{:#x}", tx_hash));
             }
             _ => anyhow::bail!(
                 "Magic/Email 需配置 POLY_BUILDER_API_KEY、POLY_BUILDER_SECRET、POLY_BUILDER_PASSPHRASE；或改用网页 merge。",
@@ -399,32 +845,434 @@ pub async fn merge_max(
         }
     }
 
-    let safe = IGnosisSafe::new(proxy, provider);
-    let nonce: U256 = safe.nonce().call().await.map_err(|e| {
-        let msg = e.to_string();
-        let hint = if msg.contains("revert") || msg.contains("reverted") {
-            " 该地址可能不是 Gnosis Safe；Magic/Email 请用 Relayer 或网页 merge。"
-        } else { "" };
-        anyhow::anyhow!("读取 Safe nonce 失败: {}{}", msg, hint)
-    })?;
+    // Safe operation：0 = call，1 = delegatecall
+    let (to, calldata, operation) = if calls.len() == 1 {
+        (ctf, calls.into_iter().next().unwrap_or_default(), 0u8)
+    } else {
+        (MULTISEND_CALL_ONLY, encode_multisend(ctf, &calls), 1u8)
+    };
+    let policy = GasPolicy::from_env();
 
-    let tx_hash_data = safe
-        .encodeTransactionData(ctf, U256::ZERO, merge_calldata.clone().into(), 0u8, U256::ZERO, U256::ZERO, U256::ZERO, Address::ZERO, Address::ZERO, nonce)
-        .call().await.map_err(|e| anyhow::anyhow!("Safe.encodeTransactionData 失败: {}", e))?.0;
+    // 1. 读取 Safe nonce 并签名；eth_call 预检（会回滚的交易不发送，不花 gas）、estimateGas、gas 价格与 EOA nonce
+    let prepared = pool
+        .with_failover("Safe 预检", |url| {
+            let signer = signer.clone();
+            let calldata = calldata.clone();
+            async move {
+                let provider = ProviderBuilder::new().wallet(signer.clone()).connect(&url).await?;
+                let safe = IGnosisSafe::new(proxy, provider.clone());
+                let safe_nonce: U256 = match safe.nonce().call().await {
+                    Ok(n) => n,
+                    Err(e) if e.to_string().contains("revert") => anyhow::bail!(
+                        "读取 Safe nonce 失败: {} 该地址可能不是 Gnosis Safe；Magic/Email 请用 Relayer 或网页 merge。",
+                        e
+                    ),
+                    Err(e) => return Err(rpc_err("读取 Safe nonce 失败", e)),
+                };
 
-    let tx_hash = keccak256(tx_hash_data.as_ref());
-    let sig = signer.sign_hash(&tx_hash).await.map_err(|e| anyhow::anyhow!("签名失败: {}", e))?;
-    let mut sig_bytes = sig.as_bytes().to_vec();
-    if sig_bytes.len() == 65 && (sig_bytes[64] == 0 || sig_bytes[64] == 1) {
-        sig_bytes[64] += 27;
+                let tx_hash_data = safe
+                    .encodeTransactionData(to, U256::ZERO, calldata.clone().into(), operation, U256::ZERO, U256::ZERO, U256::ZERO, Address::ZERO, Address::ZERO, safe_nonce)
+                    .call().await.map_err(|e| rpc_err("Safe.encodeTransactionData 失败", e))?.0;
+
+                let tx_hash = keccak256(tx_hash_data.as_ref());
+                let sig = signer.sign_hash(&tx_hash).await.map_err(|e| anyhow::anyhow!("签名失败: {}", e))?;
+                let mut signature = sig.as_bytes().to_vec();
+                if signature.len() == 65 && (signature[64] == 0 || signature[64] == 1) {
+                    signature[64] += 27;
+                }
+
+                let exec = safe.execTransaction(to, U256::ZERO, calldata.into(), operation, U256::ZERO, U256::ZERO, U256::ZERO, Address::ZERO, Address::ZERO, signature.clone().into());
+                match exec.call().await {
+                    Ok(true) => {}
+                    Ok(false) => anyhow::bail!("{} 预检失败：Safe.execTransaction 返回 false", metadata),
+                    Err(e) => return Err(rpc_err(&format!("{} 预检失败（eth_call）", metadata), e)),
+                }
+                let gas_estimate = exec.estimate_gas().await.map_err(|e| rpc_err(&format!("{} estimateGas 失败", metadata), e))?;
+                let gas_price = provider.get_gas_price().await.map_err(|e| rpc_err("读取 gas 价格失败", e))?;
+                let fees = provider.estimate_eip1559_fees().await.map_err(|e| rpc_err("估算 EIP-1559 费用失败", e))?;
+                let nonce = provider
+                    .get_transaction_count(signer.address())
+                    .pending()
+                    .await
+                    .map_err(|e| rpc_err("读取 EOA nonce 失败", e))?;
+                let chain_id = provider.get_chain_id().await.map_err(|e| rpc_err("读取 chain_id 失败", e))?;
+                Ok(SafePrepared {
+                    signature,
+                    gas_estimate,
+                    gas_price,
+                    max_fee: fees.max_fee_per_gas,
+                    priority_fee: fees.max_priority_fee_per_gas,
+                    nonce,
+                    chain_id,
+                })
+            }
+        })
+        .await?;
+
+    // 2. 网络 gas 价格高于上限时推迟；estimateGas 留 20% 余量，EIP-1559 费用按上限截断
+    if let Some(ceiling) = policy.gas_price_ceiling {
+        if prepared.gas_price > ceiling {
+            return Err(MergeError::Postponed { gas_price: prepared.gas_price, ceiling }.into());
+        }
     }
+    let gas_limit = prepared.gas_estimate + prepared.gas_estimate / 5;
+    let nonce = prepared.nonce;
+    let (mut max_fee, mut priority_fee) = policy.cap(prepared.max_fee, prepared.priority_fee);
+    info!(
+        "⛽ {} | gas:{} (估算 {}) | maxFee:{} gwei | priorityFee:{} gwei | nonce:{}",
+        metadata, gas_limit, prepared.gas_estimate, gwei(max_fee), gwei(priority_fee), nonce
+    );
 
-    let pending = safe
-        .execTransaction(ctf, U256::ZERO, merge_calldata.into(), 0u8, U256::ZERO, U256::ZERO, U256::ZERO, Address::ZERO, Address::ZERO, sig_bytes.into())
-        .send().await.map_err(|e| anyhow::anyhow!("Safe.execTransaction 失败: {}", e))?;
+    // 3. 本地签名后广播并等待 receipt；超时则同 nonce 加价重发（speed-up），任一笔上链即结束。
+    // 交易哈希在广播前就记入 sent：广播超时后节点池换节点重试会得到 already known / nonce too low，
+    // 此时交易已在内存池或已上链，按已知哈希等待 receipt 即可
+    let exec_input: Bytes = IGnosisSafe::execTransactionCall {
+        to,
+        value: U256::ZERO,
+        data: calldata.into(),
+        operation,
+        safeTxGas: U256::ZERO,
+        baseGas: U256::ZERO,
+        gasPrice: U256::ZERO,
+        gasToken: Address::ZERO,
+        refundReceiver: Address::ZERO,
+        signatures: prepared.signature.into(),
+    }
+    .abi_encode()
+    .into();
+    let eth_wallet = EthereumWallet::from(signer.clone());
+    let mut sent: Vec<B256> = Vec::new();
+    let mut speedups = 0;
+    loop {
+        let envelope = <Ethereum as Network>::TransactionRequest::default()
+            .with_from(wallet)
+            .with_to(proxy)
+            .with_input(exec_input.clone())
+            .with_chain_id(prepared.chain_id)
+            .with_nonce(nonce)
+            .with_gas_limit(gas_limit)
+            .with_max_fee_per_gas(max_fee)
+            .with_max_priority_fee_per_gas(priority_fee)
+            .build(&eth_wallet)
+            .await
+            .map_err(|e| anyhow::anyhow!("{} 交易签名失败: {}", metadata, e))?;
+        let tx_hash = *envelope.tx_hash();
+        let raw = Bytes::from(envelope.encoded_2718());
+        sent.push(tx_hash);
+
+        let send = pool
+            .with_failover("Safe.execTransaction", |url| {
+                let raw = raw.clone();
+                async move {
+                    let provider = ProviderBuilder::new().connect(&url).await?;
+                    provider
+                        .send_raw_transaction(&raw)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| rpc_err("Safe.execTransaction 失败", e))
+                }
+            })
+            .await;
+        match send {
+            Ok(()) => debug!(tx = %tx_hash, "{} 交易已广播", metadata),
+            Err(e) if is_already_broadcast(&e) => {
+                info!(tx = %tx_hash, error = %e, "{} 交易已在节点内存池或已上链，等待 receipt", metadata)
+            }
+            // 节点明确拒绝（余额不足、替换价不够等）：这笔不会上链
+            Err(e) if rpc_pool::is_node_error(&e) => {
+                sent.pop();
+                if sent.is_empty() {
+                    return Err(e);
+                }
+                warn!(error = %e, "加价重发失败，继续等待已发送的交易");
+            }
+            // 超时 / 连接失败：交易可能已送达节点，照常等待 receipt
+            Err(e) => warn!(tx = %tx_hash, error = %e, "{} 广播结果未知，按交易哈希等待 receipt", metadata),
+        }
 
-    let tx_hash_out = *pending.tx_hash();
-    let _receipt = pending.get_receipt().await.map_err(|e| anyhow::anyhow!("等待 receipt 失败: {}", e))?;
-    info!("✅ Merge 成功（Safe）tx: {:#x}", tx_hash_out);
-    Ok(format!("{:#x}", tx_hash_out))
+        if let Some((tx_hash, receipt)) = wait_for_receipt(pool, &sent, policy.receipt_timeout).await {
+            if !receipt.status() {
+                anyhow::bail!("{} 交易已上链但执行失败 tx: {:#x}", metadata, tx_hash);
+            }
+            info!("✅ {} 成功（Safe）tx: {:#x}", metadata, tx_hash);
+            return Ok(format!("{:#x}", tx_hash));
+        }
+        let next = if speedups < policy.max_speedups { policy.bump(max_fee, priority_fee) } else { None };
+        let Some(next) = next else {
+            anyhow::bail!(
+                "{} 交易 {} 秒内未确认（已加价重发 {} 次{}）tx: {:?}",
+                metadata,
+                policy.receipt_timeout.as_secs(),
+                speedups,
+                if speedups < policy.max_speedups { "，maxFee 已达上限无法继续加价" } else { "" },
+                sent
+            );
+        };
+        speedups += 1;
+        (max_fee, priority_fee) = next;
+        warn!(
+            "⏫ {} 交易 {} 秒未确认，加价重发（第 {} 次）| maxFee:{} gwei | priorityFee:{} gwei",
+            metadata,
+            policy.receipt_timeout.as_secs(),
+            speedups,
+            gwei(max_fee),
+            gwei(priority_fee)
+        );
+    }
+}
+
+/// 广播返回 already known / nonce too low：同一笔交易已被节点接收（或同 nonce 交易已上链），不是发送失败
+fn is_already_broadcast(err: &anyhow::Error) -> bool {
+    let msg = format!("{:#}", err).to_lowercase();
+    msg.contains("already known") || msg.contains("nonce too low")
+}
+
+/// 轮询多笔同 nonce 交易的 receipt，返回最先上链的一笔；超时返回 None
+async fn wait_for_receipt(
+    pool: &RpcPool,
+    tx_hashes: &[B256],
+    timeout: std::time::Duration,
+) -> Option<(B256, <Ethereum as Network>::ReceiptResponse)> {
+    const RECEIPT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+    let start = std::time::Instant::now();
+    loop {
+        let found = pool
+            .with_failover("getTransactionReceipt", |url| {
+                let tx_hashes = tx_hashes.to_vec();
+                async move {
+                    let provider = ProviderBuilder::new().connect(&url).await?;
+                    for tx_hash in tx_hashes {
+                        let receipt = provider
+                            .get_transaction_receipt(tx_hash)
+                            .await
+                            .map_err(|e| rpc_err("读取 receipt 失败", e))?;
+                        if let Some(receipt) = receipt {
+                            return Ok(Some((tx_hash, receipt)));
+                        }
+                    }
+                    Ok(None)
+                }
+            })
+            .await;
+        if let Ok(Some(found)) = found {
+            return Some(found);
+        }
+        if start.elapsed() >= timeout {
+            return None;
+        }
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_fee_cap: Option<u128>, priority_fee_cap: Option<u128>) -> GasPolicy {
+        GasPolicy {
+            max_fee_cap,
+            priority_fee_cap,
+            gas_price_ceiling: None,
+            receipt_timeout: std::time::Duration::from_secs(90),
+            max_speedups: 2,
+        }
+    }
+
+    #[test]
+    fn cap_clamps_fees_and_keeps_priority_below_max() {
+        let gwei = |v: u128| v * WEI_PER_GWEI;
+        assert_eq!(policy(None, None).cap(gwei(100), gwei(30)), (gwei(100), gwei(30)));
+        assert_eq!(policy(Some(gwei(50)), None).cap(gwei(100), gwei(30)), (gwei(50), gwei(30)));
+        assert_eq!(policy(None, Some(gwei(20))).cap(gwei(100), gwei(30)), (gwei(100), gwei(20)));
+        // priorityFee 不得超过截断后的 maxFee
+        assert_eq!(policy(Some(gwei(25)), None).cap(gwei(100), gwei(30)), (gwei(25), gwei(25)));
+    }
+
+    #[test]
+    fn bump_stops_once_max_fee_is_capped() {
+        let policy = policy(Some(110), None);
+        assert_eq!(policy.bump(80, 20), Some((100, 25)));
+        assert_eq!(policy.bump(100, 25), Some((110, 31)));
+        assert_eq!(policy.bump(110, 31), None);
+    }
+
+    #[test]
+    fn already_broadcast_errors_are_matched_through_context() {
+        let known = anyhow::anyhow!("server returned an error response: error code -32000: already known").context("Safe.execTransaction 失败");
+        assert!(is_already_broadcast(&known));
+        assert!(is_already_broadcast(&anyhow::anyhow!("nonce too low: next nonce 12, tx nonce 11")));
+        assert!(!is_already_broadcast(&anyhow::anyhow!("replacement transaction underpriced")));
+        assert!(!is_already_broadcast(&anyhow::anyhow!("insufficient funds for gas * price + value")));
+    }
+
+    #[test]
+    fn encode_multisend_packs_each_call() {
+        let to = address!("0x4D97DCd97eC945f40cF65F87097ACe5EA0476045");
+        let calls = vec![vec![0xaa, 0xbb], vec![0xcc; 3]];
+        let encoded = encode_multisend(to, &calls);
+
+        assert_eq!(encoded[..4], multiSendCall::SELECTOR);
+        let decoded = multiSendCall::abi_decode(&encoded).unwrap();
+        let packed = decoded.transactions.to_vec();
+
+        let mut expected = Vec::new();
+        for data in &calls {
+            expected.push(0u8);
+            expected.extend_from_slice(to.as_slice());
+            expected.extend_from_slice(&[0u8; 32]);
+            let mut len = [0u8; 32];
+            len[31] = data.len() as u8;
+            expected.extend_from_slice(&len);
+            expected.extend_from_slice(data);
+        }
+        assert_eq!(packed, expected);
+        assert_eq!(packed.len(), 2 * (1 + 20 + 32 + 32) + 2 + 3);
+    }
+
+    /// Safe 路径对本地 anvil 节点的集成测试（fork Polygon，使用链上的 Safe v1.3.0 与 CTF 合约），需手动运行：
+    /// `anvil --fork-url <Polygon RPC>`，然后 `ANVIL_RPC_URL=http://127.0.0.1:8545 cargo test -- --ignored anvil`。
+    /// 测试用 anvil 默认账户创建 1/1 Safe，以 CTF `prepareCondition` 作为经 Safe 执行的调用。
+    mod anvil {
+        use super::*;
+        use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+        /// anvil 默认账户 0 的私钥（公开的测试私钥）
+        const ANVIL_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        const SAFE_PROXY_FACTORY: Address = address!("0xa6B71E26C5e0845f74c812102Ca7114b6a896AB2");
+        const SAFE_SINGLETON: Address = address!("0xd9Db270c1B5E3Bd161E8c8503c55cEABeE709552");
+
+        sol! {
+            #[sol(rpc)]
+            interface ISafeProxyFactory {
+                function createProxyWithNonce(address singleton, bytes memory initializer, uint256 saltNonce) external returns (address proxy);
+            }
+            function setup(
+                address[] owners,
+                uint256 threshold,
+                address to,
+                bytes data,
+                address fallbackHandler,
+                address paymentToken,
+                uint256 payment,
+                address paymentReceiver
+            ) external;
+            function prepareCondition(address oracle, bytes32 questionId, uint256 outcomeSlotCount) external;
+        }
+
+        fn anvil_url() -> String {
+            env::var("ANVIL_RPC_URL").unwrap_or_else(|_| "http://127.0.0.1:8545".to_string())
+        }
+
+        fn unique_nonce() -> u128 {
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos()
+        }
+
+        /// 通过 SafeProxyFactory 创建 owner 为 signer、阈值 1 的 Safe
+        async fn deploy_safe(url: &str, signer: &PrivateKeySigner) -> Address {
+            let provider = ProviderBuilder::new().wallet(signer.clone()).connect(url).await.unwrap();
+            let initializer = setupCall {
+                owners: vec![signer.address()],
+                threshold: U256::from(1),
+                to: Address::ZERO,
+                data: Bytes::new(),
+                fallbackHandler: Address::ZERO,
+                paymentToken: Address::ZERO,
+                payment: U256::ZERO,
+                paymentReceiver: Address::ZERO,
+            }
+            .abi_encode();
+            let factory = ISafeProxyFactory::new(SAFE_PROXY_FACTORY, provider);
+            let create = factory.createProxyWithNonce(SAFE_SINGLETON, initializer.into(), U256::from(unique_nonce()));
+            let safe = create.call().await.unwrap();
+            let receipt = create.send().await.unwrap().get_receipt().await.unwrap();
+            assert!(receipt.status(), "创建 Safe 失败");
+            safe
+        }
+
+        /// `prepareCondition` 调用；outcome_slots < 2 时 CTF 会 revert
+        fn prepare_condition(oracle: Address, outcome_slots: u64) -> Vec<u8> {
+            let question_id = keccak256(unique_nonce().to_be_bytes());
+            prepareConditionCall { oracle, questionId: question_id, outcomeSlotCount: U256::from(outcome_slots) }.abi_encode()
+        }
+
+        async fn pending_nonce(provider: &impl Provider, who: Address) -> u64 {
+            provider.get_transaction_count(who).pending().await.unwrap()
+        }
+
+        async fn set_automine(provider: &impl Provider, enabled: bool) {
+            let _: serde_json::Value = provider.raw_request("evm_setAutomine".into(), serde_json::json!([enabled])).await.unwrap();
+        }
+
+        /// 等到内存池里的第一笔交易被同 nonce 的另一笔替换后出块，返回 (原交易, 替换交易)
+        async fn mine_after_replacement(url: String) -> (B256, B256) {
+            let provider = ProviderBuilder::new().connect(&url).await.unwrap();
+            let deadline = Instant::now() + Duration::from_secs(60);
+            let mut first: Option<B256> = None;
+            loop {
+                let content: serde_json::Value = provider.raw_request("txpool_content".into(), serde_json::json!([])).await.unwrap();
+                let pending: Vec<B256> = content["pending"]
+                    .as_object()
+                    .into_iter()
+                    .flat_map(|by_sender| by_sender.values())
+                    .filter_map(|by_nonce| by_nonce.as_object())
+                    .flat_map(|by_nonce| by_nonce.values())
+                    .filter_map(|tx| tx["hash"].as_str()?.parse().ok())
+                    .collect();
+                match (first, pending.first()) {
+                    (None, Some(&hash)) => first = Some(hash),
+                    (Some(original), Some(&hash)) if hash != original => {
+                        let _: serde_json::Value = provider.raw_request("evm_mine".into(), serde_json::json!([])).await.unwrap();
+                        return (original, hash);
+                    }
+                    _ => {}
+                }
+                assert!(Instant::now() < deadline, "60 秒内未见到加价重发的交易");
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        }
+
+        #[tokio::test]
+        #[ignore = "需要本地 anvil --fork-url <Polygon RPC>，可用 ANVIL_RPC_URL 指定地址"]
+        async fn safe_path_preflight_ceiling_and_speedup() {
+            let url = anvil_url();
+            let signer = PrivateKeySigner::from_str(ANVIL_KEY).unwrap();
+            let provider = ProviderBuilder::new().connect(&url).await.unwrap();
+            let pool = RpcPool::new(vec![url.clone()]);
+            let ctf = ctf_address().unwrap();
+            let safe = deploy_safe(&url, &signer).await;
+            assert!(!proxy_uses_relayer(&pool, safe).await.unwrap());
+            env::remove_var("MERGE_GAS_PRICE_CEILING_GWEI");
+
+            // 1. 会 revert 的调用在 eth_call 预检被拒绝，不发送交易（EOA nonce 不变）
+            let before = pending_nonce(&provider, signer.address()).await;
+            let err = execute_ctf_call(&pool, &signer, safe, ctf, prepare_condition(signer.address(), 1), "anvil revert")
+                .await
+                .unwrap_err();
+            assert!(format!("{:#}", err).contains("预检失败"), "{:#}", err);
+            assert_eq!(pending_nonce(&provider, signer.address()).await, before);
+
+            // 2. 网络 gas 价格高于上限（1 wei）时推迟，同样不发送
+            env::set_var("MERGE_GAS_PRICE_CEILING_GWEI", "0.000000001");
+            let err = execute_ctf_call(&pool, &signer, safe, ctf, prepare_condition(signer.address(), 2), "anvil ceiling")
+                .await
+                .unwrap_err();
+            env::remove_var("MERGE_GAS_PRICE_CEILING_GWEI");
+            assert!(matches!(err.downcast_ref::<MergeError>(), Some(MergeError::Postponed { .. })), "{:#}", err);
+            assert_eq!(pending_nonce(&provider, signer.address()).await, before);
+
+            // 3. 关闭自动出块让首笔交易卡住：receipt 超时后同 nonce 加价重发，替换交易上链
+            env::set_var("MERGE_RECEIPT_TIMEOUT_SECS", "4");
+            env::set_var("MERGE_MAX_SPEEDUPS", "2");
+            set_automine(&provider, false).await;
+            let miner = tokio::spawn(mine_after_replacement(url.clone()));
+            let result = execute_ctf_call(&pool, &signer, safe, ctf, prepare_condition(signer.address(), 2), "anvil speed-up").await;
+            let mined = miner.await;
+            set_automine(&provider, true).await;
+            let (original, replacement) = mined.unwrap();
+            let tx_hash: B256 = result.unwrap().parse().unwrap();
+            assert_ne!(original, replacement);
+            assert_eq!(tx_hash, replacement);
+            assert_eq!(pending_nonce(&provider, signer.address()).await, before + 1);
+        }
+    }
 }
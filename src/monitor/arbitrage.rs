@@ -3,101 +3,312 @@ use polymarket_client_sdk::types::{B256, Decimal, U256};
 use rust_decimal_macros::dec;
 use tracing::debug;
 
+use crate::config::Config as BotConfig;
+use crate::recorder::RecordedBook;
+use crate::trading::fees::{FeeModel, FeeSchedule};
+
+/// 检测器读取的订单簿视图：实盘为 WS 推送的 BookUpdate，回测为录制的 RecordedBook。
+/// 档位为 (price, size)，排序与 BookUpdate 一致：bids 价格升序（last 为买一），asks 价格降序（last 为卖一）
+pub trait BookView {
+    fn asset_id(&self) -> U256;
+    fn bids(&self) -> impl DoubleEndedIterator<Item = (Decimal, Decimal)> + '_;
+    fn asks(&self) -> impl DoubleEndedIterator<Item = (Decimal, Decimal)> + '_;
+}
+
+impl BookView for BookUpdate {
+    fn asset_id(&self) -> U256 {
+        self.asset_id
+    }
+
+    fn bids(&self) -> impl DoubleEndedIterator<Item = (Decimal, Decimal)> + '_ {
+        self.bids.iter().map(|l| (l.price, l.size))
+    }
+
+    fn asks(&self) -> impl DoubleEndedIterator<Item = (Decimal, Decimal)> + '_ {
+        self.asks.iter().map(|l| (l.price, l.size))
+    }
+}
+
+impl BookView for RecordedBook {
+    fn asset_id(&self) -> U256 {
+        self.asset_id
+    }
+
+    fn bids(&self) -> impl DoubleEndedIterator<Item = (Decimal, Decimal)> + '_ {
+        self.bids.iter().copied()
+    }
+
+    fn asks(&self) -> impl DoubleEndedIterator<Item = (Decimal, Decimal)> + '_ {
+        self.asks.iter().copied()
+    }
+}
+
+/// 单档成交计划：在该价位吃掉的份额
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelFill {
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
 #[derive(Debug, Clone)]
 pub struct ArbitrageOpportunity {
     pub market_id: B256,
     pub yes_token_id: U256,
     pub no_token_id: U256,
-    pub yes_ask_price: Decimal,
-    pub no_ask_price: Decimal,
+    pub yes_ask_price: Decimal, // 卖一价
+    pub no_ask_price: Decimal,  // 卖一价
+    pub yes_worst_price: Decimal, // 按深度吃单时最差（最高）成交价，作为限价单价格
+    pub no_worst_price: Decimal,
+    pub yes_vwap: Decimal,
+    pub no_vwap: Decimal,
+    pub yes_fills: Vec<LevelFill>, // YES 逐档成交计划（价格由低到高）
+    pub no_fills: Vec<LevelFill>,  // NO 逐档成交计划（价格由低到高）
     pub total_cost: Decimal,
-    pub profit_percentage: Decimal,
+    pub estimated_fee: Decimal, // 两腿手续费导致少 merge 的份额，按每对 1 USD 计
+    pub expected_profit: Decimal, // 扣除手续费后的预期净利润（USD）= 份额 - 手续费 - 总成本
+    pub profit_percentage: Decimal, // 每份净利润百分比
     pub yes_size: Decimal,
     pub no_size: Decimal,
 }
 
+/// 卖方向套利（split-and-sell）：yes_bid + no_bid > 1 时，用 USDC 拆分出 YES+NO 并分别卖入买盘
+#[derive(Debug, Clone)]
+pub struct SellArbitrageOpportunity {
+    pub market_id: B256,
+    pub yes_token_id: U256,
+    pub no_token_id: U256,
+    pub yes_bid_price: Decimal, // 买一价
+    pub no_bid_price: Decimal,  // 买一价
+    pub yes_worst_price: Decimal, // 按深度卖出时最差（最低）成交价，作为限价卖单价格
+    pub no_worst_price: Decimal,
+    pub yes_vwap: Decimal,
+    pub no_vwap: Decimal,
+    pub yes_fills: Vec<LevelFill>, // YES 逐档成交计划（价格由高到低）
+    pub no_fills: Vec<LevelFill>,  // NO 逐档成交计划（价格由高到低）
+    pub total_proceeds: Decimal, // 两腿卖出总收入（USD）
+    pub estimated_fee: Decimal, // 两腿卖出手续费（USD）
+    pub expected_profit: Decimal, // 扣除手续费后的预期净利润（USD）= 总收入 - 手续费 - 拆分成本（每份 1 USD）
+    pub profit_percentage: Decimal, // 每份净利润百分比
+    pub size: Decimal, // 拆分份额（= 拆分 USDC 数量）
+}
+
+/// 套利方向：买入 YES+NO 后 merge，或 split 后卖出 YES+NO
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArbitrageSide {
+    Buy,
+    Sell,
+}
+
 pub struct ArbitrageDetector {
-    min_profit_threshold: Decimal,
     max_depth: usize, // 最大探测深度
     min_order_value_usd: Decimal, // 最小订单金额（USD）
+    execution_threshold: Decimal, // 组合 VWAP 上限 = 1 - 套利执行价差
+    max_order_size: Decimal, // 单笔最大份额
+    fees: FeeSchedule,
+}
+
+/// 按深度吃单的结果：份额、各腿逐档计划与成本
+struct DepthPlan {
+    size: Decimal,
+    yes_fills: Vec<LevelFill>,
+    no_fills: Vec<LevelFill>,
+    yes_cost: Decimal,
+    no_cost: Decimal,
+    fee: Decimal,
+}
+
+/// 份额向下取整到 2 位小数
+#[inline]
+fn floor_size(size: Decimal) -> Decimal {
+    (size * dec!(100.0)).floor() / dec!(100.0)
+}
+
+/// 从卖盘中取最优的 max_depth 档，按价格由低到高排列（asks 最后一个为卖一价）
+fn ask_levels(book: &impl BookView, max_depth: usize) -> Vec<(Decimal, Decimal)> {
+    book.asks()
+        .rev()
+        .filter(|(_, size)| *size > dec!(0))
+        .take(max_depth)
+        .collect()
+}
+
+/// 从买盘中取最优的 max_depth 档，按价格由高到低排列（bids 最后一个为买一价）
+fn bid_levels(book: &impl BookView, max_depth: usize) -> Vec<(Decimal, Decimal)> {
+    book.bids()
+        .rev()
+        .filter(|(_, size)| *size > dec!(0))
+        .take(max_depth)
+        .collect()
+}
+
+/// 按给定份额逐档吃单，返回 (逐档计划, 总成本)
+fn fill_levels(levels: &[(Decimal, Decimal)], size: Decimal) -> (Vec<LevelFill>, Decimal) {
+    let mut remaining = size;
+    let mut fills = Vec::new();
+    let mut cost = dec!(0);
+    for &(price, available) in levels {
+        if remaining <= dec!(0) {
+            break;
+        }
+        let take = available.min(remaining);
+        fills.push(LevelFill { price, size: take });
+        cost += price * take;
+        remaining -= take;
+    }
+    (fills, cost)
 }
 
 impl ArbitrageDetector {
-    pub fn new(min_profit_threshold: f64) -> Self {
+    pub fn new(config: &BotConfig) -> Self {
+        let spread = Decimal::try_from(config.arbitrage_execution_spread).unwrap_or(dec!(0.01));
         Self {
-            min_profit_threshold: Decimal::try_from(min_profit_threshold)
-                .unwrap_or(dec!(0.001)),
             max_depth: 10, // 默认最多探测10档
             min_order_value_usd: dec!(1.0), // 最小订单金额$1
+            execution_threshold: dec!(1.0) - spread,
+            max_order_size: Decimal::try_from(config.max_order_size_usdc).unwrap_or(dec!(100.0)),
+            fees: FeeSchedule::from_config(config),
         }
     }
 
-    /// 选中价格：仅用卖一价。返回 (yes_ask, no_ask, size, profit_pct, total_price)。
-    /// 后续在 executor 中：比较哪个价格高 → 加滑点 → 放入订单创建。
-    fn find_best_opportunity(
+    /// 使用共享的按市场手续费登记表（发现市场时登记）
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// 同时沿 YES、NO 卖盘向下走最多 max_depth 档，找出总利润最大的份额（已向下取整到 2 位小数）。
+    /// 每多买 1 份的边际成本 = 当前 YES 档价 + 当前 NO 档价，单调不减；
+    /// 只要边际成本 + 每对手续费 < 1 就继续加量，同时保证组合 VWAP 不超过执行阈值。
+    /// unit_fee(yes_price, no_price)：在当前两档各成交 1 份时的每对手续费（USD）。
+    fn walk_depth(
         &self,
-        yes_book: &BookUpdate,
-        no_book: &BookUpdate,
-    ) -> Option<(Decimal, Decimal, Decimal, Decimal, Decimal)> {
-        // asks 最后一个为卖一价（最低卖价）
-        let yes_best = yes_book.asks.last()?;
-        let no_best = no_book.asks.last()?;
-
-        let yes_price = yes_best.price.round_dp(2);
-        let no_price = no_best.price.round_dp(2);
-        let total_price = yes_price + no_price;
-
-        if total_price > dec!(1.0) {
-            return None; // 卖一总价 > 1，无套利
-        }
+        yes_levels: &[(Decimal, Decimal)],
+        no_levels: &[(Decimal, Decimal)],
+        unit_fee: impl Fn(Decimal, Decimal) -> Decimal,
+    ) -> Option<Decimal> {
+        let threshold = self.execution_threshold;
+        let (mut i, mut j) = (0usize, 0usize);
+        let mut yes_left = yes_levels.first()?.1;
+        let mut no_left = no_levels.first()?.1;
+        let mut size = dec!(0);
+        let mut cost = dec!(0);
 
-        // 卖一档的可用份额取两者较小值，向下取整到 2 位小数
-        let raw_size = yes_best.size.min(no_best.size);
-        let final_size = if raw_size.is_zero() {
-            dec!(0.01)
-        } else {
-            (raw_size * dec!(100.0)).floor() / dec!(100.0)
-        };
+        while i < yes_levels.len() && j < no_levels.len() && size < self.max_order_size {
+            let marginal = yes_levels[i].0 + no_levels[j].0;
+            if marginal + unit_fee(yes_levels[i].0, no_levels[j].0) >= dec!(1.0) {
+                break; // 再加量扣费后已无利润
+            }
+            let step = yes_left.min(no_left).min(self.max_order_size - size);
+
+            // 加满本段后组合 VWAP 是否仍在阈值内；否则只取到 VWAP 恰好等于阈值的份额
+            if cost + step * marginal > threshold * (size + step) {
+                if marginal > threshold {
+                    let partial = (threshold * size - cost) / (marginal - threshold);
+                    if partial > dec!(0) {
+                        size += partial.min(step);
+                    }
+                }
+                break;
+            }
+
+            size += step;
+            cost += step * marginal;
+            yes_left -= step;
+            no_left -= step;
+            if yes_left <= dec!(0) {
+                i += 1;
+                yes_left = yes_levels.get(i).map(|l| l.1).unwrap_or(dec!(0));
+            }
+            if no_left <= dec!(0) {
+                j += 1;
+                no_left = no_levels.get(j).map(|l| l.1).unwrap_or(dec!(0));
+            }
+        }
 
-        let yes_order_value = yes_price * final_size;
-        let no_order_value = no_price * final_size;
-        if yes_order_value < self.min_order_value_usd || no_order_value < self.min_order_value_usd {
+        let size = floor_size(size);
+        if size <= dec!(0) {
             return None;
         }
+        Some(size)
+    }
 
-        let profit_pct = (dec!(1.0) - total_price) * dec!(100.0);
-        Some((yes_price, no_price, final_size, profit_pct, total_price))
+    /// 逐档计算两腿手续费，返回一对的损耗份额
+    fn plan_fee(fee_model: &FeeModel, yes_fills: &[LevelFill], no_fills: &[LevelFill]) -> Decimal {
+        let yes_fee: Decimal = yes_fills
+            .iter()
+            .map(|f| fee_model.fee_shares(f.price, f.size))
+            .sum();
+        let no_fee: Decimal = no_fills
+            .iter()
+            .map(|f| fee_model.fee_shares(f.price, f.size))
+            .sum();
+        fee_model.pair_fee_shares(yes_fee, no_fee)
     }
 
+    /// 按深度选档：返回逐档计划；单腿金额不足最小下单金额、或扣费后无净利润时返回 None。
+    /// 最小利润阈值由 OpportunityGate 在执行前检查。
+    /// 后续在 executor 中：以最差档价格为基准 → 加滑点 → 放入订单创建。
+    fn find_best_opportunity<B: BookView>(
+        &self,
+        yes_book: &B,
+        no_book: &B,
+    ) -> Option<DepthPlan> {
+        let yes_levels = ask_levels(yes_book, self.max_depth);
+        let no_levels = ask_levels(no_book, self.max_depth);
+
+        let fee_model = self.fees.for_token(yes_book.asset_id());
+        let size = self.walk_depth(&yes_levels, &no_levels, |yes_price, no_price| {
+            fee_model.pair_fee_shares(
+                fee_model.fee_shares(yes_price, dec!(1.0)),
+                fee_model.fee_shares(no_price, dec!(1.0)),
+            )
+        })?;
+        let (yes_fills, yes_cost) = fill_levels(&yes_levels, size);
+        let (no_fills, no_cost) = fill_levels(&no_levels, size);
+        let fee = Self::plan_fee(&fee_model, &yes_fills, &no_fills);
+        let plan = DepthPlan { size, yes_fills, no_fills, yes_cost, no_cost, fee };
 
-    /// 打印订单深度（debug 级别，减少 info 刷屏）
-    fn print_orderbook_depth(
+        if plan.yes_cost < self.min_order_value_usd || plan.no_cost < self.min_order_value_usd {
+            return None;
+        }
+
+        let net_profit = plan.size - plan.fee - plan.yes_cost - plan.no_cost;
+        if net_profit <= dec!(0) {
+            debug!(
+                size = %plan.size,
+                fee = %plan.fee,
+                net_profit = %net_profit,
+                "扣除手续费后利润不足，忽略"
+            );
+            return None;
+        }
+        Some(plan)
+    }
+
+    /// 打印订单深度（debug 级别，减少 info 刷屏），计划吃到的档位用 ← 标出
+    fn print_orderbook_depth<B: BookView>(
         &self,
-        yes_book: &BookUpdate,
-        no_book: &BookUpdate,
-        yes_final_price: Decimal,
-        no_final_price: Decimal,
-        yes_final_size: Decimal,
-        no_final_size: Decimal,
+        yes_book: &B,
+        no_book: &B,
+        yes_worst_price: Decimal,
+        no_worst_price: Decimal,
     ) {
-        let yes_asks = &yes_book.asks;
-        let yes_depth_str: Vec<String> = yes_asks
-            .iter()
+        let yes_depth_str: Vec<String> = yes_book
+            .asks()
             .rev()
             .take(5)
-            .map(|level| {
-                let m = if (level.price - yes_final_price).abs() < dec!(0.001) { "←" } else { "" };
-                format!("{:.2}@{:.2}{}", level.price, level.size, m)
+            .map(|(price, size)| {
+                let m = if price <= yes_worst_price { "←" } else { "" };
+                format!("{:.2}@{:.2}{}", price, size, m)
             })
             .collect();
-        let no_asks = &no_book.asks;
-        let no_depth_str: Vec<String> = no_asks
-            .iter()
+        let no_depth_str: Vec<String> = no_book
+            .asks()
             .rev()
             .take(5)
-            .map(|level| {
-                let m = if (level.price - no_final_price).abs() < dec!(0.001) { "←" } else { "" };
-                format!("{:.2}@{:.2}{}", level.price, level.size, m)
+            .map(|(price, size)| {
+                let m = if price <= no_worst_price { "←" } else { "" };
+                format!("{:.2}@{:.2}{}", price, size, m)
             })
             .collect();
         debug!(
@@ -109,38 +320,255 @@ impl ArbitrageDetector {
     }
 
     /// 检查订单簿是否存在套利机会
-    pub fn check_arbitrage(
+    pub fn check_arbitrage<B: BookView>(
         &self,
-        yes_book: &BookUpdate,
-        no_book: &BookUpdate,
+        yes_book: &B,
+        no_book: &B,
         market_id: &B256,
     ) -> Option<ArbitrageOpportunity> {
-        // 先选卖一价；executor 中再：比较谁高 → 加滑点 → 放入订单创建
-        let (yes_ask, no_ask, final_size, net_profit_pct, total_price) =
-            self.find_best_opportunity(yes_book, no_book)?;
+        // 先按深度选档；executor 中再：以最差档价格加滑点 → 放入订单创建
+        let plan = self.find_best_opportunity(yes_book, no_book)?;
 
-        self.print_orderbook_depth(yes_book, no_book, yes_ask, no_ask, final_size, final_size);
+        let yes_ask = plan.yes_fills.first()?.price;
+        let no_ask = plan.no_fills.first()?.price;
+        let yes_worst = plan.yes_fills.last()?.price;
+        let no_worst = plan.no_fills.last()?.price;
+        let total_cost = plan.yes_cost + plan.no_cost;
+        let expected_profit = plan.size - plan.fee - total_cost;
+        let profit_pct = expected_profit / plan.size * dec!(100.0);
+
+        self.print_orderbook_depth(yes_book, no_book, yes_worst, no_worst);
 
         debug!(
             market_id = %market_id,
             yes_price = %yes_ask,
             no_price = %no_ask,
-            total_price = %total_price,
-            net_profit_pct = %net_profit_pct,
-            order_size = %final_size,
-            "发现套利机会（卖一价）"
+            yes_worst = %yes_worst,
+            no_worst = %no_worst,
+            yes_levels = plan.yes_fills.len(),
+            no_levels = plan.no_fills.len(),
+            total_cost = %total_cost,
+            fee = %plan.fee,
+            expected_profit = %expected_profit,
+            net_profit_pct = %profit_pct,
+            order_size = %plan.size,
+            "发现套利机会（深度 VWAP）"
         );
 
         Some(ArbitrageOpportunity {
             market_id: *market_id,
-            yes_token_id: yes_book.asset_id,
-            no_token_id: no_book.asset_id,
+            yes_token_id: yes_book.asset_id(),
+            no_token_id: no_book.asset_id(),
             yes_ask_price: yes_ask,
             no_ask_price: no_ask,
-            total_cost: total_price * final_size,
-            profit_percentage: net_profit_pct,
-            yes_size: final_size,
-            no_size: final_size,
+            yes_worst_price: yes_worst,
+            no_worst_price: no_worst,
+            yes_vwap: plan.yes_cost / plan.size,
+            no_vwap: plan.no_cost / plan.size,
+            yes_fills: plan.yes_fills,
+            no_fills: plan.no_fills,
+            total_cost,
+            estimated_fee: plan.fee,
+            expected_profit,
+            profit_percentage: profit_pct,
+            yes_size: plan.size,
+            no_size: plan.size,
+        })
+    }
+
+    /// 检查订单簿是否存在卖方向套利机会（yes_bid + no_bid > 1）。
+    /// 卖出 YES@p 等价于买入 NO@(1-p)，因此把买盘价格映射为 1-p 后复用 walk_depth：
+    /// (1-yes_bid) + (1-no_bid) <= 1 - 执行价差  ⇔  yes_bid + no_bid >= 1 + 执行价差。
+    pub fn check_sell_arbitrage<B: BookView>(
+        &self,
+        yes_book: &B,
+        no_book: &B,
+        market_id: &B256,
+    ) -> Option<SellArbitrageOpportunity> {
+        let yes_levels = bid_levels(yes_book, self.max_depth);
+        let no_levels = bid_levels(no_book, self.max_depth);
+        let mirror = |levels: &[(Decimal, Decimal)]| -> Vec<(Decimal, Decimal)> {
+            levels.iter().map(|&(p, sz)| (dec!(1.0) - p, sz)).collect()
+        };
+
+        let fee_model = self.fees.for_token(yes_book.asset_id());
+        let size = self.walk_depth(&mirror(&yes_levels), &mirror(&no_levels), |yes_mirror, no_mirror| {
+            fee_model.sell_fee_usd(dec!(1.0) - yes_mirror, dec!(1.0))
+                + fee_model.sell_fee_usd(dec!(1.0) - no_mirror, dec!(1.0))
+        })?;
+
+        let (yes_fills, yes_proceeds) = fill_levels(&yes_levels, size);
+        let (no_fills, no_proceeds) = fill_levels(&no_levels, size);
+        if yes_proceeds < self.min_order_value_usd || no_proceeds < self.min_order_value_usd {
+            return None;
+        }
+
+        let fee: Decimal = yes_fills
+            .iter()
+            .chain(no_fills.iter())
+            .map(|f| fee_model.sell_fee_usd(f.price, f.size))
+            .sum();
+        let total_proceeds = yes_proceeds + no_proceeds;
+        let expected_profit = total_proceeds - fee - size;
+        if expected_profit <= dec!(0) {
+            return None;
+        }
+        let profit_pct = expected_profit / size * dec!(100.0);
+
+        let yes_bid = yes_fills.first()?.price;
+        let no_bid = no_fills.first()?.price;
+        let yes_worst = yes_fills.last()?.price;
+        let no_worst = no_fills.last()?.price;
+
+        debug!(
+            market_id = %market_id,
+            yes_bid = %yes_bid,
+            no_bid = %no_bid,
+            yes_worst = %yes_worst,
+            no_worst = %no_worst,
+            total_proceeds = %total_proceeds,
+            fee = %fee,
+            expected_profit = %expected_profit,
+            net_profit_pct = %profit_pct,
+            split_size = %size,
+            "发现卖方向套利机会（split 后卖出）"
+        );
+
+        Some(SellArbitrageOpportunity {
+            market_id: *market_id,
+            yes_token_id: yes_book.asset_id(),
+            no_token_id: no_book.asset_id(),
+            yes_bid_price: yes_bid,
+            no_bid_price: no_bid,
+            yes_worst_price: yes_worst,
+            no_worst_price: no_worst,
+            yes_vwap: yes_proceeds / size,
+            no_vwap: no_proceeds / size,
+            yes_fills,
+            no_fills,
+            total_proceeds,
+            estimated_fee: fee,
+            expected_profit,
+            profit_percentage: profit_pct,
+            size,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YES: u64 = 1;
+    const NO: u64 = 2;
+
+    /// 档位按 BookUpdate 的排序传入：bids 价格升序、asks 价格降序
+    fn book(asset: u64, bids: Vec<(Decimal, Decimal)>, asks: Vec<(Decimal, Decimal)>) -> RecordedBook {
+        RecordedBook {
+            recv_ts_ms: 0,
+            window_ts: 0,
+            market_id: B256::ZERO,
+            slug: String::new(),
+            asset_id: U256::from(asset),
+            bids,
+            asks,
+        }
+    }
+
+    fn detector(max_depth: usize, execution_threshold: Decimal, max_order_size: Decimal, fee: FeeModel) -> ArbitrageDetector {
+        ArbitrageDetector {
+            max_depth,
+            min_order_value_usd: dec!(1.0),
+            execution_threshold,
+            max_order_size,
+            fees: FeeSchedule::new(fee),
+        }
+    }
+
+    #[test]
+    fn buy_walks_levels_and_reports_vwap() {
+        let detector = detector(10, dec!(0.99), dec!(100), FeeModel::free());
+        let yes = book(YES, vec![], vec![(dec!(0.46), dec!(10)), (dec!(0.45), dec!(10))]);
+        let no = book(NO, vec![], vec![(dec!(0.50), dec!(20))]);
+
+        let opp = detector.check_arbitrage(&yes, &no, &B256::ZERO).unwrap();
+        assert_eq!(opp.yes_size, dec!(20));
+        assert_eq!(
+            opp.yes_fills,
+            vec![LevelFill { price: dec!(0.45), size: dec!(10) }, LevelFill { price: dec!(0.46), size: dec!(10) }]
+        );
+        assert_eq!(opp.yes_ask_price, dec!(0.45));
+        assert_eq!(opp.yes_worst_price, dec!(0.46));
+        assert_eq!(opp.yes_vwap, dec!(0.455));
+        assert_eq!(opp.no_vwap, dec!(0.50));
+        assert_eq!(opp.total_cost, dec!(19.1));
+        assert_eq!(opp.expected_profit, dec!(0.9));
+    }
+
+    #[test]
+    fn buy_stops_when_marginal_plus_fee_reaches_one() {
+        let yes = book(YES, vec![], vec![(dec!(0.49), dec!(10)), (dec!(0.40), dec!(10))]);
+        let no = book(NO, vec![], vec![(dec!(0.50), dec!(20))]);
+
+        // 不计手续费时第二档边际成本 0.99 < 1，继续加量
+        let free = detector(10, dec!(1.0), dec!(100), FeeModel::free());
+        assert_eq!(free.check_arbitrage(&yes, &no, &B256::ZERO).unwrap().yes_size, dec!(20));
+
+        // 计入约 1.56% 的每对手续费后 0.99 + 0.0156 >= 1，停在第一档
+        let charged = detector(10, dec!(1.0), dec!(100), FeeModel::new(0.25, 2.0));
+        let opp = charged.check_arbitrage(&yes, &no, &B256::ZERO).unwrap();
+        assert_eq!(opp.yes_size, dec!(10));
+        assert_eq!(opp.yes_worst_price, dec!(0.40));
+        assert!(opp.estimated_fee > dec!(0));
+        assert_eq!(opp.expected_profit, dec!(10) - opp.estimated_fee - dec!(9));
+    }
+
+    #[test]
+    fn buy_only_reads_max_depth_levels() {
+        let detector = detector(1, dec!(0.99), dec!(100), FeeModel::free());
+        let yes = book(YES, vec![], vec![(dec!(0.46), dec!(10)), (dec!(0.45), dec!(10))]);
+        let no = book(NO, vec![], vec![(dec!(0.50), dec!(20))]);
+
+        let opp = detector.check_arbitrage(&yes, &no, &B256::ZERO).unwrap();
+        assert_eq!(opp.yes_size, dec!(10));
+        assert_eq!(opp.yes_fills.len(), 1);
+    }
+
+    #[test]
+    fn buy_is_capped_at_max_order_size() {
+        let detector = detector(10, dec!(0.99), dec!(15), FeeModel::free());
+        let yes = book(YES, vec![], vec![(dec!(0.46), dec!(10)), (dec!(0.45), dec!(10))]);
+        let no = book(NO, vec![], vec![(dec!(0.50), dec!(20))]);
+
+        let opp = detector.check_arbitrage(&yes, &no, &B256::ZERO).unwrap();
+        assert_eq!(opp.yes_size, dec!(15));
+        assert_eq!(
+            opp.yes_fills,
+            vec![LevelFill { price: dec!(0.45), size: dec!(10) }, LevelFill { price: dec!(0.46), size: dec!(5) }]
+        );
+    }
+
+    #[test]
+    fn no_buy_opportunity_above_one() {
+        let detector = detector(10, dec!(0.99), dec!(100), FeeModel::free());
+        let yes = book(YES, vec![], vec![(dec!(0.52), dec!(10))]);
+        let no = book(NO, vec![], vec![(dec!(0.50), dec!(10))]);
+        assert!(detector.check_arbitrage(&yes, &no, &B256::ZERO).is_none());
+    }
+
+    #[test]
+    fn sell_mirrors_bid_levels() {
+        let detector = detector(10, dec!(0.99), dec!(100), FeeModel::free());
+        // 买一 0.55，第二档 0.50：映射后 0.45 + 0.50 = 0.95 可加量，0.50 + 0.50 = 1 停止
+        let yes = book(YES, vec![(dec!(0.50), dec!(10)), (dec!(0.55), dec!(10))], vec![]);
+        let no = book(NO, vec![(dec!(0.50), dec!(20))], vec![]);
+
+        let opp = detector.check_sell_arbitrage(&yes, &no, &B256::ZERO).unwrap();
+        assert_eq!(opp.size, dec!(10));
+        assert_eq!(opp.yes_bid_price, dec!(0.55));
+        assert_eq!(opp.yes_worst_price, dec!(0.55));
+        assert_eq!(opp.total_proceeds, dec!(10.5));
+        assert_eq!(opp.expected_profit, dec!(0.5));
+        assert!(detector.check_arbitrage(&yes, &no, &B256::ZERO).is_none());
+    }
+}
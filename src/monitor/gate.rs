@@ -0,0 +1,292 @@
+//! 套利机会闸门：执行前的最后一道过滤。
+//! 1) 扣费后每份净利润须达到 min_profit_threshold；
+//! 2) 同一市场同时只允许一个套利对在执行中，上一个订单对仍有腿挂单时也不再开新对；
+//! 3) 同一市场、同一价位触发后在冷却期内不再重复触发。
+
+use dashmap::DashMap;
+use polymarket_client_sdk::types::{B256, Decimal};
+use rust_decimal_macros::dec;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::trace;
+
+use super::arbitrage::{ArbitrageOpportunity, ArbitrageSide, SellArbitrageOpportunity};
+use crate::config::Config as BotConfig;
+use crate::risk::RiskManager;
+
+/// 闸门拒绝原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum GateRejection {
+    BelowThreshold { profit_pct: Decimal, threshold_pct: Decimal },
+    InFlight { elapsed: Duration },
+    Resting,
+    Cooldown { remaining: Duration },
+}
+
+impl fmt::Display for GateRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GateRejection::BelowThreshold { profit_pct, threshold_pct } => {
+                write!(f, "净利润 {:.2}% 低于阈值 {:.2}%", profit_pct, threshold_pct)
+            }
+            GateRejection::InFlight { elapsed } => {
+                write!(f, "该市场已有套利对执行中（已 {:.1} 秒）", elapsed.as_secs_f32())
+            }
+            GateRejection::Resting => write!(f, "该市场已有订单对挂单中"),
+            GateRejection::Cooldown { remaining } => {
+                write!(f, "同价位冷却中（剩余 {:.1} 秒）", remaining.as_secs_f32())
+            }
+        }
+    }
+}
+
+/// 两次交易之间的最小间隔（与闸门一起在执行前检查）
+pub const MIN_TRADE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// 冷却键：市场 + 方向 + YES/NO 触发价（买方向为卖一价，卖方向为买一价）
+type PriceLevelKey = (B256, ArbitrageSide, Decimal, Decimal);
+
+pub struct OpportunityGate {
+    min_profit_pct: Decimal, // 与 ArbitrageOpportunity::profit_percentage 同单位（百分比）
+    cooldown: Duration,
+    in_flight: DashMap<B256, Instant>, // market_id -> 开始执行时间
+    recent: DashMap<PriceLevelKey, Instant>, // 价位 -> 上次触发时间
+}
+
+/// 执行中标记：随套利任务结束（drop）自动释放该市场；之后仍在挂单的订单对由 RiskManager 判定
+pub struct InFlightGuard {
+    gate: Arc<OpportunityGate>,
+    market_id: B256,
+    cooldown_key: Option<PriceLevelKey>,
+}
+
+impl InFlightGuard {
+    /// 放行后未能执行（如每日交易次数已用完）：撤销本次记录的冷却并释放该市场
+    pub fn rollback(mut self) {
+        if let Some(key) = self.cooldown_key.take() {
+            self.gate.recent.remove(&key);
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.gate.in_flight.remove(&self.market_id);
+        trace!(market_id = %self.market_id, "释放执行中标记");
+    }
+}
+
+impl OpportunityGate {
+    pub fn new(config: &BotConfig) -> Self {
+        Self {
+            min_profit_pct: Decimal::try_from(config.min_profit_threshold).unwrap_or(dec!(0.001))
+                * dec!(100.0),
+            cooldown: Duration::from_secs(config.opportunity_cooldown_secs),
+            in_flight: DashMap::new(),
+            recent: DashMap::new(),
+        }
+    }
+
+    /// 尝试放行套利机会：通过则记录冷却并返回执行中标记，调用方需持有到执行结束
+    pub fn try_acquire(
+        self: &Arc<Self>,
+        opp: &ArbitrageOpportunity,
+        risk_manager: &RiskManager,
+    ) -> Result<InFlightGuard, GateRejection> {
+        self.try_acquire_at(opp, risk_manager, Instant::now())
+    }
+
+    /// 同 try_acquire，但使用调用方给定的时钟（回测按录制时间推进）
+    pub fn try_acquire_at(
+        self: &Arc<Self>,
+        opp: &ArbitrageOpportunity,
+        risk_manager: &RiskManager,
+        now: Instant,
+    ) -> Result<InFlightGuard, GateRejection> {
+        self.acquire(
+            opp.market_id,
+            (ArbitrageSide::Buy, opp.yes_ask_price, opp.no_ask_price),
+            opp.profit_percentage,
+            risk_manager,
+            now,
+        )
+    }
+
+    /// 卖方向（split-and-sell）套利的放行检查，与买方向共用同一市场的执行中标记
+    pub fn try_acquire_sell(
+        self: &Arc<Self>,
+        opp: &SellArbitrageOpportunity,
+        risk_manager: &RiskManager,
+    ) -> Result<InFlightGuard, GateRejection> {
+        self.try_acquire_sell_at(opp, risk_manager, Instant::now())
+    }
+
+    pub fn try_acquire_sell_at(
+        self: &Arc<Self>,
+        opp: &SellArbitrageOpportunity,
+        risk_manager: &RiskManager,
+        now: Instant,
+    ) -> Result<InFlightGuard, GateRejection> {
+        self.acquire(
+            opp.market_id,
+            (ArbitrageSide::Sell, opp.yes_bid_price, opp.no_bid_price),
+            opp.profit_percentage,
+            risk_manager,
+            now,
+        )
+    }
+
+    fn acquire(
+        self: &Arc<Self>,
+        market_id: B256,
+        (side, yes_price, no_price): (ArbitrageSide, Decimal, Decimal),
+        profit_pct: Decimal,
+        risk_manager: &RiskManager,
+        now: Instant,
+    ) -> Result<InFlightGuard, GateRejection> {
+        if profit_pct < self.min_profit_pct {
+            return Err(GateRejection::BelowThreshold {
+                profit_pct,
+                threshold_pct: self.min_profit_pct,
+            });
+        }
+
+        // 执行任务结束后 GTC/GTD 腿可能仍在挂单，直到订单对进入终态前不再开新对
+        if risk_manager.has_resting_pair(market_id) {
+            return Err(GateRejection::Resting);
+        }
+
+        let key = (market_id, side, yes_price, no_price);
+
+        // 清理过期的冷却记录，避免长期运行时无限增长
+        self.recent
+            .retain(|_, fired| now.saturating_duration_since(*fired) < self.cooldown);
+
+        if let Some(fired) = self.recent.get(&key) {
+            let elapsed = now.saturating_duration_since(*fired);
+            return Err(GateRejection::Cooldown {
+                remaining: self.cooldown.saturating_sub(elapsed),
+            });
+        }
+
+        match self.in_flight.entry(market_id) {
+            dashmap::mapref::entry::Entry::Occupied(e) => {
+                return Err(GateRejection::InFlight {
+                    elapsed: now.saturating_duration_since(*e.get()),
+                });
+            }
+            dashmap::mapref::entry::Entry::Vacant(e) => {
+                e.insert(now);
+            }
+        }
+
+        let cooldown_key = if self.cooldown.is_zero() {
+            None
+        } else {
+            self.recent.insert(key, now);
+            Some(key)
+        };
+
+        Ok(InFlightGuard {
+            gate: self.clone(),
+            market_id,
+            cooldown_key,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trading::{FeeModel, FeeSchedule, SimulatedExchange};
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    fn gate() -> Arc<OpportunityGate> {
+        Arc::new(OpportunityGate {
+            min_profit_pct: dec!(1.0),
+            cooldown: COOLDOWN,
+            in_flight: DashMap::new(),
+            recent: DashMap::new(),
+        })
+    }
+
+    fn risk_manager() -> RiskManager {
+        let config = BotConfig::from_env_offline().unwrap();
+        let fees = FeeSchedule::new(FeeModel::free());
+        RiskManager::new(Arc::new(SimulatedExchange::new(dec!(1000), fees.clone())), &config, fees)
+    }
+
+    fn opp(market: u8, yes_ask: Decimal, profit_pct: Decimal) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            market_id: B256::repeat_byte(market),
+            yes_token_id: Default::default(),
+            no_token_id: Default::default(),
+            yes_ask_price: yes_ask,
+            no_ask_price: dec!(0.50),
+            yes_worst_price: yes_ask,
+            no_worst_price: dec!(0.50),
+            yes_vwap: yes_ask,
+            no_vwap: dec!(0.50),
+            yes_fills: Vec::new(),
+            no_fills: Vec::new(),
+            total_cost: dec!(0),
+            estimated_fee: dec!(0),
+            expected_profit: dec!(0),
+            profit_percentage: profit_pct,
+            yes_size: dec!(10),
+            no_size: dec!(10),
+        }
+    }
+
+    #[test]
+    fn rejects_below_profit_threshold() {
+        let (gate, rm) = (gate(), risk_manager());
+        let result = gate.try_acquire_at(&opp(1, dec!(0.45), dec!(0.5)), &rm, Instant::now());
+        assert!(matches!(result, Err(GateRejection::BelowThreshold { .. })));
+        assert!(gate.in_flight.is_empty() && gate.recent.is_empty());
+    }
+
+    #[test]
+    fn one_execution_per_market_until_guard_drops() {
+        let (gate, rm) = (gate(), risk_manager());
+        let now = Instant::now();
+        let guard = gate.try_acquire_at(&opp(1, dec!(0.45), dec!(2)), &rm, now).unwrap();
+
+        // 同市场换一个价位仍被执行中标记挡住；其他市场不受影响
+        let later = now + Duration::from_secs(1);
+        assert!(matches!(
+            gate.try_acquire_at(&opp(1, dec!(0.44), dec!(2)), &rm, later),
+            Err(GateRejection::InFlight { .. })
+        ));
+        let other = gate.try_acquire_at(&opp(2, dec!(0.45), dec!(2)), &rm, later).unwrap();
+
+        drop(guard);
+        drop(other);
+        assert!(gate.try_acquire_at(&opp(1, dec!(0.44), dec!(2)), &rm, later).is_ok());
+    }
+
+    #[test]
+    fn same_price_level_waits_for_cooldown() {
+        let (gate, rm) = (gate(), risk_manager());
+        let now = Instant::now();
+        drop(gate.try_acquire_at(&opp(1, dec!(0.45), dec!(2)), &rm, now).unwrap());
+
+        match gate.try_acquire_at(&opp(1, dec!(0.45), dec!(2)), &rm, now + Duration::from_secs(10)) {
+            Err(GateRejection::Cooldown { remaining }) => assert_eq!(remaining, Duration::from_secs(20)),
+            other => panic!("expected cooldown, got {:?}", other.map(|_| ())),
+        }
+        assert!(gate.try_acquire_at(&opp(1, dec!(0.45), dec!(2)), &rm, now + COOLDOWN).is_ok());
+    }
+
+    #[test]
+    fn rollback_clears_cooldown_and_in_flight() {
+        let (gate, rm) = (gate(), risk_manager());
+        let now = Instant::now();
+        gate.try_acquire_at(&opp(1, dec!(0.45), dec!(2)), &rm, now).unwrap().rollback();
+
+        assert!(gate.recent.is_empty() && gate.in_flight.is_empty());
+        assert!(gate.try_acquire_at(&opp(1, dec!(0.45), dec!(2)), &rm, now).is_ok());
+    }
+}
@@ -1,5 +1,9 @@
 pub mod arbitrage;
+pub mod gate;
 pub mod orderbook;
+pub mod precheck;
 
 pub use arbitrage::*;
+pub use gate::*;
 pub use orderbook::*;
+pub use precheck::*;
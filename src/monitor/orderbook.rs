@@ -8,7 +8,7 @@ use polymarket_client_sdk::clob::ws::{
 use polymarket_client_sdk::types::{B256, U256};
 use std::collections::HashMap;
 use std::pin::Pin;
-use tracing::{debug, info};
+use tracing::info;
 
 use crate::market::MarketInfo;
 
@@ -36,6 +36,12 @@ pub struct OrderBookMonitor {
     market_map: HashMap<B256, (U256, U256)>, // market_id -> (yes, no)
 }
 
+impl Default for OrderBookMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct OrderBookPair {
     pub yes_book: BookUpdate,
     pub no_book: BookUpdate,
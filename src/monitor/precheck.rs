@@ -0,0 +1,259 @@
+//! 下单前检查：YES/NO 最低价格阈值、临近市场结束停止套利（PreTradeCheck），
+//! 以及开仓前的完整检查链（EntryChecks）：风险敞口 → 持仓平衡 → 风控熔断 → 每日交易次数 → 交易间隔 → 套利闸门。
+//! 主程序与回测共用，时间由调用方传入（回测使用录制时钟）。
+
+use chrono::{DateTime, Utc};
+use polymarket_client_sdk::types::{B256, Decimal, U256};
+use rust_decimal_macros::dec;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::arbitrage::{ArbitrageOpportunity, SellArbitrageOpportunity};
+use super::gate::{GateRejection, InFlightGuard, OpportunityGate, MIN_TRADE_INTERVAL};
+use crate::config::Config as BotConfig;
+use crate::risk::{PositionBalancer, RiskGuard, RiskManager, TradeBudget};
+
+/// 跳过原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    YesBelowThreshold { price: Decimal, threshold: Decimal },
+    NoBelowThreshold { price: Decimal, threshold: Decimal },
+    NearMarketEnd { seconds_until_end: i64, stop_minutes: u64 },
+    ExposureLimit { current: Decimal, order_cost: Decimal, limit: Decimal },
+    ExposureCap(String),
+    Imbalanced,
+    RiskGuardTripped,
+    BudgetExhausted,
+    TradeInterval { elapsed: Duration },
+    Gate(GateRejection),
+}
+
+impl SkipReason {
+    /// 敞口与持仓平衡类拒绝（主程序按警告输出，其余按调试输出）
+    pub fn is_risk_limit(&self) -> bool {
+        matches!(
+            self,
+            SkipReason::ExposureLimit { .. } | SkipReason::ExposureCap(_) | SkipReason::Imbalanced
+        )
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::YesBelowThreshold { price, threshold } => {
+                write!(f, "⏸️ YES价格未达到阈值 | YES价格:{:.4} | 阈值:{:.4}", price, threshold)
+            }
+            SkipReason::NoBelowThreshold { price, threshold } => {
+                write!(f, "⏸️ NO价格未达到阈值 | NO价格:{:.4} | 阈值:{:.4}", price, threshold)
+            }
+            SkipReason::NearMarketEnd { seconds_until_end, stop_minutes } => {
+                write!(
+                    f,
+                    "⏰ 接近市场结束时间 | 距离结束:{}秒 | 停止阈值:{}分钟",
+                    seconds_until_end, stop_minutes
+                )
+            }
+            SkipReason::ExposureLimit { current, order_cost, limit } => {
+                write!(
+                    f,
+                    "风险敞口超限 | 当前敞口:{:.2} USD | 订单成本:{:.2} USD | 限制:{:.2} USD",
+                    current, order_cost, limit
+                )
+            }
+            SkipReason::ExposureCap(reason) => write!(f, "{}", reason),
+            SkipReason::Imbalanced => write!(f, "持仓已严重不平衡"),
+            SkipReason::RiskGuardTripped => write!(f, "风控熔断中"),
+            SkipReason::BudgetExhausted => write!(f, "今日交易次数已用完"),
+            SkipReason::TradeInterval { elapsed } => {
+                write!(
+                    f,
+                    "⏱️ 交易间隔不足 {} 秒 | 距上次:{:.1}秒",
+                    MIN_TRADE_INTERVAL.as_secs(),
+                    elapsed.as_secs_f32()
+                )
+            }
+            SkipReason::Gate(rejection) => write!(f, "🚧 套利闸门拦截 | {}", rejection),
+        }
+    }
+}
+
+pub struct PreTradeCheck {
+    min_yes_price: Decimal, // 0 表示不检查
+    min_no_price: Decimal,
+    stop_before_end_minutes: u64, // 0 表示不检查
+}
+
+impl PreTradeCheck {
+    pub fn new(config: &BotConfig) -> Self {
+        Self {
+            min_yes_price: Decimal::try_from(config.min_yes_price_threshold).unwrap_or(dec!(0.0)),
+            min_no_price: Decimal::try_from(config.min_no_price_threshold).unwrap_or(dec!(0.0)),
+            stop_before_end_minutes: config.stop_arbitrage_before_end_minutes,
+        }
+    }
+
+    /// 买方向套利：价格阈值 + 临近结束
+    pub fn check_buy(
+        &self,
+        opp: &ArbitrageOpportunity,
+        end_date: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<(), SkipReason> {
+        if self.min_yes_price > dec!(0) && opp.yes_ask_price < self.min_yes_price {
+            return Err(SkipReason::YesBelowThreshold {
+                price: opp.yes_ask_price,
+                threshold: self.min_yes_price,
+            });
+        }
+        if self.min_no_price > dec!(0) && opp.no_ask_price < self.min_no_price {
+            return Err(SkipReason::NoBelowThreshold {
+                price: opp.no_ask_price,
+                threshold: self.min_no_price,
+            });
+        }
+        self.check_market_end(end_date, now)
+    }
+
+    /// 临近市场结束时间不再开仓（卖方向 split 同样适用）。
+    /// 使用秒级精度，5分钟市场下 num_minutes() 截断可能导致漏检
+    pub fn check_market_end(&self, end_date: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Result<(), SkipReason> {
+        if self.stop_before_end_minutes == 0 {
+            return Ok(());
+        }
+        let Some(end_date) = end_date else {
+            return Ok(());
+        };
+        let seconds_until_end = end_date.signed_duration_since(now).num_seconds();
+        if seconds_until_end <= self.stop_before_end_minutes as i64 * 60 {
+            return Err(SkipReason::NearMarketEnd {
+                seconds_until_end,
+                stop_minutes: self.stop_before_end_minutes,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// 开仓前的完整检查链，依赖的风控组件由调用方持有（主程序为全局实例，回测为每个窗口的模拟实例）
+pub struct EntryChecks<'a> {
+    pub pre_trade_check: &'a PreTradeCheck,
+    pub risk_manager: &'a RiskManager,
+    pub balancer: &'a PositionBalancer,
+    pub risk_guard: &'a RiskGuard,
+    pub trade_budget: &'a TradeBudget,
+    pub gate: &'a Arc<OpportunityGate>,
+}
+
+impl EntryChecks<'_> {
+    /// 买方向套利：全部检查通过后占用一次交易次数、记录交易时间并按各腿 VWAP 计入风险敞口，
+    /// 返回闸门的执行中标记（执行完成前持有）
+    pub fn admit_buy(
+        &self,
+        opp: &ArbitrageOpportunity,
+        end_date: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+        instant: Instant,
+        last_trade: &mut Option<Instant>,
+    ) -> Result<InFlightGuard, SkipReason> {
+        self.pre_trade_check.check_buy(opp, end_date, now)?;
+
+        // 份额已由 detector 按深度计算并受最大订单大小约束，成本按各腿 VWAP 计
+        let order_size = opp.yes_size.min(opp.no_size);
+        let yes_cost = opp.yes_vwap * order_size;
+        let no_cost = opp.no_vwap * order_size;
+        self.check_exposure(opp.market_id, opp.yes_token_id, opp.no_token_id, yes_cost, no_cost)?;
+        if self.balancer.should_skip_arbitrage(opp.yes_token_id, opp.no_token_id) {
+            return Err(SkipReason::Imbalanced);
+        }
+
+        let guard = self.acquire(now, instant, last_trade, || {
+            self.gate.try_acquire_at(opp, self.risk_manager, instant)
+        })?;
+
+        // 简化敞口：只要执行套利就增加敞口，不管是否成交
+        let position_tracker = self.risk_manager.position_tracker();
+        position_tracker.update_exposure_cost(opp.yes_token_id, opp.yes_vwap, order_size);
+        position_tracker.update_exposure_cost(opp.no_token_id, opp.no_vwap, order_size);
+        Ok(guard)
+    }
+
+    /// 卖方向（split-and-sell）：split 成本 = 份额（每份 1 USDC），按两腿各 0.5 计入风险敞口；
+    /// split 得到的两腿天然平衡，不检查持仓平衡
+    pub fn admit_sell(
+        &self,
+        opp: &SellArbitrageOpportunity,
+        end_date: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+        instant: Instant,
+        last_trade: &mut Option<Instant>,
+    ) -> Result<InFlightGuard, SkipReason> {
+        // 接近市场结束时间不再 split（卖不出去的两腿只能等 merge）
+        self.pre_trade_check.check_market_end(end_date, now)?;
+
+        let leg_cost = opp.size / dec!(2);
+        self.check_exposure(opp.market_id, opp.yes_token_id, opp.no_token_id, leg_cost, leg_cost)?;
+
+        let guard = self.acquire(now, instant, last_trade, || {
+            self.gate.try_acquire_sell_at(opp, self.risk_manager, instant)
+        })?;
+
+        let position_tracker = self.risk_manager.position_tracker();
+        position_tracker.update_exposure_cost(opp.yes_token_id, dec!(0.5), opp.size);
+        position_tracker.update_exposure_cost(opp.no_token_id, dec!(0.5), opp.size);
+        Ok(guard)
+    }
+
+    /// 全局敞口上限，以及按币种、按市场与未对冲单腿持仓的上限
+    fn check_exposure(
+        &self,
+        market_id: B256,
+        yes_token: U256,
+        no_token: U256,
+        yes_cost: Decimal,
+        no_cost: Decimal,
+    ) -> Result<(), SkipReason> {
+        let position_tracker = self.risk_manager.position_tracker();
+        if position_tracker.would_exceed_limit(yes_cost, no_cost) {
+            return Err(SkipReason::ExposureLimit {
+                current: position_tracker.calculate_exposure(),
+                order_cost: yes_cost + no_cost,
+                limit: position_tracker.max_exposure(),
+            });
+        }
+        position_tracker
+            .check_exposure_caps(market_id, yes_token, no_token, yes_cost, no_cost)
+            .map_err(SkipReason::ExposureCap)
+    }
+
+    /// 风控熔断或每日交易次数用完后不再开新仓（merge 与收尾不受影响）；
+    /// 两次交易间隔不少于 MIN_TRADE_INTERVAL，通过闸门并占用交易次数后才记录本次交易时间
+    fn acquire(
+        &self,
+        now: DateTime<Utc>,
+        instant: Instant,
+        last_trade: &mut Option<Instant>,
+        try_gate: impl FnOnce() -> Result<InFlightGuard, GateRejection>,
+    ) -> Result<InFlightGuard, SkipReason> {
+        if self.risk_guard.is_tripped() {
+            return Err(SkipReason::RiskGuardTripped);
+        }
+        if self.trade_budget.is_exhausted(now) {
+            return Err(SkipReason::BudgetExhausted);
+        }
+        if let Some(last) = *last_trade {
+            let elapsed = instant.saturating_duration_since(last);
+            if elapsed < MIN_TRADE_INTERVAL {
+                return Err(SkipReason::TradeInterval { elapsed });
+            }
+        }
+        let guard = try_gate().map_err(SkipReason::Gate)?;
+        if !self.trade_budget.try_consume(now) {
+            guard.rollback();
+            return Err(SkipReason::BudgetExhausted);
+        }
+        *last_trade = Some(instant);
+        Ok(guard)
+    }
+}
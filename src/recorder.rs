@@ -0,0 +1,259 @@
+//! 订单簿录制：把 WS 收到的每条 BookUpdate 连同接收时间、market_id、slug、窗口时间戳写入 gzip 压缩的 NDJSON，
+//! 每个 5 分钟窗口一个文件（`book-{window_ts}.ndjson.gz`），文件开头为该窗口的市场元数据，供事后复盘与回测回放。
+//!
+//! 写文件在独立的阻塞线程中进行；主循环只做一次非阻塞 try_send，通道满时丢弃并计数，绝不阻塞 select!。
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use polymarket_client_sdk::clob::ws::types::response::BookUpdate;
+use polymarket_client_sdk::types::{B256, Decimal, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// 录制通道容量（条）；写盘跟不上时超出部分直接丢弃
+const CHANNEL_CAPACITY: usize = 65_536;
+
+/// 窗口内的市场元数据（对应主程序的 MarketInfo）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedMarket {
+    pub market_id: B256,
+    pub slug: String,
+    pub yes_token_id: U256,
+    pub no_token_id: U256,
+    pub title: String,
+    pub end_date: DateTime<Utc>,
+    pub crypto_symbol: String,
+}
+
+/// 一条订单簿更新；档位为 (price, size)，排序与 BookUpdate 一致（bids 升序、asks 降序）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedBook {
+    pub recv_ts_ms: i64,
+    pub window_ts: i64,
+    pub market_id: B256,
+    pub slug: String,
+    pub asset_id: U256,
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+}
+
+/// NDJSON 中的一行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecordLine {
+    Market { window_ts: i64, market: RecordedMarket },
+    Book(RecordedBook),
+}
+
+enum RecorderEvent {
+    Window { window_ts: i64, markets: Vec<RecordedMarket> },
+    Book(RecordedBook),
+}
+
+/// (窗口时间戳, asset_id -> (market_id, slug))
+type TokenIndex = (i64, HashMap<U256, (B256, String)>);
+
+/// 录制句柄：可 clone，所有 clone 共用同一个后台写线程
+#[derive(Clone)]
+pub struct BookRecorder {
+    tx: mpsc::Sender<RecorderEvent>,
+    // asset_id -> (market_id, slug)，随窗口切换整体替换
+    tokens: Arc<std::sync::RwLock<TokenIndex>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl BookRecorder {
+    /// 创建录制目录并启动后台写线程
+    pub fn spawn(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).with_context(|| format!("创建录制目录失败: {}", dir.display()))?;
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        info!(dir = %dir.display(), "📼 订单簿录制已开启");
+        tokio::task::spawn_blocking(move || run_writer(dir, rx));
+        Ok(Self {
+            tx,
+            tokens: Arc::new(std::sync::RwLock::new((0, HashMap::new()))),
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// 进入新窗口：切换到新文件并写入该窗口的市场元数据
+    pub fn start_window(&self, window_ts: i64, markets: Vec<RecordedMarket>) {
+        let tokens = markets
+            .iter()
+            .flat_map(|m| {
+                [
+                    (m.yes_token_id, (m.market_id, m.slug.clone())),
+                    (m.no_token_id, (m.market_id, m.slug.clone())),
+                ]
+            })
+            .collect();
+        *self.tokens.write().unwrap() = (window_ts, tokens);
+        self.send(RecorderEvent::Window { window_ts, markets });
+    }
+
+    /// 记录一条订单簿更新（非阻塞）；不属于当前窗口市场的 token 忽略
+    pub fn record(&self, book: &BookUpdate) {
+        let recv_ts_ms = Utc::now().timestamp_millis();
+        let record = {
+            let guard = self.tokens.read().unwrap();
+            let (window_ts, tokens) = &*guard;
+            let Some((market_id, slug)) = tokens.get(&book.asset_id) else {
+                return;
+            };
+            RecordedBook {
+                recv_ts_ms,
+                window_ts: *window_ts,
+                market_id: *market_id,
+                slug: slug.clone(),
+                asset_id: book.asset_id,
+                bids: book.bids.iter().map(|l| (l.price, l.size)).collect(),
+                asks: book.asks.iter().map(|l| (l.price, l.size)).collect(),
+            }
+        };
+        self.send(RecorderEvent::Book(record));
+    }
+
+    /// 因通道已满被丢弃的事件数
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, event: RecorderEvent) {
+        match self.tx.try_send(event) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                let n = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if n.is_power_of_two() {
+                    warn!(dropped = n, "订单簿录制通道已满，丢弃记录");
+                }
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// 窗口文件路径
+pub fn window_file(dir: &Path, window_ts: i64) -> PathBuf {
+    dir.join(format!("book-{}.ndjson.gz", window_ts))
+}
+
+type Encoder = GzEncoder<BufWriter<File>>;
+
+fn open_window(dir: &Path, window_ts: i64) -> Result<Encoder> {
+    let path = window_file(dir, window_ts);
+    // 同一窗口重启后追加写入：gzip 允许多个 member 串联，读取端用 MultiGzDecoder
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("打开录制文件失败: {}", path.display()))?;
+    Ok(GzEncoder::new(BufWriter::new(file), Compression::default()))
+}
+
+fn close_window(encoder: Encoder) {
+    if let Err(e) = encoder.finish().and_then(|mut w| w.flush()) {
+        warn!(error = %e, "关闭录制文件失败");
+    }
+}
+
+fn write_line(encoder: &mut Encoder, line: &RecordLine) -> Result<()> {
+    serde_json::to_writer(&mut *encoder, line)?;
+    encoder.write_all(b"\n")?;
+    Ok(())
+}
+
+fn run_writer(dir: PathBuf, mut rx: mpsc::Receiver<RecorderEvent>) {
+    let mut current: Option<(i64, Encoder)> = None;
+    let mut written: u64 = 0;
+
+    while let Some(event) = rx.blocking_recv() {
+        match event {
+            RecorderEvent::Window { window_ts, markets } => {
+                if let Some((old_ts, encoder)) = current.take() {
+                    close_window(encoder);
+                    debug!(window_ts = old_ts, written, "录制文件已关闭");
+                }
+                written = 0;
+                match open_window(&dir, window_ts) {
+                    Ok(mut encoder) => {
+                        for market in markets {
+                            if let Err(e) = write_line(&mut encoder, &RecordLine::Market { window_ts, market }) {
+                                warn!(error = %e, "写入市场元数据失败");
+                            }
+                        }
+                        current = Some((window_ts, encoder));
+                    }
+                    Err(e) => warn!(error = %e, "订单簿录制：打开窗口文件失败，本窗口不录制"),
+                }
+            }
+            RecorderEvent::Book(book) => {
+                let Some((_, encoder)) = current.as_mut() else {
+                    continue;
+                };
+                match write_line(encoder, &RecordLine::Book(book)) {
+                    Ok(()) => written += 1,
+                    Err(e) => warn!(error = %e, "写入订单簿记录失败"),
+                }
+            }
+        }
+    }
+
+    if let Some((_, encoder)) = current.take() {
+        close_window(encoder);
+    }
+    info!("📼 订单簿录制已停止");
+}
+
+/// 读取一个录制文件的全部记录（按写入顺序）；无法解析的行跳过
+pub fn read_window_file(path: &Path) -> Result<Vec<RecordLine>> {
+    let file = File::open(path).with_context(|| format!("打开录制文件失败: {}", path.display()))?;
+    let reader = BufReader::new(MultiGzDecoder::new(file));
+    let mut lines = Vec::new();
+    for line in reader.lines() {
+        let line = match line {
+            Ok(l) => l,
+            // 进程被强制结束时文件尾部可能不完整，读到哪算哪
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "录制文件尾部损坏，已截断");
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => lines.push(record),
+            Err(e) => debug!(error = %e, "跳过无法解析的录制行"),
+        }
+    }
+    Ok(lines)
+}
+
+/// 目录下所有录制文件，按窗口时间戳升序
+pub fn list_window_files(dir: &Path) -> Result<Vec<(i64, PathBuf)>> {
+    let mut files: Vec<(i64, PathBuf)> = fs::read_dir(dir)
+        .with_context(|| format!("读取录制目录失败: {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_name()?.to_str()?;
+            let ts = name.strip_prefix("book-")?.strip_suffix(".ndjson.gz")?.parse().ok()?;
+            Some((ts, path))
+        })
+        .collect();
+    files.sort_by_key(|(ts, _)| *ts);
+    Ok(files)
+}
@@ -0,0 +1,129 @@
+//! CTF Redeem 模块：市场结算后将持有的 YES/NO 代币按结算比例赎回为 USDC。
+//!
+//! 与 [`crate::merge`] 走相同的 **Gnosis Safe**（execTransaction）/ **Magic/Email**（Polymarket Relayer）路径。
+//! 二元市场 indexSets 固定为 `[1, 2]`，CTF 合约按 proxy 上两腿的全部余额赎回，无需传入数量。
+//!
+//! ## 调用示例
+//!
+//! ```ignore
+//! let tx = poly_15min_bot::redeem::redeem(
+//!     condition_id,
+//!     proxy,
+//!     &private_key,
+//!     None,
+//! ).await?;
+//! ```
+
+use std::str::FromStr as _;
+
+use alloy::primitives::{Address, B256, U256};
+use alloy::providers::ProviderBuilder;
+use alloy::signers::local::LocalSigner;
+use alloy::signers::Signer as _;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use anyhow::Result;
+use polymarket_client_sdk::ctf::types::RedeemPositionsRequest;
+use polymarket_client_sdk::{contract_config, POLYGON};
+use tracing::info;
+
+use crate::merge::{binary_position_ids, ctf_balance, execute_ctf_call, USDC_POLYGON};
+use crate::rpc_pool::RpcPool;
+
+sol! {
+    #[sol(rpc)]
+    interface IConditionalTokens {
+        function payoutDenominator(bytes32 conditionId) external view returns (uint256);
+    }
+
+    function redeemPositions(address collateralToken, bytes32 parentCollectionId, bytes32 conditionId, uint256[] indexSets) external;
+}
+
+/// redeem 可识别的跳过原因，包在 `anyhow::Error` 中返回，调用方用 `downcast_ref::<RedeemError>()` 区分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedeemError {
+    /// 条件尚未结算（payoutDenominator == 0）
+    NotResolved,
+    /// YES、NO 余额均为 0
+    NothingToRedeem,
+}
+
+impl std::fmt::Display for RedeemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedeemError::NotResolved => write!(f, "条件尚未结算，暂不可 redeem"),
+            RedeemError::NothingToRedeem => write!(f, "无可赎回份额：YES、NO 余额均为 0"),
+        }
+    }
+}
+
+impl std::error::Error for RedeemError {}
+
+fn encode_redeem_calldata(req: &RedeemPositionsRequest) -> Vec<u8> {
+    redeemPositionsCall {
+        collateralToken: req.collateral_token,
+        parentCollectionId: req.parent_collection_id,
+        conditionId: req.condition_id,
+        indexSets: req.index_sets.clone(),
+    }
+    .abi_encode()
+}
+
+/// proxy 在各 position id 上的余额之和
+async fn total_balance(pool: &RpcPool, proxy: Address, position_ids: &[U256]) -> Result<U256> {
+    let mut total = U256::ZERO;
+    for id in position_ids {
+        total += ctf_balance(pool, proxy, *id).await?;
+    }
+    Ok(total)
+}
+
+/// 对已结算的 `condition_id` 在 `proxy` 上赎回 YES+NO 全部余额。
+///
+/// 条件尚未结算（`payoutDenominator == 0`）或两腿余额均为 0 时返回 [`RedeemError`]，不发交易。
+/// Safe 路径等待 receipt，Relayer 路径轮询交易状态，确认上链后返回。
+///
+/// 返回交易哈希（十六进制字符串）。
+pub async fn redeem(
+    condition_id: B256,
+    proxy: Address,
+    private_key: &str,
+    rpc_url: Option<&str>,
+) -> Result<String> {
+    let pool = RpcPool::resolve(rpc_url);
+    let chain = POLYGON;
+    let signer = LocalSigner::from_str(private_key)?.with_chain_id(Some(chain));
+    let config = contract_config(chain, false).ok_or_else(|| anyhow::anyhow!("不支持的 chain_id: {}", chain))?;
+    let ctf = config.conditional_tokens;
+
+    let denominator: U256 = pool
+        .with_failover("payoutDenominator", move |url| async move {
+            let provider = ProviderBuilder::new().connect(&url).await?;
+            IConditionalTokens::new(ctf, provider)
+                .payoutDenominator(condition_id)
+                .call()
+                .await
+                .map_err(|e| {
+                    let msg = format!("读取 payoutDenominator 失败: {}", e);
+                    anyhow::Error::new(e).context(msg)
+                })
+        })
+        .await?;
+    if denominator == U256::ZERO {
+        return Err(RedeemError::NotResolved.into());
+    }
+
+    let position_ids = binary_position_ids(&pool, condition_id).await?;
+
+    let held = total_balance(&pool, proxy, &position_ids).await?;
+    if held == U256::ZERO {
+        return Err(RedeemError::NothingToRedeem.into());
+    }
+    info!("💵 赎回份额: {} ({} 份)", held, held / U256::from(1_000_000));
+
+    let redeem_req = RedeemPositionsRequest::for_binary_market(USDC_POLYGON, condition_id);
+    let redeem_calldata = encode_redeem_calldata(&redeem_req);
+    let tx = execute_ctf_call(&pool, &signer, proxy, ctf, redeem_calldata, "Redeem positions").await?;
+    info!("✅ Redeem 成功 tx: {}", tx);
+    Ok(tx)
+}
@@ -1,20 +1,18 @@
 use anyhow::Result;
-use alloy::signers::Signer;
-use alloy::signers::local::LocalSigner;
 use dashmap::DashMap;
-use polymarket_client_sdk::clob::Client;
 use polymarket_client_sdk::clob::types::{OrderType, Side};
 use polymarket_client_sdk::clob::ws::types::response::BookUpdate;
-use polymarket_client_sdk::types::{Address, Decimal, U256};
-use polymarket_client_sdk::POLYGON;
+use polymarket_client_sdk::types::{Decimal, U256};
 use rust_decimal::prelude::ToPrimitive;
 use rust_decimal_macros::dec;
-use std::str::FromStr;
 use std::sync::Arc;
-use tracing::{debug, error, info, trace, warn};
+use tracing::{error, info, warn};
 
 use super::positions::PositionTracker;
 use super::recovery::RecoveryAction;
+use crate::ledger::{LedgerKind, TradeLedger};
+use crate::trading::exchange::{Exchange, OrderRequest};
+use crate::trading::fees::{FeeModel, FeeSchedule};
 
 #[derive(Debug, Clone)]
 pub struct HedgePosition {
@@ -31,29 +29,34 @@ pub struct HedgePosition {
 }
 
 pub struct HedgeMonitor {
-    client: Client<polymarket_client_sdk::auth::state::Authenticated<polymarket_client_sdk::auth::Normal>>,
-    private_key: String,
-    proxy_address: Option<Address>,
+    exchange: Arc<dyn Exchange>,
     positions: DashMap<String, HedgePosition>, // pair_id -> position
     position_tracker: Arc<PositionTracker>, // 用于更新风险敞口
+    fees: FeeSchedule,
+    ledger: TradeLedger,
 }
 
 impl HedgeMonitor {
     pub fn new(
-        client: Client<polymarket_client_sdk::auth::state::Authenticated<polymarket_client_sdk::auth::Normal>>,
-        private_key: String,
-        proxy_address: Option<Address>,
+        exchange: Arc<dyn Exchange>,
         position_tracker: Arc<PositionTracker>,
+        fees: FeeSchedule,
     ) -> Self {
         Self {
-            client,
-            private_key,
-            proxy_address,
+            exchange,
             positions: DashMap::new(),
             position_tracker,
+            fees,
+            ledger: TradeLedger::disabled(),
         }
     }
 
+    /// 止盈 / 止损卖单写入交易台账
+    pub fn with_ledger(mut self, ledger: TradeLedger) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
     /// 添加需要监测的对冲仓位
     pub fn add_position(&self, action: &RecoveryAction) -> Result<()> {
         if let RecoveryAction::MonitorForExit {
@@ -220,8 +223,9 @@ impl HedgeMonitor {
                 let pair_id_clone = pair_id.clone();
                 let position_tracker = self.position_tracker.clone();
                 let positions = self.positions.clone();
-                let client = self.client.clone();
-                let private_key = self.private_key.clone();
+                let exchange = self.exchange.clone();
+                let fee_model = self.fees.for_token(position.token_id);
+                let ledger = self.ledger.clone();
                 
                 // 先标记为正在处理，避免重复下单（使用remove+insert避免阻塞）
                 if let Some((_, mut pos)) = self.positions.remove(&pair_id) {
@@ -230,26 +234,14 @@ impl HedgeMonitor {
                 }
                 
                 tokio::spawn(async move {
-                    // 重新创建 signer（因为不能在 spawn 中直接使用 self）
-                    let signer = match LocalSigner::from_str(&private_key) {
-                        Ok(s) => s.with_chain_id(Some(POLYGON)),
-                        Err(e) => {
-                            error!(
-                                "❌ 创建signer失败 | 市场:{} | 错误:{}",
-                                position_clone.market_display,
-                                e
-                            );
-                            return;
-                        }
-                    };
-                    
                     // 执行卖出操作
                     match Self::execute_sell_order(
-                        &client,
-                        &signer,
+                        exchange.as_ref(),
                         &position_clone,
                         best_bid_price,
                         sell_amount,
+                        &fee_model,
+                        &ledger,
                     ).await {
                         Ok((order_id, filled, remaining)) => {
                             // 更新仓位，标记已下订单（使用remove+insert避免get_mut阻塞）
@@ -322,104 +314,19 @@ impl HedgeMonitor {
         Ok(())
     }
 
-    /// 计算实际卖出数量（考虑手续费）
-    fn calculate_sell_amount(&self, position: &HedgePosition) -> Decimal {
-        self.calculate_sell_amount_with_size(position, position.amount)
-    }
-
-    /// 计算指定数量的实际卖出数量（考虑手续费）
-    fn calculate_sell_amount_with_size(&self, position: &HedgePosition, base_amount: Decimal) -> Decimal {
-        // 计算手续费
-        let p = position.entry_price.to_f64().unwrap_or(0.0);
-        let c = 100.0;
-        let fee_rate = 0.25;
-        let exponent = 2.0;
-        
-        let base = p * (1.0 - p);
-        let fee_value = c * fee_rate * base.powf(exponent);
-        let fee_decimal = Decimal::try_from(fee_value).unwrap_or(dec!(0));
-        
-        // 计算实际可用份额
-        let available_amount = if fee_decimal >= dec!(100.0) {
-            dec!(0.01)
-        } else {
-            let multiplier = (dec!(100.0) - fee_decimal) / dec!(100.0);
-            base_amount * multiplier
-        };
-        
-        // 向下取整到2位小数
-        let floored_size = (available_amount * dec!(100.0)).floor() / dec!(100.0);
-        
-        if floored_size.is_zero() {
-            dec!(0.01)
-        } else {
-            floored_size
-        }
-    }
-
-    /// 静态方法：计算指定数量的实际卖出数量（考虑手续费）
-    fn calculate_sell_amount_static(position: &HedgePosition, base_amount: Decimal) -> Decimal {
-        // 计算手续费
-        let p = position.entry_price.to_f64().unwrap_or(0.0);
-        let c = 100.0;
-        let fee_rate = 0.25;
-        let exponent = 2.0;
-        
-        let base = p * (1.0 - p);
-        let fee_value = c * fee_rate * base.powf(exponent);
-        let fee_decimal = Decimal::try_from(fee_value).unwrap_or(dec!(0));
-        
-        // 计算实际可用份额
-        let available_amount = if fee_decimal >= dec!(100.0) {
-            dec!(0.01)
-        } else {
-            let multiplier = (dec!(100.0) - fee_decimal) / dec!(100.0);
-            base_amount * multiplier
-        };
-        
-        // 向下取整到2位小数
-        let floored_size = (available_amount * dec!(100.0)).floor() / dec!(100.0);
-        
-        if floored_size.is_zero() {
-            dec!(0.01)
-        } else {
-            floored_size
-        }
-    }
-
     /// 静态方法：执行卖出订单
     async fn execute_sell_order(
-        client: &Client<polymarket_client_sdk::auth::state::Authenticated<polymarket_client_sdk::auth::Normal>>,
-        signer: &impl Signer<alloy::primitives::Signature>,
+        exchange: &dyn Exchange,
         position: &HedgePosition,
         price: Decimal,
         size: Decimal,
+        fee_model: &FeeModel,
+        ledger: &TradeLedger,
     ) -> Result<(String, Decimal, Decimal)> {
-        // 计算手续费
-        let p = position.entry_price.to_f64().unwrap_or(0.0);
-        let c = 100.0;
-        let fee_rate = 0.25;
-        let exponent = 2.0;
-        
-        let base = p * (1.0 - p);
-        let fee_value = c * fee_rate * base.powf(exponent);
-        let fee_decimal = Decimal::try_from(fee_value).unwrap_or(dec!(0));
-        
-        // 计算实际可用份额
-        let available_amount = if fee_decimal >= dec!(100.0) {
-            dec!(0.01)
-        } else {
-            let multiplier = (dec!(100.0) - fee_decimal) / dec!(100.0);
-            size * multiplier
-        };
-        
-        // 向下取整到2位小数
-        let floored_size = (available_amount * dec!(100.0)).floor() / dec!(100.0);
-        let order_size = if floored_size.is_zero() {
-            dec!(0.01)
-        } else {
-            floored_size
-        };
+        // 计算手续费与实际可用份额（向下取整到2位小数）
+        let fee_decimal = fee_model.fee_pct(position.entry_price);
+        let available_amount = fee_model.net_shares(position.entry_price, size);
+        let order_size = fee_model.sellable_size(position.entry_price, size);
 
         info!(
             "💰 计算卖出份额 | 市场:{} | 基础数量:{:.2}份 | 买入价:{:.4} | 手续费:{:.2}% | 可用份额:{:.2}份 | 下单数量:{:.2}份",
@@ -431,140 +338,18 @@ impl HedgeMonitor {
             order_size
         );
 
-        // 构建GTC卖出订单
-        let sell_order = client
-            .limit_order()
-            .token_id(position.token_id)
-            .side(Side::Sell)
-            .price(price)
-            .size(order_size)
-            .order_type(OrderType::GTC)
-            .build()
-            .await?;
-
-        // 签名订单
-        let signed_order = client.sign(signer, sell_order).await?;
-
-        // 提交订单
-        let result = client.post_order(signed_order).await?;
-
-        if !result.success {
-            let error_msg = result.error_msg.as_deref().unwrap_or("未知错误");
-            return Err(anyhow::anyhow!("GTC卖出订单失败: {}", error_msg));
-        }
-
-        // 检查订单是否立即成交
-        let filled = result.taking_amount;
-        let remaining = order_size - filled;
-        
-        if filled > dec!(0) {
-            info!(
-                "💰 卖出订单已部分成交 | 市场:{} | 订单ID:{} | 已成交:{}份 | 剩余:{}份",
-                position.market_display,
-                &result.order_id[..16],
-                filled,
-                remaining
-            );
-        } else {
-            info!(
-                "📋 卖出订单已提交（未立即成交） | 市场:{} | 订单ID:{} | 数量:{}份 | 价格:{:.4}",
-                position.market_display,
-                &result.order_id[..16],
-                order_size,
-                price
-            );
-        }
-        
-        Ok((result.order_id, filled, remaining))
-    }
-
-    /// 使用GTC订单卖出
-    /// size: 可选，如果提供则使用该数量，否则使用position.amount
-    async fn sell_with_gtc(
-        &self,
-        position: &HedgePosition,
-        price: Decimal,
-        size: Option<Decimal>,
-    ) -> Result<(String, Decimal, Decimal)> {
-        let signer = LocalSigner::from_str(&self.private_key)?
-            .with_chain_id(Some(POLYGON));
-
-        // 计算手续费
-        // 公式: fee = c * fee_rate * (p * (1-p))^exponent
-        // 其中: p = entry_price（买入单价），c = 100（固定值），fee_rate = 0.25，exponent = 2
-        // 手续费计算出来是一个介于 0-1.56 之间的浮点数（比例值，不是绝对值）
-        let p = position.entry_price.to_f64().unwrap_or(0.0);
-        let c = 100.0; // 固定为100
-        let fee_rate = 0.25;
-        let exponent = 2.0;
-        
-        // 计算手续费比例值（0-1.56之间）
-        let base = p * (1.0 - p);
-        let fee_value = c * fee_rate * base.powf(exponent);
-        
-        // 将手续费转换为 Decimal
-        let fee_decimal = Decimal::try_from(fee_value).unwrap_or(dec!(0));
-        
-        // 使用提供的size，如果没有提供则使用position.amount
-        let base_amount = size.unwrap_or(position.amount);
-        
-        // 计算实际可用份额 = 成交份额 * (100 - Fee) / 100
-        // 如果 Fee >= 100，说明异常情况，使用最小可交易单位
-        let available_amount = if fee_decimal >= dec!(100.0) {
-            dec!(0.01) // 异常情况，使用最小单位
-        } else {
-            // 正常情况：可用份额 = 成交份额 * (100 - Fee) / 100
-            let multiplier = (dec!(100.0) - fee_decimal) / dec!(100.0);
-            base_amount * multiplier
-        };
-        
-        // 将订单大小向下取整到2位小数（Polymarket要求）
-        // 使用向下取整而不是四舍五入，避免订单大小超过实际持有份额
-        // 方法：乘以100，向下取整，再除以100
-        let floored_size = (available_amount * dec!(100.0)).floor() / dec!(100.0);
-        
-        // 如果向下取整后为0，则使用最小可交易单位
-        let order_size = if floored_size.is_zero() {
-            dec!(0.01) // 最小单位
-        } else {
-            floored_size
-        };
-
-        info!(
-            "💰 计算卖出份额 | 市场:{} | 基础数量:{:.2}份 | 买入价:{:.4} | 手续费:{:.2}% | 可用份额:{:.2}份 | 下单数量:{:.2}份",
-            position.market_display,
-            base_amount,
-            position.entry_price,
-            fee_decimal,
-            available_amount,
-            order_size
-        );
-
-        // 构建GTC卖出订单
-        let sell_order = self
-            .client
-            .limit_order()
-            .token_id(position.token_id)
-            .side(Side::Sell)
-            .price(price)
-            .size(order_size)
-            .order_type(OrderType::GTC)
-            .build()
-            .await?;
-
-        // 签名订单
-        let signed_order = self.client.sign(&signer, sell_order).await?;
-
-        // 提交订单
-        let result = self.client.post_order(signed_order).await?;
+        // 构建、签名并提交GTC卖出订单
+        let request = OrderRequest::new(position.token_id, Side::Sell, price, order_size, OrderType::GTC);
+        let result = exchange.post_order(request.clone()).await?;
+        ledger.record_order(LedgerKind::OrderSubmitted, Some(&position.pair_id), None, &request, &result, fee_model);
 
         if !result.success {
             let error_msg = result.error_msg.as_deref().unwrap_or("未知错误");
             return Err(anyhow::anyhow!("GTC卖出订单失败: {}", error_msg));
         }
 
-        // 检查订单是否立即成交
-        let filled = result.taking_amount;
+        // 检查订单是否立即成交（卖单：making_amount 为卖出份额，taking_amount 为收到 USDC）
+        let filled = result.making_amount;
         let remaining = order_size - filled;
         
         if filled > dec!(0) {
@@ -594,6 +379,11 @@ impl HedgeMonitor {
         info!(pair_id = %pair_id, "移除对冲仓位");
     }
 
+    /// 窗口结束：清空监测中的仓位（剩余持仓由收尾卖出处理）
+    pub fn clear(&self) {
+        self.positions.clear();
+    }
+
     /// 获取所有监测中的仓位
     pub fn get_positions(&self) -> Vec<HedgePosition> {
         self.positions.iter().map(|e| e.value().clone()).collect()
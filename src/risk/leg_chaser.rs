@@ -0,0 +1,225 @@
+//! 单边成交补齐：恢复策略为 chase 时，按订单簿跟踪缺失一腿的卖一价，
+//! 只要 已成交价 + 卖一价 <= 1 + 容差（即卖一价不高于 max_price）就以 max_price 重新 FAK 吃单，
+//! 未成交部分等下一次行情再试，直到订单对补齐或到达截止时间（窗口结束前 RECOVERY_CHASE_CUTOFF_SECS 秒）；
+//! 截止后仍未补齐的部分才回退为卖出已成交的一腿。收尾开始时放弃全部补单，剩余单腿由收尾卖出。
+//! 补单与卖出的成交经 RiskManager::apply_recovery_fill 计入持仓、敞口与所属订单对。
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use polymarket_client_sdk::clob::types::Side;
+use polymarket_client_sdk::types::{Decimal, U256};
+use rust_decimal_macros::dec;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::manager::RiskManager;
+use super::recovery::RecoveryAction;
+use crate::monitor::OrderBookMonitor;
+use crate::trading::{FeeSchedule, TradingExecutor};
+
+/// 同一订单对两次补单之间的最短间隔
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// 剩余份额低于此值视为已补齐
+const MIN_CHASE_SIZE: Decimal = dec!(0.01);
+
+/// 一个补单中的订单对
+#[derive(Debug, Clone)]
+struct Chase {
+    token_id: U256,        // 缺失的一腿
+    filled_token_id: U256, // 已成交的一腿
+    filled_price: Decimal,
+    max_price: Decimal,
+    remaining: Decimal,
+    last_attempt: Option<Instant>,
+    in_flight: bool,
+}
+
+pub struct LegChaser {
+    executor: Arc<TradingExecutor>,
+    risk_manager: Arc<RiskManager>,
+    fees: FeeSchedule,
+    sell_price: Decimal, // 卖出单腿时的最低价（FAK，按买盘逐档成交）
+    chases: Arc<DashMap<String, Chase>>, // pair_id -> 补单状态
+}
+
+impl LegChaser {
+    pub fn new(
+        executor: Arc<TradingExecutor>,
+        risk_manager: Arc<RiskManager>,
+        fees: FeeSchedule,
+        sell_price: Decimal,
+    ) -> Self {
+        Self {
+            executor,
+            risk_manager,
+            fees,
+            sell_price,
+            chases: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// 开始补齐缺失一腿（同一订单对已在补单中时忽略）
+    pub fn start(&self, action: &RecoveryAction) {
+        let RecoveryAction::ChaseMissingLeg {
+            token_id,
+            filled_token_id,
+            filled_price,
+            amount,
+            max_price,
+            pair_id,
+            ..
+        } = action
+        else {
+            return;
+        };
+        if self.chases.contains_key(pair_id) {
+            return;
+        }
+        info!(
+            "🎯 开始补齐缺失一腿 | pair_id:{} | token_id={:#x} | 数量:{}份 | 已成交价:{:.4} | 价格上限:{:.4}",
+            pair_id, token_id, amount, filled_price, max_price
+        );
+        self.chases.insert(
+            pair_id.clone(),
+            Chase {
+                token_id: *token_id,
+                filled_token_id: *filled_token_id,
+                filled_price: *filled_price,
+                max_price: *max_price,
+                remaining: *amount,
+                last_attempt: None,
+                in_flight: false,
+            },
+        );
+    }
+
+    /// 主循环每次迭代调用：卖一价进入上限内时补单；到达截止时间的订单对回退为卖出已成交一腿
+    pub fn on_tick(&self, monitor: &OrderBookMonitor, now: DateTime<Utc>, cutoff: DateTime<Utc>) {
+        if self.chases.is_empty() {
+            return;
+        }
+        let pair_ids: Vec<String> = self.chases.iter().map(|c| c.key().clone()).collect();
+        for pair_id in pair_ids {
+            if now >= cutoff {
+                let idle = self.chases.get(&pair_id).map(|c| !c.in_flight).unwrap_or(false);
+                if idle {
+                    self.fall_back(&pair_id);
+                }
+                continue;
+            }
+
+            let Some(mut chase) = self.chases.get_mut(&pair_id) else { continue };
+            if chase.in_flight || chase.last_attempt.map(|t| t.elapsed() < RETRY_INTERVAL).unwrap_or(false) {
+                continue;
+            }
+            let Some(ask) = monitor.get_book(chase.token_id).and_then(|b| b.asks.last().map(|l| l.price)) else {
+                continue;
+            };
+            if ask > chase.max_price {
+                continue;
+            }
+            let size = (chase.remaining * dec!(100)).floor() / dec!(100);
+            if size < MIN_CHASE_SIZE {
+                drop(chase);
+                self.chases.remove(&pair_id);
+                continue;
+            }
+            chase.in_flight = true;
+            chase.last_attempt = Some(Instant::now());
+            let token_id = chase.token_id;
+            let max_price = chase.max_price;
+            drop(chase);
+            self.spawn_buy(pair_id, token_id, ask, max_price, size);
+        }
+    }
+
+    fn spawn_buy(&self, pair_id: String, token_id: U256, ask: Decimal, max_price: Decimal, size: Decimal) {
+        let executor = self.executor.clone();
+        let risk_manager = self.risk_manager.clone();
+        let chases = self.chases.clone();
+        tokio::spawn(async move {
+            info!(
+                "🎯 补单 | pair_id:{} | 卖一:{:.4} | 以上限 {:.4} 吃单 {}份",
+                pair_id, ask, max_price, size
+            );
+            let bought = match executor.take_at_price(token_id, Side::Buy, max_price, size).await {
+                Ok(ack) if ack.taking_amount > dec!(0) => {
+                    // 买单：taking_amount 为成交份额，making_amount 为支付 USDC
+                    let bought = ack.taking_amount;
+                    let price = ack.making_amount / bought;
+                    risk_manager.apply_recovery_fill(&pair_id, token_id, Side::Buy, price, bought);
+                    info!("✅ 补单成交 | pair_id:{} | 成交:{}份 | 均价:{:.4}", pair_id, bought, price);
+                    bought
+                }
+                Ok(_) => dec!(0),
+                Err(e) => {
+                    warn!(pair_id = %pair_id, error = %e, "补单失败，下次行情重试");
+                    dec!(0)
+                }
+            };
+            let done = match chases.get_mut(&pair_id) {
+                Some(mut chase) => {
+                    chase.remaining -= bought;
+                    chase.in_flight = false;
+                    chase.remaining < MIN_CHASE_SIZE
+                }
+                None => false,
+            };
+            if done {
+                chases.remove(&pair_id);
+                info!("✅ 订单对已补齐 | pair_id:{}", pair_id);
+            }
+        });
+    }
+
+    /// 截止时仍未补齐：卖出已成交一腿中未配对的部分
+    fn fall_back(&self, pair_id: &str) {
+        let Some((_, chase)) = self.chases.remove(pair_id) else { return };
+        // 买入手续费以份额扣除，按到手份额卖出
+        let amount = self.fees.for_token(chase.filled_token_id).sellable_size(chase.filled_price, chase.remaining);
+        warn!(
+            "⏰ 补单截止仍未补齐 | pair_id:{} | 缺失:{}份 | 回退为卖出已成交一腿 {}份",
+            pair_id, chase.remaining, amount
+        );
+        if amount > dec!(0) {
+            self.sell_leg(chase.filled_token_id, amount, pair_id.to_string());
+        }
+    }
+
+    /// 立即卖出单腿持仓（FAK，最低价 sell_price），成交后扣减持仓、风险敞口与订单对该腿成交
+    pub fn sell_leg(&self, token_id: U256, amount: Decimal, pair_id: String) {
+        let executor = self.executor.clone();
+        let risk_manager = self.risk_manager.clone();
+        let sell_price = self.sell_price;
+        tokio::spawn(async move {
+            info!("🔻 卖出单腿 | pair_id:{} | token_id={:#x} | 数量:{}份", pair_id, token_id, amount);
+            match executor.take_at_price(token_id, Side::Sell, sell_price, amount).await {
+                Ok(ack) if ack.making_amount > dec!(0) => {
+                    // 卖单：making_amount 为卖出份额，taking_amount 为收到 USDC
+                    let sold = ack.making_amount;
+                    risk_manager.apply_recovery_fill(&pair_id, token_id, Side::Sell, ack.taking_amount / sold, sold);
+                    info!("✅ 单腿已卖出 | pair_id:{} | 卖出:{}份 | 收到:{:.4} USDC", pair_id, sold, ack.taking_amount);
+                }
+                Ok(ack) => warn!(pair_id = %pair_id, error = ?ack.error_msg, "单腿卖出未成交，留给收尾处理"),
+                Err(e) => warn!(pair_id = %pair_id, error = %e, "单腿卖出失败，留给收尾处理"),
+            }
+        });
+    }
+
+    /// 收尾开始或窗口结束：放弃全部补单，剩余单腿由收尾卖出
+    pub fn abandon_all(&self, reason: &str) {
+        if self.chases.is_empty() {
+            return;
+        }
+        for chase in self.chases.iter() {
+            warn!(
+                "补单放弃（{}）| pair_id:{} | 缺失:{}份，单腿持仓交由收尾处理",
+                reason,
+                chase.key(),
+                chase.remaining
+            );
+        }
+        self.chases.clear();
+    }
+}
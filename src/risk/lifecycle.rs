@@ -0,0 +1,106 @@
+//! 套利订单对生命周期：submitted → resting → partially filled → filled / cancelled / expired → merged → closed。
+//! 迁移来自下单回执、成交推送、GTD 到期、撤单与 merge，每次迁移记录时间与原因；
+//! 进入 closed 后订单对从 RiskManager 中清理。
+
+use chrono::{DateTime, Utc};
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleStage {
+    Submitted,       // 已提交，等待回执
+    Resting,         // 挂单中，尚无成交
+    PartiallyFilled, // 有成交，仍有腿在挂单
+    Filled,          // 两腿全部成交
+    Cancelled,       // 未成交部分已撤销（含 FAK/FOK 剩余、下单失败、收尾撤单）
+    Expired,         // GTD 到期，未成交部分失效
+    Merged,          // 成交部分已 merge 回 USDC
+    Closed,          // 终态，已从风控中移除
+}
+
+impl LifecycleStage {
+    /// 仍有腿在挂单
+    pub fn is_open(self) -> bool {
+        matches!(self, Self::Submitted | Self::Resting | Self::PartiallyFilled)
+    }
+
+    /// 是否允许迁移到 next；撤单/过期后仍可能收到撤单前的成交推送，允许补记为 Filled
+    pub fn can_transition_to(self, next: LifecycleStage) -> bool {
+        use LifecycleStage::*;
+        match (self, next) {
+            (Closed, _) => false,
+            (Submitted, Submitted) => false,
+            (Submitted, _) => true,
+            (Resting, PartiallyFilled | Filled | Cancelled | Expired | Closed) => true,
+            (PartiallyFilled, Filled | Cancelled | Expired | Closed) => true,
+            (Cancelled | Expired, Filled) => true,
+            (Filled | Cancelled | Expired, Merged | Closed) => true,
+            (Merged, Closed) => true,
+            _ => false,
+        }
+    }
+}
+
+/// 一次状态迁移
+#[derive(Debug, Clone)]
+pub struct StageTransition {
+    pub from: LifecycleStage,
+    pub to: LifecycleStage,
+    pub at: DateTime<Utc>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lifecycle {
+    stage: LifecycleStage,
+    history: Vec<StageTransition>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self {
+            stage: LifecycleStage::Submitted,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn stage(&self) -> LifecycleStage {
+        self.stage
+    }
+
+    pub fn history(&self) -> &[StageTransition] {
+        &self.history
+    }
+
+    /// 迁移到 next；非法迁移或状态未变时忽略并返回 false
+    pub fn transition(&mut self, next: LifecycleStage, at: DateTime<Utc>, reason: impl Into<String>) -> bool {
+        if !self.stage.can_transition_to(next) {
+            if self.stage != next {
+                debug!(from = ?self.stage, to = ?next, "忽略非法的订单对状态迁移");
+            }
+            return false;
+        }
+        self.history.push(StageTransition {
+            from: self.stage,
+            to: next,
+            at,
+            reason: reason.into(),
+        });
+        self.stage = next;
+        true
+    }
+
+    /// 迁移轨迹，用于日志，例如 "Submitted → Resting(挂单) → Filled(成交推送)"
+    pub fn trail(&self) -> String {
+        let mut out = format!("{:?}", LifecycleStage::Submitted);
+        for t in &self.history {
+            out.push_str(&format!(" → {:?}({})", t.to, t.reason));
+        }
+        out
+    }
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,132 +1,397 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use polymarket_client_sdk::clob::Client;
+use polymarket_client_sdk::clob::types::Side;
 use polymarket_client_sdk::types::{B256, Decimal, U256};
 use rust_decimal_macros::dec;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info};
 
-use super::positions::PositionTracker;
+use super::lifecycle::{Lifecycle, LifecycleStage};
+use super::positions::{ExposureLimits, PositionTracker};
 use super::recovery::{RecoveryAction, RecoveryStrategy};
+use super::risk_guard::RiskGuard;
 use crate::config::Config as BotConfig;
+use crate::ledger::{LedgerEntry, LedgerKind, TradeLedger};
 use crate::trading::executor::OrderPairResult;
+use crate::trading::{Exchange, FeeSchedule, FillEvent};
+
+/// 注册前先到达的成交推送保留时长，超时未匹配到订单对即丢弃（多为卖单或其他订单）
+const UNMATCHED_FILL_TTL: Duration = Duration::from_secs(600);
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PairStatus {
-    Submitted,
     BothFilled,
     PartiallyFilled,
     OneFailed,
     BothFailed,
-    Recovering,
+}
+
+impl PairStatus {
+    /// 按两腿成交量判定订单对状态
+    pub fn from_fills(yes_filled: Decimal, yes_size: Decimal, no_filled: Decimal, no_size: Decimal) -> Self {
+        if yes_filled >= yes_size && no_filled >= no_size {
+            PairStatus::BothFilled
+        } else if yes_filled > dec!(0) && no_filled > dec!(0) {
+            PairStatus::PartiallyFilled
+        } else if yes_filled > dec!(0) || no_filled > dec!(0) {
+            PairStatus::OneFailed
+        } else {
+            PairStatus::BothFailed
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct OrderPair {
     pub pair_id: String,
     pub market_id: B256,
+    pub side: Side, // Buy：买入 YES+NO 后 merge；Sell：split 后卖出两腿
     pub yes_order_id: String,
     pub no_order_id: String,
     pub yes_token_id: U256,
     pub no_token_id: U256,
     pub yes_size: Decimal,
     pub no_size: Decimal,
+    pub yes_price: Decimal, // 下单限价
+    pub no_price: Decimal,
     pub yes_filled: Decimal,
     pub no_filled: Decimal,
     pub status: PairStatus,
     pub created_at: DateTime<Utc>,
+    pub yes_resting: bool, // 该腿仍有未成交部分在挂单
+    pub no_resting: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub lifecycle: Lifecycle,
+    pub outcome_reported: bool, // 单边/双边结果已计入风控熔断
+    pub recovery_dispatched: bool, // 已派发恢复动作（补单 / 卖出 / 监测），之后不再重复派发
+}
+
+impl OrderPair {
+    /// 按成交量与挂单情况推导应处的阶段（不含 merge / close）
+    fn fill_stage(&self) -> LifecycleStage {
+        if self.yes_filled >= self.yes_size && self.no_filled >= self.no_size {
+            LifecycleStage::Filled
+        } else if !self.yes_resting && !self.no_resting {
+            LifecycleStage::Cancelled
+        } else if self.yes_filled > dec!(0) || self.no_filled > dec!(0) {
+            LifecycleStage::PartiallyFilled
+        } else {
+            LifecycleStage::Resting
+        }
+    }
+
+    /// 两腿都成交的份额，merge 后即结清
+    fn hedged(&self) -> Decimal {
+        self.yes_filled.min(self.no_filled)
+    }
+
+    /// 不再挂单但未全部成交的腿：(订单号, token, 未成交份额)
+    fn unfilled_legs(&self) -> Vec<(String, U256, Decimal)> {
+        let mut legs = Vec::new();
+        if !self.yes_resting && self.yes_filled < self.yes_size {
+            legs.push((self.yes_order_id.clone(), self.yes_token_id, self.yes_size - self.yes_filled));
+        }
+        if !self.no_resting && self.no_filled < self.no_size {
+            legs.push((self.no_order_id.clone(), self.no_token_id, self.no_size - self.no_filled));
+        }
+        legs
+    }
 }
 
 pub struct RiskManager {
-    clob_client: Client<polymarket_client_sdk::auth::state::Authenticated<polymarket_client_sdk::auth::Normal>>,
+    exchange: std::sync::Arc<dyn Exchange>,
     pending_pairs: DashMap<String, OrderPair>,
+    order_index: DashMap<String, (String, bool)>, // order_id -> (pair_id, 是否 YES 腿)
+    unmatched_fills: DashMap<String, (Decimal, Instant)>, // 订单对注册前到达的累计成交
     position_tracker: std::sync::Arc<PositionTracker>,
     recovery_strategy: RecoveryStrategy,
+    ledger: TradeLedger,
+    risk_guard: Option<std::sync::Arc<RiskGuard>>,
 }
 
 impl RiskManager {
-    pub fn new(
-        clob_client: Client<polymarket_client_sdk::auth::state::Authenticated<polymarket_client_sdk::auth::Normal>>,
-        config: &BotConfig,
-    ) -> Self {
+    pub fn new(exchange: std::sync::Arc<dyn Exchange>, config: &BotConfig, fees: FeeSchedule) -> Self {
         Self {
-            clob_client,
+            exchange,
             pending_pairs: DashMap::new(),
-            position_tracker: std::sync::Arc::new(PositionTracker::new(
-                Decimal::try_from(config.risk_max_exposure_usdc).unwrap_or(dec!(1000.0)),
-            )),
-            recovery_strategy: RecoveryStrategy::new(
-                config.risk_imbalance_threshold,
-                config.hedge_take_profit_pct,
-                config.hedge_stop_loss_pct,
+            order_index: DashMap::new(),
+            unmatched_fills: DashMap::new(),
+            position_tracker: std::sync::Arc::new(
+                PositionTracker::new(
+                    Decimal::try_from(config.risk_max_exposure_usdc).unwrap_or(dec!(1000.0)),
+                )
+                .with_limits(ExposureLimits::from_config(config)),
             ),
+            recovery_strategy: RecoveryStrategy::new(config, fees),
+            ledger: TradeLedger::disabled(),
+            risk_guard: None,
+        }
+    }
+
+    /// 挂单后续成交与撤单写入交易台账
+    pub fn with_ledger(mut self, ledger: TradeLedger) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    /// 买入订单对的单边/双边结果计入风控熔断的连续单边计数
+    pub fn with_risk_guard(mut self, risk_guard: std::sync::Arc<RiskGuard>) -> Self {
+        self.risk_guard = Some(risk_guard);
+        self
+    }
+
+    /// 未成交部分撤销 / 过期，逐腿记入台账
+    fn record_cancelled_legs(&self, pair: &OrderPair, reason: &str) {
+        for (order_id, token_id, size) in pair.unfilled_legs() {
+            self.ledger.record(
+                LedgerEntry::new(LedgerKind::Cancel)
+                    .with_pair(pair.pair_id.clone())
+                    .with_market(pair.market_id)
+                    .with_order(order_id)
+                    .with_token(token_id)
+                    .with_size(size)
+                    .with_note(reason),
+            );
         }
     }
 
     /// 注册新的订单对
-    /// yes_price: YES订单的买入价格
-    /// no_price: NO订单的买入价格
     pub fn register_order_pair(
         &self,
         result: OrderPairResult,
         market_id: B256,
         yes_token: U256,
         no_token: U256,
-        yes_price: Decimal,
-        no_price: Decimal,
     ) {
-        let status = if result.yes_filled == result.yes_size && result.no_filled == result.no_size {
-            PairStatus::BothFilled
-        } else if result.yes_filled > dec!(0) && result.no_filled > dec!(0) {
-            PairStatus::PartiallyFilled
-        } else if result.yes_filled > dec!(0) && result.no_filled == dec!(0) {
-            PairStatus::OneFailed
-        } else if result.yes_filled == dec!(0) && result.no_filled > dec!(0) {
-            PairStatus::OneFailed
-        } else {
-            PairStatus::BothFailed
-        };
+        self.register_pair(result, market_id, yes_token, no_token, Side::Buy);
+    }
 
-        let pair = OrderPair {
+    /// 注册 split 后卖出两腿的订单对：持仓先加上 split 得到的份额，后续成交推送扣减持仓与敞口
+    pub fn register_sell_pair(
+        &self,
+        result: OrderPairResult,
+        market_id: B256,
+        yes_token: U256,
+        no_token: U256,
+    ) {
+        self.position_tracker.update_position(yes_token, result.yes_size);
+        self.position_tracker.update_position(no_token, result.no_size);
+        self.register_pair(result, market_id, yes_token, no_token, Side::Sell);
+    }
+
+    fn register_pair(
+        &self,
+        result: OrderPairResult,
+        market_id: B256,
+        yes_token: U256,
+        no_token: U256,
+        side: Side,
+    ) {
+        // 下单回执之后、注册之前可能已收到用户频道的成交推送，取较大值
+        let yes_filled = result.yes_filled.max(self.take_unmatched_fill(&result.yes_order_id));
+        let no_filled = result.no_filled.max(self.take_unmatched_fill(&result.no_order_id));
+        let status = PairStatus::from_fills(yes_filled, result.yes_size, no_filled, result.no_size);
+
+        let now = Utc::now();
+        let mut pair = OrderPair {
             pair_id: result.pair_id.clone(),
             market_id,
+            side,
             yes_order_id: result.yes_order_id,
             no_order_id: result.no_order_id,
             yes_token_id: yes_token,
             no_token_id: no_token,
             yes_size: result.yes_size,
             no_size: result.no_size,
-            yes_filled: result.yes_filled,
-            no_filled: result.no_filled,
+            yes_price: result.yes_price,
+            no_price: result.no_price,
+            yes_filled,
+            no_filled,
             status: status.clone(),
-            created_at: Utc::now(),
+            created_at: now,
+            yes_resting: result.yes_resting && yes_filled < result.yes_size,
+            no_resting: result.no_resting && no_filled < result.no_size,
+            expires_at: result.expires_at,
+            lifecycle: Lifecycle::new(),
+            outcome_reported: false,
+            recovery_dispatched: false,
+        };
+        let stage = pair.fill_stage();
+        let reason = match stage {
+            LifecycleStage::Filled => "下单即全部成交",
+            LifecycleStage::Cancelled => "未成交部分已撤销（FAK/FOK 或下单失败）",
+            LifecycleStage::PartiallyFilled => "部分成交，剩余挂单",
+            _ => "挂单等待成交",
         };
+        pair.lifecycle.transition(stage, now, reason);
+        if stage == LifecycleStage::Cancelled {
+            self.record_cancelled_legs(&pair, reason);
+        }
+        self.report_outcome(&mut pair, now);
 
         // 更新持仓（敞口已在「执行套利」时按订单成本增加，此处不再按成交更新敞口）
-        self.position_tracker.update_position(yes_token, pair.yes_filled);
-        self.position_tracker.update_position(no_token, pair.no_filled);
+        self.apply_leg_fill(side, yes_token, pair.yes_filled);
+        self.apply_leg_fill(side, no_token, pair.no_filled);
 
         // 这个日志已经在executor中打印了，这里不再重复打印
         debug!(
             pair_id = %pair.pair_id,
             status = ?status,
+            stage = ?pair.lifecycle.stage(),
             yes_filled = %pair.yes_filled,
             no_filled = %pair.no_filled,
             "注册订单对"
         );
 
+        // 建立订单 -> 订单对索引，后续成交推送据此找到订单对
+        if !pair.yes_order_id.is_empty() {
+            self.order_index.insert(pair.yes_order_id.clone(), (pair.pair_id.clone(), true));
+        }
+        if !pair.no_order_id.is_empty() {
+            self.order_index.insert(pair.no_order_id.clone(), (pair.pair_id.clone(), false));
+        }
+
         // 使用 pair.pair_id 的克隆来插入，因为 DashMap 需要拥有所有权
-        self.pending_pairs.insert(pair.pair_id.clone(), pair);
+        let pair_id = pair.pair_id.clone();
+        let nothing_filled = pair.yes_filled == dec!(0) && pair.no_filled == dec!(0);
+        let cancelled = pair.lifecycle.stage() == LifecycleStage::Cancelled;
+        let settled = !pair.lifecycle.stage().is_open();
+        self.pending_pairs.insert(pair_id.clone(), pair);
+        if cancelled && nothing_filled {
+            self.close_pair(&pair_id, "无成交且无挂单");
+        } else if side == Side::Sell && settled {
+            self.close_pair(&pair_id, "卖单不再挂单，剩余持仓由 merge 与收尾处理");
+        }
+    }
+
+    /// 一腿成交计入持仓：买入增加持仓；卖出先按比例扣减敞口再扣减持仓
+    fn apply_leg_fill(&self, side: Side, token_id: U256, size: Decimal) {
+        if side == Side::Sell {
+            self.position_tracker.update_exposure_cost(token_id, dec!(0), -size);
+            self.position_tracker.update_position(token_id, -size);
+        } else {
+            self.position_tracker.update_position(token_id, size);
+        }
+    }
+
+    fn take_unmatched_fill(&self, order_id: &str) -> Decimal {
+        self.unmatched_fills
+            .remove(order_id)
+            .map(|(_, (matched, _))| matched)
+            .unwrap_or(dec!(0))
+    }
+
+    /// 处理用户频道的成交推送：更新订单对成交量与持仓、重新判定状态；
+    /// 状态发生变化时返回 pair_id，由调用方触发恢复处理
+    pub fn apply_fill(&self, event: &FillEvent) -> Option<String> {
+        let Some((pair_id, is_yes)) = self.order_index.get(&event.order_id).map(|e| e.clone()) else {
+            // 订单对尚未注册（下单回执还没返回）或非套利订单，暂存等待注册
+            let now = Instant::now();
+            self.unmatched_fills
+                .retain(|_, (_, at)| now.duration_since(*at) < UNMATCHED_FILL_TTL);
+            let mut entry = self
+                .unmatched_fills
+                .entry(event.order_id.clone())
+                .or_insert((dec!(0), now));
+            entry.0 = entry.0.max(event.size_matched);
+            return None;
+        };
+
+        let mut entry = self.pending_pairs.get_mut(&pair_id)?;
+        let pair = entry.value_mut();
+        let (filled, resting, size, price, token) = if is_yes {
+            (&mut pair.yes_filled, &mut pair.yes_resting, pair.yes_size, pair.yes_price, pair.yes_token_id)
+        } else {
+            (&mut pair.no_filled, &mut pair.no_resting, pair.no_size, pair.no_price, pair.no_token_id)
+        };
+        // 推送为累计量，只增不减
+        let delta = event.size_matched.min(size) - *filled;
+        if delta <= dec!(0) {
+            return None;
+        }
+        *filled += delta;
+        if *filled >= size {
+            *resting = false;
+        }
+        self.apply_leg_fill(pair.side, token, delta);
+        self.ledger.record(
+            LedgerEntry::new(LedgerKind::Fill)
+                .with_pair(pair_id.clone())
+                .with_market(pair.market_id)
+                .with_order(event.order_id.clone())
+                .with_token(token)
+                .with_trade(pair.side, price, delta)
+                .with_amount(price * delta)
+                .with_note("成交推送"),
+        );
+
+        // 已撤单/过期的订单对只可能补记为全部成交，其余保持原阶段
+        let stage = pair.fill_stage();
+        let now = Utc::now();
+        if pair.lifecycle.stage().is_open() || stage == LifecycleStage::Filled {
+            pair.lifecycle.transition(stage, now, "成交推送");
+        }
+        self.report_outcome(pair, now);
+
+        let old_status = pair.status.clone();
+        let new_status = PairStatus::from_fills(pair.yes_filled, pair.yes_size, pair.no_filled, pair.no_size);
+        info!(
+            pair_id = %pair_id,
+            side = if is_yes { "YES" } else { "NO" },
+            delta = %delta,
+            yes_filled = %pair.yes_filled,
+            no_filled = %pair.no_filled,
+            "📥 挂单后续成交"
+        );
+        if pair.side == Side::Sell {
+            // 卖单无需补单；全部成交后结清，剩余持仓由 merge 与收尾处理
+            pair.status = new_status;
+            let settled = !pair.lifecycle.stage().is_open();
+            drop(entry);
+            if settled {
+                self.close_pair(&pair_id, "卖单全部成交");
+            }
+            return None;
+        }
+        if new_status == old_status {
+            return None;
+        }
+        info!(pair_id = %pair_id, from = ?old_status, to = ?new_status, "订单对状态变化");
+        pair.status = new_status;
+        Some(pair_id)
+    }
+
+    /// 成交推送入口：更新订单对，状态变化时按新状态重新决定恢复策略
+    pub async fn on_fill(&self, event: &FillEvent) -> Result<Option<RecoveryAction>> {
+        match self.apply_fill(event) {
+            Some(pair_id) => self.handle_order_pair(&pair_id).await.map(Some),
+            None => Ok(None),
+        }
     }
 
-    /// 处理订单对并决定恢复策略
+    /// 处理订单对并决定恢复策略；每个订单对只派发一次恢复动作，补单与卖出的成交由 apply_recovery_fill 计入订单对
     pub async fn handle_order_pair(&self, pair_id: &str) -> Result<RecoveryAction> {
         let pair = self
             .pending_pairs
             .get(pair_id)
             .ok_or_else(|| anyhow::anyhow!("订单对 {} 不存在", pair_id))?
             .clone();
+        if pair.recovery_dispatched {
+            debug!(pair_id = %pair.pair_id, "恢复动作已派发，不再重复处理");
+            return Ok(RecoveryAction::None);
+        }
+
+        let action = self.decide_recovery(&pair).await?;
+        if !matches!(action, RecoveryAction::None) {
+            if let Some(mut pair) = self.pending_pairs.get_mut(pair_id) {
+                pair.recovery_dispatched = true;
+            }
+        }
+        Ok(action)
+    }
 
+    async fn decide_recovery(&self, pair: &OrderPair) -> Result<RecoveryAction> {
         match pair.status {
             PairStatus::BothFilled => {
                 info!(pair_id = %pair.pair_id, "两个订单都完全成交，无需恢复");
@@ -134,14 +399,18 @@ impl RiskManager {
             }
             PairStatus::PartiallyFilled => {
                 self.recovery_strategy
-                    .handle_partial_fill(&pair, &self.position_tracker)
+                    .handle_partial_fill(pair, &self.position_tracker)
                     .await
             }
             PairStatus::OneFailed => {
                 self.recovery_strategy
-                    .handle_one_sided_fill(&pair, &self.position_tracker)
+                    .handle_one_sided_fill(pair, &self.position_tracker)
                     .await
             }
+            PairStatus::BothFailed if pair.lifecycle.stage().is_open() => {
+                debug!(pair_id = %pair.pair_id, "两腿挂单中，暂无成交");
+                Ok(RecoveryAction::None)
+            }
             PairStatus::BothFailed => {
                 error!(
                     "❌ 套利失败 | YES和NO订单都未成交，可能原因：价格已变化或流动性不足"
@@ -150,12 +419,261 @@ impl RiskManager {
                     reason: "两个订单都失败".to_string(),
                 })
             }
-            _ => Ok(RecoveryAction::None),
         }
     }
 
+    /// 恢复动作的成交（补买缺失一腿 / 卖出多出一腿）：计入持仓与风险敞口，并记入所属订单对的该腿成交，
+    /// 使订单对按补齐后的两腿参与 merge 结清；订单对已结清时只更新持仓
+    pub fn apply_recovery_fill(&self, pair_id: &str, token_id: U256, side: Side, price: Decimal, size: Decimal) {
+        if size <= dec!(0) {
+            return;
+        }
+        if side == Side::Sell {
+            self.position_tracker.update_exposure_cost(token_id, dec!(0), -size);
+            self.position_tracker.update_position(token_id, -size);
+        } else {
+            self.position_tracker.update_position(token_id, size);
+            self.position_tracker.update_exposure_cost(token_id, price, size);
+        }
+
+        let Some(mut pair) = self.pending_pairs.get_mut(pair_id) else { return };
+        let filled = if token_id == pair.yes_token_id {
+            &mut pair.yes_filled
+        } else if token_id == pair.no_token_id {
+            &mut pair.no_filled
+        } else {
+            return;
+        };
+        *filled = if side == Side::Sell { (*filled - size).max(dec!(0)) } else { *filled + size };
+        pair.status = PairStatus::from_fills(pair.yes_filled, pair.yes_size, pair.no_filled, pair.no_size);
+        debug!(
+            pair_id = %pair_id,
+            yes_filled = %pair.yes_filled,
+            no_filled = %pair.no_filled,
+            "恢复成交已计入订单对"
+        );
+    }
+
+    /// GTD 到期：仍在挂单的订单对标记为过期，无成交的直接结清
+    /// 返回仍有成交、需要重新执行恢复策略的订单对（挂单到期后两腿可能不平衡）
+    pub fn expire_pairs(&self, now: DateTime<Utc>) -> Vec<String> {
+        let expired: Vec<String> = self
+            .pending_pairs
+            .iter()
+            .filter(|p| p.lifecycle.stage().is_open() && p.expires_at.map(|exp| exp <= now).unwrap_or(false))
+            .map(|p| p.pair_id.clone())
+            .collect();
+        for pair_id in &expired {
+            self.settle_open_pair(pair_id, LifecycleStage::Expired, "GTD 到期", now);
+        }
+        expired
+            .into_iter()
+            .filter(|pair_id| self.pending_pairs.contains_key(pair_id))
+            .collect()
+    }
+
+    /// 已撤销全部挂单（如收尾撤单）：仍在挂单的订单对标记为撤销
+    pub fn cancel_open_pairs(&self, reason: &str) {
+        let now = Utc::now();
+        let open: Vec<String> = self
+            .pending_pairs
+            .iter()
+            .filter(|p| p.lifecycle.stage().is_open())
+            .map(|p| p.pair_id.clone())
+            .collect();
+        for pair_id in open {
+            self.settle_open_pair(&pair_id, LifecycleStage::Cancelled, reason, now);
+        }
+    }
+
+    fn settle_open_pair(&self, pair_id: &str, stage: LifecycleStage, reason: &str, now: DateTime<Utc>) {
+        let close_reason = {
+            let Some(mut pair) = self.pending_pairs.get_mut(pair_id) else { return };
+            pair.yes_resting = false;
+            pair.no_resting = false;
+            if pair.lifecycle.transition(stage, now, reason) {
+                self.record_cancelled_legs(&pair, reason);
+            }
+            self.report_outcome(&mut pair, now);
+            if pair.yes_filled == dec!(0) && pair.no_filled == dec!(0) {
+                Some("无成交")
+            } else if pair.side == Side::Sell {
+                Some("卖单不再挂单，剩余持仓由 merge 与收尾处理")
+            } else {
+                None
+            }
+        };
+        if let Some(close_reason) = close_reason {
+            self.close_pair(pair_id, close_reason);
+        }
+    }
+
+    /// 某市场 merge 完成：不再挂单且两腿都有成交的订单对已结清
+    pub fn on_merged(&self, market_id: B256) {
+        let now = Utc::now();
+        let merged: Vec<String> = self
+            .pending_pairs
+            .iter_mut()
+            .filter(|p| p.market_id == market_id && !p.lifecycle.stage().is_open() && p.hedged() > dec!(0))
+            .map(|mut p| {
+                let reason = format!("merge {} 份", p.hedged());
+                p.lifecycle.transition(LifecycleStage::Merged, now, reason);
+                p.pair_id.clone()
+            })
+            .collect();
+        for pair_id in merged {
+            self.close_pair(&pair_id, "merge 完成");
+        }
+    }
+
+    /// 市场窗口结束：该市场剩余订单对（单腿持仓、未 merge 部分已交由收尾处理）全部结清
+    pub fn close_market_pairs(&self, market_ids: &[B256], reason: &str) {
+        let stale: Vec<String> = self
+            .pending_pairs
+            .iter()
+            .filter(|p| market_ids.contains(&p.market_id))
+            .map(|p| p.pair_id.clone())
+            .collect();
+        for pair_id in stale {
+            self.close_pair(&pair_id, reason);
+        }
+    }
+
+    /// 终态清理：迁移到 Closed 并从 pending_pairs 与订单索引中移除
+    fn close_pair(&self, pair_id: &str, reason: &str) {
+        let Some((_, mut pair)) = self.pending_pairs.remove(pair_id) else { return };
+        let now = Utc::now();
+        pair.lifecycle.transition(LifecycleStage::Closed, now, reason);
+        self.report_outcome(&mut pair, now);
+        self.order_index.remove(&pair.yes_order_id);
+        self.order_index.remove(&pair.no_order_id);
+        info!(
+            pair_id = %pair.pair_id,
+            yes_filled = %pair.yes_filled,
+            no_filled = %pair.no_filled,
+            "🗂️ 订单对结清 | {}",
+            pair.lifecycle.trail()
+        );
+    }
+
+    /// 买入订单对的结果可判定时计入风控熔断（每个订单对只计一次）：两腿都有成交为双边；
+    /// 只有一腿成交且已不再挂单为单边；仍在挂单或无成交时暂不计，等后续成交推送、撤单/过期或结清时再判定
+    fn report_outcome(&self, pair: &mut OrderPair, now: DateTime<Utc>) {
+        if pair.side != Side::Buy || pair.outcome_reported {
+            return;
+        }
+        let Some(risk_guard) = &self.risk_guard else { return };
+        let one_sided = if pair.hedged() > dec!(0) {
+            false
+        } else if (pair.yes_filled > dec!(0) || pair.no_filled > dec!(0)) && !pair.lifecycle.stage().is_open() {
+            true
+        } else {
+            return;
+        };
+        pair.outcome_reported = true;
+        risk_guard.on_pair_outcome(one_sided, now);
+    }
+
+    /// 该市场是否有订单对仍有腿在挂单
+    pub fn has_resting_pair(&self, market_id: B256) -> bool {
+        self.pending_pairs
+            .iter()
+            .any(|p| p.market_id == market_id && p.lifecycle.stage().is_open())
+    }
+
+    /// 仍未结清的订单对（风控视图）
+    pub fn open_pairs(&self) -> Vec<OrderPair> {
+        self.pending_pairs.iter().map(|p| p.clone()).collect()
+    }
+
+    /// 按 Data API 持仓与 CLOB 挂单重建风险敞口（新一轮开始时调用），返回重建后的总敞口
+    pub async fn rebuild_exposure(&self) -> Result<Decimal> {
+        let positions = self.position_tracker.sync_from_api().await?;
+        let open_orders = self.exchange.open_orders().await?;
+        Ok(self.position_tracker.rebuild_exposure(&positions, &open_orders))
+    }
+
     /// 获取持仓跟踪器（Arc引用）
     pub fn position_tracker(&self) -> std::sync::Arc<PositionTracker> {
         self.position_tracker.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trading::{FeeModel, SimulatedExchange};
+    use std::sync::Arc;
+
+    const YES: U256 = U256::from_limbs([1, 0, 0, 0]);
+    const NO: U256 = U256::from_limbs([2, 0, 0, 0]);
+
+    fn risk_manager() -> RiskManager {
+        let mut config = BotConfig::from_env_offline().unwrap();
+        config.recovery_policy = "chase".to_string();
+        config.risk_imbalance_threshold = 0.1;
+        let fees = FeeSchedule::new(FeeModel::free());
+        RiskManager::new(Arc::new(SimulatedExchange::new(dec!(1000), fees.clone())), &config, fees)
+    }
+
+    /// YES 全部成交、NO 未成交且已不再挂单
+    fn register_one_sided(rm: &RiskManager) {
+        rm.register_order_pair(
+            OrderPairResult {
+                pair_id: "p1".to_string(),
+                yes_order_id: "y1".to_string(),
+                no_order_id: "n1".to_string(),
+                yes_filled: dec!(10),
+                no_filled: dec!(0),
+                yes_size: dec!(10),
+                no_size: dec!(10),
+                yes_price: dec!(0.45),
+                no_price: dec!(0.5),
+                success: true,
+                yes_resting: false,
+                no_resting: false,
+                expires_at: None,
+            },
+            B256::repeat_byte(1),
+            YES,
+            NO,
+        );
+    }
+
+    #[tokio::test]
+    async fn recovery_is_dispatched_once_per_pair() {
+        let rm = risk_manager();
+        register_one_sided(&rm);
+        let action = rm.handle_order_pair("p1").await.unwrap();
+        assert!(matches!(action, RecoveryAction::ChaseMissingLeg { token_id, .. } if token_id == NO));
+        // 状态未变（补单尚未成交）时再次处理不重复派发
+        assert!(matches!(rm.handle_order_pair("p1").await.unwrap(), RecoveryAction::None));
+    }
+
+    #[tokio::test]
+    async fn recovery_fills_are_credited_to_the_pair() {
+        let rm = risk_manager();
+        register_one_sided(&rm);
+        rm.handle_order_pair("p1").await.unwrap();
+
+        rm.apply_recovery_fill("p1", NO, Side::Buy, dec!(0.5), dec!(10));
+        let pair = rm.pending_pairs.get("p1").unwrap().clone();
+        assert_eq!(pair.no_filled, dec!(10));
+        assert_eq!(pair.status, PairStatus::BothFilled);
+        assert_eq!(rm.position_tracker().get_position(NO), dec!(10));
+
+        // 补齐后 merge 结清订单对
+        rm.on_merged(B256::repeat_byte(1));
+        assert!(rm.pending_pairs.get("p1").is_none());
+    }
+
+    #[test]
+    fn sold_excess_reduces_the_filled_leg() {
+        let rm = risk_manager();
+        register_one_sided(&rm);
+        rm.apply_recovery_fill("p1", YES, Side::Sell, dec!(0.4), dec!(10));
+        let pair = rm.pending_pairs.get("p1").unwrap().clone();
+        assert_eq!(pair.yes_filled, dec!(0));
+        assert_eq!(rm.position_tracker().get_position(YES), dec!(0));
+    }
+}
@@ -1,9 +1,28 @@
 pub mod hedge_monitor;
+pub mod leg_chaser;
+pub mod lifecycle;
 pub mod manager;
+pub mod pnl;
 pub mod position_balancer;
 pub mod positions;
 pub mod recovery;
+pub mod risk_guard;
+pub mod trade_budget;
 
 pub use hedge_monitor::HedgeMonitor;
+pub use leg_chaser::LegChaser;
+pub use lifecycle::LifecycleStage;
 pub use manager::RiskManager;
-pub use position_balancer::PositionBalancer;
\ No newline at end of file
+pub use pnl::{PnlEngine, PnlSummary};
+pub use position_balancer::PositionBalancer;
+pub use risk_guard::RiskGuard;
+pub use trade_budget::TradeBudget;
+
+/// 状态文件路径：可执行文件所在目录下的 name（取不到时使用当前目录）
+pub(crate) fn state_file_path(name: &str) -> std::path::PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(std::path::Path::to_path_buf))
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join(name)
+}
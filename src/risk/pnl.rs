@@ -0,0 +1,435 @@
+//! 盈亏核算：由交易台账的成交、split、merge（每对 YES+NO 值 1 USDC）与 redeem 记录驱动，
+//! 按平均成本计算已实现盈亏与手续费，未平仓持仓按订单簿买一价估值得到未实现盈亏；
+//! 结果按窗口、币种与 UTC 日汇总。收尾卖出以其成交记录计入。
+//!
+//! 重启前的持仓没有本次运行的成交记录：启动时按 Data API 持仓的均价（avg_price）补齐成本（seed_positions）；
+//! 仍无成本记录的份额在 merge / 卖出 / redeem 时按 0 成本计，并记录警告。
+
+use chrono::{DateTime, NaiveDate, Utc};
+use polymarket_client_sdk::clob::types::Side;
+use polymarket_client_sdk::types::{B256, Decimal, U256};
+use rust_decimal_macros::dec;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::ledger::{LedgerEntry, LedgerKind, LedgerMarket};
+use crate::positions::Position;
+
+/// 未登记市场的记录归入此币种
+const UNKNOWN_SYMBOL: &str = "unknown";
+
+/// 无成本记录份额的警告阈值（忽略按份额扣除手续费带来的取整误差）
+const UNCOVERED_WARN_SHARES: Decimal = dec!(0.01);
+
+/// 从 5 分钟市场 slug（如 btc-updown-5m-1770972300）解析币种与窗口时间戳
+fn parse_slug(slug: &str) -> Option<(String, i64)> {
+    let (symbol, rest) = slug.split_once('-')?;
+    let window_ts = rest.rsplit('-').next()?.parse().ok()?;
+    Some((symbol.to_string(), window_ts))
+}
+
+/// 已实现盈亏与手续费累计
+#[derive(Debug, Clone, Copy, Default)]
+struct PnlBucket {
+    realized: Decimal,
+    fees: Decimal,
+}
+
+/// 盈亏汇总；realized 已扣除手续费，fees 单独列出供参考
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PnlSummary {
+    pub realized: Decimal,
+    pub unrealized: Decimal,
+    pub fees: Decimal,
+}
+
+impl PnlSummary {
+    pub fn total(&self) -> Decimal {
+        self.realized + self.unrealized
+    }
+
+    fn add_bucket(&mut self, bucket: &PnlBucket) {
+        self.realized += bucket.realized;
+        self.fees += bucket.fees;
+    }
+}
+
+impl fmt::Display for PnlSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "已实现:{:+.4} 未实现:{:+.4} 合计:{:+.4} USD | 手续费:{:.4}",
+            self.realized,
+            self.unrealized,
+            self.total(),
+            self.fees
+        )
+    }
+}
+
+/// 单个 token 的持仓与成本（cost 为剩余份额的总成本 USDC）
+#[derive(Debug, Clone, Copy, Default)]
+struct TokenPosition {
+    size: Decimal,
+    cost: Decimal,
+}
+
+impl TokenPosition {
+    /// 移出 size 份，返回按平均成本计的成本；超出持仓的部分按 0 成本
+    fn take(&mut self, size: Decimal) -> Decimal {
+        if self.size <= dec!(0) {
+            return dec!(0);
+        }
+        let taken = size.min(self.size);
+        let cost = self.cost * taken / self.size;
+        self.size -= taken;
+        self.cost -= cost;
+        cost
+    }
+}
+
+/// 市场归属：(币种, 窗口时间戳, YES token, NO token)
+#[derive(Debug, Clone)]
+struct MarketKey {
+    symbol: String,
+    window_ts: i64,
+    yes_token_id: U256,
+    no_token_id: U256,
+}
+
+#[derive(Default)]
+struct PnlState {
+    markets: HashMap<B256, MarketKey>,
+    token_market: HashMap<U256, B256>,
+    positions: HashMap<U256, TokenPosition>,
+    by_window: HashMap<i64, PnlBucket>,
+    by_day_symbol: HashMap<(NaiveDate, String), PnlBucket>,
+}
+
+impl PnlState {
+    fn register(&mut self, market: &LedgerMarket) {
+        self.markets.entry(market.market_id).or_insert_with(|| MarketKey {
+            symbol: market.symbol.clone(),
+            window_ts: market.window_ts,
+            yes_token_id: market.yes_token_id,
+            no_token_id: market.no_token_id,
+        });
+        self.token_market.insert(market.yes_token_id, market.market_id);
+        self.token_market.insert(market.no_token_id, market.market_id);
+    }
+
+    /// 移出 token 的 size 份并返回成本；超出已知持仓的部分没有成本记录，按 0 成本计并警告
+    fn take(&mut self, token_id: U256, size: Decimal) -> Decimal {
+        let position = self.positions.entry(token_id).or_default();
+        let uncovered = size - position.size.max(dec!(0));
+        if uncovered > UNCOVERED_WARN_SHARES {
+            warn!(token_id = %token_id, uncovered = %uncovered, "份额没有成本记录，按 0 成本计入盈亏");
+        }
+        position.take(size)
+    }
+
+    fn key_for_token(&self, token_id: U256) -> Option<&MarketKey> {
+        self.token_market.get(&token_id).and_then(|m| self.markets.get(m))
+    }
+
+    /// 已实现盈亏与手续费计入所属窗口、币种与当日
+    fn book(&mut self, market_id: Option<B256>, at: DateTime<Utc>, realized: Decimal, fees: Decimal) {
+        let key = market_id.and_then(|m| self.markets.get(&m)).cloned();
+        if let Some(key) = &key {
+            let bucket = self.by_window.entry(key.window_ts).or_default();
+            bucket.realized += realized;
+            bucket.fees += fees;
+        }
+        let symbol = key.map(|k| k.symbol).unwrap_or_else(|| UNKNOWN_SYMBOL.to_string());
+        let bucket = self.by_day_symbol.entry((at.date_naive(), symbol)).or_default();
+        bucket.realized += realized;
+        bucket.fees += fees;
+    }
+
+    fn on_fill(&mut self, entry: &LedgerEntry, market_id: Option<B256>, at: DateTime<Utc>) {
+        let (Some(token_id), Some(side), Some(size)) = (entry.token_id, entry.side, entry.size) else {
+            return;
+        };
+        if size <= dec!(0) {
+            return;
+        }
+        let price = entry.price.unwrap_or(dec!(0));
+        let usdc = entry.amount.unwrap_or(price * size);
+        let fee = entry.fee.unwrap_or(dec!(0));
+        match side {
+            Side::Buy => {
+                // 买入手续费以份额扣除：到手份额减少，成本为实际支付的 USDC
+                let fee_shares = if price > dec!(0) { fee / price } else { dec!(0) };
+                let position = self.positions.entry(token_id).or_default();
+                position.size += (size - fee_shares).max(dec!(0));
+                position.cost += usdc;
+                self.book(market_id, at, dec!(0), fee);
+            }
+            Side::Sell => {
+                let cost = self.take(token_id, size);
+                self.book(market_id, at, usdc - fee - cost, fee);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_split(&mut self, market_id: B256, amount: Decimal) {
+        let Some(key) = self.markets.get(&market_id).cloned() else { return };
+        for token_id in [key.yes_token_id, key.no_token_id] {
+            let position = self.positions.entry(token_id).or_default();
+            position.size += amount;
+            position.cost += amount / dec!(2);
+        }
+    }
+
+    fn on_merge(&mut self, market_id: B256, size: Decimal, at: DateTime<Utc>) {
+        let Some(key) = self.markets.get(&market_id).cloned() else {
+            warn!(market_id = %market_id, "merge 的市场没有持仓记录，按 0 成本计入盈亏");
+            self.book(Some(market_id), at, size, dec!(0));
+            return;
+        };
+        let cost: Decimal = [key.yes_token_id, key.no_token_id]
+            .iter()
+            .map(|t| self.take(*t, size))
+            .sum();
+        self.book(Some(market_id), at, size - cost, dec!(0));
+    }
+
+    /// redeem：给出 token 时按该 token 份额计成本；只给 market 时该市场两腿持仓全部结清
+    fn on_redeem(&mut self, entry: &LedgerEntry, market_id: Option<B256>, at: DateTime<Utc>) {
+        let amount = entry.amount.unwrap_or(dec!(0));
+        let cost = match (entry.token_id, entry.size) {
+            (Some(token_id), Some(size)) => self.take(token_id, size),
+            _ => {
+                let Some(key) = market_id.and_then(|m| self.markets.get(&m)).cloned() else {
+                    self.book(market_id, at, amount, dec!(0));
+                    return;
+                };
+                [key.yes_token_id, key.no_token_id]
+                    .iter()
+                    .filter_map(|t| self.positions.remove(t))
+                    .map(|p| p.cost)
+                    .sum()
+            }
+        };
+        self.book(market_id, at, amount - cost, dec!(0));
+    }
+
+    /// 未实现盈亏：持仓按买一价估值；没有买一价的持仓不计
+    fn unrealized<F>(&self, bid: &F, filter: impl Fn(&MarketKey) -> bool) -> Decimal
+    where
+        F: Fn(U256) -> Option<Decimal>,
+    {
+        self.positions
+            .iter()
+            .filter(|(_, p)| p.size > dec!(0))
+            .filter(|(t, _)| self.key_for_token(**t).map(&filter).unwrap_or(false))
+            .filter_map(|(t, p)| bid(*t).map(|price| p.size * price - p.cost))
+            .sum()
+    }
+}
+
+/// 盈亏引擎：可在多个任务间共享（Arc）
+#[derive(Default)]
+pub struct PnlEngine {
+    state: Mutex<PnlState>,
+}
+
+impl PnlEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 台账记录入口（由 TradeLedger 调用）
+    pub fn on_entry(&self, entry: &LedgerEntry, market: Option<&LedgerMarket>, at: DateTime<Utc>) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(market) = market {
+            state.register(market);
+        }
+        let market_id = market.map(|m| m.market_id).or(entry.market_id);
+        match entry.kind {
+            LedgerKind::Fill => state.on_fill(entry, market_id, at),
+            LedgerKind::Split => {
+                if let (Some(market_id), Some(amount)) = (market_id, entry.amount.or(entry.size)) {
+                    state.on_split(market_id, amount);
+                }
+            }
+            LedgerKind::Merge => {
+                if let (Some(market_id), Some(size)) = (market_id, entry.size.or(entry.amount)) {
+                    state.on_merge(market_id, size, at);
+                }
+            }
+            LedgerKind::Redeem => state.on_redeem(entry, market_id, at),
+            // 下单、撤单与收尾卖单本身不产生盈亏，收尾卖出按其成交记录计入
+            LedgerKind::OrderSubmitted | LedgerKind::Cancel | LedgerKind::WindDownSell => {}
+        }
+    }
+
+    /// 按 Data API 持仓补齐重启前的成本：引擎记录的份额少于实际持仓时，差额按 avg_price 计成本，
+    /// 未登记的市场按 slug 登记（币种与窗口时间戳），返回补齐的 token 数
+    pub fn seed_positions(&self, positions: &[Position]) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let mut seeded = 0;
+        for pos in positions.iter().filter(|p| p.size > dec!(0)) {
+            if !state.markets.contains_key(&pos.condition_id) {
+                let (symbol, window_ts) =
+                    parse_slug(&pos.slug).unwrap_or_else(|| (UNKNOWN_SYMBOL.to_string(), 0));
+                // merge 与 redeem 对两腿对称处理，YES/NO 顺序不影响成本
+                state.register(&LedgerMarket {
+                    market_id: pos.condition_id,
+                    slug: pos.slug.clone(),
+                    symbol,
+                    window_ts,
+                    yes_token_id: pos.asset,
+                    no_token_id: pos.opposite_asset,
+                });
+            }
+            let position = state.positions.entry(pos.asset).or_default();
+            let missing = pos.size - position.size;
+            if missing > dec!(0) {
+                position.size += missing;
+                position.cost += missing * pos.avg_price;
+                seeded += 1;
+            }
+        }
+        seeded
+    }
+
+    /// 某窗口的盈亏；bid 返回 token 当前买一价
+    pub fn window_summary<F>(&self, window_ts: i64, bid: F) -> PnlSummary
+    where
+        F: Fn(U256) -> Option<Decimal>,
+    {
+        let state = self.state.lock().unwrap();
+        let mut summary = PnlSummary::default();
+        if let Some(bucket) = state.by_window.get(&window_ts) {
+            summary.add_bucket(bucket);
+        }
+        summary.unrealized = state.unrealized(&bid, |k| k.window_ts == window_ts);
+        summary
+    }
+
+    /// 某 UTC 日按币种的盈亏（按币种排序）；未实现部分按当前持仓计入
+    pub fn day_by_symbol<F>(&self, day: NaiveDate, bid: F) -> Vec<(String, PnlSummary)>
+    where
+        F: Fn(U256) -> Option<Decimal>,
+    {
+        let state = self.state.lock().unwrap();
+        let mut by_symbol: HashMap<String, PnlSummary> = HashMap::new();
+        for ((d, symbol), bucket) in &state.by_day_symbol {
+            if *d == day {
+                by_symbol.entry(symbol.clone()).or_default().add_bucket(bucket);
+            }
+        }
+        let symbols: BTreeSet<String> = state.markets.values().map(|k| k.symbol.clone()).collect();
+        for symbol in symbols {
+            let unrealized = state.unrealized(&bid, |k| k.symbol == symbol);
+            if !unrealized.is_zero() {
+                by_symbol.entry(symbol).or_default().unrealized = unrealized;
+            }
+        }
+        let mut rows: Vec<(String, PnlSummary)> = by_symbol.into_iter().collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        rows
+    }
+
+    /// 某 UTC 日合计盈亏
+    pub fn day_summary<F>(&self, day: NaiveDate, bid: F) -> PnlSummary
+    where
+        F: Fn(U256) -> Option<Decimal>,
+    {
+        self.day_by_symbol(day, bid)
+            .into_iter()
+            .fold(PnlSummary::default(), |mut acc, (_, s)| {
+                acc.realized += s.realized;
+                acc.unrealized += s.unrealized;
+                acc.fees += s.fees;
+                acc
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market_id() -> B256 {
+        B256::repeat_byte(0xab)
+    }
+
+    fn api_position(asset: u64, opposite: u64, size: &str, avg_price: &str) -> Position {
+        serde_json::from_value(serde_json::json!({
+            "proxyWallet": "0x0000000000000000000000000000000000000001",
+            "asset": asset.to_string(),
+            "conditionId": format!("{:#x}", market_id()),
+            "size": size,
+            "avgPrice": avg_price,
+            "initialValue": 0, "currentValue": 0, "cashPnl": 0, "percentPnl": 0,
+            "totalBought": 0, "realizedPnl": 0, "percentRealizedPnl": 0, "curPrice": 0,
+            "redeemable": false, "mergeable": true,
+            "title": "", "slug": "btc-updown-5m-1770972300", "icon": "", "eventSlug": "",
+            "outcome": "Up", "outcomeIndex": 0, "oppositeOutcome": "Down",
+            "oppositeAsset": opposite.to_string(), "negativeRisk": false
+        }))
+        .unwrap()
+    }
+
+    fn merge(size: Decimal) -> LedgerEntry {
+        LedgerEntry::new(LedgerKind::Merge).with_market(market_id()).with_size(size)
+    }
+
+    #[test]
+    fn parses_symbol_and_window_from_slug() {
+        assert_eq!(parse_slug("btc-updown-5m-1770972300"), Some(("btc".to_string(), 1770972300)));
+        assert_eq!(parse_slug("garbage"), None);
+    }
+
+    #[test]
+    fn merge_of_seeded_positions_books_cost() {
+        let pnl = PnlEngine::new();
+        let seeded = pnl.seed_positions(&[
+            api_position(1, 2, "10", "0.45"),
+            api_position(2, 1, "10", "0.5"),
+        ]);
+        assert_eq!(seeded, 2);
+
+        let now = Utc::now();
+        pnl.on_entry(&merge(dec!(10)), None, now);
+        // 10 对 merge 得 10 USDC，成本 4.5 + 5
+        let day = pnl.day_summary(now.date_naive(), |_| None);
+        assert_eq!(day.realized, dec!(0.5));
+        assert_eq!(pnl.window_summary(1770972300, |_| None).realized, dec!(0.5));
+    }
+
+    #[test]
+    fn seeding_only_fills_the_unrecorded_part() {
+        let pnl = PnlEngine::new();
+        let market = LedgerMarket {
+            market_id: market_id(),
+            slug: "btc-updown-5m-1770972300".to_string(),
+            symbol: "btc".to_string(),
+            window_ts: 1770972300,
+            yes_token_id: U256::from(1),
+            no_token_id: U256::from(2),
+        };
+        let now = Utc::now();
+        // 本次运行已记录 4 份 @0.40（免手续费），Data API 显示共 10 份
+        let fill = LedgerEntry::new(LedgerKind::Fill)
+            .with_token(U256::from(1))
+            .with_trade(Side::Buy, dec!(0.4), dec!(4))
+            .with_amount(dec!(1.6));
+        pnl.on_entry(&fill, Some(&market), now);
+        assert_eq!(pnl.seed_positions(&[api_position(1, 2, "10", "0.5")]), 1);
+        assert_eq!(pnl.seed_positions(&[api_position(1, 2, "10", "0.5")]), 0);
+
+        let sell = LedgerEntry::new(LedgerKind::Fill)
+            .with_token(U256::from(1))
+            .with_trade(Side::Sell, dec!(0.6), dec!(10))
+            .with_amount(dec!(6));
+        pnl.on_entry(&sell, Some(&market), now);
+        // 成本 1.6 + 6 × 0.5 = 4.6
+        assert_eq!(pnl.day_summary(now.date_naive(), |_| None).realized, dec!(1.4));
+    }
+}
@@ -1,8 +1,6 @@
 //! 仓位平衡器：定时检查持仓和挂单，取消多余挂单以保持平衡
 
 use anyhow::Result;
-use polymarket_client_sdk::clob::Client;
-use polymarket_client_sdk::clob::types::request::OrdersRequest;
 use polymarket_client_sdk::clob::types::Side;
 use polymarket_client_sdk::types::{B256, Decimal, U256};
 use rust_decimal_macros::dec;
@@ -11,29 +9,28 @@ use tracing::{debug, error, info, warn};
 
 use super::positions::PositionTracker;
 use crate::config::Config as BotConfig;
-use poly_5min_bot::positions::get_positions;
+use crate::trading::Exchange;
+use crate::positions::get_positions;
 
 /// 仓位平衡器
 pub struct PositionBalancer {
-    clob_client: Client<polymarket_client_sdk::auth::state::Authenticated<polymarket_client_sdk::auth::Normal>>,
+    exchange: std::sync::Arc<dyn Exchange>,
     position_tracker: std::sync::Arc<PositionTracker>,
     threshold: Decimal,
     min_total: Decimal,
-    max_order_size: Decimal,
 }
 
 impl PositionBalancer {
     pub fn new(
-        clob_client: Client<polymarket_client_sdk::auth::state::Authenticated<polymarket_client_sdk::auth::Normal>>,
+        exchange: std::sync::Arc<dyn Exchange>,
         position_tracker: std::sync::Arc<PositionTracker>,
         config: &BotConfig,
     ) -> Self {
         Self {
-            clob_client,
+            exchange,
             position_tracker,
             threshold: Decimal::try_from(config.position_balance_threshold).unwrap_or(dec!(2.0)),
             min_total: Decimal::try_from(config.position_balance_min_total).unwrap_or(dec!(5.0)),
-            max_order_size: Decimal::try_from(config.max_order_size_usdc).unwrap_or(dec!(5.0)),
         }
     }
 
@@ -42,22 +39,8 @@ impl PositionBalancer {
         &self,
         market_map: &HashMap<B256, (U256, U256)>, // condition_id -> (yes_token_id, no_token_id)
     ) -> Result<()> {
-        // 获取所有活跃订单（处理分页）
-        let mut all_orders = Vec::new();
-        let mut cursor: Option<String> = None;
-        loop {
-            let page = self
-                .clob_client
-                .orders(&OrdersRequest::default(), cursor)
-                .await?;
-            
-            all_orders.extend(page.data);
-            
-            if page.next_cursor.is_empty() || page.next_cursor == "LTE=" {
-                break;
-            }
-            cursor = Some(page.next_cursor);
-        }
+        // 获取所有活跃订单（交易所实现负责分页）
+        let all_orders = self.exchange.open_orders().await?;
 
         if all_orders.is_empty() {
             debug!("没有活跃订单，跳过仓位平衡检查");
@@ -73,7 +56,6 @@ impl PositionBalancer {
         // 初始化市场数据
         for (condition_id, (yes_token, no_token)) in market_map {
             market_data.insert(*condition_id, MarketBalanceData {
-                condition_id: *condition_id,
                 yes_token_id: *yes_token,
                 no_token_id: *no_token,
                 yes_position: dec!(0),
@@ -178,7 +160,7 @@ impl PositionBalancer {
                     // 取消YES订单
                     if cancel_yes_count > 0 {
                         let yes_order_ids: Vec<&str> = cancel_yes_order_ids.iter().map(|s| s.as_str()).collect();
-                        if let Err(e) = self.clob_client.cancel_orders(&yes_order_ids).await {
+                        if let Err(e) = self.exchange.cancel_orders(&yes_order_ids).await {
                             error!(error = %e, "❌ 取消YES订单失败");
                         } else {
                             info!("✅ 已取消 {} 个YES订单", cancel_yes_count);
@@ -203,7 +185,7 @@ impl PositionBalancer {
                         
                         if !cancel_no_order_ids.is_empty() {
                             let cancel_no_order_ids_ref: Vec<&str> = cancel_no_order_ids.iter().map(|s| s.as_str()).collect();
-                            if let Err(e) = self.clob_client.cancel_orders(&cancel_no_order_ids_ref).await {
+                            if let Err(e) = self.exchange.cancel_orders(&cancel_no_order_ids_ref).await {
                                 error!(error = %e, "取消NO订单失败");
                             } else {
                                 info!("已取消 {} 个NO订单（累计 {} 份）", cancel_no_order_ids.len(), accumulated_size);
@@ -231,7 +213,7 @@ impl PositionBalancer {
                     // 取消NO订单
                     if cancel_no_count > 0 {
                         let no_order_ids: Vec<&str> = cancel_no_order_ids.iter().map(|s| s.as_str()).collect();
-                        if let Err(e) = self.clob_client.cancel_orders(&no_order_ids).await {
+                        if let Err(e) = self.exchange.cancel_orders(&no_order_ids).await {
                             error!(error = %e, "取消NO订单失败");
                         } else {
                             info!("已取消 {} 个NO订单", cancel_no_count);
@@ -256,7 +238,7 @@ impl PositionBalancer {
                         
                         if !cancel_yes_order_ids.is_empty() {
                             let cancel_yes_order_ids_ref: Vec<&str> = cancel_yes_order_ids.iter().map(|s| s.as_str()).collect();
-                            if let Err(e) = self.clob_client.cancel_orders(&cancel_yes_order_ids_ref).await {
+                            if let Err(e) = self.exchange.cancel_orders(&cancel_yes_order_ids_ref).await {
                                 error!(error = %e, "❌ 取消YES订单失败");
                             } else {
                                 info!("✅ 已取消 {} 个YES订单（累计 {} 份）", cancel_yes_order_ids.len(), accumulated_size);
@@ -293,7 +275,7 @@ impl PositionBalancer {
                 info!("⚠️ YES挂单过多，取消 {} 个YES订单", cancel_order_ids.len());
 
                 let cancel_order_ids_ref: Vec<&str> = cancel_order_ids.iter().map(|s| s.as_str()).collect();
-                if let Err(e) = self.clob_client.cancel_orders(&cancel_order_ids_ref).await {
+                if let Err(e) = self.exchange.cancel_orders(&cancel_order_ids_ref).await {
                     error!(error = %e, "❌ 取消YES订单失败");
                 } else {
                     info!("✅ 已取消 {} 个YES订单", cancel_order_ids.len());
@@ -321,7 +303,7 @@ impl PositionBalancer {
                 info!("NO挂单过多，取消 {} 个NO订单", cancel_order_ids.len());
 
                 let cancel_order_ids_ref: Vec<&str> = cancel_order_ids.iter().map(|s| s.as_str()).collect();
-                if let Err(e) = self.clob_client.cancel_orders(&cancel_order_ids_ref).await {
+                if let Err(e) = self.exchange.cancel_orders(&cancel_order_ids_ref).await {
                     error!(error = %e, "取消NO订单失败");
                 } else {
                     info!("已取消 {} 个NO订单", cancel_order_ids.len());
@@ -355,7 +337,6 @@ impl PositionBalancer {
 
 /// 市场平衡数据
 struct MarketBalanceData {
-    condition_id: B256,
     yes_token_id: U256,
     no_token_id: U256,
     yes_position: Decimal,
@@ -1,15 +1,59 @@
 use anyhow::Result;
 use dashmap::DashMap;
-use polymarket_client_sdk::types::{Decimal, U256};
+use polymarket_client_sdk::clob::types::Side;
+use polymarket_client_sdk::types::{B256, Decimal, U256};
 use rust_decimal_macros::dec;
-use tracing::{debug, info, trace};
+use std::collections::HashMap;
+use tracing::{info, trace};
 
-use poly_5min_bot::positions::{get_positions, Position};
+use crate::config::Config;
+use crate::positions::{get_positions, Position};
+use crate::trading::exchange::OpenOrder;
+
+/// 细分敞口上限（USD），0 表示不限制；全局上限仍为 PositionTracker::max_exposure
+#[derive(Debug, Clone, Default)]
+pub struct ExposureLimits {
+    pub per_symbol: Decimal,
+    pub by_symbol: HashMap<String, Decimal>, // 按币种覆盖 per_symbol
+    pub per_market: Decimal,
+    pub max_unhedged: Decimal, // 未对冲单腿持仓（两腿数量差按成本计）
+}
+
+impl ExposureLimits {
+    pub fn from_config(config: &Config) -> Self {
+        let to_decimal = |v: f64| Decimal::try_from(v).unwrap_or(dec!(0));
+        Self {
+            per_symbol: to_decimal(config.risk_max_exposure_per_symbol_usdc),
+            by_symbol: config
+                .risk_max_exposure_by_symbol
+                .iter()
+                .map(|(symbol, limit)| (symbol.clone(), to_decimal(*limit)))
+                .collect(),
+            per_market: to_decimal(config.risk_max_exposure_per_market_usdc),
+            max_unhedged: to_decimal(config.risk_max_unhedged_usdc),
+        }
+    }
+
+    pub fn symbol_limit(&self, symbol: &str) -> Decimal {
+        self.by_symbol.get(symbol).copied().unwrap_or(self.per_symbol)
+    }
+}
+
+/// 已登记市场：币种与两腿 token
+#[derive(Debug, Clone)]
+struct TrackedMarket {
+    symbol: String,
+    yes_token_id: U256,
+    no_token_id: U256,
+}
 
 pub struct PositionTracker {
     positions: DashMap<U256, Decimal>, // token_id -> 数量（正数=持有多头，负数=持有空头）
     exposure_costs: DashMap<U256, Decimal>, // token_id -> 成本（USD），用于跟踪风险敞口
     max_exposure: Decimal,
+    limits: ExposureLimits,
+    markets: DashMap<B256, TrackedMarket>, // market_id -> 币种与 token，用于按币种 / 市场汇总敞口
+    token_markets: DashMap<U256, B256>,    // token_id -> market_id
 }
 
 impl PositionTracker {
@@ -18,6 +62,43 @@ impl PositionTracker {
             positions: DashMap::new(),
             exposure_costs: DashMap::new(),
             max_exposure,
+            limits: ExposureLimits::default(),
+            markets: DashMap::new(),
+            token_markets: DashMap::new(),
+        }
+    }
+
+    /// 设置按币种、按市场与未对冲持仓的敞口上限
+    pub fn with_limits(mut self, limits: ExposureLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &ExposureLimits {
+        &self.limits
+    }
+
+    /// 登记市场所属币种与两腿 token（每个窗口获取市场后调用，跨窗口保留）
+    pub fn register_market(&self, market_id: B256, symbol: &str, yes_token: U256, no_token: U256) {
+        self.markets.insert(
+            market_id,
+            TrackedMarket {
+                symbol: symbol.to_lowercase(),
+                yes_token_id: yes_token,
+                no_token_id: no_token,
+            },
+        );
+        self.token_markets.insert(yes_token, market_id);
+        self.token_markets.insert(no_token, market_id);
+    }
+
+    /// 市场已结算（持仓可 redeem 或已 redeem）：注销市场并清除两腿持仓与敞口，不再计入各项上限
+    pub fn unregister_market(&self, market_id: B256) {
+        let Some((_, market)) = self.markets.remove(&market_id) else { return };
+        for token_id in [market.yes_token_id, market.no_token_id] {
+            self.token_markets.remove(&token_id);
+            self.positions.remove(&token_id);
+            self.exposure_costs.remove(&token_id);
         }
     }
 
@@ -126,15 +207,59 @@ impl PositionTracker {
         trace!("update_exposure_cost: 完成");
     }
 
+    /// 撤回执行前按 price * size 预先计入、但最终未成交的敞口（不按持仓比例扣减）
+    pub fn revert_exposure_cost(&self, token_id: U256, price: Decimal, size: Decimal) {
+        let cost = price * size;
+        if cost <= dec!(0) {
+            return;
+        }
+        let remove = match self.exposure_costs.get_mut(&token_id) {
+            Some(mut entry) => {
+                *entry = (*entry - cost).max(dec!(0));
+                *entry < dec!(0.01)
+            }
+            None => false,
+        };
+        if remove {
+            self.exposure_costs.remove(&token_id);
+        }
+    }
+
     /// 获取最大风险敞口限制
     pub fn max_exposure(&self) -> Decimal {
         self.max_exposure
     }
 
-    /// 重置风险敞口（新一轮开始时调用，清空成本缓存，使本轮从 0 敞口重新累计）
-    pub fn reset_exposure(&self) {
+    /// 按实际持仓与挂单重建风险敞口（新一轮开始时调用，上一轮未 merge 或单边的持仓继续计入）
+    /// - 持仓：按 Data API 返回的均价计成本，尚未结算的旧市场同样计入；已可 redeem 的持仓不再计入
+    /// - 挂单：未成交的买单按 价格 * 剩余份额 计入
+    ///
+    /// 返回重建后的总敞口
+    pub fn rebuild_exposure(&self, positions: &[Position], open_orders: &[OpenOrder]) -> Decimal {
         self.exposure_costs.clear();
-        info!("🔄 风险敞口已重置（新一轮）");
+
+        for pos in positions.iter().filter(|p| p.size > dec!(0) && !p.redeemable) {
+            // 旧市场不在本轮市场列表中，按持仓补登记，使其计入按币种 / 市场的敞口
+            if !self.markets.contains_key(&pos.condition_id) {
+                let (yes_token, no_token) = if pos.outcome_index == 0 {
+                    (pos.asset, pos.opposite_asset)
+                } else {
+                    (pos.opposite_asset, pos.asset)
+                };
+                let symbol = pos.slug.split('-').next().unwrap_or("");
+                self.register_market(pos.condition_id, symbol, yes_token, no_token);
+            }
+            *self.exposure_costs.entry(pos.asset).or_insert(dec!(0)) += pos.size * pos.avg_price;
+        }
+
+        for order in open_orders.iter().filter(|o| matches!(o.side, Side::Buy)) {
+            let remaining = order.original_size - order.size_matched;
+            if remaining > dec!(0) {
+                *self.exposure_costs.entry(order.asset_id).or_insert(dec!(0)) += order.price * remaining;
+            }
+        }
+
+        self.calculate_exposure()
     }
 
     pub fn get_position(&self, token_id: U256) -> Decimal {
@@ -155,8 +280,7 @@ impl PositionTracker {
         }
 
         // 不平衡度 = abs(yes - no) / (yes + no)
-        let imbalance = (yes_pos - no_pos).abs() / total;
-        imbalance
+        (yes_pos - no_pos).abs() / total
     }
 
     /// 计算当前总风险敞口（USD）
@@ -184,6 +308,119 @@ impl PositionTracker {
         (current_exposure + new_order_cost) > self.max_exposure
     }
 
+    fn exposure_cost(&self, token_id: U256) -> Decimal {
+        self.exposure_costs
+            .get(&token_id)
+            .map(|v| *v.value())
+            .unwrap_or(dec!(0))
+    }
+
+    /// 某个市场（两腿合计）的风险敞口（USD）
+    pub fn market_exposure(&self, yes_token: U256, no_token: U256) -> Decimal {
+        self.exposure_cost(yes_token) + self.exposure_cost(no_token)
+    }
+
+    /// 某个币种所有已登记市场的风险敞口（USD）
+    pub fn symbol_exposure(&self, symbol: &str) -> Decimal {
+        // 先收集成本再查市场归属，避免同时持有两个 map 的锁
+        let costs: Vec<(U256, Decimal)> = self.exposure_costs
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect();
+        costs
+            .into_iter()
+            .filter(|(token_id, _)| {
+                let market_id = self.token_markets.get(token_id).map(|m| *m.value());
+                market_id
+                    .and_then(|m| self.markets.get(&m).map(|tm| tm.symbol == symbol))
+                    .unwrap_or(false)
+            })
+            .map(|(_, cost)| cost)
+            .sum()
+    }
+
+    /// 单个市场未对冲的单腿持仓（USD）：两腿数量差按多出一腿的平均成本计，
+    /// 没有成本记录（如同步自 API 的持仓）时按每份 1 USD（最大可能损失）计
+    pub fn market_unhedged(&self, yes_token: U256, no_token: U256) -> Decimal {
+        let (yes_pos, no_pos) = self.get_pair_positions(yes_token, no_token);
+        let excess = (yes_pos - no_pos).abs();
+        let (token, pos) = if yes_pos >= no_pos { (yes_token, yes_pos) } else { (no_token, no_pos) };
+        if excess <= dec!(0) {
+            return dec!(0);
+        }
+        let cost = self.exposure_cost(token);
+        let unit_cost = if cost > dec!(0) && pos > dec!(0) {
+            (cost / pos).min(dec!(1))
+        } else {
+            dec!(1)
+        };
+        excess * unit_cost
+    }
+
+    /// 所有已登记市场未对冲单腿持仓合计（USD）
+    pub fn unhedged_exposure(&self) -> Decimal {
+        let markets: Vec<(U256, U256)> = self.markets
+            .iter()
+            .map(|entry| (entry.yes_token_id, entry.no_token_id))
+            .collect();
+        markets
+            .into_iter()
+            .map(|(yes, no)| self.market_unhedged(yes, no))
+            .sum()
+    }
+
+    /// 检查新订单对是否会超过按币种、按市场或未对冲持仓的上限（全局上限见 would_exceed_limit）
+    /// 未对冲部分按最坏情况计：只有成本较高的一腿成交
+    /// 返回 Err 时附带原因，供日志输出
+    pub fn check_exposure_caps(
+        &self,
+        market_id: B256,
+        yes_token: U256,
+        no_token: U256,
+        yes_cost: Decimal,
+        no_cost: Decimal,
+    ) -> Result<(), String> {
+        let order_cost = yes_cost + no_cost;
+        let limits = &self.limits;
+
+        if limits.per_market > dec!(0) {
+            let current = self.market_exposure(yes_token, no_token);
+            if current + order_cost > limits.per_market {
+                return Err(format!(
+                    "市场敞口超限 | 当前:{:.2} USD | 订单成本:{:.2} USD | 限制:{:.2} USD",
+                    current, order_cost, limits.per_market
+                ));
+            }
+        }
+
+        let symbol = self.markets.get(&market_id).map(|m| m.symbol.clone());
+        if let Some(symbol) = symbol {
+            let limit = limits.symbol_limit(&symbol);
+            if limit > dec!(0) {
+                let current = self.symbol_exposure(&symbol);
+                if current + order_cost > limit {
+                    return Err(format!(
+                        "币种 {} 敞口超限 | 当前:{:.2} USD | 订单成本:{:.2} USD | 限制:{:.2} USD",
+                        symbol.to_uppercase(), current, order_cost, limit
+                    ));
+                }
+            }
+        }
+
+        if limits.max_unhedged > dec!(0) {
+            let current = self.unhedged_exposure();
+            let worst_leg = yes_cost.max(no_cost);
+            if current + worst_leg > limits.max_unhedged {
+                return Err(format!(
+                    "未对冲持仓超限 | 当前:{:.2} USD | 单腿最坏:{:.2} USD | 限制:{:.2} USD",
+                    current, worst_leg, limits.max_unhedged
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     /// 获取YES和NO的持仓
     pub fn get_pair_positions(&self, yes_token: U256, no_token: U256) -> (Decimal, Decimal) {
         (self.get_position(yes_token), self.get_position(no_token))
@@ -193,19 +430,26 @@ impl PositionTracker {
     /// 这个方法会从API获取最新持仓，清空并重建本地positions map
     /// 用于定时同步任务，确保本地缓存与链上实际持仓一致
     pub async fn sync_from_api(&self) -> Result<Vec<Position>> {
-        use std::collections::HashMap;
+        use std::collections::{HashMap, HashSet};
         use polymarket_client_sdk::types::B256;
         
         let positions = get_positions().await?;
         
-        // 清空现有持仓（敞口仅由「执行套利」时增加、Merge 时扣减，不从 API 回填）
+        // 清空现有持仓（敞口在执行套利时增加、Merge 时扣减，仅在新一轮开始时由 rebuild_exposure 按持仓重建）
         self.positions.clear();
         
         // 从API获取的持仓更新到本地缓存
         let mut updated_count = 0;
         let mut valid_positions = Vec::new();
+        let mut resolved = HashSet::new();
         
         for pos in positions {
+            if pos.redeemable {
+                // 已结算的市场只等待 redeem，不再计入持仓与敞口
+                self.exposure_costs.remove(&pos.asset);
+                resolved.insert(pos.condition_id);
+                continue;
+            }
             if pos.size > dec!(0) {
                 // Position.asset 就是 token_id
                 self.positions.insert(pos.asset, pos.size);
@@ -214,6 +458,10 @@ impl PositionTracker {
             }
         }
         
+        for market_id in resolved {
+            self.unregister_market(market_id);
+        }
+        
         // 按市场分组打印持仓
         if !valid_positions.is_empty() {
             let mut by_market: HashMap<B256, Vec<&Position>> = HashMap::new();
@@ -224,7 +472,7 @@ impl PositionTracker {
             info!("📊 持仓同步完成 | 共 {} 个持仓，{} 个市场", updated_count, by_market.len());
             
             // 按市场分组打印，每个市场一行
-            for (_condition_id, market_positions) in by_market.iter() {
+            for market_positions in by_market.values() {
                 let mut yes_pos = dec!(0);
                 let mut no_pos = dec!(0);
                 let mut market_title = "";
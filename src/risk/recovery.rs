@@ -1,15 +1,21 @@
 use anyhow::Result;
-use polymarket_client_sdk::types::{Decimal, U256};
+use polymarket_client_sdk::types::{B256, Decimal, U256};
 use rust_decimal_macros::dec;
-use tracing::debug;
+use tracing::{debug, info, warn};
 
 use super::manager::OrderPair;
 use super::positions::PositionTracker;
+use crate::config::Config;
+use crate::trading::FeeSchedule;
 
 #[derive(Debug, Clone)]
 pub enum RecoveryAction {
     None,
-    SellExcess { token_id: String, amount: Decimal },
+    SellExcess {
+        token_id: U256,
+        amount: Decimal, // 可卖出份额（已扣买入手续费）
+        pair_id: String,
+    },
     MonitorForExit {
         token_id: U256,
         opposite_token_id: U256, // 对立边的token_id（用于计算差值）
@@ -20,29 +26,223 @@ pub enum RecoveryAction {
         pair_id: String,
         market_display: String, // 市场显示名称（例如"btc预测市场"）
     },
+    ChaseMissingLeg {
+        token_id: U256,        // 未成交（或成交不足）的一腿
+        filled_token_id: U256, // 已成交的一腿，补单截止仍未补齐时卖出
+        filled_price: Decimal, // 已成交一腿的买入价
+        amount: Decimal,       // 需补买的份额
+        max_price: Decimal,    // 补单价格上限：已成交价 + 补单价 + 每对手续费损耗 <= 1 + 容差
+        pair_id: String,
+        market_id: B256,
+    },
     ManualIntervention { reason: String },
 }
 
+/// 订单对两腿成交不平衡：由 RecoveryStrategy 判定后交给恢复策略决定处理方式
+#[derive(Debug, Clone)]
+pub struct LegImbalance {
+    pub pair_id: String,
+    pub market_id: B256,
+    pub excess_token_id: U256,  // 成交较多的一腿
+    pub missing_token_id: U256, // 成交较少的一腿
+    pub amount: Decimal,        // 两腿成交差（份）
+    pub excess_price: Decimal,  // 成交较多一腿的买入限价
+}
+
+/// 可插拔的恢复策略（RECOVERY_POLICY 选择）
+pub trait RecoveryPolicy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// 两腿成交不平衡且成交不足的一腿已不再挂单时调用
+    fn recover(&self, imbalance: &LegImbalance) -> RecoveryAction;
+}
+
+/// none：不处理，单腿持仓留给定时 merge 与收尾
+pub struct NoopPolicy;
+
+impl RecoveryPolicy for NoopPolicy {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn recover(&self, imbalance: &LegImbalance) -> RecoveryAction {
+        debug!(
+            pair_id = %imbalance.pair_id,
+            amount = %imbalance.amount,
+            "两腿成交不平衡，恢复策略为 none，不处理"
+        );
+        RecoveryAction::None
+    }
+}
+
+/// chase：在价格上限内补买缺失的一腿（由 LegChaser 跟踪订单簿执行），使订单对仍不亏损
+pub struct ChasePolicy {
+    tolerance: Decimal,
+    fees: FeeSchedule,
+}
+
+impl ChasePolicy {
+    /// 补单价格上限：从不计手续费的上限 1 + 容差 - 已成交价起按 0.01 逐档下调，
+    /// 直到两腿价格加上每对的 taker 手续费损耗（两腿按份额扣费，merge 对数取决于扣得多的一腿）不超过 1 + 容差
+    fn max_price(&self, imbalance: &LegImbalance) -> Decimal {
+        let excess_model = self.fees.for_token(imbalance.excess_token_id);
+        let missing_model = self.fees.for_token(imbalance.missing_token_id);
+        let excess_fee = excess_model.fee_shares(imbalance.excess_price, dec!(1));
+        let limit = dec!(1) + self.tolerance;
+        let mut price = ((limit - imbalance.excess_price) * dec!(100)).floor() / dec!(100);
+        price = price.min(dec!(0.99));
+        while price >= dec!(0.01) {
+            let fee = excess_model.pair_fee_shares(excess_fee, missing_model.fee_shares(price, dec!(1)));
+            if imbalance.excess_price + price + fee <= limit {
+                break;
+            }
+            price -= dec!(0.01);
+        }
+        price
+    }
+}
+
+impl RecoveryPolicy for ChasePolicy {
+    fn name(&self) -> &'static str {
+        "chase"
+    }
+
+    fn recover(&self, imbalance: &LegImbalance) -> RecoveryAction {
+        let max_price = self.max_price(imbalance);
+        if max_price < dec!(0.01) {
+            warn!(
+                pair_id = %imbalance.pair_id,
+                filled_price = %imbalance.excess_price,
+                "已成交一腿价格过高（含手续费），无法补单"
+            );
+            return RecoveryAction::None;
+        }
+        RecoveryAction::ChaseMissingLeg {
+            token_id: imbalance.missing_token_id,
+            filled_token_id: imbalance.excess_token_id,
+            filled_price: imbalance.excess_price,
+            amount: imbalance.amount,
+            max_price,
+            pair_id: imbalance.pair_id.clone(),
+            market_id: imbalance.market_id,
+        }
+    }
+}
+
+/// monitor：交给 HedgeMonitor 按止盈 / 止损卖出多出的一腿
+pub struct MonitorPolicy {
+    take_profit_pct: Decimal,
+    stop_loss_pct: Decimal,
+}
+
+impl RecoveryPolicy for MonitorPolicy {
+    fn name(&self) -> &'static str {
+        "monitor"
+    }
+
+    fn recover(&self, imbalance: &LegImbalance) -> RecoveryAction {
+        let market = format!("{:#x}", imbalance.market_id);
+        RecoveryAction::MonitorForExit {
+            token_id: imbalance.excess_token_id,
+            opposite_token_id: imbalance.missing_token_id,
+            amount: imbalance.amount,
+            entry_price: imbalance.excess_price,
+            take_profit_pct: self.take_profit_pct,
+            stop_loss_pct: self.stop_loss_pct,
+            pair_id: imbalance.pair_id.clone(),
+            market_display: format!("{}…", &market[..10.min(market.len())]),
+        }
+    }
+}
+
+/// sell：立即卖出多出的一腿
+pub struct SellExcessPolicy {
+    fees: FeeSchedule,
+}
+
+impl RecoveryPolicy for SellExcessPolicy {
+    fn name(&self) -> &'static str {
+        "sell"
+    }
+
+    fn recover(&self, imbalance: &LegImbalance) -> RecoveryAction {
+        // 买入手续费以份额扣除，按到手份额卖出
+        let amount = self.fees.for_token(imbalance.excess_token_id).sellable_size(imbalance.excess_price, imbalance.amount);
+        if amount <= dec!(0) {
+            return RecoveryAction::None;
+        }
+        RecoveryAction::SellExcess {
+            token_id: imbalance.excess_token_id,
+            amount,
+            pair_id: imbalance.pair_id.clone(),
+        }
+    }
+}
+
+/// 按 RECOVERY_POLICY 创建恢复策略；未知取值按 none 处理
+pub fn policy_from_config(config: &Config, fees: FeeSchedule) -> Box<dyn RecoveryPolicy> {
+    match config.recovery_policy.as_str() {
+        "none" => Box::new(NoopPolicy),
+        "chase" => Box::new(ChasePolicy {
+            tolerance: Decimal::try_from(config.recovery_chase_tolerance).unwrap_or(dec!(0)),
+            fees,
+        }),
+        "monitor" => Box::new(MonitorPolicy {
+            take_profit_pct: Decimal::try_from(config.hedge_take_profit_pct).unwrap_or(dec!(0.05)), // 默认5%止盈
+            stop_loss_pct: Decimal::try_from(config.hedge_stop_loss_pct).unwrap_or(dec!(0.05)),     // 默认5%止损
+        }),
+        "sell" => Box::new(SellExcessPolicy {
+            fees,
+        }),
+        other => {
+            warn!(policy = other, "未知的 RECOVERY_POLICY，按 none 处理");
+            Box::new(NoopPolicy)
+        }
+    }
+}
+
 pub struct RecoveryStrategy {
     imbalance_threshold: Decimal,
-    take_profit_pct: Decimal, // 止盈百分比
-    stop_loss_pct: Decimal,   // 止损百分比
+    policy: Box<dyn RecoveryPolicy>,
 }
 
 impl RecoveryStrategy {
-    pub fn new(imbalance_threshold: f64, take_profit_pct: f64, stop_loss_pct: f64) -> Self {
+    pub fn new(config: &Config, fees: FeeSchedule) -> Self {
+        let policy = policy_from_config(config, fees);
+        info!(policy = policy.name(), "恢复策略");
         Self {
-            imbalance_threshold: Decimal::try_from(imbalance_threshold)
+            imbalance_threshold: Decimal::try_from(config.risk_imbalance_threshold)
                 .unwrap_or(dec!(0.1)),
-            take_profit_pct: Decimal::try_from(take_profit_pct)
-                .unwrap_or(dec!(0.05)), // 默认5%止盈
-            stop_loss_pct: Decimal::try_from(stop_loss_pct)
-                .unwrap_or(dec!(0.05)), // 默认5%止损
+            policy,
+        }
+    }
+
+    /// 成交较少的一腿仍在挂单时先等待成交，不交给恢复策略
+    fn imbalance(pair: &OrderPair) -> Option<LegImbalance> {
+        let (excess_token_id, missing_token_id, amount, excess_price, missing_resting) =
+            if pair.yes_filled > pair.no_filled {
+                (pair.yes_token_id, pair.no_token_id, pair.yes_filled - pair.no_filled, pair.yes_price, pair.no_resting)
+            } else {
+                (pair.no_token_id, pair.yes_token_id, pair.no_filled - pair.yes_filled, pair.no_price, pair.yes_resting)
+            };
+        if amount <= dec!(0) {
+            return None;
+        }
+        if missing_resting {
+            debug!(pair_id = %pair.pair_id, amount = %amount, "成交不足的一腿仍在挂单，等待成交");
+            return None;
         }
+        Some(LegImbalance {
+            pair_id: pair.pair_id.clone(),
+            market_id: pair.market_id,
+            excess_token_id,
+            missing_token_id,
+            amount,
+            excess_price,
+        })
     }
 
-    /// 处理部分成交（GTC订单的情况）
-    /// 对冲策略已暂时关闭，部分成交不平衡不做任何处理
+    /// 处理部分成交（GTC订单的情况）：不平衡比例超过阈值时交给恢复策略
     pub async fn handle_partial_fill(
         &self,
         pair: &OrderPair,
@@ -59,106 +259,153 @@ impl RecoveryStrategy {
             dec!(0)
         };
 
-        // 对冲策略已关闭，部分成交不平衡不做任何处理
-        if imbalance_ratio > self.imbalance_threshold {
-            let (side, amount) = if pair.yes_filled > pair.no_filled {
-                // YES成交多
-                ("YES", pair.yes_filled - pair.no_filled)
-            } else {
-                // NO成交多
-                ("NO", pair.no_filled - pair.yes_filled)
-            };
-
-            debug!(
-                pair_id = %pair.pair_id,
-                side = side,
-                imbalance_amount = %amount,
-                imbalance_ratio = %imbalance_ratio,
-                "部分成交不平衡，对冲已关，不处理"
-            );
+        // 不平衡在可接受范围内
+        if imbalance_ratio <= self.imbalance_threshold {
+            return Ok(RecoveryAction::None);
         }
 
-        // 返回None，不做任何对冲处理
-        Ok(RecoveryAction::None)
-        
-        // 旧代码：如果不平衡超过阈值，需要对冲
-        // if imbalance_ratio > self.imbalance_threshold {
-        //     let (token_to_sell, amount) = if pair.yes_filled > pair.no_filled {
-        //         // YES成交多，卖出多余的YES
-        //         (pair.yes_token_id, pair.yes_filled - pair.no_filled)
-        //     } else {
-        //         // NO成交多，卖出多余的NO
-        //         (pair.no_token_id, pair.no_filled - pair.yes_filled)
-        //     };
-        // 
-        //     info!(
-        //         pair_id = %pair.pair_id,
-        //         token_id = %token_to_sell,
-        //         amount = %amount,
-        //         imbalance_ratio = %imbalance_ratio,
-        //         "部分成交不平衡，执行对冲"
-        //     );
-        // 
-        //     return Ok(RecoveryAction::SellExcess {
-        //         token_id: token_to_sell.to_string(),
-        //         amount,
-        //     });
-        // }
-        // 
-        // // 不平衡在可接受范围内
-        // Ok(RecoveryAction::None)
-    }
-
-    /// 处理只购买一边成功（GTC订单的情况）
-    /// 对冲策略已暂时关闭，单边成交不做任何处理
+        let Some(imbalance) = Self::imbalance(pair) else {
+            return Ok(RecoveryAction::None);
+        };
+        info!(
+            pair_id = %pair.pair_id,
+            imbalance_amount = %imbalance.amount,
+            imbalance_ratio = %imbalance_ratio,
+            policy = self.policy.name(),
+            "部分成交不平衡，执行恢复策略"
+        );
+        Ok(self.policy.recover(&imbalance))
+    }
+
+    /// 处理只购买一边成功（GTC订单的情况）：另一腿不再挂单后交给恢复策略
     pub async fn handle_one_sided_fill(
         &self,
         pair: &OrderPair,
         _position_tracker: &PositionTracker,
     ) -> Result<RecoveryAction> {
-        // 确定哪个订单成功，哪个失败
-        let (side, filled_amount) =
-            if pair.yes_filled > dec!(0) && pair.no_filled == dec!(0) {
-                // YES成功，NO失败（可能还在挂单）
-                ("YES", pair.yes_filled)
-            } else if pair.no_filled > dec!(0) && pair.yes_filled == dec!(0) {
-                // NO成功，YES失败（可能还在挂单）
-                ("NO", pair.no_filled)
-            } else {
-                return Ok(RecoveryAction::None);
-            };
-
-        // 对冲策略已关闭，单边成交不做任何处理（详情由 executor 的 ⚠️ 单边成交 已记录）
-        debug!(
-            "单边成交 | {} 成交 {} 份 | 对冲已关，不处理",
-            side, filled_amount
+        if pair.yes_filled > dec!(0) && pair.no_filled > dec!(0) {
+            return Ok(RecoveryAction::None);
+        }
+        let Some(imbalance) = Self::imbalance(pair) else {
+            return Ok(RecoveryAction::None);
+        };
+        info!(
+            pair_id = %pair.pair_id,
+            filled = %imbalance.amount,
+            policy = self.policy.name(),
+            "单边成交，执行恢复策略"
         );
+        Ok(self.policy.recover(&imbalance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::risk::lifecycle::Lifecycle;
+    use crate::risk::manager::PairStatus;
+    use crate::trading::FeeModel;
+    use polymarket_client_sdk::clob::types::Side;
+
+    fn chase(tolerance: Decimal, fee: FeeModel) -> ChasePolicy {
+        ChasePolicy {
+            tolerance,
+            fees: FeeSchedule::new(fee),
+        }
+    }
+
+    fn imbalance(excess_price: Decimal) -> LegImbalance {
+        LegImbalance {
+            pair_id: "p1".to_string(),
+            market_id: B256::ZERO,
+            excess_token_id: U256::from(1),
+            missing_token_id: U256::from(2),
+            amount: dec!(10),
+            excess_price,
+        }
+    }
+
+    fn pair(yes_filled: Decimal, no_filled: Decimal, yes_resting: bool, no_resting: bool) -> OrderPair {
+        OrderPair {
+            pair_id: "p1".to_string(),
+            market_id: B256::ZERO,
+            side: Side::Buy,
+            yes_order_id: "y".to_string(),
+            no_order_id: "n".to_string(),
+            yes_token_id: U256::from(1),
+            no_token_id: U256::from(2),
+            yes_size: dec!(10),
+            no_size: dec!(10),
+            yes_price: dec!(0.45),
+            no_price: dec!(0.52),
+            yes_filled,
+            no_filled,
+            status: PairStatus::PartiallyFilled,
+            created_at: chrono::Utc::now(),
+            yes_resting,
+            no_resting,
+            expires_at: None,
+            lifecycle: Lifecycle::new(),
+            outcome_reported: false,
+            recovery_dispatched: false,
+        }
+    }
+
+    #[test]
+    fn max_price_without_fees_is_one_plus_tolerance_minus_filled() {
+        let free = FeeModel::free();
+        assert_eq!(chase(dec!(0), free).max_price(&imbalance(dec!(0.45))), dec!(0.55));
+        assert_eq!(chase(dec!(0.02), free).max_price(&imbalance(dec!(0.45))), dec!(0.57));
+        // 向下取整到 0.01 档，且不超过 0.99
+        assert_eq!(chase(dec!(0), free).max_price(&imbalance(dec!(0.455))), dec!(0.54));
+        assert_eq!(chase(dec!(0), free).max_price(&imbalance(dec!(0.005))), dec!(0.99));
+    }
+
+    #[test]
+    fn max_price_subtracts_larger_leg_fee_not_the_sum() {
+        let fee = FeeModel::new(0.25, 2.0);
+        let policy = chase(dec!(0), fee);
+        let price = policy.max_price(&imbalance(dec!(0.45)));
+        // 两腿各约 0.0153 / 0.0155 份手续费：取较大一腿时 0.53 可行，按两腿之和则只能到 0.52
+        assert_eq!(price, dec!(0.53));
+
+        let pair_fee = |p: Decimal| fee.pair_fee_shares(fee.fee_shares(dec!(0.45), dec!(1)), fee.fee_shares(p, dec!(1)));
+        assert!(dec!(0.45) + price + pair_fee(price) <= dec!(1));
+        assert!(dec!(0.45) + price + dec!(0.01) + pair_fee(price + dec!(0.01)) > dec!(1));
+    }
+
+    #[test]
+    fn chase_gives_up_when_filled_leg_is_too_expensive() {
+        let policy = chase(dec!(0), FeeModel::new(0.25, 2.0));
+        assert!(matches!(policy.recover(&imbalance(dec!(0.99))), RecoveryAction::None));
+        match policy.recover(&imbalance(dec!(0.45))) {
+            RecoveryAction::ChaseMissingLeg { token_id, filled_token_id, max_price, .. } => {
+                assert_eq!(token_id, U256::from(2));
+                assert_eq!(filled_token_id, U256::from(1));
+                assert_eq!(max_price, dec!(0.53));
+            }
+            other => panic!("expected chase, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn imbalance_waits_while_missing_leg_is_resting() {
+        // YES 多成交、NO 仍在挂单：等待
+        assert!(RecoveryStrategy::imbalance(&pair(dec!(10), dec!(0), false, true)).is_none());
+        // 多成交一腿仍挂单不影响判定
+        let imb = RecoveryStrategy::imbalance(&pair(dec!(10), dec!(4), true, false)).unwrap();
+        assert_eq!(imb.excess_token_id, U256::from(1));
+        assert_eq!(imb.missing_token_id, U256::from(2));
+        assert_eq!(imb.amount, dec!(6));
+        assert_eq!(imb.excess_price, dec!(0.45));
+
+        // NO 多成交、YES 已不挂单
+        let imb = RecoveryStrategy::imbalance(&pair(dec!(0), dec!(3), false, false)).unwrap();
+        assert_eq!(imb.excess_token_id, U256::from(2));
+        assert_eq!(imb.excess_price, dec!(0.52));
+        assert!(RecoveryStrategy::imbalance(&pair(dec!(0), dec!(3), true, false)).is_none());
 
-        // 返回None，不做任何对冲处理
-        Ok(RecoveryAction::None)
-        
-        // 旧代码：对冲策略：监测买一价，达到止盈止损时卖出
-        // // 确定对立边的token_id
-        // let success_token = if pair.yes_filled > dec!(0) {
-        //     pair.yes_token_id
-        // } else {
-        //     pair.no_token_id
-        // };
-        // let opposite_token = if success_token == pair.yes_token_id {
-        //     pair.no_token_id
-        // } else {
-        //     pair.yes_token_id
-        // };
-        // 
-        // Ok(RecoveryAction::MonitorForExit {
-        //     token_id: success_token,
-        //     opposite_token_id: opposite_token,
-        //     amount: filled_amount,
-        //     entry_price: dec!(0), // 占位符，需要在主程序中从订单簿获取
-        //     take_profit_pct: self.take_profit_pct,
-        //     stop_loss_pct: self.stop_loss_pct,
-        //     pair_id: pair.pair_id.clone(),
-        //     market_display: "未知市场".to_string(), // 占位符，需要在主程序中从市场信息获取
-        // })
+        // 两腿平衡
+        assert!(RecoveryStrategy::imbalance(&pair(dec!(5), dec!(5), false, false)).is_none());
     }
 }
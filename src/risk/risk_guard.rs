@@ -0,0 +1,317 @@
+//! 全局风控熔断：当日盈亏（已实现+未实现）超过亏损上限、自当日峰值回撤超过上限，
+//! 或连续单边成交的订单对达到上限时触发。触发后主程序撤销全部挂单、停止开新仓并执行收尾；
+//! 熔断状态写入可执行文件同目录的状态文件，重启后保持，直到下一个 UTC 交易日或手动解除（删除状态文件）。
+//! 手动解除后，当日亏损与回撤从解除时的盈亏重新起算，避免立即再次触发。
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+
+/// 状态文件名（位于可执行文件同目录）
+pub const RISK_GUARD_FILE: &str = "risk_guard.json";
+
+/// 熔断记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TripRecord {
+    day: NaiveDate,
+    at: DateTime<Utc>,
+    reason: String,
+}
+
+struct GuardState {
+    day: NaiveDate,
+    baseline: Decimal, // 亏损起算点：当日为 0，手动解除后为解除时的盈亏
+    peak: Decimal,     // 当日盈亏峰值（不低于 baseline）
+    rebase: bool,      // 已手动解除，下一次检查时以当前盈亏为新的起算点
+    one_sided_streak: u32,
+    trip: Option<TripRecord>,
+    persisted: bool, // 熔断记录已写盘（写盘失败时不把「文件不存在」当作手动解除）
+    announced: bool, // 本次熔断是否已交给主程序处理（撤单、收尾）
+}
+
+pub struct RiskGuard {
+    max_daily_loss: Decimal, // 0 表示不限制
+    max_drawdown: Decimal,   // 0 表示不限制
+    max_one_sided: u32,      // 0 表示不限制
+    path: Option<PathBuf>,   // None 表示不写状态文件（回测）
+    tripped: AtomicBool,
+    state: Mutex<GuardState>,
+}
+
+impl RiskGuard {
+    /// 默认状态文件路径：可执行文件所在目录下的 risk_guard.json
+    pub fn default_path() -> PathBuf {
+        super::state_file_path(RISK_GUARD_FILE)
+    }
+
+    /// 按配置创建；状态文件中存在当日的熔断记录时保持熔断
+    pub fn load(path: impl Into<PathBuf>, config: &Config) -> Self {
+        let path = path.into();
+        let today = Utc::now().date_naive();
+        let trip = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| match serde_json::from_str::<TripRecord>(&text) {
+                Ok(trip) => Some(trip),
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "熔断状态文件无法解析，忽略");
+                    None
+                }
+            })
+            .filter(|trip| trip.day == today);
+        if let Some(trip) = &trip {
+            warn!(
+                "🚨 风控熔断仍有效（{}，触发于 {}），今日不再开新仓；删除 {} 可手动解除",
+                trip.reason,
+                trip.at.format("%H:%M:%S"),
+                path.display()
+            );
+        } else if path.exists() {
+            let _ = std::fs::remove_file(&path);
+        }
+        Self::with_state(Some(path), config, today, trip)
+    }
+
+    /// 不读写状态文件的熔断（回测用，交易日由 check_pnl 传入的录制时间推进）
+    pub fn in_memory(config: &Config) -> Self {
+        Self::with_state(None, config, NaiveDate::MIN, None)
+    }
+
+    fn with_state(path: Option<PathBuf>, config: &Config, day: NaiveDate, trip: Option<TripRecord>) -> Self {
+        Self {
+            max_daily_loss: Decimal::try_from(config.risk_max_daily_loss_usdc).unwrap_or(dec!(0)),
+            max_drawdown: Decimal::try_from(config.risk_max_drawdown_usdc).unwrap_or(dec!(0)),
+            max_one_sided: config.risk_max_consecutive_one_sided,
+            path,
+            tripped: AtomicBool::new(trip.is_some()),
+            state: Mutex::new(GuardState {
+                day,
+                baseline: dec!(0),
+                peak: dec!(0),
+                rebase: false,
+                one_sided_streak: 0,
+                persisted: trip.is_some(),
+                announced: false,
+                trip,
+            }),
+        }
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.tripped.load(Ordering::Relaxed)
+    }
+
+    /// 按当日盈亏（已实现+未实现）检查亏损与回撤；同时处理跨日与手动解除
+    pub fn check_pnl(&self, now: DateTime<Utc>, day_pnl: Decimal) {
+        let mut state = self.state.lock().unwrap();
+        let today = now.date_naive();
+        if state.day != today {
+            if state.trip.is_some() {
+                self.clear(&mut state, "进入新的交易日");
+            }
+            state.day = today;
+            state.baseline = dec!(0);
+            state.peak = dec!(0);
+            state.rebase = false;
+        }
+        if state.trip.is_some() {
+            if state.persisted && self.path.as_ref().is_some_and(|p| !p.exists()) {
+                self.clear(&mut state, "状态文件已删除，手动解除");
+            }
+            return;
+        }
+        if state.rebase {
+            state.rebase = false;
+            state.baseline = day_pnl;
+            state.peak = day_pnl;
+        }
+
+        state.peak = state.peak.max(day_pnl);
+        let loss = state.baseline - day_pnl;
+        if self.max_daily_loss > dec!(0) && loss >= self.max_daily_loss {
+            let reason = format!("当日亏损 {:.4} USD 达到上限 {}", loss, self.max_daily_loss);
+            self.trip(&mut state, now, reason);
+        } else if self.max_drawdown > dec!(0) && state.peak - day_pnl >= self.max_drawdown {
+            let reason = format!(
+                "自当日峰值 {:+.4} 回撤 {:.4} USD 达到上限 {}",
+                state.peak,
+                state.peak - day_pnl,
+                self.max_drawdown
+            );
+            self.trip(&mut state, now, reason);
+        }
+    }
+
+    /// 订单对的成交结果（由 RiskManager 在生命周期可判定时上报）：单边成交累加连续次数，两腿都有成交则清零
+    pub fn on_pair_outcome(&self, one_sided: bool, now: DateTime<Utc>) {
+        let mut state = self.state.lock().unwrap();
+        if !one_sided {
+            state.one_sided_streak = 0;
+            return;
+        }
+        state.one_sided_streak += 1;
+        if self.max_one_sided > 0 && state.one_sided_streak >= self.max_one_sided && state.trip.is_none() {
+            let reason = format!("连续 {} 个订单对单边成交", state.one_sided_streak);
+            self.trip(&mut state, now, reason);
+        }
+    }
+
+    /// 新触发的熔断原因（每次熔断只返回一次），主程序据此撤单并执行收尾
+    pub fn take_new_trip(&self) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        if state.announced {
+            return None;
+        }
+        let reason = state.trip.as_ref()?.reason.clone();
+        state.announced = true;
+        Some(reason)
+    }
+
+    fn trip(&self, state: &mut GuardState, now: DateTime<Utc>, reason: String) {
+        error!("🚨 风控熔断 | {} | 撤销全部挂单、停止开新仓并执行收尾", reason);
+        let trip = TripRecord {
+            day: now.date_naive(),
+            at: now,
+            reason,
+        };
+        state.persisted = match &self.path {
+            Some(path) => match serde_json::to_vec(&trip).map(|bytes| std::fs::write(path, bytes)) {
+                Ok(Ok(())) => true,
+                Ok(Err(e)) => {
+                    warn!(path = %path.display(), error = %e, "熔断状态写盘失败，重启后不会保持熔断");
+                    false
+                }
+                Err(e) => {
+                    warn!(error = %e, "熔断状态序列化失败");
+                    false
+                }
+            },
+            None => false,
+        };
+        state.trip = Some(trip);
+        state.announced = false;
+        self.tripped.store(true, Ordering::Relaxed);
+    }
+
+    fn clear(&self, state: &mut GuardState, reason: &str) {
+        info!("✅ 风控熔断解除 | {}", reason);
+        state.trip = None;
+        state.announced = false;
+        state.rebase = true;
+        state.one_sided_streak = 0;
+        if let Some(path) = self.path.as_ref().filter(|p| p.exists()) {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!(path = %path.display(), error = %e, "删除熔断状态文件失败");
+            }
+        }
+        self.tripped.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config(max_daily_loss: f64, max_drawdown: f64, max_one_sided: u32) -> Config {
+        let mut config = Config::from_env_offline().unwrap();
+        config.risk_max_daily_loss_usdc = max_daily_loss;
+        config.risk_max_drawdown_usdc = max_drawdown;
+        config.risk_max_consecutive_one_sided = max_one_sided;
+        config
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("poly_risk_guard_{}_{}.json", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn trips_on_daily_loss_and_persists() {
+        let path = temp_path("daily_loss");
+        let guard = RiskGuard::load(&path, &config(10.0, 0.0, 0));
+        let now = Utc::now();
+        guard.check_pnl(now, dec!(-9.99));
+        assert!(!guard.is_tripped());
+        guard.check_pnl(now, dec!(-10));
+        assert!(guard.is_tripped());
+        assert!(guard.take_new_trip().is_some());
+        assert!(guard.take_new_trip().is_none());
+        assert!(path.exists());
+
+        // 当日熔断记录在重启后保持
+        assert!(RiskGuard::load(&path, &config(10.0, 0.0, 0)).is_tripped());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn trips_on_drawdown_from_peak() {
+        let guard = RiskGuard::in_memory(&config(0.0, 5.0, 0));
+        let now = at(10, 12);
+        guard.check_pnl(now, dec!(8));
+        guard.check_pnl(now, dec!(3.01));
+        assert!(!guard.is_tripped());
+        guard.check_pnl(now, dec!(3));
+        assert!(guard.is_tripped());
+    }
+
+    #[test]
+    fn one_sided_streak_resets_on_two_sided_pair() {
+        let guard = RiskGuard::in_memory(&config(0.0, 0.0, 3));
+        let now = at(10, 12);
+        guard.on_pair_outcome(true, now);
+        guard.on_pair_outcome(true, now);
+        guard.on_pair_outcome(false, now);
+        guard.on_pair_outcome(true, now);
+        guard.on_pair_outcome(true, now);
+        assert!(!guard.is_tripped());
+        guard.on_pair_outcome(true, now);
+        assert!(guard.is_tripped());
+    }
+
+    #[test]
+    fn new_day_clears_trip_and_baseline() {
+        let guard = RiskGuard::in_memory(&config(10.0, 0.0, 0));
+        guard.check_pnl(at(10, 12), dec!(-10));
+        assert!(guard.is_tripped());
+        guard.check_pnl(at(10, 23), dec!(-4));
+        assert!(guard.is_tripped());
+
+        // 新的一天：熔断解除，当日盈亏从 0 重新起算
+        guard.check_pnl(at(11, 0), dec!(-9));
+        assert!(!guard.is_tripped());
+        guard.check_pnl(at(11, 1), dec!(-10));
+        assert!(guard.is_tripped());
+    }
+
+    #[test]
+    fn deleting_state_file_resets_and_rebases_loss() {
+        let path = temp_path("manual_reset");
+        let guard = RiskGuard::load(&path, &config(10.0, 0.0, 0));
+        let now = Utc::now();
+        guard.check_pnl(now, dec!(-12));
+        assert!(guard.is_tripped());
+
+        std::fs::remove_file(&path).unwrap();
+        guard.check_pnl(now, dec!(-12));
+        assert!(!guard.is_tripped());
+        // 解除后以解除时的盈亏（-12）为起算点
+        guard.check_pnl(now, dec!(-12));
+        guard.check_pnl(now, dec!(-21));
+        assert!(!guard.is_tripped());
+        guard.check_pnl(now, dec!(-22));
+        assert!(guard.is_tripped());
+        let _ = std::fs::remove_file(&path);
+    }
+}
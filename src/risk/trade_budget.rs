@@ -0,0 +1,219 @@
+//! 每日交易次数预算（MAX_TRADES_PER_DAY）：套利买入、拆分卖出与剥头皮入场共用，
+//! 每天在 TRADE_BUDGET_RESET_HOUR_UTC 点（UTC）重置；已用次数写入可执行文件同目录的状态文件，重启后继续累计。
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// 状态文件名（位于可执行文件同目录）
+pub const TRADE_BUDGET_FILE: &str = "trade_budget.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BudgetState {
+    period_start: i64, // 当前预算周期开始时间（Unix 秒）
+    used: u32,
+}
+
+pub struct TradeBudget {
+    max_per_day: u32, // 0 表示不限制
+    reset_hour: u32,
+    path: Option<PathBuf>, // None 表示不写状态文件（回测）
+    state: Mutex<BudgetState>,
+}
+
+/// now 所在预算周期的开始时间：最近一次（含当前）UTC reset_hour 整点
+fn period_start(now: DateTime<Utc>, reset_hour: u32) -> DateTime<Utc> {
+    let today = now
+        .date_naive()
+        .and_hms_opt(reset_hour.min(23), 0, 0)
+        .map(|t| t.and_utc())
+        .unwrap_or(now);
+    if now >= today {
+        today
+    } else {
+        today - Duration::days(1)
+    }
+}
+
+impl TradeBudget {
+    /// 默认状态文件路径：可执行文件所在目录下的 trade_budget.json（取不到时使用当前目录）
+    pub fn default_path() -> PathBuf {
+        super::state_file_path(TRADE_BUDGET_FILE)
+    }
+
+    /// 读取状态文件；文件不存在或损坏时从 0 开始（损坏时记录警告）
+    pub fn load(path: impl Into<PathBuf>, max_per_day: u32, reset_hour: u32) -> Self {
+        let path = path.into();
+        let now = Utc::now();
+        let state = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str::<BudgetState>(&text).unwrap_or_else(|e| {
+                warn!(path = %path.display(), error = %e, "交易次数状态文件无法解析，从 0 开始计数");
+                BudgetState { period_start: period_start(now, reset_hour).timestamp(), used: 0 }
+            }),
+            Err(_) => BudgetState { period_start: period_start(now, reset_hour).timestamp(), used: 0 },
+        };
+        let budget = Self {
+            max_per_day,
+            reset_hour,
+            path: Some(path),
+            state: Mutex::new(state),
+        };
+        budget.roll(&mut budget.state.lock().unwrap(), now);
+        budget
+    }
+
+    /// 不读写状态文件的预算（回测用，周期按调用方传入的录制时间滚动）
+    pub fn in_memory(max_per_day: u32, reset_hour: u32) -> Self {
+        Self {
+            max_per_day,
+            reset_hour,
+            path: None,
+            state: Mutex::new(BudgetState { period_start: 0, used: 0 }),
+        }
+    }
+
+    /// 进入新周期时清零
+    fn roll(&self, state: &mut BudgetState, now: DateTime<Utc>) {
+        let start = period_start(now, self.reset_hour).timestamp();
+        if state.period_start != start {
+            if state.used > 0 {
+                info!("交易次数预算已重置 | 上一周期已用 {} 次", state.used);
+            }
+            state.period_start = start;
+            state.used = 0;
+        }
+    }
+
+    fn save(&self, state: &BudgetState) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(state)?)
+            .with_context(|| format!("写入交易次数状态失败: {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("替换交易次数状态失败: {}", path.display()))?;
+        Ok(())
+    }
+
+    /// 当前周期剩余次数；不限制时返回 None
+    pub fn remaining(&self, now: DateTime<Utc>) -> Option<u32> {
+        if self.max_per_day == 0 {
+            return None;
+        }
+        let mut state = self.state.lock().unwrap();
+        self.roll(&mut state, now);
+        Some(self.max_per_day.saturating_sub(state.used))
+    }
+
+    pub fn is_exhausted(&self, now: DateTime<Utc>) -> bool {
+        self.remaining(now) == Some(0)
+    }
+
+    /// 占用一次交易；预算已用完返回 false。写盘失败只记录警告，不影响交易
+    pub fn try_consume(&self, now: DateTime<Utc>) -> bool {
+        if self.max_per_day == 0 {
+            return true;
+        }
+        let mut state = self.state.lock().unwrap();
+        self.roll(&mut state, now);
+        if state.used >= self.max_per_day {
+            return false;
+        }
+        state.used += 1;
+        if let Err(e) = self.save(&state) {
+            warn!(error = %e, "交易次数状态写盘失败");
+        }
+        if state.used == self.max_per_day {
+            warn!(
+                "今日交易次数已用完（{}/{}），{} 点（UTC）重置前不再开新仓，merge 与收尾照常执行",
+                state.used, self.max_per_day, self.reset_hour
+            );
+        }
+        true
+    }
+
+    /// (已用, 上限)，用于日志
+    pub fn usage(&self, now: DateTime<Utc>) -> (u32, u32) {
+        let mut state = self.state.lock().unwrap();
+        self.roll(&mut state, now);
+        (state.used, self.max_per_day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, min, 0).unwrap()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("poly_trade_budget_{}_{}.json", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn period_starts_at_reset_hour() {
+        assert_eq!(period_start(at(10, 7, 59), 8), at(9, 8, 0));
+        assert_eq!(period_start(at(10, 8, 0), 8), at(10, 8, 0));
+        assert_eq!(period_start(at(10, 23, 30), 8), at(10, 8, 0));
+        // 0 点重置：跨过午夜即进入新周期
+        assert_eq!(period_start(at(10, 23, 59), 0), at(10, 0, 0));
+        assert_eq!(period_start(at(11, 0, 1), 0), at(11, 0, 0));
+    }
+
+    #[test]
+    fn budget_resets_at_reset_hour_not_midnight() {
+        let budget = TradeBudget::in_memory(1, 8);
+        assert!(budget.try_consume(at(10, 9, 0)));
+        // 午夜之后、重置整点之前仍属于同一周期
+        assert!(budget.is_exhausted(at(11, 0, 30)));
+        assert!(budget.is_exhausted(at(11, 7, 59)));
+        assert_eq!(budget.remaining(at(11, 8, 0)), Some(1));
+        assert!(budget.try_consume(at(11, 8, 0)));
+    }
+
+    #[test]
+    fn budget_is_exhausted_after_max_trades() {
+        let budget = TradeBudget::in_memory(2, 0);
+        let now = at(10, 12, 0);
+        assert!(budget.try_consume(now));
+        assert!(budget.try_consume(now));
+        assert!(!budget.try_consume(now));
+        assert!(budget.is_exhausted(now));
+        assert_eq!(budget.usage(now), (2, 2));
+
+        let unlimited = TradeBudget::in_memory(0, 0);
+        assert!((0..10).all(|_| unlimited.try_consume(now)));
+        assert_eq!(unlimited.remaining(now), None);
+        assert!(!unlimited.is_exhausted(now));
+    }
+
+    #[test]
+    fn usage_survives_reload_from_state_file() {
+        let path = temp_path("reload");
+        // load 以当前时间滚动周期，重载测试使用真实时钟
+        let now = Utc::now();
+        let budget = TradeBudget::load(&path, 3, 0);
+        assert!(budget.try_consume(now));
+        assert!(budget.try_consume(now));
+        drop(budget);
+
+        let reloaded = TradeBudget::load(&path, 3, 0);
+        assert_eq!(reloaded.usage(now), (2, 3));
+        assert!(reloaded.try_consume(now));
+        assert!(reloaded.is_exhausted(now));
+
+        // 损坏的状态文件从 0 开始
+        std::fs::write(&path, b"not json").unwrap();
+        assert_eq!(TradeBudget::load(&path, 3, 0).usage(now), (0, 3));
+        let _ = std::fs::remove_file(&path);
+    }
+}
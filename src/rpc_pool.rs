@@ -0,0 +1,341 @@
+//! Polygon RPC 节点池：split / merge / redeem 的链上读写按节点池自动切换。
+//!
+//! - 节点来自 `POLYGON_RPC_URLS`（逗号分隔，按顺序优先），未设置时取 `POLYGON_RPC_URL`，再退回默认公共节点
+//! - 熔断：连续 `RPC_FAILURE_THRESHOLD` 次连接类失败后该节点熔断 `RPC_CIRCUIT_OPEN_SECS` 秒，到期后放行一次试探
+//! - 限速：返回 429 / 限速错误的节点按错误给出的等待时间（或指数退避）暂停使用，请求立即切到下一个节点
+//! - 健康探测：后台每 `RPC_HEALTH_CHECK_SECS` 秒对各节点调用 `eth_blockNumber`，恢复的节点解除熔断
+//!
+//! 节点执行了请求但返回业务错误（revert、nonce too low 等）时不切换节点，原样返回。
+
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use alloy::contract::Error as ContractError;
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::transports::layers::{RateLimitRetryPolicy, RetryPolicy};
+use alloy::transports::{RpcError, TransportError};
+use anyhow::Result;
+use tracing::{debug, info, warn};
+
+const RPC_URL_DEFAULT: &str = "https://polygon-bor-rpc.publicnode.com";
+/// 限速退避的起点与上限
+const RATE_LIMIT_BACKOFF_BASE: Duration = Duration::from_secs(2);
+const RATE_LIMIT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// 所有节点都不可用时，最多等待这么久再重试一轮
+const MAX_WAIT_FOR_ENDPOINT: Duration = Duration::from_secs(30);
+/// 健康探测单次超时
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 请求失败的归类
+enum Failure {
+    /// 节点限速：暂停使用该节点（可带节点给出的等待时间）
+    RateLimited(Option<Duration>),
+    /// 连接失败、超时、5xx 等：计入熔断
+    Unavailable,
+    /// 节点已执行请求并返回错误（revert 等）：换节点也一样，直接返回
+    Fatal,
+}
+
+fn classify_transport(err: &TransportError) -> Failure {
+    let policy = RateLimitRetryPolicy::default();
+    if policy.should_retry(err) {
+        return Failure::RateLimited(policy.backoff_hint(err));
+    }
+    match err {
+        RpcError::ErrorResp(_) => Failure::Fatal,
+        _ => Failure::Unavailable,
+    }
+}
+
+/// 从 "retry in 10s" 一类的错误信息中解析等待时间
+fn parse_retry_in(msg: &str) -> Option<Duration> {
+    let after = msg.split_once("retry in ")?.1.trim_start();
+    let digits: String = after.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let value: u64 = digits.parse().ok()?;
+    let rest = &after[digits.len()..];
+    if rest.starts_with("ms") {
+        Some(Duration::from_millis(value))
+    } else {
+        Some(Duration::from_secs(value))
+    }
+}
+
+fn classify(err: &anyhow::Error) -> Failure {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<TransportError>() {
+            return classify_transport(e);
+        }
+        if let Some(ContractError::TransportError(e)) = cause.downcast_ref::<ContractError>() {
+            return classify_transport(e);
+        }
+    }
+    // SDK 等把 RPC 错误转成了字符串，按错误信息判断
+    let msg = err.to_string().to_lowercase();
+    if msg.contains("rate limit") || msg.contains("too many requests") || msg.contains("429") || msg.contains("retry in") {
+        return Failure::RateLimited(parse_retry_in(&msg));
+    }
+    if msg.contains("error sending request")
+        || msg.contains("connection")
+        || msg.contains("timed out")
+        || msg.contains("dns error")
+        || msg.contains("502")
+        || msg.contains("503")
+        || msg.contains("504")
+    {
+        return Failure::Unavailable;
+    }
+    Failure::Fatal
+}
+
+/// 错误是否为节点已执行请求后返回的业务错误（revert、nonce too low 等）；
+/// 连接失败、超时、限速返回 false，此时请求可能已经送达节点
+pub(crate) fn is_node_error(err: &anyhow::Error) -> bool {
+    matches!(classify(err), Failure::Fatal)
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,    // 熔断到期时间
+    backoff_until: Option<Instant>, // 限速退避到期时间
+    rate_limit_strikes: u32,        // 连续限速次数，决定指数退避
+    latency: Option<Duration>,      // 最近一次成功请求的耗时
+}
+
+impl Health {
+    /// 不可用时返回还需等待的时长
+    fn blocked_for(&self, now: Instant) -> Option<Duration> {
+        [self.open_until, self.backoff_until]
+            .into_iter()
+            .flatten()
+            .filter(|t| *t > now)
+            .max()
+            .map(|t| t - now)
+    }
+}
+
+struct Endpoint {
+    url: String,
+    health: Mutex<Health>,
+}
+
+pub struct RpcPool {
+    endpoints: Vec<Endpoint>,
+    failure_threshold: u32,
+    open_duration: Duration,
+    health_check_interval: Duration,
+}
+
+static GLOBAL: OnceLock<Arc<RpcPool>> = OnceLock::new();
+
+impl RpcPool {
+    pub fn new(urls: Vec<String>) -> Self {
+        let urls = if urls.is_empty() { vec![RPC_URL_DEFAULT.to_string()] } else { urls };
+        Self {
+            endpoints: urls
+                .into_iter()
+                .map(|url| Endpoint { url, health: Mutex::new(Health::default()) })
+                .collect(),
+            failure_threshold: 3,
+            open_duration: Duration::from_secs(60),
+            health_check_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// 按环境变量创建：`POLYGON_RPC_URLS`、`POLYGON_RPC_URL`、`RPC_FAILURE_THRESHOLD`（默认 3）、
+    /// `RPC_CIRCUIT_OPEN_SECS`（默认 60）、`RPC_HEALTH_CHECK_SECS`（默认 30，0 表示不探测）
+    pub fn from_env() -> Self {
+        let urls: Vec<String> = ["POLYGON_RPC_URLS", "POLYGON_RPC_URL"]
+            .iter()
+            .filter_map(|key| env::var(key).ok().filter(|s| !s.trim().is_empty()))
+            .next()
+            .map(|s| {
+                s.split(',')
+                    .map(|u| u.trim().to_string())
+                    .filter(|u| !u.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let env_u64 = |key: &str, default: u64| env::var(key).ok().and_then(|s| s.trim().parse().ok()).unwrap_or(default);
+        let mut pool = Self::new(urls);
+        pool.failure_threshold = env_u64("RPC_FAILURE_THRESHOLD", 3).max(1) as u32;
+        pool.open_duration = Duration::from_secs(env_u64("RPC_CIRCUIT_OPEN_SECS", 60));
+        pool.health_check_interval = Duration::from_secs(env_u64("RPC_HEALTH_CHECK_SECS", 30));
+        pool
+    }
+
+    /// 进程内共用的节点池（首次使用时按环境变量创建）
+    pub fn global() -> Arc<RpcPool> {
+        GLOBAL.get_or_init(|| Arc::new(Self::from_env())).clone()
+    }
+
+    /// 显式指定 RPC 时使用单节点池，否则使用全局节点池
+    pub fn resolve(rpc_url: Option<&str>) -> Arc<RpcPool> {
+        match rpc_url {
+            Some(url) => Arc::new(Self::new(vec![url.to_string()])),
+            None => Self::global(),
+        }
+    }
+
+    pub fn urls(&self) -> Vec<&str> {
+        self.endpoints.iter().map(|e| e.url.as_str()).collect()
+    }
+
+    /// 当前可用的节点：未熔断、未在限速退避中，按连续失败次数、延迟排序
+    fn candidates(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let mut ready: Vec<(usize, u32, Duration)> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| {
+                let h = e.health.lock().unwrap();
+                h.blocked_for(now)
+                    .is_none()
+                    .then(|| (i, h.consecutive_failures, h.latency.unwrap_or(Duration::MAX)))
+            })
+            .collect();
+        ready.sort_by_key(|&(i, failures, latency)| (failures, latency, i));
+        ready.into_iter().map(|(i, _, _)| i).collect()
+    }
+
+    /// 最早恢复可用的节点还需等待的时长
+    fn next_available_in(&self) -> Duration {
+        let now = Instant::now();
+        self.endpoints
+            .iter()
+            .map(|e| e.health.lock().unwrap().blocked_for(now).unwrap_or_default())
+            .min()
+            .unwrap_or_default()
+    }
+
+    fn record_success(&self, i: usize, latency: Duration) {
+        let mut h = self.endpoints[i].health.lock().unwrap();
+        if h.open_until.is_some() {
+            info!(endpoint = %self.endpoints[i].url, "✅ RPC 节点恢复，解除熔断");
+        }
+        *h = Health { latency: Some(latency), ..Health::default() };
+    }
+
+    fn record_failure(&self, i: usize) {
+        let mut h = self.endpoints[i].health.lock().unwrap();
+        h.consecutive_failures += 1;
+        if h.consecutive_failures >= self.failure_threshold {
+            h.open_until = Some(Instant::now() + self.open_duration);
+            warn!(
+                endpoint = %self.endpoints[i].url,
+                failures = h.consecutive_failures,
+                "🔌 RPC 节点连续失败，熔断 {} 秒",
+                self.open_duration.as_secs()
+            );
+        }
+    }
+
+    fn record_rate_limit(&self, i: usize, hint: Option<Duration>) -> Duration {
+        let mut h = self.endpoints[i].health.lock().unwrap();
+        h.rate_limit_strikes += 1;
+        let backoff = hint.unwrap_or_else(|| RATE_LIMIT_BACKOFF_BASE * 2u32.saturating_pow(h.rate_limit_strikes - 1).min(64));
+        let backoff = backoff.min(RATE_LIMIT_BACKOFF_MAX);
+        h.backoff_until = Some(Instant::now() + backoff);
+        backoff
+    }
+
+    /// 依次在可用节点上执行 `f(url)`，直到成功；限速与连接类失败切换到下一个节点，业务错误直接返回。
+    /// 一轮下来所有节点都失败（或都在熔断 / 退避中）时，等待最早恢复的节点（最多 30 秒）再试一轮。
+    pub async fn with_failover<T, F, Fut>(&self, op: &str, mut f: F) -> Result<T>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let mut last_err = None;
+        for round in 0..2 {
+            if round > 0 {
+                let wait = self.next_available_in().min(MAX_WAIT_FOR_ENDPOINT);
+                if !wait.is_zero() {
+                    warn!(op, "⏳ RPC 节点均不可用，等待 {} 秒后重试", wait.as_secs_f64().ceil());
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            for i in self.candidates() {
+                let url = &self.endpoints[i].url;
+                let start = Instant::now();
+                match f(url.clone()).await {
+                    Ok(v) => {
+                        self.record_success(i, start.elapsed());
+                        return Ok(v);
+                    }
+                    Err(e) => match classify(&e) {
+                        Failure::Fatal => return Err(e),
+                        Failure::RateLimited(hint) => {
+                            let backoff = self.record_rate_limit(i, hint);
+                            warn!(op, endpoint = %url, "⏳ RPC 限速，该节点暂停 {} 秒，切换节点", backoff.as_secs_f64().ceil());
+                            last_err = Some(e);
+                        }
+                        Failure::Unavailable => {
+                            self.record_failure(i);
+                            warn!(op, endpoint = %url, error = %e, "RPC 请求失败，切换节点");
+                            last_err = Some(e);
+                        }
+                    },
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow::anyhow!("{}：所有 RPC 节点均在熔断或限速中", op)))
+    }
+
+    /// 对所有节点调用一次 `eth_blockNumber`：成功解除熔断并记录延迟，失败计入熔断；限速退避中的节点跳过
+    pub async fn probe(&self) {
+        for (i, endpoint) in self.endpoints.iter().enumerate() {
+            let backing_off = endpoint
+                .health
+                .lock()
+                .unwrap()
+                .backoff_until
+                .is_some_and(|t| t > Instant::now());
+            if backing_off {
+                continue;
+            }
+            let start = Instant::now();
+            let result = tokio::time::timeout(PROBE_TIMEOUT, async {
+                let provider = ProviderBuilder::new().connect(&endpoint.url).await?;
+                anyhow::Ok(provider.get_block_number().await?)
+            })
+            .await;
+            match result {
+                Ok(Ok(block)) => {
+                    debug!(endpoint = %endpoint.url, block, latency_ms = start.elapsed().as_millis() as u64, "RPC 节点健康");
+                    self.record_success(i, start.elapsed());
+                }
+                Ok(Err(e)) => {
+                    if let Failure::RateLimited(hint) = classify(&e) {
+                        self.record_rate_limit(i, hint);
+                    } else {
+                        warn!(endpoint = %endpoint.url, error = %e, "RPC 节点健康探测失败");
+                        self.record_failure(i);
+                    }
+                }
+                Err(_) => {
+                    warn!(endpoint = %endpoint.url, "RPC 节点健康探测超时");
+                    self.record_failure(i);
+                }
+            }
+        }
+    }
+
+    /// 启动后台健康探测（`RPC_HEALTH_CHECK_SECS=0` 时不启动）
+    pub fn spawn_health_checks(self: &Arc<Self>) {
+        if self.health_check_interval.is_zero() {
+            return;
+        }
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(pool.health_check_interval);
+            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                timer.tick().await;
+                pool.probe().await;
+            }
+        });
+    }
+}
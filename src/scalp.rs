@@ -1,48 +1,376 @@
-use std::collections::HashMap;
-use rust_decimal::Decimal;
-use polymarket_client_sdk::types::B256;
+//! 剥头皮策略：YES 中间价相对上一拍变动超过 SCALP_SIGNAL_PCT 时顺势入场（上涨买 YES，下跌买 NO），
+//! 每个市场最多持有一笔，按买一价估算扣除手续费后的净盈亏（入场支付的 USDC 对比卖出所得减卖出手续费），
+//! 达到止盈 / 止损或持有超过 SCALP_MAX_HOLD_SECONDS 时卖出。
+//! 入场与离场都通过 TradingExecutor 以 FAK 吃单，下单在独立任务中执行，不阻塞订单簿处理。
+//! 入场与套利遵循同样的开仓限制：收尾开始后、临近市场结束（STOP_ARBITRAGE_BEFORE_END_MINUTES）
+//! 或入场成本超过风险敞口上限时不再入场；成交后计入 PositionTracker 的持仓与敞口，离场时扣回。
+
+use chrono::{DateTime, Utc};
+use dashmap::{DashMap, DashSet};
+use polymarket_client_sdk::clob::types::Side;
 use polymarket_client_sdk::clob::ws::types::response::BookUpdate;
-use tracing::info;
+use polymarket_client_sdk::types::{B256, U256};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::monitor::PreTradeCheck;
+use crate::risk::positions::PositionTracker;
+use crate::risk::{RiskGuard, TradeBudget};
+use crate::trading::{FeeModel, FeeSchedule, TradingExecutor};
+
+/// 一笔持有中的剥头皮仓位
+#[derive(Debug, Clone)]
+struct ScalpPosition {
+    token_id: U256,
+    side: &'static str, // "YES" / "NO"，用于日志
+    entry_price: Decimal,
+    cost: Decimal, // 入场支付的 USDC（买入手续费以份额扣除，已体现在 size 中）
+    size: Decimal, // 可卖出份额（已扣买入手续费）
+    opened_at: Instant,
+}
+
+impl ScalpPosition {
+    /// 按 bid 卖出全部可卖份额的净盈亏百分比：所得扣除卖出手续费，对比入场支付的 USDC
+    fn net_pnl_pct(&self, fee_model: &FeeModel, bid: Decimal) -> Option<Decimal> {
+        if self.cost <= dec!(0) {
+            return None;
+        }
+        let proceeds = bid * self.size - fee_model.sell_fee_usd(bid, self.size);
+        Some((proceeds - self.cost) / self.cost * dec!(100))
+    }
+}
+
+/// 离场原因
+#[derive(Debug, Clone, Copy)]
+enum ExitReason {
+    TakeProfit,
+    StopLoss,
+    MaxHold,
+}
+
+impl std::fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExitReason::TakeProfit => write!(f, "止盈"),
+            ExitReason::StopLoss => write!(f, "止损"),
+            ExitReason::MaxHold => write!(f, "超过最长持有时间"),
+        }
+    }
+}
 
 pub struct ScalpState {
+    executor: Arc<TradingExecutor>,
+    fees: FeeSchedule,
     last_mid_price: HashMap<B256, Decimal>,
+    positions: Arc<DashMap<B256, ScalpPosition>>,
+    in_flight: Arc<DashSet<B256>>, // 正在入场或离场的市场
+    order_size_usdc: Decimal,
+    signal_pct: Decimal,
+    take_profit_pct: Decimal,
+    stop_loss_pct: Decimal,
+    max_hold: Duration,
+    trade_budget: Arc<TradeBudget>, // 与套利共用的每日交易次数
+    risk_guard: Arc<RiskGuard>,     // 熔断后只离场、不再入场
+    position_tracker: Arc<PositionTracker>, // 入场前检查敞口上限，成交后记持仓与敞口
+    pre_trade_check: PreTradeCheck,         // 临近市场结束不再入场
+    entries_stopped: bool,                  // 本窗口已开始收尾，只离场
 }
 
 impl ScalpState {
-    pub fn new() -> Self {
+    pub fn new(
+        config: &Config,
+        executor: Arc<TradingExecutor>,
+        trade_budget: Arc<TradeBudget>,
+        risk_guard: Arc<RiskGuard>,
+        position_tracker: Arc<PositionTracker>,
+    ) -> Self {
         Self {
+            executor,
+            fees: FeeSchedule::from_config(config),
             last_mid_price: HashMap::new(),
+            positions: Arc::new(DashMap::new()),
+            in_flight: Arc::new(DashSet::new()),
+            order_size_usdc: Decimal::try_from(config.scalp_order_size_usdc).unwrap_or(dec!(1.0)),
+            signal_pct: Decimal::try_from(config.scalp_signal_pct).unwrap_or(dec!(2.0)),
+            take_profit_pct: Decimal::try_from(config.scalp_take_profit_pct).unwrap_or(dec!(1.0)),
+            stop_loss_pct: Decimal::try_from(config.scalp_stop_loss_pct).unwrap_or(dec!(0.5)),
+            max_hold: Duration::from_secs(config.scalp_max_hold_seconds),
+            trade_budget,
+            risk_guard,
+            position_tracker,
+            pre_trade_check: PreTradeCheck::new(config),
+            entries_stopped: false,
         }
     }
 
+    /// 使用共享的按市场手续费登记表（发现市场时登记）
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// 中间价（bids 最后为买一，asks 最后为卖一）
     fn mid_price(book: &BookUpdate) -> Option<Decimal> {
-        let bid = book.bids.first()?.price;
-        let ask = book.asks.first()?.price;
+        let bid = book.bids.last()?.price;
+        let ask = book.asks.last()?.price;
         Some((bid + ask) / Decimal::from(2))
     }
 
-    pub fn detect(
+    /// 本窗口开始收尾：之后只处理离场，不再入场（end_window 时恢复）
+    pub fn stop_entries(&mut self) {
+        self.entries_stopped = true;
+    }
+
+    /// 每次收到某市场的 YES/NO 订单簿对时调用：有持仓则检查离场，否则检查入场信号
+    /// end_date 为市场结束时间，用于临近结束停止入场
+    pub fn on_orderbook(
         &mut self,
         market_id: B256,
         yes_book: &BookUpdate,
-        threshold_pct: Decimal,
+        no_book: &BookUpdate,
+        end_date: Option<DateTime<Utc>>,
     ) {
-        let mid = match Self::mid_price(yes_book) {
-            Some(m) => m,
-            None => return,
+        let Some(mid) = Self::mid_price(yes_book) else { return };
+        let last = self.last_mid_price.insert(market_id, mid);
+
+        if self.in_flight.contains(&market_id) {
+            return;
+        }
+
+        if let Some(position) = self.positions.get(&market_id).map(|p| p.clone()) {
+            let book = if position.token_id == yes_book.asset_id { yes_book } else { no_book };
+            self.check_exit(market_id, position, book);
+            return;
+        }
+
+        if self.entries_stopped || self.risk_guard.is_tripped() {
+            return;
+        }
+        let Some(last) = last.filter(|l| !l.is_zero()) else { return };
+        let move_pct = (mid - last) / last * dec!(100);
+        if move_pct.abs() < self.signal_pct {
+            return;
+        }
+
+        // 顺势：YES 上涨买 YES，下跌买 NO
+        let (book, side) = if move_pct > dec!(0) { (yes_book, "YES") } else { (no_book, "NO") };
+        let Some(ask) = book.asks.last() else { return };
+        if ask.price <= dec!(0) || ask.price >= dec!(1) {
+            return;
+        }
+
+        let size = (self.order_size_usdc / ask.price * dec!(100)).floor() / dec!(100);
+        if size <= dec!(0) {
+            return;
+        }
+        let now = Utc::now();
+        if let Err(reason) = self.pre_trade_check.check_market_end(end_date, now) {
+            debug!("剥头皮：{}，忽略信号 | market={:#x}", reason, market_id);
+            return;
+        }
+
+        // 与套利相同的敞口检查：全局上限 + 按币种 / 按市场 / 未对冲上限（单腿入场，整笔按未对冲计）
+        let cost = ask.price * size;
+        let (yes_cost, no_cost) = if side == "YES" { (cost, dec!(0)) } else { (dec!(0), cost) };
+        if self.position_tracker.would_exceed_limit(yes_cost, no_cost) {
+            warn!(
+                "⚠️ 剥头皮：风险敞口超限，忽略信号 | market={:#x} | 当前敞口:{:.2} USD | 订单成本:{:.2} USD | 限制:{:.2} USD",
+                market_id,
+                self.position_tracker.calculate_exposure(),
+                cost,
+                self.position_tracker.max_exposure()
+            );
+            return;
+        }
+        if let Err(reason) = self.position_tracker.check_exposure_caps(
+            market_id,
+            yes_book.asset_id,
+            no_book.asset_id,
+            yes_cost,
+            no_cost,
+        ) {
+            warn!("⚠️ 剥头皮：{}，忽略信号 | market={:#x}", reason, market_id);
+            return;
+        }
+
+        if !self.trade_budget.try_consume(now) {
+            debug!("剥头皮：今日交易次数已用完，忽略信号 | market={:#x}", market_id);
+            return;
+        }
+
+        let (used, max) = self.trade_budget.usage(now);
+        info!(
+            "📈 剥头皮入场 | market={:#x} | 中间价 {:.4}→{:.4} ({:+.2}%) | 买入 {} {}份 @ {:.4} | 今日第 {}/{} 笔",
+            market_id, last, mid, move_pct, side, size, ask.price, used, max
+        );
+        self.spawn_entry(market_id, book.asset_id, side, ask.price, size);
+    }
+
+    fn check_exit(&self, market_id: B256, position: ScalpPosition, book: &BookUpdate) {
+        let Some(bid) = book.bids.last() else { return };
+        let Some(pnl_pct) = position.net_pnl_pct(&self.fees.for_token(position.token_id), bid.price) else {
+            return;
         };
+        let reason = if pnl_pct >= self.take_profit_pct {
+            ExitReason::TakeProfit
+        } else if pnl_pct <= -self.stop_loss_pct {
+            ExitReason::StopLoss
+        } else if position.opened_at.elapsed() >= self.max_hold {
+            ExitReason::MaxHold
+        } else {
+            return;
+        };
+
+        info!(
+            "📉 剥头皮离场 | market={:#x} | {} | 入场 {:.4} 买一 {:.4} (扣费后 {:+.2}%) | 卖出 {} {}份 | 持有 {}秒",
+            market_id,
+            reason,
+            position.entry_price,
+            bid.price,
+            pnl_pct,
+            position.side,
+            position.size,
+            position.opened_at.elapsed().as_secs()
+        );
+        self.spawn_exit(market_id, position, bid.price);
+    }
 
-        if let Some(last) = self.last_mid_price.get(&market_id) {
-            let diff = (mid - *last) / *last;
+    fn spawn_entry(&self, market_id: B256, token_id: U256, side: &'static str, price: Decimal, size: Decimal) {
+        self.in_flight.insert(market_id);
+        let executor = self.executor.clone();
+        let positions = self.positions.clone();
+        let in_flight = self.in_flight.clone();
+        let position_tracker = self.position_tracker.clone();
+        let fee_model = self.fees.for_token(token_id);
+        tokio::spawn(async move {
+            match executor.take_at_price(token_id, Side::Buy, price, size).await {
+                Ok(ack) if ack.taking_amount > dec!(0) => {
+                    // 买单：taking_amount 为成交份额，making_amount 为支付 USDC
+                    let entry_price = ack.making_amount / ack.taking_amount;
+                    let sellable = fee_model.sellable_size(entry_price, ack.taking_amount);
+                    info!("✅ 剥头皮已入场 | {} {}份 @ {:.4}", side, sellable, entry_price);
+                    position_tracker.update_exposure_cost(token_id, entry_price, ack.taking_amount);
+                    position_tracker.update_position(token_id, sellable);
+                    positions.insert(
+                        market_id,
+                        ScalpPosition {
+                            token_id,
+                            side,
+                            entry_price,
+                            cost: ack.making_amount,
+                            size: sellable,
+                            opened_at: Instant::now(),
+                        },
+                    );
+                }
+                Ok(ack) => {
+                    debug!(error = ?ack.error_msg, "剥头皮入场未成交");
+                }
+                Err(e) => warn!(error = %e, "剥头皮入场失败"),
+            }
+            in_flight.remove(&market_id);
+        });
+    }
 
-            if diff.abs() >= threshold_pct {
-                info!(
-                    "📈 scalp signal | market={:?} last={} now={} move={}",
-                    market_id, last, mid, diff
-                );
+    fn spawn_exit(&self, market_id: B256, position: ScalpPosition, price: Decimal) {
+        self.in_flight.insert(market_id);
+        let executor = self.executor.clone();
+        let positions = self.positions.clone();
+        let in_flight = self.in_flight.clone();
+        let position_tracker = self.position_tracker.clone();
+        tokio::spawn(async move {
+            match executor.take_at_price(position.token_id, Side::Sell, price, position.size).await {
+                Ok(ack) if ack.making_amount > dec!(0) => {
+                    // 卖单：making_amount 为卖出份额，taking_amount 为收到 USDC
+                    let sold = ack.making_amount.min(position.size);
+                    let cost = position.cost * sold / position.size;
+                    let pnl = ack.taking_amount - cost;
+                    info!(
+                        "✅ 剥头皮已离场 | {} 卖出 {}份 | 收到 {:.4} USDC | 盈亏 {:+.4} USD",
+                        position.side, sold, ack.taking_amount, pnl
+                    );
+                    // 先扣敞口再扣持仓，保证 update_exposure_cost 读到的是卖出前持仓
+                    position_tracker.update_exposure_cost(position.token_id, dec!(0), -sold);
+                    position_tracker.update_position(position.token_id, -sold);
+                    if sold >= position.size {
+                        positions.remove(&market_id);
+                    } else if let Some(mut p) = positions.get_mut(&market_id) {
+                        p.cost -= cost;
+                        p.size -= sold;
+                    }
+                }
+                Ok(ack) => {
+                    debug!(error = ?ack.error_msg, "剥头皮离场未成交，下一拍重试");
+                }
+                Err(e) => warn!(error = %e, "剥头皮离场失败，下一拍重试"),
             }
+            in_flight.remove(&market_id);
+        });
+    }
+
+    /// 窗口结束：清空价格与持仓记录（剩余持仓由收尾卖出处理）
+    pub fn end_window(&mut self) {
+        for p in self.positions.iter() {
+            warn!(
+                "剥头皮：窗口结束仍持有 {} {}份（入场 {:.4}），交由收尾处理",
+                p.side, p.size, p.entry_price
+            );
         }
+        self.positions.clear();
+        self.last_mid_price.clear();
+        self.entries_stopped = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fee_model: &FeeModel, entry_price: Decimal, shares: Decimal) -> ScalpPosition {
+        ScalpPosition {
+            token_id: U256::from(1),
+            side: "YES",
+            entry_price,
+            cost: entry_price * shares,
+            size: fee_model.sellable_size(entry_price, shares),
+            opened_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn net_pnl_is_zero_only_for_free_round_trip() {
+        let free = FeeModel::free();
+        let p = position(&free, dec!(0.5), dec!(100));
+        assert_eq!(p.net_pnl_pct(&free, dec!(0.5)), Some(dec!(0)));
+        assert_eq!(p.net_pnl_pct(&free, dec!(0.51)), Some(dec!(2)));
+
+        // 有手续费时原价卖出为净亏损：买入扣份额、卖出扣 USDC
+        let fees = FeeModel::new(0.25, 2.0);
+        let p = position(&fees, dec!(0.5), dec!(100));
+        let flat = p.net_pnl_pct(&fees, dec!(0.5)).unwrap();
+        assert!(flat < dec!(-3), "round trip at entry price should lose both fees, got {}", flat);
+    }
+
+    #[test]
+    fn gross_move_below_round_trip_fee_is_not_profit() {
+        let fees = FeeModel::new(0.25, 2.0);
+        let p = position(&fees, dec!(0.5), dec!(100));
+        // 买一价上涨 2%（毛利），不足以覆盖两次约 1.56% 的手续费
+        let pnl = p.net_pnl_pct(&fees, dec!(0.51)).unwrap();
+        assert!(pnl < dec!(0), "expected net loss, got {}", pnl);
+        assert!(p.net_pnl_pct(&fees, dec!(0.54)).unwrap() > dec!(0));
+    }
 
-        self.last_mid_price.insert(market_id, mid);
+    #[test]
+    fn no_pnl_without_cost() {
+        let free = FeeModel::free();
+        let mut p = position(&free, dec!(0.5), dec!(100));
+        p.cost = dec!(0);
+        assert_eq!(p.net_pnl_pct(&free, dec!(0.6)), None);
     }
 }
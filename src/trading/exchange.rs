@@ -0,0 +1,387 @@
+//! 交易所抽象：下单（构建+签名+提交）、撤单、查询挂单与成交、订单簿快照，以及 CTF split/merge/redeem 结算。
+//! 实盘使用 ClobExchange（认证后的 CLOB 客户端，signer 只创建一次），
+//! 离线/测试使用 SimulatedExchange（内存撮合，成交确定可复现）。
+
+use anyhow::Result;
+use alloy::signers::Signer;
+use alloy::signers::local::{LocalSigner, PrivateKeySigner};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use polymarket_client_sdk::auth::state::Authenticated;
+use polymarket_client_sdk::auth::Normal;
+use polymarket_client_sdk::clob::types::request::{OrderBookSummaryRequest, OrdersRequest, TradesRequest};
+use polymarket_client_sdk::clob::types::response::{PostOrderResponse, TradeResponse};
+use polymarket_client_sdk::clob::types::{OrderType, Side, SignatureType, TraderSide};
+use polymarket_client_sdk::clob::{Client, Config};
+use polymarket_client_sdk::types::{Address, B256, Decimal, U256};
+use polymarket_client_sdk::POLYGON;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use std::str::FromStr;
+
+use super::user_stream::{subscribe_user_fills, FillStream};
+use crate::merge::{MergeError, MergeOutcome};
+
+pub type AuthenticatedClient = Client<Authenticated<Normal>>;
+
+/// 限价单请求（与交易所实现无关）
+#[derive(Debug, Clone)]
+pub struct OrderRequest {
+    pub token_id: U256,
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub order_type: OrderType,
+    pub expiration: Option<DateTime<Utc>>, // 仅 GTD 设置
+}
+
+impl OrderRequest {
+    pub fn new(token_id: U256, side: Side, price: Decimal, size: Decimal, order_type: OrderType) -> Self {
+        Self { token_id, side, price, size, order_type, expiration: None }
+    }
+
+    /// 仅 GTD 时设置过期时间（SDK 规定非 GTD 不可设过期）
+    pub fn with_expiration(mut self, expiration: DateTime<Utc>) -> Self {
+        if matches!(self.order_type, OrderType::GTD) {
+            self.expiration = Some(expiration);
+        }
+        self
+    }
+}
+
+/// 下单回执：买单 taking_amount 为成交份额、making_amount 为支付 USDC；卖单相反
+#[derive(Debug, Clone)]
+pub struct OrderAck {
+    pub order_id: String,
+    pub success: bool,
+    pub error_msg: Option<String>,
+    pub making_amount: Decimal,
+    pub taking_amount: Decimal,
+}
+
+impl From<PostOrderResponse> for OrderAck {
+    fn from(r: PostOrderResponse) -> Self {
+        Self {
+            order_id: r.order_id,
+            success: r.success,
+            error_msg: r.error_msg,
+            making_amount: r.making_amount,
+            taking_amount: r.taking_amount,
+        }
+    }
+}
+
+/// 活跃挂单
+#[derive(Debug, Clone)]
+pub struct OpenOrder {
+    pub id: String,
+    pub asset_id: U256,
+    pub side: Side,
+    pub price: Decimal,
+    pub original_size: Decimal,
+    pub size_matched: Decimal,
+}
+
+/// 成交记录
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub id: String,
+    pub order_id: String,
+    pub asset_id: U256,
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+/// 订单簿档位
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookLevel {
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+/// 订单簿快照，排序与 WS BookUpdate 一致：bids 价格升序（last 为买一），asks 价格降序（last 为卖一）
+#[derive(Debug, Clone, Default)]
+pub struct BookSnapshot {
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+impl BookSnapshot {
+    pub fn best_bid(&self) -> Option<BookLevel> {
+        self.bids.last().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<BookLevel> {
+        self.asks.last().copied()
+    }
+}
+
+#[async_trait]
+pub trait Exchange: Send + Sync {
+    /// 验证认证是否有效
+    async fn verify_authentication(&self) -> Result<()>;
+
+    /// 批量下单，回执顺序与请求顺序一致
+    async fn post_orders(&self, orders: Vec<OrderRequest>) -> Result<Vec<OrderAck>>;
+
+    /// 单笔下单
+    async fn post_order(&self, order: OrderRequest) -> Result<OrderAck> {
+        self.post_orders(vec![order])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("下单未返回结果"))
+    }
+
+    async fn cancel_orders(&self, order_ids: &[&str]) -> Result<()>;
+
+    async fn cancel_all_orders(&self) -> Result<()>;
+
+    /// 当前账户所有活跃挂单
+    async fn open_orders(&self) -> Result<Vec<OpenOrder>>;
+
+    /// 当前账户成交记录
+    async fn trades(&self) -> Result<Vec<Trade>>;
+
+    /// 单个 token 的订单簿快照
+    async fn book(&self, token_id: U256) -> Result<BookSnapshot>;
+
+    /// 拆分：amount USDC -> YES + NO 各 amount 份，返回交易哈希
+    async fn split(&self, condition_id: B256, amount: Decimal) -> Result<String>;
+
+    /// 合并：按 YES、NO 可用余额的较小值合并回 USDC，交易确认后返回合并结果；无可用份额时返回 MergeError::NothingToMerge
+    async fn merge_max(&self, condition_id: B256) -> Result<MergeOutcome>;
+
+    /// 批量合并：在一笔交易中合并多个市场，交易确认后返回合并结果（只含实际合并的市场）；
+    /// 默认逐个调用 merge_max（无可用份额或失败的市场不计入返回结果），实盘覆盖为 Safe MultiSend / Relayer 多调用
+    async fn merge_max_batch(&self, condition_ids: &[B256]) -> Result<MergeOutcome> {
+        let mut txs = Vec::new();
+        let mut merged = Vec::new();
+        let mut last_err = None;
+        for &condition_id in condition_ids {
+            match self.merge_max(condition_id).await {
+                Ok(outcome) => {
+                    txs.push(outcome.tx_hash);
+                    merged.extend(outcome.merged);
+                }
+                Err(e) if e.downcast_ref::<MergeError>() == Some(&MergeError::NothingToMerge) => {}
+                Err(e) => last_err = Some(e),
+            }
+        }
+        if merged.is_empty() {
+            return Err(last_err.unwrap_or_else(|| MergeError::NothingToMerge.into()));
+        }
+        Ok(MergeOutcome { tx_hash: txs.join(","), merged })
+    }
+
+    /// 赎回：市场结算后按结算比例将 YES、NO 全部余额换回 USDC，返回交易哈希
+    async fn redeem(&self, condition_id: B256) -> Result<String>;
+
+    /// 订阅本账户订单的成交推送（按订单累计成交量）；markets 为空表示所有市场
+    async fn subscribe_fills(&self, markets: Vec<B256>) -> Result<FillStream>;
+}
+
+/// 实盘 CLOB 交易所
+pub struct ClobExchange {
+    client: AuthenticatedClient,
+    signer: PrivateKeySigner,
+    private_key: String, // 链上 split/merge 使用
+    proxy_address: Option<Address>,
+}
+
+impl ClobExchange {
+    /// 创建并认证 CLOB 客户端；提供 proxy_address 时使用 Proxy 签名类型（按照 Python SDK 模式）
+    pub async fn connect(private_key: &str, proxy_address: Option<Address>) -> Result<Self> {
+        let signer = LocalSigner::from_str(private_key)
+            .map_err(|e| anyhow::anyhow!("私钥格式无效: {}. 请确保私钥是64字符的十六进制字符串（不带0x前缀）", e))?
+            .with_chain_id(Some(POLYGON));
+
+        let config = Config::builder().use_server_time(true).build();
+        let mut auth_builder = Client::new("https://clob.polymarket.com", config)
+            .map_err(|e| anyhow::anyhow!("创建CLOB客户端失败: {}", e))?
+            .authentication_builder(&signer);
+
+        if let Some(funder) = proxy_address {
+            auth_builder = auth_builder
+                .funder(funder)
+                .signature_type(SignatureType::Proxy);
+        }
+
+        let client = auth_builder.authenticate().await.map_err(|e| {
+            anyhow::anyhow!(
+                "API认证失败: {}. 可能的原因：1) 私钥无效 2) 网络问题 3) Polymarket API服务不可用",
+                e
+            )
+        })?;
+
+        Ok(Self {
+            client,
+            signer,
+            private_key: private_key.to_string(),
+            proxy_address,
+        })
+    }
+
+    /// 成交记录按我方订单展开：吃单时为 taker 订单；挂单被吃时为 maker_orders 中属于本账户的挂单
+    /// （本账户下单地址为代理钱包或 EOA），数量与价格按该挂单的成交计
+    fn own_trades(&self, trade: TradeResponse) -> Vec<Trade> {
+        if trade.trader_side != TraderSide::Maker {
+            return vec![Trade {
+                id: trade.id,
+                order_id: trade.taker_order_id,
+                asset_id: trade.asset_id,
+                side: trade.side,
+                price: trade.price,
+                size: trade.size,
+            }];
+        }
+        let maker_address = self.proxy_address.unwrap_or_else(|| self.signer.address());
+        trade
+            .maker_orders
+            .into_iter()
+            .filter(|m| m.maker_address == maker_address)
+            .map(|m| Trade {
+                id: trade.id.clone(),
+                order_id: m.order_id,
+                asset_id: m.asset_id,
+                side: m.side,
+                price: m.price,
+                size: m.matched_amount,
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Exchange for ClobExchange {
+    async fn verify_authentication(&self) -> Result<()> {
+        // 按照官方示例，使用 api_keys() 来验证认证状态
+        self.client
+            .api_keys()
+            .await
+            .map_err(|e| anyhow::anyhow!("认证验证失败: API调用返回错误: {}", e))?;
+        Ok(())
+    }
+
+    async fn post_orders(&self, orders: Vec<OrderRequest>) -> Result<Vec<OrderAck>> {
+        // 并行构建并签名
+        let signed = futures::future::try_join_all(orders.into_iter().map(|req| async move {
+            let b = self
+                .client
+                .limit_order()
+                .token_id(req.token_id)
+                .side(req.side)
+                .price(req.price)
+                .size(req.size)
+                .order_type(req.order_type);
+            let order = match req.expiration {
+                Some(expiration) => b.expiration(expiration).build().await?,
+                None => b.build().await?,
+            };
+            anyhow::Ok(self.client.sign(&self.signer, order).await?)
+        }))
+        .await?;
+
+        let results = if signed.len() == 1 {
+            vec![self.client.post_order(signed.into_iter().next().unwrap()).await?]
+        } else {
+            self.client.post_orders(signed).await?
+        };
+        Ok(results.into_iter().map(OrderAck::from).collect())
+    }
+
+    async fn cancel_orders(&self, order_ids: &[&str]) -> Result<()> {
+        self.client
+            .cancel_orders(order_ids)
+            .await
+            .map_err(|e| anyhow::anyhow!("取消订单失败: {}", e))?;
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&self) -> Result<()> {
+        self.client
+            .cancel_all_orders()
+            .await
+            .map_err(|e| anyhow::anyhow!("取消所有挂单失败: {}", e))?;
+        Ok(())
+    }
+
+    async fn open_orders(&self) -> Result<Vec<OpenOrder>> {
+        // 处理分页
+        let mut all_orders = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = self.client.orders(&OrdersRequest::default(), cursor).await?;
+            all_orders.extend(page.data.into_iter().map(|o| OpenOrder {
+                id: o.id,
+                asset_id: o.asset_id,
+                side: o.side,
+                price: o.price,
+                original_size: o.original_size,
+                size_matched: o.size_matched,
+            }));
+            if page.next_cursor.is_empty() || page.next_cursor == "LTE=" {
+                break;
+            }
+            cursor = Some(page.next_cursor);
+        }
+        Ok(all_orders)
+    }
+
+    async fn trades(&self) -> Result<Vec<Trade>> {
+        let mut all_trades = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = self.client.trades(&TradesRequest::default(), cursor).await?;
+            all_trades.extend(page.data.into_iter().flat_map(|t| self.own_trades(t)));
+            if page.next_cursor.is_empty() || page.next_cursor == "LTE=" {
+                break;
+            }
+            cursor = Some(page.next_cursor);
+        }
+        Ok(all_trades)
+    }
+
+    async fn book(&self, token_id: U256) -> Result<BookSnapshot> {
+        let request = OrderBookSummaryRequest::builder().token_id(token_id).build();
+        let book = self.client.order_book(&request).await?;
+        Ok(BookSnapshot {
+            bids: book.bids.into_iter().map(|l| BookLevel { price: l.price, size: l.size }).collect(),
+            asks: book.asks.into_iter().map(|l| BookLevel { price: l.price, size: l.size }).collect(),
+        })
+    }
+
+    async fn split(&self, condition_id: B256, amount: Decimal) -> Result<String> {
+        let proxy = self
+            .proxy_address
+            .ok_or_else(|| anyhow::anyhow!("split 需要配置 POLYMARKET_PROXY_ADDRESS"))?;
+        // USDC 为 6 位小数
+        let raw = (amount * dec!(1_000_000)).trunc().to_u64().unwrap_or(0);
+        crate::merge::split(condition_id, proxy, &self.private_key, None, U256::from(raw)).await
+    }
+
+    async fn merge_max(&self, condition_id: B256) -> Result<MergeOutcome> {
+        let proxy = self
+            .proxy_address
+            .ok_or_else(|| anyhow::anyhow!("merge 需要配置 POLYMARKET_PROXY_ADDRESS"))?;
+        crate::merge::merge_max(condition_id, proxy, &self.private_key, None).await
+    }
+
+    async fn merge_max_batch(&self, condition_ids: &[B256]) -> Result<MergeOutcome> {
+        let proxy = self
+            .proxy_address
+            .ok_or_else(|| anyhow::anyhow!("merge 需要配置 POLYMARKET_PROXY_ADDRESS"))?;
+        crate::merge::merge_max_batch(condition_ids, proxy, &self.private_key, None).await
+    }
+
+    async fn redeem(&self, condition_id: B256) -> Result<String> {
+        let proxy = self
+            .proxy_address
+            .ok_or_else(|| anyhow::anyhow!("redeem 需要配置 POLYMARKET_PROXY_ADDRESS"))?;
+        crate::redeem::redeem(condition_id, proxy, &self.private_key, None).await
+    }
+
+    async fn subscribe_fills(&self, markets: Vec<B256>) -> Result<FillStream> {
+        subscribe_user_fills(&self.client, markets)
+    }
+}
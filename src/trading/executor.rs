@@ -1,18 +1,18 @@
 use anyhow::Result;
-use alloy::signers::Signer;
-use alloy::signers::local::LocalSigner;
-use chrono::Utc;
-use polymarket_client_sdk::clob::{Client, Config};
-use polymarket_client_sdk::clob::types::{OrderType, Side, SignatureType};
-use polymarket_client_sdk::types::{Address, Decimal, U256};
-use polymarket_client_sdk::POLYGON;
+use chrono::{DateTime, Utc};
+use polymarket_client_sdk::clob::types::{OrderType, Side};
+use polymarket_client_sdk::types::{B256, Decimal, U256};
 use rust_decimal_macros::dec;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Instant;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::monitor::arbitrage::ArbitrageOpportunity;
+use super::exchange::{Exchange, OrderAck, OrderRequest};
+use super::fees::FeeSchedule;
+use crate::ledger::{LedgerEntry, LedgerKind, TradeLedger};
+use crate::monitor::arbitrage::{ArbitrageOpportunity, SellArbitrageOpportunity};
 
 pub struct OrderPairResult {
     pub pair_id: String,
@@ -22,57 +22,35 @@ pub struct OrderPairResult {
     pub no_filled: Decimal,
     pub yes_size: Decimal,
     pub no_size: Decimal,
+    pub yes_price: Decimal, // 限价（含滑点），挂单后续成交按此价格记账
+    pub no_price: Decimal,
     pub success: bool,
+    pub yes_resting: bool, // 未成交部分仍在挂单（GTC/GTD 且下单成功）
+    pub no_resting: bool,
+    pub expires_at: Option<DateTime<Utc>>, // GTD 挂单过期时间
 }
 
 pub struct TradingExecutor {
-    client: Client<polymarket_client_sdk::auth::state::Authenticated<polymarket_client_sdk::auth::Normal>>,
-    private_key: String,
+    exchange: Arc<dyn Exchange>,
     max_order_size: Decimal,
     slippage: [Decimal; 2], // [first, second]，仅下降侧用 second，上涨与持平用 first
     gtd_expiration_secs: u64,
     arbitrage_order_type: OrderType,
+    fees: FeeSchedule,
+    ledger: TradeLedger,
 }
 
 impl TradingExecutor {
-    pub async fn new(
-        private_key: String,
+    pub fn new(
+        exchange: Arc<dyn Exchange>,
         max_order_size_usdc: f64,
-        proxy_address: Option<Address>,
         slippage: [f64; 2],
         gtd_expiration_secs: u64,
         arbitrage_order_type: OrderType,
-    ) -> Result<Self> {
-        // 验证私钥格式
-        let signer = LocalSigner::from_str(&private_key)
-            .map_err(|e| anyhow::anyhow!("私钥格式无效: {}. 请确保私钥是64字符的十六进制字符串（不带0x前缀）", e))?
-            .with_chain_id(Some(POLYGON));
-
-        let config = Config::builder().use_server_time(false).build();
-        let mut auth_builder = Client::new("https://clob.polymarket.com", config)
-            .map_err(|e| anyhow::anyhow!("创建CLOB客户端失败: {}", e))?
-            .authentication_builder(&signer);
-        
-        // 如果提供了proxy_address，设置funder和signature_type（按照Python SDK模式）
-        if let Some(funder) = proxy_address {
-            auth_builder = auth_builder
-                .funder(funder)
-                .signature_type(SignatureType::Proxy);
-        }
-        
-        let client = auth_builder
-            .authenticate()
-            .await
-            .map_err(|e| {
-                anyhow::anyhow!(
-                    "API认证失败: {}. 可能的原因：1) 私钥无效 2) 网络问题 3) Polymarket API服务不可用",
-                    e
-                )
-            })?;
-
-        Ok(Self {
-            client,
-            private_key,
+        fees: FeeSchedule,
+    ) -> Self {
+        Self {
+            exchange,
             max_order_size: Decimal::try_from(max_order_size_usdc)
                 .unwrap_or(rust_decimal_macros::dec!(100.0)),
             slippage: [
@@ -81,49 +59,73 @@ impl TradingExecutor {
             ],
             gtd_expiration_secs,
             arbitrage_order_type,
-        })
+            fees,
+            ledger: TradeLedger::disabled(),
+        }
+    }
+
+    /// 下单回执与成交写入交易台账
+    pub fn with_ledger(mut self, ledger: TradeLedger) -> Self {
+        self.ledger = ledger;
+        self
     }
 
-    /// 验证认证是否真的成功 - 按照官方示例使用 api_keys() 来验证
+    /// 记录一笔下单回执（kind 为下单或收尾卖出），见 TradeLedger::record_order
+    fn record_order(
+        &self,
+        kind: LedgerKind,
+        pair_id: Option<&str>,
+        market_id: Option<B256>,
+        request: &OrderRequest,
+        ack: &OrderAck,
+    ) {
+        self.ledger
+            .record_order(kind, pair_id, market_id, request, ack, &self.fees.for_token(request.token_id));
+    }
+
+    /// 验证认证是否真的成功
     pub async fn verify_authentication(&self) -> Result<()> {
-        // 按照官方示例，使用 api_keys() 来验证认证状态
-        self.client.api_keys().await
-            .map_err(|e| anyhow::anyhow!("认证验证失败: API调用返回错误: {}", e))?;
-        Ok(())
+        self.exchange.verify_authentication().await
     }
 
     /// 取消该账户所有挂单（收尾时使用）
-    pub async fn cancel_all_orders(&self) -> Result<polymarket_client_sdk::clob::types::response::CancelOrdersResponse> {
-        self.client
-            .cancel_all_orders()
-            .await
-            .map_err(|e| anyhow::anyhow!("取消所有挂单失败: {}", e))
+    pub async fn cancel_all_orders(&self) -> Result<()> {
+        self.exchange.cancel_all_orders().await
     }
 
-    /// 以指定价格下 GTC 卖单（收尾时市价意图卖出单腿持仓）
+    /// 以指定价格下 GTC 卖单（收尾时市价意图卖出单腿持仓），以收尾卖出记入台账
     pub async fn sell_at_price(
         &self,
         token_id: U256,
         price: Decimal,
         size: Decimal,
-    ) -> Result<polymarket_client_sdk::clob::types::response::PostOrderResponse> {
-        let signer = LocalSigner::from_str(&self.private_key)?
-            .with_chain_id(Some(POLYGON));
-        let order = self
-            .client
-            .limit_order()
-            .token_id(token_id)
-            .side(Side::Sell)
-            .price(price)
-            .size(size)
-            .order_type(OrderType::GTC)
-            .build()
-            .await?;
-        let signed = self.client.sign(&signer, order).await?;
-        self.client
-            .post_order(signed)
+    ) -> Result<OrderAck> {
+        let request = OrderRequest::new(token_id, Side::Sell, price, size, OrderType::GTC);
+        let ack = self
+            .exchange
+            .post_order(request.clone())
             .await
-            .map_err(|e| anyhow::anyhow!("卖出订单提交失败: {}", e))
+            .map_err(|e| anyhow::anyhow!("卖出订单提交失败: {}", e))?;
+        self.record_order(LedgerKind::WindDownSell, None, None, &request, &ack);
+        Ok(ack)
+    }
+
+    /// FAK 吃单：按限价立即与对手盘成交，未成交部分撤销（不留挂单）
+    pub async fn take_at_price(
+        &self,
+        token_id: U256,
+        side: Side,
+        price: Decimal,
+        size: Decimal,
+    ) -> Result<OrderAck> {
+        let request = OrderRequest::new(token_id, side, price, size, OrderType::FAK);
+        let ack = self
+            .exchange
+            .post_order(request.clone())
+            .await
+            .map_err(|e| anyhow::anyhow!("吃单提交失败: {}", e))?;
+        self.record_order(LedgerKind::OrderSubmitted, None, None, &request, &ack);
+        Ok(ack)
     }
 
     /// 按方向取滑点：仅下降(↓)用 second，上涨(↑)和持平(−/空)用 first
@@ -174,16 +176,17 @@ impl TradingExecutor {
         let expiration = Utc::now() + chrono::Duration::seconds(self.gtd_expiration_secs as i64);
 
         // 滑点按涨跌方向分配：上涨=first，下降/持平=second
+        // 限价以深度计划中最差一档为基准，保证按计划逐档吃单
         let yes_slippage_apply = self.slippage_for_direction(yes_dir);
         let no_slippage_apply = self.slippage_for_direction(no_dir);
-        let yes_price_with_slippage = (opp.yes_ask_price + yes_slippage_apply).min(dec!(1.0));
-        let no_price_with_slippage = (opp.no_ask_price + no_slippage_apply).min(dec!(1.0));
+        let yes_price_with_slippage = (opp.yes_worst_price + yes_slippage_apply).min(dec!(1.0));
+        let no_price_with_slippage = (opp.no_worst_price + no_slippage_apply).min(dec!(1.0));
         
         // 打印选档信息（加滑点后的价格）
         info!(
-            "📋 选档 | YES {:.4}×{:.2} NO {:.4}×{:.2}",
-            yes_price_with_slippage, order_size,
-            no_price_with_slippage, order_size
+            "📋 选档 | YES {:.4}×{:.2} ({}档 VWAP {:.4}) NO {:.4}×{:.2} ({}档 VWAP {:.4})",
+            yes_price_with_slippage, order_size, opp.yes_fills.len(), opp.yes_vwap,
+            no_price_with_slippage, order_size, opp.no_fills.len(), opp.no_vwap
         );
         
         let expiry_suffix = if matches!(self.arbitrage_order_type, OrderType::GTD) {
@@ -193,8 +196,8 @@ impl TradingExecutor {
         };
         info!(
             "📤 下单 | YES {:.4}→{:.4}×{} NO {:.4}→{:.4}×{} | {}{}",
-            opp.yes_ask_price, yes_price_with_slippage, order_size,
-            opp.no_ask_price, no_price_with_slippage, order_size,
+            opp.yes_worst_price, yes_price_with_slippage, order_size,
+            opp.no_worst_price, no_price_with_slippage, order_size,
             self.arbitrage_order_type, expiry_suffix
         );
 
@@ -212,80 +215,48 @@ impl TradingExecutor {
             ));
         }
 
-        // 性能计时：并行构建YES和NO订单开始
-        let build_start = Instant::now();
-        
-        // 并行构建YES和NO订单；仅 GTD 时设置 expiration（SDK 规定非 GTD 不可设过期）
-        let (yes_order, no_order) = tokio::join!(
-            async {
-                let b = self.client
-                    .limit_order()
-                    .token_id(yes_token_id)
-                    .side(Side::Buy)
-                    .price(yes_price_with_slippage)
-                    .size(order_size)
-                    .order_type(self.arbitrage_order_type.clone());
-                if matches!(&self.arbitrage_order_type, OrderType::GTD) {
-                    b.expiration(expiration).build().await
-                } else {
-                    b.build().await
-                }
-            },
-            async {
-                let b = self.client
-                    .limit_order()
-                    .token_id(no_token_id)
-                    .side(Side::Buy)
-                    .price(no_price_with_slippage)
-                    .size(order_size)
-                    .order_type(self.arbitrage_order_type.clone());
-                if matches!(&self.arbitrage_order_type, OrderType::GTD) {
-                    b.expiration(expiration).build().await
-                } else {
-                    b.build().await
-                }
-            }
+        // 下单前检查：按含滑点限价（最坏成交价）扣除两腿手续费后，净利润不得为负
+        let fee_model = self.fees.for_token(opp.yes_token_id);
+        let worst_fee = fee_model.pair_fee_shares(
+            fee_model.fee_shares(yes_price_with_slippage, order_size),
+            fee_model.fee_shares(no_price_with_slippage, order_size),
         );
-        
-        let yes_order = yes_order?;
-        let no_order = no_order?;
-        let build_elapsed = build_start.elapsed().as_millis();
+        let worst_net_profit = order_size - worst_fee - yes_amount_usd - no_amount_usd;
+        if worst_net_profit < dec!(0) {
+            warn!(
+                "⏭️ 跳过下单 | 含滑点最坏净利润:{:.4} USD (手续费:{:.4}份) | 滑点或手续费吞噬利润",
+                worst_net_profit, worst_fee
+            );
+            return Err(anyhow::anyhow!(
+                "含滑点与手续费后最坏净利润为负: {:.4} USD",
+                worst_net_profit
+            ));
+        }
 
-        // 性能计时：并行签名开始
-        let sign_start = Instant::now();
-        
-        // 创建signer
-        let signer = LocalSigner::from_str(&self.private_key)?
-            .with_chain_id(Some(POLYGON));
-        
-        // 并行签名YES和NO订单
-        let (signed_yes_result, signed_no_result) = tokio::join!(
-            self.client.sign(&signer, yes_order),
-            self.client.sign(&signer, no_order)
-        );
-        
-        let signed_yes = signed_yes_result?;
-        let signed_no = signed_no_result?;
-        let sign_elapsed = sign_start.elapsed().as_millis();
+        // 仅 GTD 时设置 expiration（SDK 规定非 GTD 不可设过期）
+        let yes_request = OrderRequest::new(yes_token_id, Side::Buy, yes_price_with_slippage, order_size, self.arbitrage_order_type.clone())
+            .with_expiration(expiration);
+        let no_request = OrderRequest::new(no_token_id, Side::Buy, no_price_with_slippage, order_size, self.arbitrage_order_type.clone())
+            .with_expiration(expiration);
 
-        // 性能计时：发送订单开始
+        // 性能计时：构建、签名与发送开始
         let send_start = Instant::now();
         
         // 单价高的排前面发送；提交后需按相同顺序从 results 中解析 yes_result / no_result
         let yes_first = yes_price_with_slippage >= no_price_with_slippage;
-        let orders_to_send: Vec<_> = if yes_first {
-            vec![signed_yes, signed_no]
+        let orders_to_send = if yes_first {
+            vec![yes_request.clone(), no_request.clone()]
         } else {
-            vec![signed_no, signed_yes]
+            vec![no_request.clone(), yes_request.clone()]
         };
-        let results = match self.client.post_orders(orders_to_send).await {
+        let results = match self.exchange.post_orders(orders_to_send).await {
             Ok(results) => {
                 let send_elapsed = send_start.elapsed().as_millis();
                 let total_elapsed = total_start.elapsed().as_millis();
                 
                 info!(
-                    "⏱️ 耗时 | {} | 构建{}ms 签名{}ms 发送{}ms 总{}ms",
-                    &pair_id[..8], build_elapsed, sign_elapsed, send_elapsed, total_elapsed
+                    "⏱️ 耗时 | {} | 构建签名发送{}ms 总{}ms",
+                    &pair_id[..8], send_elapsed, total_elapsed
                 );
                 
                 results
@@ -295,13 +266,11 @@ impl TradingExecutor {
                 let total_elapsed = total_start.elapsed().as_millis();
                 
                 error!(
-                    "❌ 批量下单API调用失败 | 订单对ID:{} | YES价格:{} (含滑点) | NO价格:{} (含滑点) | 数量:{} | 构建耗时:{}ms | 签名耗时:{}ms | 发送耗时:{}ms | 总耗时:{}ms | 错误:{}",
+                    "❌ 批量下单API调用失败 | 订单对ID:{} | YES价格:{} (含滑点) | NO价格:{} (含滑点) | 数量:{} | 构建签名发送耗时:{}ms | 总耗时:{}ms | 错误:{}",
                     &pair_id[..8],
                     yes_price_with_slippage,
                     no_price_with_slippage,
                     order_size,
-                    build_elapsed,
-                    sign_elapsed,
                     send_elapsed,
                     total_elapsed,
                     e
@@ -329,6 +298,8 @@ impl TradingExecutor {
         } else {
             (&results[1], &results[0])
         };
+        self.record_order(LedgerKind::OrderSubmitted, Some(&pair_id), Some(opp.market_id), &yes_request, yes_result);
+        self.record_order(LedgerKind::OrderSubmitted, Some(&pair_id), Some(opp.market_id), &no_request, no_result);
 
         // 订单返回结果详情已移除，只保留关键信息在后续日志中
 
@@ -336,10 +307,16 @@ impl TradingExecutor {
         let yes_filled = yes_result.taking_amount;
         let no_filled = no_result.taking_amount;
 
+        // GTC/GTD 下单成功且未全部成交时，剩余部分挂在订单簿上，之后的成交由用户频道推送
+        let rests = matches!(self.arbitrage_order_type, OrderType::GTC | OrderType::GTD);
+        let yes_resting = rests && yes_result.success && yes_filled < order_size;
+        let no_resting = rests && no_result.success && no_filled < order_size;
+        let expires_at = matches!(self.arbitrage_order_type, OrderType::GTD).then_some(expiration);
+
         // 对于GTD订单，如果无法在90秒内全部成交，订单会在过期后取消
         // 我们应该检查实际的成交数量，而不是 success 字段
-        // 只有在两个订单都完全没有成交时，才返回错误
-        if yes_filled == dec!(0) && no_filled == dec!(0) {
+        // 只有在两个订单都完全没有成交且都没有挂单时，才返回错误
+        if yes_filled == dec!(0) && no_filled == dec!(0) && !yes_resting && !no_resting {
             // 提取简化的错误信息
             let yes_error_msg = yes_result
                 .error_msg
@@ -406,17 +383,17 @@ impl TradingExecutor {
                 .unwrap_or("未知错误");
 
             // 简化错误消息
-            let yes_error_simple = if yes_error_msg.contains("no orders found to match") {
-                "部分未成交（已挂单）"
-            } else if yes_error_msg.contains("GTD") || yes_error_msg.contains("FOK") || yes_error_msg.contains("FAK") || yes_error_msg.contains("GTC") {
+            let yes_error_simple = if yes_error_msg.contains("no orders found to match")
+                || yes_error_msg.contains("GTD") || yes_error_msg.contains("FOK") || yes_error_msg.contains("FAK") || yes_error_msg.contains("GTC")
+            {
                 "部分未成交（已挂单）"
             } else {
                 "状态异常"
             };
             
-            let no_error_simple = if no_error_msg.contains("no orders found to match") {
-                "部分未成交（已挂单）"
-            } else if no_error_msg.contains("GTD") || no_error_msg.contains("FOK") || no_error_msg.contains("FAK") || no_error_msg.contains("GTC") {
+            let no_error_simple = if no_error_msg.contains("no orders found to match")
+                || no_error_msg.contains("GTD") || no_error_msg.contains("FOK") || no_error_msg.contains("FAK") || no_error_msg.contains("GTC")
+            {
                 "部分未成交（已挂单）"
             } else {
                 "状态异常"
@@ -445,7 +422,13 @@ impl TradingExecutor {
         }
 
         // 根据成交情况打印不同的日志
-        if yes_filled > dec!(0) && no_filled > dec!(0) {
+        if yes_filled == dec!(0) && no_filled == dec!(0) {
+            info!(
+                "📌 已挂单 | 订单对ID:{} | 两腿暂未成交，等待后续成交{}",
+                &pair_id[..8],
+                expiry_suffix
+            );
+        } else if yes_filled > dec!(0) && no_filled > dec!(0) {
             info!(
                 "✅ 套利交易成功 | 订单对ID:{} | YES成交:{}份 | NO成交:{}份 | 总成交:{}份",
                 &pair_id[..8],
@@ -476,7 +459,167 @@ impl TradingExecutor {
             no_filled,
             yes_size: order_size,
             no_size: order_size,
+            yes_price: yes_price_with_slippage,
+            no_price: no_price_with_slippage,
+            success: true,
+            yes_resting,
+            no_resting,
+            expires_at,
+        })
+    }
+
+    /// 执行卖方向套利：先用 USDC split 出等量 YES+NO，再批量挂卖两腿
+    /// 卖单限价以深度计划中最差（最低）一档为基准减去滑点；卖单成交份额取 making_amount
+    /// 返回错误时未发生 split；split 之后的卖出失败以 success=false 的结果返回
+    pub async fn execute_split_and_sell(
+        &self,
+        opp: &SellArbitrageOpportunity,
+        yes_dir: &str,
+        no_dir: &str,
+    ) -> Result<OrderPairResult> {
+        let total_start = Instant::now();
+        let yes_token_id = U256::from_str(&opp.yes_token_id.to_string())?;
+        let no_token_id = U256::from_str(&opp.no_token_id.to_string())?;
+
+        // split 份额 = 拆分 USDC 数量，按最大订单限制截断并取 2 位小数
+        let order_size = (opp.size.min(self.max_order_size) * dec!(100.0)).floor() / dec!(100.0);
+        let pair_id = Uuid::new_v4().to_string();
+
+        // 卖出滑点方向与买入相反：价格下跌时多让一些
+        let yes_price_with_slippage = (opp.yes_worst_price - self.slippage_for_direction(yes_dir)).max(dec!(0.01));
+        let no_price_with_slippage = (opp.no_worst_price - self.slippage_for_direction(no_dir)).max(dec!(0.01));
+
+        let yes_amount_usd = yes_price_with_slippage * order_size;
+        let no_amount_usd = no_price_with_slippage * order_size;
+        if yes_amount_usd <= dec!(1) || no_amount_usd <= dec!(1) {
+            warn!(
+                "⏭️ 跳过拆分卖出 | YES金额:{:.2} USD NO金额:{:.2} USD | 双边均须 > $1",
+                yes_amount_usd, no_amount_usd
+            );
+            return Err(anyhow::anyhow!(
+                "卖出金额不满足交易所最小要求: YES {:.2} USD, NO {:.2} USD，双边均须 > $1",
+                yes_amount_usd, no_amount_usd
+            ));
+        }
+
+        // 下单前检查：按含滑点限价（最坏成交价）扣除两腿卖出手续费后，净利润不得为负
+        let fee_model = self.fees.for_token(opp.yes_token_id);
+        let worst_fee = fee_model.sell_fee_usd(yes_price_with_slippage, order_size)
+            + fee_model.sell_fee_usd(no_price_with_slippage, order_size);
+        let worst_net_profit = yes_amount_usd + no_amount_usd - worst_fee - order_size;
+        if worst_net_profit < dec!(0) {
+            warn!(
+                "⏭️ 跳过拆分卖出 | 含滑点最坏净利润:{:.4} USD (手续费:{:.4} USD) | 滑点或手续费吞噬利润",
+                worst_net_profit, worst_fee
+            );
+            return Err(anyhow::anyhow!(
+                "含滑点与手续费后最坏净利润为负: {:.4} USD",
+                worst_net_profit
+            ));
+        }
+
+        info!(
+            "📋 拆分卖出 | {} | split {:.2} USDC | YES {:.4}→{:.4} ({}档 VWAP {:.4}) NO {:.4}→{:.4} ({}档 VWAP {:.4})",
+            &pair_id[..8], order_size,
+            opp.yes_worst_price, yes_price_with_slippage, opp.yes_fills.len(), opp.yes_vwap,
+            opp.no_worst_price, no_price_with_slippage, opp.no_fills.len(), opp.no_vwap
+        );
+
+        // 1) split：USDC -> YES + NO
+        let split_start = Instant::now();
+        let tx = self.exchange.split(opp.market_id, order_size).await?;
+        let split_elapsed = split_start.elapsed().as_millis();
+        info!("✅ split 完成 | {} | {:.2} 份 | tx={}", &pair_id[..8], order_size, tx);
+        self.ledger.record(
+            LedgerEntry::new(LedgerKind::Split)
+                .with_pair(pair_id.clone())
+                .with_market(opp.market_id)
+                .with_size(order_size)
+                .with_amount(order_size)
+                .with_tx(tx.clone()),
+        );
+
+        // 2) 批量挂卖两腿
+        let expiration = Utc::now() + chrono::Duration::seconds(self.gtd_expiration_secs as i64);
+        let sell_requests = vec![
+            OrderRequest::new(yes_token_id, Side::Sell, yes_price_with_slippage, order_size, self.arbitrage_order_type.clone())
+                .with_expiration(expiration),
+            OrderRequest::new(no_token_id, Side::Sell, no_price_with_slippage, order_size, self.arbitrage_order_type.clone())
+                .with_expiration(expiration),
+        ];
+
+        // split 已完成：之后的失败不再返回错误，而是返回无成交、无挂单的订单对，由风控登记 split 得到的持仓
+        let send_start = Instant::now();
+        let results = match self.exchange.post_orders(sell_requests.clone()).await {
+            Ok(results) if results.len() == 2 => results,
+            outcome => {
+                let reason = match outcome {
+                    Ok(results) => format!("返回结果数量不正确 | 期望:2 | 实际:{}", results.len()),
+                    Err(e) => e.to_string(),
+                };
+                error!(
+                    "❌ 拆分后批量卖出失败 | 订单对ID:{} | 已持有 YES/NO 各 {} 份，交由风控处理 | 错误:{}",
+                    &pair_id[..8], order_size, reason
+                );
+                return Ok(OrderPairResult {
+                    pair_id,
+                    yes_order_id: String::new(),
+                    no_order_id: String::new(),
+                    yes_filled: dec!(0),
+                    no_filled: dec!(0),
+                    yes_size: order_size,
+                    no_size: order_size,
+                    yes_price: yes_price_with_slippage,
+                    no_price: no_price_with_slippage,
+                    success: false,
+                    yes_resting: false,
+                    no_resting: false,
+                    expires_at: None,
+                });
+            }
+        };
+        let (yes_result, no_result) = (&results[0], &results[1]);
+        self.record_order(LedgerKind::OrderSubmitted, Some(&pair_id), Some(opp.market_id), &sell_requests[0], yes_result);
+        self.record_order(LedgerKind::OrderSubmitted, Some(&pair_id), Some(opp.market_id), &sell_requests[1], no_result);
+        let yes_filled = yes_result.making_amount;
+        let no_filled = no_result.making_amount;
+        let rests = matches!(self.arbitrage_order_type, OrderType::GTC | OrderType::GTD);
+        let expires_at = matches!(self.arbitrage_order_type, OrderType::GTD).then_some(expiration);
+
+        info!(
+            "⏱️ 耗时 | {} | split{}ms 发送{}ms 总{}ms",
+            &pair_id[..8],
+            split_elapsed,
+            send_start.elapsed().as_millis(),
+            total_start.elapsed().as_millis()
+        );
+
+        if yes_filled >= order_size && no_filled >= order_size {
+            info!(
+                "✅ 拆分卖出成功 | 订单对ID:{} | YES卖出:{}份 | NO卖出:{}份",
+                &pair_id[..8], yes_filled, no_filled
+            );
+        } else {
+            warn!(
+                "⚠️ 拆分卖出未完全成交 | 订单对ID:{} | YES卖出:{}/{}份 | NO卖出:{}/{}份 | 剩余持仓可 merge 回收",
+                &pair_id[..8], yes_filled, order_size, no_filled, order_size
+            );
+        }
+
+        Ok(OrderPairResult {
+            pair_id,
+            yes_order_id: yes_result.order_id.clone(),
+            no_order_id: no_result.order_id.clone(),
+            yes_filled,
+            no_filled,
+            yes_size: order_size,
+            no_size: order_size,
+            yes_price: yes_price_with_slippage,
+            no_price: no_price_with_slippage,
             success: true,
+            yes_resting: rests && yes_result.success && yes_filled < order_size,
+            no_resting: rests && no_result.success && no_filled < order_size,
+            expires_at,
         })
     }
 }
@@ -0,0 +1,186 @@
+//! 手续费模型：taker 手续费按成交价计算，买入时以份额形式扣除。
+//!
+//! 公式: fee_pct = c * fee_rate * (p * (1-p))^exponent，其中 c = 100，
+//! 结果为百分比（默认参数下介于 0-1.56 之间），实际到手份额 = 成交份额 * (100 - fee_pct) / 100。
+//!
+//! 各市场的手续费在发现市场时按 Gamma 元数据登记到 FeeSchedule，配置中的参数仅作未登记市场的默认值。
+
+use dashmap::DashMap;
+use polymarket_client_sdk::types::{Decimal, U256};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use std::sync::Arc;
+
+use crate::config::Config as BotConfig;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeeModel {
+    fee_rate: f64,
+    exponent: f64,
+}
+
+impl FeeModel {
+    pub fn new(fee_rate: f64, exponent: f64) -> Self {
+        Self { fee_rate, exponent }
+    }
+
+    pub fn from_config(config: &BotConfig) -> Self {
+        Self::new(config.taker_fee_rate, config.taker_fee_exponent)
+    }
+
+    /// 不收手续费的市场
+    pub fn free() -> Self {
+        Self::new(0.0, 1.0)
+    }
+
+    /// 按 Gamma 市场元数据确定手续费：`feesEnabled=false` 或 `takerBaseFee=0` 时不收费；
+    /// 元数据缺失或已启用时使用 fallback 的费率与指数（Gamma 未公布费率曲线参数）
+    pub fn from_market(fees_enabled: Option<bool>, taker_base_fee: Option<i32>, fallback: FeeModel) -> Self {
+        if fees_enabled == Some(false) || taker_base_fee == Some(0) {
+            Self::free()
+        } else {
+            fallback
+        }
+    }
+
+    /// 指定成交价下的手续费百分比（0-100）
+    pub fn fee_pct(&self, price: Decimal) -> Decimal {
+        let p = price.to_f64().unwrap_or(0.0);
+        let c = 100.0; // 固定为100
+        let base = p * (1.0 - p);
+        let fee_value = c * self.fee_rate * base.powf(self.exponent);
+        Decimal::try_from(fee_value).unwrap_or(dec!(0))
+    }
+
+    /// 按成交价买入 size 份时被扣除的份额
+    pub fn fee_shares(&self, price: Decimal, size: Decimal) -> Decimal {
+        size * self.fee_pct(price) / dec!(100.0)
+    }
+
+    /// 扣除手续费后的实际可用份额 = 成交份额 * (100 - Fee) / 100
+    /// 如果 Fee >= 100，说明异常情况，返回最小可交易单位
+    pub fn net_shares(&self, price: Decimal, size: Decimal) -> Decimal {
+        let fee_pct = self.fee_pct(price);
+        if fee_pct >= dec!(100.0) {
+            dec!(0.01)
+        } else {
+            size * (dec!(100.0) - fee_pct) / dec!(100.0)
+        }
+    }
+
+    /// 可卖出份额：扣除手续费后向下取整到 2 位小数（Polymarket要求），为 0 时使用最小单位 0.01
+    pub fn sellable_size(&self, entry_price: Decimal, size: Decimal) -> Decimal {
+        let floored = (self.net_shares(entry_price, size) * dec!(100.0)).floor() / dec!(100.0);
+        if floored.is_zero() {
+            dec!(0.01)
+        } else {
+            floored
+        }
+    }
+
+    /// 卖出 size 份时的手续费（USD）：卖出手续费从所得 USDC 中扣除
+    pub fn sell_fee_usd(&self, price: Decimal, size: Decimal) -> Decimal {
+        price * size * self.fee_pct(price) / dec!(100.0)
+    }
+
+    /// 一对 YES+NO 的手续费损耗（份额）：两腿各自被扣份额后，可 merge 的对数取决于扣得多的一腿
+    pub fn pair_fee_shares(&self, yes_fee_shares: Decimal, no_fee_shares: Decimal) -> Decimal {
+        yes_fee_shares.max(no_fee_shares)
+    }
+}
+
+/// 按 token 登记的各市场手续费，未登记的 token 使用默认模型；克隆后共享同一份登记表
+#[derive(Debug, Clone)]
+pub struct FeeSchedule {
+    default: FeeModel,
+    by_token: Arc<DashMap<U256, FeeModel>>,
+}
+
+impl FeeSchedule {
+    pub fn new(default: FeeModel) -> Self {
+        Self {
+            default,
+            by_token: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &BotConfig) -> Self {
+        Self::new(FeeModel::from_config(config))
+    }
+
+    /// 发现市场时登记其 YES / NO 两个 token 的手续费模型
+    pub fn register_market(&self, yes_token_id: U256, no_token_id: U256, model: FeeModel) {
+        self.by_token.insert(yes_token_id, model);
+        self.by_token.insert(no_token_id, model);
+    }
+
+    /// 市场窗口结束后不再交易：注销两个 token 的登记，避免登记表随窗口无限增长
+    pub fn unregister_market(&self, yes_token_id: U256, no_token_id: U256) {
+        self.by_token.remove(&yes_token_id);
+        self.by_token.remove(&no_token_id);
+    }
+
+    /// 配置中的默认手续费模型
+    pub fn default_model(&self) -> FeeModel {
+        self.default
+    }
+
+    /// 指定 token 的手续费模型
+    pub fn for_token(&self, token_id: U256) -> FeeModel {
+        self.by_token.get(&token_id).map(|m| *m).unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Decimal, expected: Decimal) {
+        assert!((actual - expected).abs() < dec!(0.0000001), "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn fee_pct_follows_published_curve() {
+        let model = FeeModel::new(0.25, 2.0);
+        // 100 * 0.25 * (0.5 * 0.5)^2 = 1.5625%
+        assert_close(model.fee_pct(dec!(0.5)), dec!(1.5625));
+        // 100 * 0.25 * (0.01 * 0.99)^2 = 0.00245025%，两端对称
+        assert_close(model.fee_pct(dec!(0.01)), dec!(0.00245025));
+        assert_close(model.fee_pct(dec!(0.99)), dec!(0.00245025));
+        assert_close(model.fee_shares(dec!(0.5), dec!(100)), dec!(1.5625));
+        assert_close(model.net_shares(dec!(0.5), dec!(100)), dec!(98.4375));
+        assert_close(model.sell_fee_usd(dec!(0.5), dec!(100)), dec!(0.78125));
+    }
+
+    #[test]
+    fn pair_fee_is_the_larger_leg() {
+        let model = FeeModel::new(0.25, 2.0);
+        assert_eq!(model.pair_fee_shares(dec!(0.3), dec!(0.5)), dec!(0.5));
+        assert_eq!(model.pair_fee_shares(dec!(0.5), dec!(0.3)), dec!(0.5));
+    }
+
+    #[test]
+    fn from_market_uses_metadata_then_fallback() {
+        let fallback = FeeModel::new(0.25, 2.0);
+        let free = |m: FeeModel| m.fee_pct(dec!(0.5)).is_zero();
+
+        assert!(free(FeeModel::from_market(Some(false), Some(1000), fallback)));
+        assert!(free(FeeModel::from_market(Some(true), Some(0), fallback)));
+        assert!(free(FeeModel::from_market(None, Some(0), fallback)));
+        assert_close(FeeModel::from_market(Some(true), Some(1000), fallback).fee_pct(dec!(0.5)), dec!(1.5625));
+        assert_close(FeeModel::from_market(None, None, fallback).fee_pct(dec!(0.5)), dec!(1.5625));
+    }
+
+    #[test]
+    fn schedule_registers_and_unregisters_markets() {
+        let schedule = FeeSchedule::new(FeeModel::new(0.25, 2.0));
+        let (yes, no) = (U256::from(1u64), U256::from(2u64));
+        schedule.register_market(yes, no, FeeModel::free());
+        assert!(schedule.for_token(yes).fee_pct(dec!(0.5)).is_zero());
+        assert!(schedule.clone().for_token(no).fee_pct(dec!(0.5)).is_zero());
+
+        schedule.unregister_market(yes, no);
+        assert_close(schedule.for_token(yes).fee_pct(dec!(0.5)), dec!(1.5625));
+        assert!(schedule.by_token.is_empty());
+    }
+}
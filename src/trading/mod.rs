@@ -1,4 +1,12 @@
+pub mod exchange;
 pub mod executor;
+pub mod fees;
 pub mod orders;
+pub mod simulated;
+pub mod user_stream;
 
+pub use exchange::{ClobExchange, Exchange};
 pub use executor::TradingExecutor;
+pub use fees::{FeeModel, FeeSchedule};
+pub use simulated::SimulatedExchange;
+pub use user_stream::{FillEvent, FillStream};
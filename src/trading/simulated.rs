@@ -0,0 +1,771 @@
+//! 内存模拟交易所：按当前订单簿快照逐档撮合，成交确定可复现。
+//! 吃单按对手盘档位价格成交并消耗该档流动性（薄档位会部分成交）；GTC/GTD 未成交部分挂单，
+//! FOK 不能全部成交则整单拒绝，FAK 剩余部分撤销。
+//! 挂单按队列位置成交：挂入时记下同价位已有的挂单量（排在前面），之后订单簿更新时该价位减少的量
+//! 先消耗前面的队列，超出部分才成交到我们的挂单；对手盘价格穿过挂单价时直接按挂单价成交。
+//! 同时维护虚拟 USDC 与各 token 余额：吃单按 FeeModel 扣手续费（买入扣份额、卖出扣 USDC），挂单成交免手续费；
+//! split / merge 按 1 USDC = 1 YES + 1 NO 记账。时钟默认取系统时间，回测时由调用方按录制时间推进。
+//! 每笔成交同时通过广播通道推送累计成交量（与实盘用户频道一致的 FillEvent）。
+//! 用于 DRY_RUN 模拟盘、回测与离线测试。
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use polymarket_client_sdk::clob::types::{OrderType, Side};
+use polymarket_client_sdk::clob::ws::types::response::BookUpdate;
+use polymarket_client_sdk::types::{B256, Decimal, U256};
+use rust_decimal_macros::dec;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use super::exchange::{BookLevel, BookSnapshot, Exchange, OpenOrder, OrderAck, OrderRequest, Trade};
+use super::fees::FeeSchedule;
+use super::user_stream::{FillEvent, FillStream};
+use crate::merge::{MergeError, MergeOutcome};
+
+/// 模拟挂单
+#[derive(Debug, Clone)]
+struct RestingOrder {
+    order: OpenOrder,
+    expiration: Option<DateTime<Utc>>,
+    queue_ahead: Decimal, // 同价位排在前面的份额
+}
+
+struct SimState {
+    books: HashMap<U256, BookSnapshot>,
+    resting: BTreeMap<u64, RestingOrder>, // 按提交顺序撮合
+    trades: Vec<Trade>,
+    next_id: u64,
+    markets: HashMap<B256, (U256, U256)>, // condition_id -> (yes_token_id, no_token_id)，split/merge 使用
+    token_market: HashMap<U256, B256>,
+    market_cash: HashMap<B256, Decimal>, // 各市场的 USDC 净流入（成交、split、merge），用于分市场盈亏
+    fees: FeeSchedule,
+    initial_usdc: Decimal,
+    usdc: Decimal,
+    tokens: HashMap<U256, Decimal>,
+    fees_paid: Decimal, // 累计手续费（USD，买入扣的份额按成交价折算）
+    now: Option<DateTime<Utc>>, // 模拟时钟；None 时使用系统时间
+    order_matched: HashMap<String, Decimal>, // 各订单累计成交份额
+    fill_tx: broadcast::Sender<FillEvent>,
+}
+
+/// 订单簿中某价位的挂单量（按挂单方向：买单看 bids，卖单看 asks）
+fn level_size(book: &BookSnapshot, side: Side, price: Decimal) -> Decimal {
+    let levels = match side {
+        Side::Buy => &book.bids,
+        _ => &book.asks,
+    };
+    levels.iter().filter(|l| l.price == price).map(|l| l.size).sum()
+}
+
+impl SimState {
+    fn clock(&self) -> DateTime<Utc> {
+        self.now.unwrap_or_else(Utc::now)
+    }
+
+    fn add_market_cash(&mut self, token_id: U256, delta: Decimal) {
+        if let Some(condition_id) = self.token_market.get(&token_id).copied() {
+            *self.market_cash.entry(condition_id).or_insert(dec!(0)) += delta;
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn token_balance(&self, token_id: U256) -> Decimal {
+        self.tokens.get(&token_id).copied().unwrap_or(dec!(0))
+    }
+
+    fn add_tokens(&mut self, token_id: U256, delta: Decimal) {
+        let balance = self.tokens.entry(token_id).or_insert(dec!(0));
+        *balance += delta;
+        if *balance <= dec!(0) {
+            self.tokens.remove(&token_id);
+        }
+    }
+
+    /// 未成交买单占用的 USDC
+    fn reserved_usdc(&self) -> Decimal {
+        self.resting
+            .values()
+            .map(|r| &r.order)
+            .filter(|o| o.side == Side::Buy)
+            .map(|o| (o.original_size - o.size_matched) * o.price)
+            .sum()
+    }
+
+    /// 未成交卖单占用的 token 份额
+    fn reserved_tokens(&self, token_id: U256) -> Decimal {
+        self.resting
+            .values()
+            .map(|r| &r.order)
+            .filter(|o| o.side != Side::Buy && o.asset_id == token_id)
+            .map(|o| o.original_size - o.size_matched)
+            .sum()
+    }
+
+    /// 从对手盘最优档开始逐档成交，返回 (成交份额, 成交金额)
+    /// as_maker：挂单被动成交时按自身限价计价且不收手续费，否则按对手盘档位价格计价并收 taker 手续费
+    fn take_liquidity(&mut self, order_id: &str, req: &OrderRequest, size: Decimal, as_maker: bool) -> (Decimal, Decimal) {
+        let book = self.books.entry(req.token_id).or_default();
+        let levels = match req.side {
+            Side::Buy => &mut book.asks,
+            _ => &mut book.bids,
+        };
+
+        let mut filled = dec!(0);
+        let mut notional = dec!(0);
+        let mut fills = Vec::new();
+        while filled < size {
+            let Some(level) = levels.last_mut() else { break };
+            let crosses = match req.side {
+                Side::Buy => level.price <= req.price,
+                _ => level.price >= req.price,
+            };
+            if !crosses {
+                break;
+            }
+            let take = level.size.min(size - filled);
+            let price = if as_maker { req.price } else { level.price };
+            filled += take;
+            notional += take * price;
+            fills.push((price, take));
+            level.size -= take;
+            if level.size <= dec!(0) {
+                levels.pop();
+            }
+        }
+
+        for (price, take) in fills {
+            self.settle_fill(req.token_id, req.side, price, take, as_maker);
+            self.record_trade(order_id, req.token_id, req.side, price, take);
+        }
+        (filled, notional)
+    }
+
+    /// 成交记账：买入付 USDC 得份额（taker 扣份额手续费），卖出付份额得 USDC（taker 扣 USDC 手续费）
+    fn settle_fill(&mut self, token_id: U256, side: Side, price: Decimal, size: Decimal, as_maker: bool) {
+        let fee_model = self.fees.for_token(token_id);
+        match side {
+            Side::Buy => {
+                let fee_shares = if as_maker { dec!(0) } else { fee_model.fee_shares(price, size) };
+                self.usdc -= price * size;
+                self.add_market_cash(token_id, -price * size);
+                self.add_tokens(token_id, size - fee_shares);
+                self.fees_paid += fee_shares * price;
+            }
+            _ => {
+                let fee_usd = if as_maker { dec!(0) } else { fee_model.sell_fee_usd(price, size) };
+                self.add_tokens(token_id, -size);
+                self.usdc += price * size - fee_usd;
+                self.add_market_cash(token_id, price * size - fee_usd);
+                self.fees_paid += fee_usd;
+            }
+        }
+    }
+
+    /// 可立即成交的份额（FOK 预检查，不消耗流动性）
+    fn available_liquidity(&self, req: &OrderRequest) -> Decimal {
+        let Some(book) = self.books.get(&req.token_id) else { return dec!(0) };
+        match req.side {
+            Side::Buy => book.asks.iter().filter(|l| l.price <= req.price).map(|l| l.size).sum(),
+            _ => book.bids.iter().filter(|l| l.price >= req.price).map(|l| l.size).sum(),
+        }
+    }
+
+    fn record_trade(&mut self, order_id: &str, asset_id: U256, side: Side, price: Decimal, size: Decimal) {
+        let id = format!("sim-trade-{:010}", self.trades.len() + 1);
+        self.trades.push(Trade {
+            id,
+            order_id: order_id.to_string(),
+            asset_id,
+            side,
+            price,
+            size,
+        });
+        let matched = self.order_matched.entry(order_id.to_string()).or_insert(dec!(0));
+        *matched += size;
+        // 没有订阅者时发送失败，忽略即可
+        let _ = self.fill_tx.send(FillEvent {
+            order_id: order_id.to_string(),
+            asset_id,
+            size_matched: *matched,
+        });
+    }
+
+    fn reject(order_id: String, msg: String) -> OrderAck {
+        OrderAck {
+            order_id,
+            success: false,
+            error_msg: Some(msg),
+            making_amount: dec!(0),
+            taking_amount: dec!(0),
+        }
+    }
+
+    fn submit(&mut self, req: OrderRequest) -> OrderAck {
+        let seq = self.next_id();
+        let order_id = format!("sim-order-{:010}", seq);
+
+        if req.size <= dec!(0) || req.price <= dec!(0) || req.price >= dec!(1) {
+            return Self::reject(order_id, format!("invalid order: price {} size {}", req.price, req.size));
+        }
+
+        // 余额检查（扣除挂单占用）：买单按限价全额占用 USDC，卖单占用 token
+        match req.side {
+            Side::Buy => {
+                let available = self.usdc - self.reserved_usdc();
+                if available < req.price * req.size {
+                    return Self::reject(
+                        order_id,
+                        format!("not enough balance / allowance: need {:.4} USDC, available {:.4}", req.price * req.size, available),
+                    );
+                }
+            }
+            _ => {
+                let available = self.token_balance(req.token_id) - self.reserved_tokens(req.token_id);
+                if available < req.size {
+                    return Self::reject(
+                        order_id,
+                        format!("not enough balance / allowance: need {} shares, available {}", req.size, available),
+                    );
+                }
+            }
+        }
+
+        if matches!(req.order_type, OrderType::FOK) && self.available_liquidity(&req) < req.size {
+            return Self::reject(
+                order_id,
+                "order couldn't be fully filled. FOK orders are fully filled or killed.".to_string(),
+            );
+        }
+
+        let (filled, notional) = self.take_liquidity(&order_id, &req, req.size, false);
+        let remaining = req.size - filled;
+
+        let (success, error_msg) = if remaining > dec!(0) {
+            match req.order_type {
+                OrderType::FAK | OrderType::FOK => {
+                    if filled > dec!(0) {
+                        (true, None)
+                    } else {
+                        (false, Some("no orders found to match with FAK order".to_string()))
+                    }
+                }
+                _ => {
+                    // GTC/GTD：剩余部分挂单，排在同价位已有挂单之后
+                    let queue_ahead = self
+                        .books
+                        .get(&req.token_id)
+                        .map(|b| level_size(b, req.side, req.price))
+                        .unwrap_or(dec!(0));
+                    // 请求的过期时间按系统时间计算；使用模拟时钟时换算成相对时长
+                    let expiration = match self.now {
+                        Some(now) => req.expiration.map(|exp| now + (exp - Utc::now())),
+                        None => req.expiration,
+                    };
+                    self.resting.insert(
+                        seq,
+                        RestingOrder {
+                            order: OpenOrder {
+                                id: order_id.clone(),
+                                asset_id: req.token_id,
+                                side: req.side,
+                                price: req.price,
+                                original_size: req.size,
+                                size_matched: filled,
+                            },
+                            expiration,
+                            queue_ahead,
+                        },
+                    );
+                    (true, None)
+                }
+            }
+        } else {
+            (true, None)
+        };
+
+        debug!(
+            order_id = %order_id,
+            side = ?req.side,
+            price = %req.price,
+            size = %req.size,
+            filled = %filled,
+            "模拟撮合"
+        );
+
+        // 买单：付出 USDC、得到份额；卖单：付出份额、得到 USDC
+        let (making_amount, taking_amount) = match req.side {
+            Side::Buy => (notional, filled),
+            _ => (filled, notional),
+        };
+        OrderAck {
+            order_id,
+            success,
+            error_msg,
+            making_amount,
+            taking_amount,
+        }
+    }
+
+    /// 清理过期挂单，并按新订单簿撮合挂单：
+    /// 1) 对手盘穿过挂单价 → 按挂单价吃掉对手盘；
+    /// 2) 否则该价位相对上一快照减少的量视为成交，先消耗排在前面的队列，超出部分成交到挂单。
+    fn match_resting(&mut self, token_id: U256, prev: Option<&BookSnapshot>) {
+        let now = self.clock();
+        self.resting
+            .retain(|_, r| r.expiration.map(|exp| exp > now).unwrap_or(true));
+
+        let seqs: Vec<u64> = self
+            .resting
+            .iter()
+            .filter(|(_, r)| r.order.asset_id == token_id)
+            .map(|(seq, _)| *seq)
+            .collect();
+        // 同一价位多个挂单共用一次减少量：(方向是否为买, 价格) -> 已分配给挂单的成交量
+        let mut allocated: HashMap<(bool, Decimal), Decimal> = HashMap::new();
+        for seq in seqs {
+            let Some(resting) = self.resting.get(&seq).cloned() else { continue };
+            let order = resting.order;
+            let req = OrderRequest::new(order.asset_id, order.side, order.price, order.original_size, OrderType::GTC);
+            let remaining = order.original_size - order.size_matched;
+            let (mut filled, _) = self.take_liquidity(&order.id, &req, remaining, true);
+
+            if filled < remaining {
+                if let Some(prev) = prev {
+                    let current = self
+                        .books
+                        .get(&token_id)
+                        .map(|b| level_size(b, order.side, order.price))
+                        .unwrap_or(dec!(0));
+                    let depleted = level_size(prev, order.side, order.price) - current;
+                    if depleted > dec!(0) {
+                        let passed = depleted.min(resting.queue_ahead);
+                        let key = (order.side == Side::Buy, order.price);
+                        let used = allocated.entry(key).or_insert(dec!(0));
+                        let through = (depleted - passed - *used).max(dec!(0)).min(remaining - filled);
+                        *used += through;
+                        if let Some(r) = self.resting.get_mut(&seq) {
+                            r.queue_ahead -= passed;
+                        }
+                        if through > dec!(0) {
+                            self.settle_fill(order.asset_id, order.side, order.price, through, true);
+                            self.record_trade(&order.id, order.asset_id, order.side, order.price, through);
+                            filled += through;
+                        }
+                    }
+                }
+            }
+
+            if filled <= dec!(0) {
+                continue;
+            }
+            if filled >= remaining {
+                self.resting.remove(&seq);
+            } else if let Some(r) = self.resting.get_mut(&seq) {
+                r.order.size_matched += filled;
+            }
+        }
+    }
+
+    fn market_tokens(&self, condition_id: B256) -> Result<(U256, U256)> {
+        self.markets
+            .get(&condition_id)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("模拟盘未登记市场 {:#x}", condition_id))
+    }
+}
+
+/// 模拟盘账户快照与盈亏（持仓按买一价估值）
+#[derive(Debug, Clone)]
+pub struct SimReport {
+    pub initial_usdc: Decimal,
+    pub usdc: Decimal,
+    pub holdings_value: Decimal,
+    pub fees_paid: Decimal,
+    pub trade_count: usize,
+    pub open_orders: usize,
+    pub holdings: Vec<(U256, Decimal, Decimal)>, // (token_id, 份额, 买一价)
+}
+
+impl SimReport {
+    pub fn equity(&self) -> Decimal {
+        self.usdc + self.holdings_value
+    }
+
+    pub fn pnl(&self) -> Decimal {
+        self.equity() - self.initial_usdc
+    }
+}
+
+impl fmt::Display for SimReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "USDC:{:.4} 持仓估值:{:.4} 权益:{:.4} | 盈亏:{:+.4} USD | 手续费:{:.4} | 成交{}笔 挂单{}个 持仓{}个",
+            self.usdc,
+            self.holdings_value,
+            self.equity(),
+            self.pnl(),
+            self.fees_paid,
+            self.trade_count,
+            self.open_orders,
+            self.holdings.len()
+        )
+    }
+}
+
+/// 内存模拟交易所
+pub struct SimulatedExchange {
+    state: Mutex<SimState>,
+}
+
+impl SimulatedExchange {
+    pub fn new(initial_usdc: Decimal, fees: FeeSchedule) -> Self {
+        Self {
+            state: Mutex::new(SimState {
+                books: HashMap::new(),
+                resting: BTreeMap::new(),
+                trades: Vec::new(),
+                next_id: 0,
+                markets: HashMap::new(),
+                token_market: HashMap::new(),
+                market_cash: HashMap::new(),
+                fees,
+                initial_usdc,
+                usdc: initial_usdc,
+                tokens: HashMap::new(),
+                fees_paid: dec!(0),
+                now: None,
+                order_matched: HashMap::new(),
+                fill_tx: broadcast::channel(1024).0,
+            }),
+        }
+    }
+
+    /// 登记市场的 YES/NO token，split / merge 需要
+    pub fn register_market(&self, condition_id: B256, yes_token_id: U256, no_token_id: U256) {
+        let mut state = self.state.lock().unwrap();
+        state.markets.insert(condition_id, (yes_token_id, no_token_id));
+        state.token_market.insert(yes_token_id, condition_id);
+        state.token_market.insert(no_token_id, condition_id);
+    }
+
+    /// 推进模拟时钟（回测按录制时间回放；不调用则使用系统时间）
+    pub fn set_time(&self, now: DateTime<Utc>) {
+        self.state.lock().unwrap().now = Some(now);
+    }
+
+    /// 设置某 token 的订单簿快照（bids 升序、asks 降序），并撮合挂单
+    pub fn set_book(&self, token_id: U256, book: BookSnapshot) {
+        let mut state = self.state.lock().unwrap();
+        let prev = state.books.insert(token_id, book);
+        state.match_resting(token_id, prev.as_ref());
+    }
+
+    /// 用 WS 订单簿推送更新快照
+    pub fn apply_book_update(&self, update: &BookUpdate) {
+        let book = BookSnapshot {
+            bids: update.bids.iter().map(|l| BookLevel { price: l.price, size: l.size }).collect(),
+            asks: update.asks.iter().map(|l| BookLevel { price: l.price, size: l.size }).collect(),
+        };
+        self.set_book(update.asset_id, book);
+    }
+
+    /// 各 token 虚拟余额
+    pub fn token_balances(&self) -> Vec<(U256, Decimal)> {
+        self.state
+            .lock()
+            .unwrap()
+            .tokens
+            .iter()
+            .map(|(t, s)| (*t, *s))
+            .collect()
+    }
+
+    /// 已登记市场中 YES+NO 双边都有余额的可 merge 数量：condition_id -> (yes_token_id, no_token_id, merge_amount)
+    pub fn mergeable(&self) -> HashMap<B256, (U256, U256, Decimal)> {
+        let state = self.state.lock().unwrap();
+        state
+            .markets
+            .iter()
+            .filter_map(|(condition_id, (yes, no))| {
+                let amount = state.token_balance(*yes).min(state.token_balance(*no));
+                (amount > dec!(0)).then_some((*condition_id, (*yes, *no, amount)))
+            })
+            .collect()
+    }
+
+    /// 单个市场的盈亏：USDC 净流入 + 剩余 YES/NO 按买一价估值
+    pub fn market_pnl(&self, condition_id: B256) -> Decimal {
+        let state = self.state.lock().unwrap();
+        let cash = state.market_cash.get(&condition_id).copied().unwrap_or(dec!(0));
+        let Some((yes, no)) = state.markets.get(&condition_id).copied() else {
+            return cash;
+        };
+        let holdings: Decimal = [yes, no]
+            .iter()
+            .map(|token_id| {
+                let mark = state
+                    .books
+                    .get(token_id)
+                    .and_then(|b| b.best_bid())
+                    .map(|l| l.price)
+                    .unwrap_or(dec!(0));
+                state.token_balance(*token_id) * mark
+            })
+            .sum();
+        cash + holdings
+    }
+
+    /// 账户快照与盈亏
+    pub fn report(&self) -> SimReport {
+        let state = self.state.lock().unwrap();
+        let holdings: Vec<(U256, Decimal, Decimal)> = state
+            .tokens
+            .iter()
+            .map(|(token_id, size)| {
+                let mark = state
+                    .books
+                    .get(token_id)
+                    .and_then(|b| b.best_bid())
+                    .map(|l| l.price)
+                    .unwrap_or(dec!(0));
+                (*token_id, *size, mark)
+            })
+            .collect();
+        SimReport {
+            initial_usdc: state.initial_usdc,
+            usdc: state.usdc,
+            holdings_value: holdings.iter().map(|(_, size, mark)| size * mark).sum(),
+            fees_paid: state.fees_paid,
+            trade_count: state.trades.len(),
+            open_orders: state.resting.len(),
+            holdings,
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for SimulatedExchange {
+    async fn verify_authentication(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn post_orders(&self, orders: Vec<OrderRequest>) -> Result<Vec<OrderAck>> {
+        let mut state = self.state.lock().unwrap();
+        Ok(orders.into_iter().map(|req| state.submit(req)).collect())
+    }
+
+    async fn cancel_orders(&self, order_ids: &[&str]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.resting.retain(|_, r| !order_ids.contains(&r.order.id.as_str()));
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&self) -> Result<()> {
+        self.state.lock().unwrap().resting.clear();
+        Ok(())
+    }
+
+    async fn open_orders(&self) -> Result<Vec<OpenOrder>> {
+        let mut state = self.state.lock().unwrap();
+        let now = state.clock();
+        state
+            .resting
+            .retain(|_, r| r.expiration.map(|exp| exp > now).unwrap_or(true));
+        Ok(state.resting.values().map(|r| r.order.clone()).collect())
+    }
+
+    async fn trades(&self) -> Result<Vec<Trade>> {
+        Ok(self.state.lock().unwrap().trades.clone())
+    }
+
+    async fn book(&self, token_id: U256) -> Result<BookSnapshot> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .books
+            .get(&token_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn split(&self, condition_id: B256, amount: Decimal) -> Result<String> {
+        let mut state = self.state.lock().unwrap();
+        let (yes, no) = state.market_tokens(condition_id)?;
+        let available = state.usdc - state.reserved_usdc();
+        if available < amount {
+            anyhow::bail!("USDC 余额不足以 split：需要 {} 可用 {}", amount, available);
+        }
+        state.usdc -= amount;
+        *state.market_cash.entry(condition_id).or_insert(dec!(0)) -= amount;
+        state.add_tokens(yes, amount);
+        state.add_tokens(no, amount);
+        let id = state.next_id();
+        Ok(format!("sim-split-{:010}", id))
+    }
+
+    async fn merge_max(&self, condition_id: B256) -> Result<MergeOutcome> {
+        let mut state = self.state.lock().unwrap();
+        let (yes, no) = state.market_tokens(condition_id)?;
+        let amount = (state.token_balance(yes) - state.reserved_tokens(yes))
+            .min(state.token_balance(no) - state.reserved_tokens(no));
+        if amount <= dec!(0) {
+            return Err(MergeError::NothingToMerge.into());
+        }
+        state.add_tokens(yes, -amount);
+        state.add_tokens(no, -amount);
+        state.usdc += amount;
+        *state.market_cash.entry(condition_id).or_insert(dec!(0)) += amount;
+        let id = state.next_id();
+        Ok(MergeOutcome { tx_hash: format!("sim-merge-{:010}", id), merged: vec![(condition_id, amount)] })
+    }
+
+    async fn redeem(&self, _condition_id: B256) -> Result<String> {
+        // 模拟交易所不跟踪市场结算结果，单腿持仓由收尾卖出
+        anyhow::bail!("模拟盘不支持 redeem")
+    }
+
+    async fn subscribe_fills(&self, _markets: Vec<B256>) -> Result<FillStream> {
+        let rx = self.state.lock().unwrap().fill_tx.subscribe();
+        Ok(Box::pin(futures::stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((Ok(event), rx)),
+                    // 累计量推送，丢掉中间几条不影响最终结果
+                    Err(broadcast::error::RecvError::Lagged(n)) => warn!(skipped = n, "模拟盘成交推送积压，跳过部分事件"),
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trading::fees::FeeModel;
+    use chrono::Duration;
+
+    fn token() -> U256 {
+        U256::from(1u64)
+    }
+
+    fn exchange_with_asks(asks: Vec<BookLevel>) -> SimulatedExchange {
+        let exchange = SimulatedExchange::new(dec!(100), FeeSchedule::new(FeeModel::free()));
+        exchange.set_book(token(), BookSnapshot { bids: Vec::new(), asks });
+        exchange
+    }
+
+    fn level(price: Decimal, size: Decimal) -> BookLevel {
+        BookLevel { price, size }
+    }
+
+    #[tokio::test]
+    async fn crossing_buy_walks_levels_at_book_prices() {
+        // asks 降序：卖一 0.50 × 10，其后 0.52 × 10
+        let exchange = exchange_with_asks(vec![level(dec!(0.52), dec!(10)), level(dec!(0.50), dec!(10))]);
+        let ack = exchange
+            .post_order(OrderRequest::new(token(), Side::Buy, dec!(0.55), dec!(15), OrderType::GTC))
+            .await
+            .unwrap();
+
+        assert!(ack.success);
+        assert_eq!(ack.taking_amount, dec!(15));
+        assert_eq!(ack.making_amount, dec!(7.6)); // 10 × 0.50 + 5 × 0.52
+        let trades = exchange.trades().await.unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!((trades[0].price, trades[0].size), (dec!(0.50), dec!(10)));
+        assert_eq!((trades[1].price, trades[1].size), (dec!(0.52), dec!(5)));
+        assert!(exchange.open_orders().await.unwrap().is_empty());
+        assert_eq!(exchange.report().usdc, dec!(92.4));
+    }
+
+    #[tokio::test]
+    async fn partial_fill_rests_remainder_and_fills_on_cross() {
+        let exchange = exchange_with_asks(vec![level(dec!(0.50), dec!(4))]);
+        let ack = exchange
+            .post_order(OrderRequest::new(token(), Side::Buy, dec!(0.50), dec!(10), OrderType::GTC))
+            .await
+            .unwrap();
+
+        assert!(ack.success);
+        assert_eq!(ack.taking_amount, dec!(4));
+        let open = exchange.open_orders().await.unwrap();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].size_matched, dec!(4));
+
+        // 新卖单挂到 0.49，穿过挂单价 → 按挂单价 0.50 成交剩余 6 份
+        exchange.set_book(token(), BookSnapshot { bids: Vec::new(), asks: vec![level(dec!(0.49), dec!(20))] });
+        assert!(exchange.open_orders().await.unwrap().is_empty());
+        let trades = exchange.trades().await.unwrap();
+        assert_eq!((trades[1].price, trades[1].size), (dec!(0.50), dec!(6)));
+        assert_eq!(exchange.report().usdc, dec!(95));
+    }
+
+    #[tokio::test]
+    async fn fok_rejects_when_liquidity_short() {
+        let exchange = exchange_with_asks(vec![level(dec!(0.50), dec!(4))]);
+        let ack = exchange
+            .post_order(OrderRequest::new(token(), Side::Buy, dec!(0.50), dec!(10), OrderType::FOK))
+            .await
+            .unwrap();
+
+        assert!(!ack.success);
+        assert!(ack.error_msg.unwrap().contains("FOK"));
+        assert!(exchange.trades().await.unwrap().is_empty());
+        assert_eq!(exchange.report().usdc, dec!(100));
+    }
+
+    #[tokio::test]
+    async fn fak_keeps_partial_fill_and_cancels_rest() {
+        let exchange = exchange_with_asks(vec![level(dec!(0.50), dec!(4))]);
+        let ack = exchange
+            .post_order(OrderRequest::new(token(), Side::Buy, dec!(0.50), dec!(10), OrderType::FAK))
+            .await
+            .unwrap();
+
+        assert!(ack.success);
+        assert_eq!(ack.taking_amount, dec!(4));
+        assert!(exchange.open_orders().await.unwrap().is_empty());
+
+        let ack = exchange
+            .post_order(OrderRequest::new(token(), Side::Buy, dec!(0.50), dec!(10), OrderType::FAK))
+            .await
+            .unwrap();
+        assert!(!ack.success);
+        assert!(ack.error_msg.unwrap().contains("FAK"));
+    }
+
+    #[tokio::test]
+    async fn gtd_order_expires_on_sim_clock() {
+        let start = Utc::now();
+        let exchange = exchange_with_asks(vec![level(dec!(0.60), dec!(10))]);
+        exchange.set_time(start);
+        let ack = exchange
+            .post_order(
+                OrderRequest::new(token(), Side::Buy, dec!(0.50), dec!(10), OrderType::GTD)
+                    .with_expiration(Utc::now() + Duration::seconds(60)),
+            )
+            .await
+            .unwrap();
+        assert!(ack.success);
+
+        exchange.set_time(start + Duration::seconds(30));
+        assert_eq!(exchange.open_orders().await.unwrap().len(), 1);
+
+        exchange.set_time(start + Duration::seconds(90));
+        exchange.set_book(token(), BookSnapshot { bids: Vec::new(), asks: vec![level(dec!(0.45), dec!(10))] });
+        assert!(exchange.open_orders().await.unwrap().is_empty());
+        assert!(exchange.trades().await.unwrap().is_empty());
+        assert_eq!(exchange.report().usdc, dec!(100));
+    }
+}
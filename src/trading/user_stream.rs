@@ -0,0 +1,131 @@
+//! 用户频道成交推送：订阅认证后的 CLOB user channel（order / trade 事件），
+//! 统一转换为按订单累计成交量的 FillEvent，供风控在 GTC/GTD 挂单后续成交时更新订单对。
+//! trade 事件会随链上状态（MATCHED → MINED → CONFIRMED）重复推送，按 trade id 去重；
+//! order 事件自带累计 size_matched，两者取较大值，只在累计量增加时输出。
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use polymarket_client_sdk::clob::ws::types::response::{TradeMessageStatus, WsMessage};
+use polymarket_client_sdk::clob::ws::Client as WsClient;
+use polymarket_client_sdk::types::{B256, Decimal, U256};
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use super::exchange::AuthenticatedClient;
+
+/// 某订单的成交进度（size_matched 为累计成交份额，不是增量）
+#[derive(Debug, Clone)]
+pub struct FillEvent {
+    pub order_id: String,
+    pub asset_id: U256,
+    pub size_matched: Decimal,
+}
+
+pub type FillStream = Pin<Box<dyn Stream<Item = Result<FillEvent>> + Send>>;
+
+/// 把 order / trade 事件合并为按订单累计的成交量
+#[derive(Debug, Default)]
+pub struct FillAggregator {
+    seen_trades: HashSet<String>,
+    trade_sums: HashMap<String, Decimal>, // order_id -> 已去重 trade 的成交量之和
+    matched: HashMap<String, Decimal>,    // order_id -> 已输出的累计成交量
+}
+
+impl FillAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 订单事件：size_matched 为累计量
+    pub fn on_order(&mut self, order_id: &str, asset_id: U256, size_matched: Decimal) -> Option<FillEvent> {
+        self.advance(order_id, asset_id, size_matched)
+    }
+
+    /// 成交事件：size 为本笔成交量，同一 trade_id 只计一次
+    pub fn on_trade(&mut self, trade_id: &str, order_id: &str, asset_id: U256, size: Decimal) -> Option<FillEvent> {
+        if !self.seen_trades.insert(format!("{}:{}", trade_id, order_id)) {
+            return None;
+        }
+        let sum = self.trade_sums.entry(order_id.to_string()).or_default();
+        *sum += size;
+        let total = *sum;
+        self.advance(order_id, asset_id, total)
+    }
+
+    fn advance(&mut self, order_id: &str, asset_id: U256, size_matched: Decimal) -> Option<FillEvent> {
+        let current = self.matched.entry(order_id.to_string()).or_default();
+        if size_matched <= *current {
+            return None;
+        }
+        *current = size_matched;
+        Some(FillEvent {
+            order_id: order_id.to_string(),
+            asset_id,
+            size_matched,
+        })
+    }
+}
+
+/// 订阅用户频道；markets 为空表示订阅账户在所有市场的事件。
+/// WS 客户端由后台任务持有，返回的流被丢弃后任务自动退出。
+pub fn subscribe_user_fills(client: &AuthenticatedClient, markets: Vec<B256>) -> Result<FillStream> {
+    let ws_client = WsClient::default()
+        .authenticate(client.credentials().clone(), client.address())
+        .map_err(|e| anyhow::anyhow!("用户频道认证失败: {}", e))?;
+    let (tx, rx) = mpsc::channel::<Result<FillEvent>>(1024);
+
+    tokio::spawn(async move {
+        let mut stream = match ws_client.subscribe_user_events(markets) {
+            Ok(stream) => Box::pin(stream),
+            Err(e) => {
+                let _ = tx.send(Err(anyhow::anyhow!("订阅用户频道失败: {}", e))).await;
+                return;
+            }
+        };
+        let mut aggregator = FillAggregator::new();
+        while let Some(msg) = stream.next().await {
+            let events = match msg {
+                Ok(WsMessage::Order(order)) => order
+                    .size_matched
+                    .and_then(|matched| aggregator.on_order(&order.id, order.asset_id, matched))
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+                Ok(WsMessage::Trade(trade)) => {
+                    // 撮合失败的成交不计入
+                    if matches!(&trade.status, TradeMessageStatus::Unknown(s) if s.eq_ignore_ascii_case("FAILED")) {
+                        warn!(trade_id = %trade.id, "用户频道：成交失败，忽略");
+                        continue;
+                    }
+                    // 我方可能是吃单方，也可能是被吃的挂单（maker_orders 中）
+                    let mut events: Vec<FillEvent> = trade
+                        .taker_order_id
+                        .as_deref()
+                        .and_then(|taker| aggregator.on_trade(&trade.id, taker, trade.asset_id, trade.size))
+                        .into_iter()
+                        .collect();
+                    for maker in &trade.maker_orders {
+                        events.extend(aggregator.on_trade(&trade.id, &maker.order_id, maker.asset_id, maker.matched_amount));
+                    }
+                    events
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    let _ = tx.send(Err(anyhow::anyhow!("{e}"))).await;
+                    return;
+                }
+            };
+            for event in events {
+                debug!(order_id = %event.order_id, size_matched = %event.size_matched, "用户频道成交更新");
+                if tx.send(Ok(event)).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(Box::pin(futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    })))
+}
@@ -1,3 +1,2 @@
-pub mod arbitrage_logger;
 pub mod errors;
 pub mod logger;